@@ -0,0 +1,294 @@
+pub use aerospike;
+use aerospike::{
+    Bin, Bins, Client, Expiration, GenerationPolicy, Key, ReadPolicy, RecordExistsAction,
+    ResultCode, Value, WritePolicy,
+};
+use async_trait::async_trait;
+use tower_sessions_core::{session::Id, Session, SessionStore};
+
+/// The name of the bin holding the serialized session.
+const DATA_BIN: &str = "data";
+
+/// An error type for `AerospikeStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum AerospikeStoreError {
+    /// A variant to map to `aerospike::Error` errors.
+    #[error("Aerospike error: {0}")]
+    Aerospike(#[from] aerospike::Error),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for sessions which could not be saved because their record
+    /// was modified or removed since it was loaded.
+    #[error("Session generation mismatch")]
+    GenerationMismatch,
+
+    /// A variant for records without a serialized session.
+    #[error("Invalid session record")]
+    InvalidRecord,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// An Aerospike session store.
+///
+/// Sessions are stored as records of the session set, keyed by their ID,
+/// holding the serialized session in the `data` bin. Records are written with
+/// a TTL matching the session's expiry date, after which Aerospike no longer
+/// returns them and removes them by itself. This requires the namespace to
+/// allow TTLs, i.e. to have `nsup-period` set.
+///
+/// Every write bumps the record's generation, which
+/// [`load_with_generation`](AerospikeStore::load_with_generation) and
+/// [`save_with_generation`](AerospikeStore::save_with_generation) expose for
+/// applications needing to detect concurrent modifications of a session.
+#[derive(Clone)]
+pub struct AerospikeStore {
+    client: Client,
+    namespace: String,
+    set_name: String,
+}
+
+impl std::fmt::Debug for AerospikeStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AerospikeStore")
+            .field("namespace", &self.namespace)
+            .field("set_name", &self.set_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AerospikeStore {
+    /// Create a new Aerospike store with the provided client and namespace.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_aerospike_store::{
+    ///     aerospike::{Client, ClientPolicy},
+    ///     AerospikeStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::new(&ClientPolicy::default(), &"localhost:3000")
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = AerospikeStore::new(client, "sessions");
+    /// # })
+    /// ```
+    pub fn new(client: Client, namespace: &str) -> Self {
+        Self {
+            client,
+            namespace: namespace.to_string(),
+            set_name: "tower_sessions".to_string(),
+        }
+    }
+
+    /// Set the session set name with the provided name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_aerospike_store::{
+    ///     aerospike::{Client, ClientPolicy},
+    ///     AerospikeStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::new(&ClientPolicy::default(), &"localhost:3000")
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = AerospikeStore::new(client, "sessions").with_set_name("my_sessions");
+    /// # })
+    /// ```
+    pub fn with_set_name(mut self, set_name: &str) -> Self {
+        self.set_name = set_name.to_string();
+        self
+    }
+
+    /// Load a session along with the generation of its record.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_aerospike_store::{
+    ///     aerospike::{Client, ClientPolicy},
+    ///     AerospikeStore,
+    /// };
+    /// use tower_sessions_core::session::Id;
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let session_id = Id::default();
+    /// let client = Client::new(&ClientPolicy::default(), &"localhost:3000")
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = AerospikeStore::new(client, "sessions");
+    /// if let Some((session, generation)) = session_store
+    ///     .load_with_generation(&session_id)
+    ///     .await
+    ///     .unwrap()
+    /// {
+    ///     session.insert("foo", 42).unwrap();
+    ///     session_store
+    ///         .save_with_generation(&session, generation)
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// # })
+    /// ```
+    pub async fn load_with_generation(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<(Session, u32)>, AerospikeStoreError> {
+        let record = match self
+            .client
+            .get(
+                &ReadPolicy::default(),
+                &self.key(session_id)?,
+                Bins::from([DATA_BIN]),
+            )
+            .await
+        {
+            Ok(record) => record,
+            Err(err) if err.matches(&[ResultCode::KeyNotFoundError]) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let Some(Value::Blob(data)) = record.bins.get(DATA_BIN) else {
+            return Err(AerospikeStoreError::InvalidRecord);
+        };
+
+        Ok(Some((rmp_serde::from_slice(data)?, record.generation)))
+    }
+
+    /// Save a session, provided its record is still at the given generation.
+    ///
+    /// This fails with [`AerospikeStoreError::GenerationMismatch`] if the
+    /// session was saved or removed since it was loaded with
+    /// [`load_with_generation`](AerospikeStore::load_with_generation).
+    pub async fn save_with_generation(
+        &self,
+        session: &Session,
+        generation: u32,
+    ) -> Result<(), AerospikeStoreError> {
+        let mut policy = WritePolicy {
+            generation_policy: GenerationPolicy::ExpectGenEqual,
+            generation,
+            ..WritePolicy::default()
+        };
+
+        let key = self.key(session.id())?;
+        let written = match ttl(session) {
+            // Expired sessions needn't be stored, see `save`.
+            0 => self.client.delete(&policy, &key).await.map(|_| ()),
+            ttl => {
+                policy.record_exists_action = RecordExistsAction::ReplaceOnly;
+                policy.expiration = Expiration::Seconds(ttl);
+                self.client.put(&policy, &key, &[data_bin(session)?]).await
+            }
+        };
+
+        match written {
+            Ok(()) => Ok(()),
+            Err(err)
+                if err.matches(&[ResultCode::GenerationError, ResultCode::KeyNotFoundError]) =>
+            {
+                Err(AerospikeStoreError::GenerationMismatch)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn key(&self, session_id: &Id) -> Result<Key, AerospikeStoreError> {
+        Ok(Key::new(
+            self.namespace.as_str(),
+            self.set_name.as_str(),
+            Value::from(session_id.to_string()),
+        )?)
+    }
+}
+
+#[async_trait]
+impl SessionStore for AerospikeStore {
+    type Error = AerospikeStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        // Record TTLs must be positive, and expired sessions needn't be stored.
+        let ttl = ttl(session);
+        if ttl == 0 {
+            return self.delete(session.id()).await;
+        }
+
+        let policy = WritePolicy {
+            record_exists_action: RecordExistsAction::Replace,
+            expiration: Expiration::Seconds(ttl),
+            ..WritePolicy::default()
+        };
+        self.client
+            .put(&policy, &self.key(session.id())?, &[data_bin(session)?])
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // Expired sessions aren't stored, see `save`.
+        let ttl = ttl(session);
+        if ttl == 0 {
+            return Ok(());
+        }
+
+        let policy = WritePolicy {
+            record_exists_action: RecordExistsAction::CreateOnly,
+            expiration: Expiration::Seconds(ttl),
+            ..WritePolicy::default()
+        };
+        let key = self.key(session.id())?;
+        match self.client.put(&policy, &key, &[data_bin(session)?]).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.matches(&[ResultCode::KeyExistsError]) => {
+                Err(AerospikeStoreError::IdCollision)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        Ok(self
+            .load_with_generation(session_id)
+            .await?
+            .map(|(session, _)| session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.client
+            .delete(&WritePolicy::default(), &self.key(session_id)?)
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("aerospike")
+    }
+}
+
+fn data_bin(session: &Session) -> Result<Bin, AerospikeStoreError> {
+    Ok(Bin::new(DATA_BIN, Value::Blob(rmp_serde::to_vec(session)?)))
+}
+
+fn ttl(session: &Session) -> u32 {
+    session
+        .expiry_age()
+        .whole_seconds()
+        .clamp(0, u32::MAX.into()) as u32
+}
@@ -0,0 +1,163 @@
+//! Latency and throughput of session store operations.
+//!
+//! `MemoryStore` is always measured. Other stores are measured when their
+//! feature is enabled: `sqlite`, for an in-memory `SqliteStore`, and `redis`,
+//! `postgres` and `mysql`, for stores connecting to `REDIS_URL`,
+//! `POSTGRES_URL` and `MYSQL_URL`, e.g. the services of
+//! `tests/docker-compose.yml`:
+//!
+//! ```sh
+//! REDIS_URL=redis://localhost:6379 cargo bench -p tower-sessions-benches --features sqlite,redis
+//! ```
+use std::time::Instant;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::try_join_all;
+use tokio::runtime::Runtime;
+use tower_sessions::{MemoryStore, Session, SessionStore};
+
+/// Sizes of the session payloads, in bytes.
+const PAYLOAD_SIZES: [usize; 3] = [64, 1024, 16 * 1024];
+
+/// Numbers of operations in flight at once.
+const CONCURRENCY: [usize; 3] = [1, 8, 64];
+
+/// The payload size of sessions in concurrent benchmarks.
+const CONCURRENT_PAYLOAD_SIZE: usize = 1024;
+
+fn session_with_payload(payload_size: usize) -> Session {
+    let session = Session::default();
+    session.insert("payload", "x".repeat(payload_size)).unwrap();
+    session
+}
+
+fn bench_store<Store: SessionStore>(c: &mut Criterion, rt: &Runtime, name: &str, store: Store) {
+    let store = &store;
+
+    let mut group = c.benchmark_group(name);
+    for payload_size in PAYLOAD_SIZES {
+        group.throughput(Throughput::Bytes(payload_size as u64));
+
+        let session = &session_with_payload(payload_size);
+        rt.block_on(store.save(session)).unwrap();
+
+        group.bench_function(BenchmarkId::new("save", payload_size), |b| {
+            b.to_async(rt)
+                .iter(|| async move { store.save(session).await.unwrap() })
+        });
+
+        group.bench_function(BenchmarkId::new("load", payload_size), |b| {
+            b.to_async(rt)
+                .iter(|| async move { store.load(session.id()).await.unwrap() })
+        });
+
+        group.bench_function(BenchmarkId::new("delete", payload_size), |b| {
+            b.to_async(rt).iter_custom(|iters| async move {
+                let sessions = (0..iters).map(|_| session_with_payload(payload_size));
+                let sessions = sessions.collect::<Vec<_>>();
+                for session in &sessions {
+                    store.create(session).await.unwrap();
+                }
+
+                let started = Instant::now();
+                for session in &sessions {
+                    store.delete(session.id()).await.unwrap();
+                }
+                started.elapsed()
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group(format!("{name}/concurrent"));
+    for concurrency in CONCURRENCY {
+        group.throughput(Throughput::Elements(concurrency as u64));
+
+        let sessions = (0..concurrency).map(|_| session_with_payload(CONCURRENT_PAYLOAD_SIZE));
+        let sessions = &sessions.collect::<Vec<_>>();
+        rt.block_on(try_join_all(sessions.iter().map(|s| store.save(s))))
+            .unwrap();
+
+        group.bench_function(BenchmarkId::new("save", concurrency), |b| {
+            b.to_async(rt).iter(|| async move {
+                try_join_all(sessions.iter().map(|s| store.save(s)))
+                    .await
+                    .unwrap()
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("load", concurrency), |b| {
+            b.to_async(rt).iter(|| async move {
+                try_join_all(sessions.iter().map(|s| store.load(s.id())))
+                    .await
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn stores(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    bench_store(c, &rt, "memory", MemoryStore::default());
+
+    #[cfg(feature = "sqlite")]
+    {
+        use tower_sessions::{sqlx::SqlitePool, SqliteStore};
+
+        let sqlite_store = rt.block_on(async {
+            let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+            let store = SqliteStore::new(pool);
+            store.migrate().await.unwrap();
+            store
+        });
+        bench_store(c, &rt, "sqlite", sqlite_store);
+    }
+
+    #[cfg(feature = "redis")]
+    {
+        use tower_sessions::{fred::prelude::*, RedisStore};
+
+        let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
+        let redis_store = rt.block_on(async {
+            let config = RedisConfig::from_url(&redis_url).unwrap();
+            let pool = RedisPool::new(config, None, None, None, 6).unwrap();
+            pool.connect();
+            pool.wait_for_connect().await.unwrap();
+            RedisStore::new(pool)
+        });
+        bench_store(c, &rt, "redis", redis_store);
+    }
+
+    #[cfg(feature = "postgres")]
+    {
+        use tower_sessions::{sqlx::PgPool, PostgresStore};
+
+        let postgres_url = std::env::var("POSTGRES_URL").expect("POSTGRES_URL must be set");
+        let postgres_store = rt.block_on(async {
+            let pool = PgPool::connect(&postgres_url).await.unwrap();
+            let store = PostgresStore::new(pool);
+            store.migrate().await.unwrap();
+            store
+        });
+        bench_store(c, &rt, "postgres", postgres_store);
+    }
+
+    #[cfg(feature = "mysql")]
+    {
+        use tower_sessions::{sqlx::MySqlPool, MySqlStore};
+
+        let mysql_url = std::env::var("MYSQL_URL").expect("MYSQL_URL must be set");
+        let mysql_store = rt.block_on(async {
+            let pool = MySqlPool::connect(&mysql_url).await.unwrap();
+            let store = MySqlStore::new(pool);
+            store.migrate().await.unwrap();
+            store
+        });
+        bench_store(c, &rt, "mysql", mysql_store);
+    }
+}
+
+criterion_group!(benches, stores);
+criterion_main!(benches);
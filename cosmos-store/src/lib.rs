@@ -0,0 +1,197 @@
+use async_trait::async_trait;
+pub use azure_data_cosmos;
+use azure_data_cosmos::{
+    clients::ContainerClient,
+    models::{ContainerProperties, TimeToLive},
+    CosmosError,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tower_sessions_core::{session::Id, Session, SessionStore};
+
+/// An error type for `CosmosStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum CosmosStoreError {
+    /// A variant to map to `azure_data_cosmos::CosmosError` errors.
+    #[error("Cosmos DB error: {0}")]
+    Cosmos(#[from] CosmosError),
+
+    /// A variant for sessions which could not be created because an item
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `base64` decode errors.
+    #[error("Base64 decode error: {0}")]
+    Base64Decode(#[from] base64::DecodeError),
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionItem {
+    id: String,
+    data: String,
+    ttl: i32,
+}
+
+/// An Azure Cosmos DB session store.
+///
+/// Sessions are stored as items of the provided container, with their ID as
+/// both the item ID and the partition key, so the container must be
+/// partitioned on `/id`. Items hold the serialized session, base64-encoded,
+/// and a TTL matching the session's expiry date, after which Cosmos DB no
+/// longer returns them and removes them by itself.
+///
+/// Item TTLs only apply once TTL is enabled on the container, which
+/// [`migrate`](CosmosStore::migrate) does.
+#[derive(Clone)]
+pub struct CosmosStore {
+    container: ContainerClient,
+}
+
+impl std::fmt::Debug for CosmosStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CosmosStore").finish_non_exhaustive()
+    }
+}
+
+impl CosmosStore {
+    /// Create a new Cosmos DB store with the provided container client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_cosmos_store::{
+    ///     azure_data_cosmos::{AccountReference, CosmosClient, RoutingStrategy},
+    ///     CosmosStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let account: AccountReference = unimplemented!();
+    /// let client = CosmosClient::builder()
+    ///     .build(account, RoutingStrategy::ProximityTo("East US".into()))
+    ///     .await
+    ///     .unwrap();
+    /// let container = client
+    ///     .database_client("my_app")
+    ///     .container_client("tower_sessions", None)
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = CosmosStore::new(container);
+    /// # })
+    /// ```
+    pub fn new(container: ContainerClient) -> Self {
+        Self { container }
+    }
+
+    /// Migrate the container, enabling TTL without a default, so items expire
+    /// according to their own TTL.
+    ///
+    /// This leaves containers with TTL already enabled unchanged. It's a
+    /// control-plane operation, which requires authenticating with a key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_cosmos_store::{azure_data_cosmos::clients::ContainerClient, CosmosStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let container: ContainerClient = unimplemented!();
+    /// let session_store = CosmosStore::new(container);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), CosmosStoreError> {
+        let properties: ContainerProperties = self.container.read(None).await?.into_model()?;
+        if properties.default_ttl == TimeToLive::Forever {
+            self.container
+                .replace(properties.with_default_ttl(TimeToLive::NoDefault), None)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn item(session: &Session) -> Result<SessionItem, CosmosStoreError> {
+        Ok(SessionItem {
+            id: session.id().to_string(),
+            data: STANDARD.encode(rmp_serde::to_vec(session)?),
+            ttl: Self::ttl(session),
+        })
+    }
+
+    fn ttl(session: &Session) -> i32 {
+        session
+            .expiry_age()
+            .whole_seconds()
+            .clamp(0, i32::MAX.into()) as i32
+    }
+}
+
+#[async_trait]
+impl SessionStore for CosmosStore {
+    type Error = CosmosStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        // Item TTLs must be positive, and expired sessions needn't be stored.
+        let item = Self::item(session)?;
+        if item.ttl == 0 {
+            return self.delete(session.id()).await;
+        }
+
+        self.container
+            .upsert_item(item.id.clone(), &item.id, &item, None)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // Expired sessions aren't stored, see `save`.
+        let item = Self::item(session)?;
+        if item.ttl == 0 {
+            return Ok(());
+        }
+
+        match self
+            .container
+            .create_item(item.id.clone(), &item.id, &item, None)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if err.status().is_conflict() => Err(CosmosStoreError::IdCollision),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let id = session_id.to_string();
+        let item: SessionItem = match self.container.read_item(id.clone(), &id, None).await {
+            Ok(response) => response.into_model()?,
+            Err(err) if err.status().is_not_found() => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Some(rmp_serde::from_slice(&STANDARD.decode(item.data)?)?))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let id = session_id.to_string();
+        match self.container.delete_item(id.clone(), &id, None).await {
+            Ok(_) => Ok(()),
+            Err(err) if err.status().is_not_found() => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("cosmosdb")
+    }
+}
@@ -0,0 +1,59 @@
+pub use diesel;
+pub use diesel_async;
+use diesel_async::pooled_connection::deadpool::PoolError;
+
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+pub use self::mysql_store::DieselMySqlStore;
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+pub use self::postgres_store::DieselPostgresStore;
+
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+mod postgres_store;
+
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+mod mysql_store;
+
+pub mod schema;
+
+/// An error type for diesel stores.
+#[derive(thiserror::Error, Debug)]
+pub enum DieselStoreError {
+    /// A variant to map `diesel` errors.
+    #[error("Diesel error: {0}")]
+    Diesel(#[from] diesel::result::Error),
+
+    /// A variant to map connection pool errors.
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] PoolError),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+impl DieselStoreError {
+    /// Maps unique violations on insert to `IdCollision`.
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    fn from_insert(err: diesel::result::Error) -> Self {
+        match err {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => Self::IdCollision,
+            err => Self::Diesel(err),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use async_trait::async_trait;
+use diesel::{dsl::DuplicatedKeys, ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{pooled_connection::deadpool::Pool, AsyncMysqlConnection, RunQueryDsl};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+use crate::{schema::mysql::session, DieselStoreError};
+
+/// A MySQL session store built on `diesel-async`.
+///
+/// Sessions are stored in the table described by
+/// [`schema::mysql::session`](crate::schema::mysql::session).
+#[derive(Clone)]
+pub struct DieselMySqlStore {
+    pool: Pool<AsyncMysqlConnection>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for DieselMySqlStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DieselMySqlStore")
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DieselMySqlStore {
+    /// Create a new MySQL store with the provided connection pool.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_diesel_store::{
+    ///     diesel_async::{
+    ///         pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    ///         AsyncMysqlConnection,
+    ///     },
+    ///     DieselMySqlStore,
+    /// };
+    ///
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let manager = AsyncDieselConnectionManager::<AsyncMysqlConnection>::new(database_url);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = DieselMySqlStore::new(pool);
+    /// ```
+    pub fn new(pool: Pool<AsyncMysqlConnection>) -> Self {
+        Self {
+            pool,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session schema.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_diesel_store::{
+    ///     diesel_async::{
+    ///         pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    ///         AsyncMysqlConnection,
+    ///     },
+    ///     DieselMySqlStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let manager = AsyncDieselConnectionManager::<AsyncMysqlConnection>::new(database_url);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = DieselMySqlStore::new(pool);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), DieselStoreError> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::sql_query("create schema if not exists `tower_sessions`")
+            .execute(&mut conn)
+            .await?;
+
+        diesel::sql_query(
+            r#"
+            create table if not exists `tower_sessions`.`session`
+            (
+                id char(36) primary key not null,
+                data blob not null,
+                expiry_date timestamp(6) not null
+            )
+            "#,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for DieselMySqlStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::delete(session::table.filter(session::expiry_date.lt(self.clock.now())))
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for DieselMySqlStore {
+    type Error = DieselStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let data = rmp_serde::to_vec(&session)?;
+        let expiry_date = session.expiry_date();
+
+        let mut conn = self.pool.get().await?;
+        diesel::insert_into(session::table)
+            .values((
+                session::id.eq(session.id().to_string()),
+                session::data.eq(&data),
+                session::expiry_date.eq(expiry_date),
+            ))
+            .on_conflict(DuplicatedKeys)
+            .do_update()
+            .set((
+                session::data.eq(&data),
+                session::expiry_date.eq(expiry_date),
+            ))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::insert_into(session::table)
+            .values((
+                session::id.eq(session.id().to_string()),
+                session::data.eq(rmp_serde::to_vec(&session)?),
+                session::expiry_date.eq(session.expiry_date()),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(DieselStoreError::from_insert)?;
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let mut conn = self.pool.get().await?;
+        let data: Option<Vec<u8>> = session::table
+            .select(session::data)
+            .filter(session::id.eq(session_id.to_string()))
+            .filter(session::expiry_date.gt(self.clock.now()))
+            .first(&mut conn)
+            .await
+            .optional()?;
+
+        if let Some(data) = data {
+            Ok(Some(rmp_serde::from_slice(&data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::delete(session::table.find(session_id.to_string()))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("mysql")
+    }
+}
@@ -0,0 +1,197 @@
+use async_trait::async_trait;
+use diesel::{upsert::excluded, ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{pooled_connection::deadpool::Pool, AsyncPgConnection, RunQueryDsl};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+use crate::{schema::postgres::session, DieselStoreError};
+
+/// A PostgreSQL session store built on `diesel-async`.
+///
+/// Sessions are stored in the table described by
+/// [`schema::postgres::session`](crate::schema::postgres::session).
+#[derive(Clone)]
+pub struct DieselPostgresStore {
+    pool: Pool<AsyncPgConnection>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for DieselPostgresStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DieselPostgresStore")
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DieselPostgresStore {
+    /// Create a new PostgreSQL store with the provided connection pool.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_diesel_store::{
+    ///     diesel_async::{
+    ///         pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    ///         AsyncPgConnection,
+    ///     },
+    ///     DieselPostgresStore,
+    /// };
+    ///
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = DieselPostgresStore::new(pool);
+    /// ```
+    pub fn new(pool: Pool<AsyncPgConnection>) -> Self {
+        Self {
+            pool,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session schema.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_diesel_store::{
+    ///     diesel_async::{
+    ///         pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    ///         AsyncPgConnection,
+    ///     },
+    ///     DieselPostgresStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = DieselPostgresStore::new(pool);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), DieselStoreError> {
+        let mut conn = self.pool.get().await?;
+
+        // Concurrent create schema may fail due to duplicate key violations.
+        //
+        // This works around that by assuming the schema must exist on such an error.
+        match diesel::sql_query(r#"create schema if not exists "tower_sessions""#)
+            .execute(&mut conn)
+            .await
+        {
+            Ok(_)
+            | Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        diesel::sql_query(
+            r#"
+            create table if not exists "tower_sessions"."session"
+            (
+                id text primary key not null,
+                data bytea not null,
+                expiry_date timestamptz not null
+            )
+            "#,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for DieselPostgresStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::delete(session::table.filter(session::expiry_date.lt(self.clock.now())))
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for DieselPostgresStore {
+    type Error = DieselStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::insert_into(session::table)
+            .values((
+                session::id.eq(session.id().to_string()),
+                session::data.eq(rmp_serde::to_vec(&session)?),
+                session::expiry_date.eq(session.expiry_date()),
+            ))
+            .on_conflict(session::id)
+            .do_update()
+            .set((
+                session::data.eq(excluded(session::data)),
+                session::expiry_date.eq(excluded(session::expiry_date)),
+            ))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::insert_into(session::table)
+            .values((
+                session::id.eq(session.id().to_string()),
+                session::data.eq(rmp_serde::to_vec(&session)?),
+                session::expiry_date.eq(session.expiry_date()),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(DieselStoreError::from_insert)?;
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let mut conn = self.pool.get().await?;
+        let data: Option<Vec<u8>> = session::table
+            .select(session::data)
+            .filter(session::id.eq(session_id.to_string()))
+            .filter(session::expiry_date.gt(self.clock.now()))
+            .first(&mut conn)
+            .await
+            .optional()?;
+
+        if let Some(data) = data {
+            Ok(Some(rmp_serde::from_slice(&data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let mut conn = self.pool.get().await?;
+        diesel::delete(session::table.find(session_id.to_string()))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("postgresql")
+    }
+}
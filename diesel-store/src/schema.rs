@@ -0,0 +1,38 @@
+//! Diesel schema definitions for the session tables.
+//!
+//! Sessions are stored in the `session` table of the `tower_sessions` schema,
+//! which the stores' `migrate` methods create.
+
+/// The session table for PostgreSQL.
+#[cfg(feature = "postgres")]
+#[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
+pub mod postgres {
+    diesel::table! {
+        /// Sessions stored by [`DieselPostgresStore`](crate::DieselPostgresStore).
+        tower_sessions.session (id) {
+            /// The session ID.
+            id -> Text,
+            /// The MessagePack-serialized session.
+            data -> Binary,
+            /// The date and time at which the session expires.
+            expiry_date -> Timestamptz,
+        }
+    }
+}
+
+/// The session table for MySQL.
+#[cfg(feature = "mysql")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mysql")))]
+pub mod mysql {
+    diesel::table! {
+        /// Sessions stored by [`DieselMySqlStore`](crate::DieselMySqlStore).
+        tower_sessions.session (id) {
+            /// The session ID.
+            id -> Text,
+            /// The MessagePack-serialized session.
+            data -> Binary,
+            /// The date and time at which the session expires.
+            expiry_date -> Datetime,
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+pub use aws_sdk_dynamodb;
+use aws_sdk_dynamodb::{
+    error::SdkError,
+    operation::put_item::PutItemError,
+    primitives::Blob,
+    types::{
+        AttributeDefinition, AttributeValue, BillingMode, KeySchemaElement, KeyType,
+        ScalarAttributeType, TimeToLiveSpecification, TimeToLiveStatus,
+    },
+    Client,
+};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    Session, SessionStore,
+};
+
+const DATA_ATTRIBUTE: &str = "data";
+const EXPIRY_ATTRIBUTE: &str = "expiry_date";
+
+/// An error type for `DynamoDBStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum DynamoDBStoreError {
+    /// A variant to map to `aws_sdk_dynamodb::Error` errors.
+    #[error("DynamoDB error: {0}")]
+    DynamoDB(#[from] aws_sdk_dynamodb::Error),
+
+    /// A variant for sessions which could not be created because an item with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for items which are missing attributes or have attributes of
+    /// unexpected types.
+    #[error("Invalid session item: {0}")]
+    InvalidItem(&'static str),
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A DynamoDB session store.
+///
+/// Sessions are stored as items keyed by their ID, holding the serialized
+/// session as binary `data` and its expiry as a Unix timestamp in
+/// `expiry_date`, which [`migrate`](DynamoDBStore::migrate) registers as the
+/// table's TTL attribute. Because DynamoDB may take some time to remove
+/// expired items, expiry is also checked when loading.
+#[derive(Clone, Debug)]
+pub struct DynamoDBStore {
+    client: Client,
+    table_name: String,
+    partition_key_name: String,
+    clock: SharedClock,
+}
+
+impl DynamoDBStore {
+    /// Create a new DynamoDB store with the provided client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_dynamodb_store::{aws_sdk_dynamodb, DynamoDBStore};
+    ///
+    /// let config = aws_sdk_dynamodb::Config::builder()
+    ///     .behavior_version_latest()
+    ///     .build();
+    /// let client = aws_sdk_dynamodb::Client::from_conf(config);
+    /// let session_store = DynamoDBStore::new(client);
+    /// ```
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            table_name: "tower_sessions".to_string(),
+            partition_key_name: "id".to_string(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Set the session table name with the provided name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_dynamodb_store::{aws_sdk_dynamodb, DynamoDBStore};
+    ///
+    /// # let config = aws_sdk_dynamodb::Config::builder().behavior_version_latest().build();
+    /// # let client = aws_sdk_dynamodb::Client::from_conf(config);
+    /// let session_store = DynamoDBStore::new(client).with_table_name("sessions");
+    /// ```
+    pub fn with_table_name(mut self, table_name: &str) -> Self {
+        self.table_name = table_name.to_string();
+        self
+    }
+
+    /// Set the name of the table's partition key, which holds the session ID.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_dynamodb_store::{aws_sdk_dynamodb, DynamoDBStore};
+    ///
+    /// # let config = aws_sdk_dynamodb::Config::builder().behavior_version_latest().build();
+    /// # let client = aws_sdk_dynamodb::Client::from_conf(config);
+    /// let session_store = DynamoDBStore::new(client).with_partition_key_name("session_id");
+    /// ```
+    pub fn with_partition_key_name(mut self, partition_key_name: &str) -> Self {
+        self.partition_key_name = partition_key_name.to_string();
+        self
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// Note that DynamoDB removes expired items according to its own time.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session table, creating it if it doesn't exist and
+    /// enabling TTL on the expiry attribute.
+    ///
+    /// Tables created here use on-demand billing. Tables with other settings
+    /// may be created ahead of time, so long as the partition key is a string.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_dynamodb_store::{aws_sdk_dynamodb, DynamoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let config = aws_sdk_dynamodb::Config::builder().behavior_version_latest().build();
+    /// # let client = aws_sdk_dynamodb::Client::from_conf(config);
+    /// let session_store = DynamoDBStore::new(client);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), DynamoDBStoreError> {
+        let partition_key_definition = AttributeDefinition::builder()
+            .attribute_name(&self.partition_key_name)
+            .attribute_type(ScalarAttributeType::S)
+            .build()
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        let partition_key_schema = KeySchemaElement::builder()
+            .attribute_name(&self.partition_key_name)
+            .key_type(KeyType::Hash)
+            .build()
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        let created = self
+            .client
+            .create_table()
+            .table_name(&self.table_name)
+            .attribute_definitions(partition_key_definition)
+            .key_schema(partition_key_schema)
+            .billing_mode(BillingMode::PayPerRequest)
+            .send()
+            .await;
+        match created {
+            Ok(_) => {}
+            // The table already exists.
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_resource_in_use_exception()) => {}
+            Err(err) => return Err(aws_sdk_dynamodb::Error::from(err).into()),
+        }
+
+        let time_to_live = self
+            .client
+            .describe_time_to_live()
+            .table_name(&self.table_name)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        let ttl_enabled = time_to_live
+            .time_to_live_description()
+            .and_then(|description| description.time_to_live_status())
+            .is_some_and(|status| {
+                matches!(
+                    status,
+                    TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling
+                )
+            });
+
+        if !ttl_enabled {
+            let specification = TimeToLiveSpecification::builder()
+                .attribute_name(EXPIRY_ATTRIBUTE)
+                .enabled(true)
+                .build()
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+            self.client
+                .update_time_to_live()
+                .table_name(&self.table_name)
+                .time_to_live_specification(specification)
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+        }
+
+        Ok(())
+    }
+
+    fn key(&self, session_id: &Id) -> (String, AttributeValue) {
+        (
+            self.partition_key_name.clone(),
+            AttributeValue::S(session_id.to_string()),
+        )
+    }
+
+    fn item(
+        &self,
+        session: &Session,
+    ) -> Result<HashMap<String, AttributeValue>, rmp_serde::encode::Error> {
+        let (key_name, key) = self.key(session.id());
+        Ok(HashMap::from([
+            (key_name, key),
+            (
+                DATA_ATTRIBUTE.to_string(),
+                AttributeValue::B(Blob::new(rmp_serde::to_vec(session)?)),
+            ),
+            (
+                EXPIRY_ATTRIBUTE.to_string(),
+                AttributeValue::N(session.expiry_date().unix_timestamp().to_string()),
+            ),
+        ]))
+    }
+}
+
+#[async_trait]
+impl SessionStore for DynamoDBStore {
+    type Error = DynamoDBStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(self.item(session)?))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let created = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(self.item(session)?))
+            .condition_expression("attribute_not_exists(#key)")
+            .expression_attribute_names("#key", &self.partition_key_name)
+            .send()
+            .await;
+
+        match created {
+            Ok(_) => Ok(()),
+            Err(SdkError::ServiceError(err))
+                if matches!(err.err(), PutItemError::ConditionalCheckFailedException(_)) =>
+            {
+                Err(DynamoDBStoreError::IdCollision)
+            }
+            Err(err) => Err(aws_sdk_dynamodb::Error::from(err).into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let (key_name, key) = self.key(session_id);
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key(key_name, key)
+            .consistent_read(true)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        let Some(item) = output.item else {
+            return Ok(None);
+        };
+
+        let expiry_date = item
+            .get(EXPIRY_ATTRIBUTE)
+            .and_then(|value| value.as_n().ok())
+            .and_then(|value| value.parse::<i64>().ok())
+            .ok_or(DynamoDBStoreError::InvalidItem(EXPIRY_ATTRIBUTE))?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        let data = item
+            .get(DATA_ATTRIBUTE)
+            .and_then(|value| value.as_b().ok())
+            .ok_or(DynamoDBStoreError::InvalidItem(DATA_ATTRIBUTE))?;
+
+        Ok(Some(rmp_serde::from_slice(data.as_ref())?))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let (key_name, key) = self.key(session_id);
+        self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key(key_name, key)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("dynamodb")
+    }
+}
@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+pub use etcd_client;
+use etcd_client::{Client, Compare, CompareOp, DeleteOptions, PutOptions, Txn, TxnOp};
+use tower_sessions_core::{session::Id, Session, SessionStore};
+
+/// An error type for `EtcdStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum EtcdStoreError {
+    /// A variant to map to `etcd_client::Error` errors.
+    #[error("etcd error: {0}")]
+    Etcd(#[from] etcd_client::Error),
+
+    /// A variant for sessions which could not be created because a key with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// An etcd session store.
+///
+/// Each session is stored under its own key, attached to a lease whose TTL
+/// matches the session's expiry, so that etcd removes sessions once they
+/// expire. Saving a session grants a new lease and revokes the previous one,
+/// and deleting a session revokes its lease.
+///
+/// Note that building `etcd-client` requires the Protocol Buffers compiler,
+/// `protoc`.
+#[derive(Clone)]
+pub struct EtcdStore {
+    client: Client,
+    prefix: String,
+}
+
+impl std::fmt::Debug for EtcdStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EtcdStore")
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EtcdStore {
+    /// Create a new etcd store with the provided client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_etcd_store::{etcd_client::Client, EtcdStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::connect(["localhost:2379"], None).await.unwrap();
+    /// let session_store = EtcdStore::new(client);
+    /// # })
+    /// ```
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            prefix: "tower_sessions/".to_string(),
+        }
+    }
+
+    /// Set the prefix of session keys, which defaults to `tower_sessions/`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_etcd_store::{etcd_client::Client, EtcdStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::connect(["localhost:2379"], None).await.unwrap();
+    /// let session_store = EtcdStore::new(client).with_prefix("my-app/sessions/");
+    /// # })
+    /// ```
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, session_id: &Id) -> String {
+        format!("{}{}", self.prefix, session_id)
+    }
+
+    fn ttl(session: &Session) -> i64 {
+        session.expiry_age().whole_seconds().max(0)
+    }
+
+    /// Revokes the lease, ignoring leases which have already expired.
+    async fn revoke(&self, lease: i64) -> Result<(), EtcdStoreError> {
+        match self.client.clone().lease_revoke(lease).await {
+            Ok(_) => Ok(()),
+            Err(etcd_client::Error::GRpcStatus(status))
+                if status.code() == tonic::Code::NotFound =>
+            {
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for EtcdStore {
+    type Error = EtcdStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        // Leases must have a positive TTL, and expired sessions needn't be stored.
+        let ttl = Self::ttl(session);
+        if ttl == 0 {
+            return self.delete(session.id()).await;
+        }
+
+        let mut client = self.client.clone();
+        let lease = client.lease_grant(ttl, None).await?.id();
+        let response = client
+            .put(
+                self.key(session.id()),
+                rmp_serde::to_vec(session)?,
+                Some(PutOptions::new().with_lease(lease).with_prev_key()),
+            )
+            .await?;
+
+        if let Some(previous) = response.prev_key() {
+            if previous.lease() != 0 && previous.lease() != lease {
+                self.revoke(previous.lease()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // Expired sessions aren't stored, see `save`.
+        let ttl = Self::ttl(session);
+        if ttl == 0 {
+            return Ok(());
+        }
+
+        let mut client = self.client.clone();
+        let key = self.key(session.id());
+        let lease = client.lease_grant(ttl, None).await?.id();
+
+        // A create revision of zero means the key doesn't exist.
+        let txn = Txn::new()
+            .when([Compare::create_revision(key.clone(), CompareOp::Equal, 0)])
+            .and_then([TxnOp::put(
+                key,
+                rmp_serde::to_vec(session)?,
+                Some(PutOptions::new().with_lease(lease)),
+            )]);
+        if !client.txn(txn).await?.succeeded() {
+            self.revoke(lease).await?;
+            return Err(EtcdStoreError::IdCollision);
+        }
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let response = self.client.clone().get(self.key(session_id), None).await?;
+
+        if let Some(kv) = response.kvs().first() {
+            Ok(Some(rmp_serde::from_slice(kv.value())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let response = self
+            .client
+            .clone()
+            .delete(
+                self.key(session_id),
+                Some(DeleteOptions::new().with_prev_key()),
+            )
+            .await?;
+
+        for previous in response.prev_kvs() {
+            if previous.lease() != 0 {
+                self.revoke(previous.lease()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("etcd")
+    }
+}
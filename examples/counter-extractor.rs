@@ -9,7 +9,9 @@ use http::{request::Parts, StatusCode};
 use serde::{Deserialize, Serialize};
 use time::Duration;
 use tower::ServiceBuilder;
-use tower_sessions::{Expiry, MemoryStore, Session, SessionManagerLayer};
+use tower_sessions::{
+    extract::SessionRejection, Expiry, MemoryStore, Session, SessionManagerLayer,
+};
 
 const COUNTER_KEY: &str = "counter";
 
@@ -21,7 +23,7 @@ impl<S> FromRequestParts<S> for Counter
 where
     S: Send + Sync,
 {
-    type Rejection = (http::StatusCode, &'static str);
+    type Rejection = SessionRejection;
 
     async fn from_request_parts(req: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let session = Session::from_request_parts(req, state).await?;
@@ -0,0 +1,275 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use serde::de::IgnoredAny;
+use tokio::fs;
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// Prefix of temporary files, which can't collide with session IDs.
+const TEMPORARY_PREFIX: &str = ".tmp-";
+
+/// An error type for `FileStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum FileStoreError {
+    /// A variant to map to `std::io::Error` errors.
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A variant for sessions which could not be created because a file with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A filesystem session store.
+///
+/// Each session is stored as a file named after its ID in the configured
+/// directory, holding its expiry date alongside the serialized session. Files
+/// are written to a temporary file first and then moved into place, so
+/// readers never observe partially written sessions.
+///
+/// Expired sessions are ignored when loading, replaced by sessions created
+/// with the same ID, and may be removed with
+/// [`delete_expired`](ExpiredDeletion::delete_expired), which skips files it
+/// can't decode.
+///
+/// This is intended for single-node deployments: the directory shouldn't be
+/// shared by stores on several machines unless the filesystem provides atomic
+/// renames and hard links.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    clock: SharedClock,
+    counter: Arc<AtomicU64>,
+}
+
+impl FileStore {
+    /// Create a new filesystem store storing sessions in the provided
+    /// directory.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_file_store::FileStore;
+    ///
+    /// let session_store = FileStore::new("/var/lib/my-app/sessions");
+    /// ```
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            clock: SharedClock::default(),
+            counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session directory, creating it if it doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_file_store::FileStore;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = FileStore::new("/var/lib/my-app/sessions");
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), FileStoreError> {
+        fs::create_dir_all(&self.dir).await?;
+        Ok(())
+    }
+
+    fn path(&self, session_id: &Id) -> PathBuf {
+        self.dir.join(session_id.to_string())
+    }
+
+    /// Returns the path of a new temporary file for the session file of the
+    /// given name.
+    fn temporary_path(&self, name: &str) -> PathBuf {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        self.dir.join(format!(
+            "{TEMPORARY_PREFIX}{name}-{}-{counter}",
+            std::process::id()
+        ))
+    }
+
+    /// Writes the session to a new temporary file and returns its path.
+    async fn write_temporary(&self, session: &Session) -> Result<PathBuf, FileStoreError> {
+        let record = rmp_serde::to_vec(&(session.expiry_date().unix_timestamp(), session))?;
+
+        let path = self.temporary_path(&session.id().to_string());
+        fs::write(&path, record).await?;
+
+        Ok(path)
+    }
+
+    async fn read_expiry(path: &Path) -> Result<Option<i64>, FileStoreError> {
+        match fs::read(path).await {
+            Ok(record) => {
+                let (expiry_date, _): (i64, IgnoredAny) = rmp_serde::from_slice(&record)?;
+                Ok(Some(expiry_date))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Removes the session file at the path if it has expired, returning
+    /// whether it did.
+    ///
+    /// The file is moved aside before it's removed, and checked again then, so
+    /// that a session saved in its place since its expiry was read isn't
+    /// removed instead: such a file is moved back, unless another session has
+    /// been saved in its place by then.
+    async fn remove_if_expired(&self, path: &Path, now: i64) -> Result<bool, FileStoreError> {
+        let Some(expiry_date) = Self::read_expiry(path).await? else {
+            return Ok(false);
+        };
+        if expiry_date > now {
+            return Ok(false);
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let moved_path = self.temporary_path(&name);
+        match fs::rename(path, &moved_path).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+
+        let is_expired = matches!(
+            Self::read_expiry(&moved_path).await,
+            Ok(Some(expiry_date)) if expiry_date <= now
+        );
+        if !is_expired {
+            match fs::hard_link(&moved_path, path).await {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        fs::remove_file(&moved_path).await?;
+
+        Ok(is_expired)
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for FileStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let now = self.clock.now().unix_timestamp();
+
+        let mut entries = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(TEMPORARY_PREFIX)
+            {
+                continue;
+            }
+
+            let path = entry.path();
+            match self.remove_if_expired(&path, now).await {
+                Ok(_) => {}
+                // A file which isn't a session mustn't stop the others from being removed.
+                Err(FileStoreError::RmpSerdeDecode(err)) => {
+                    tracing::warn!(err = %err, path = %path.display(), "skipped undecodable session file");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileStore {
+    type Error = FileStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let temporary_path = self.write_temporary(session).await?;
+        if let Err(err) = fs::rename(&temporary_path, self.path(session.id())).await {
+            let _ = fs::remove_file(&temporary_path).await;
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // Unlike renaming, linking fails if the session file already exists.
+        let temporary_path = self.write_temporary(session).await?;
+        let path = self.path(session.id());
+        let mut linked = fs::hard_link(&temporary_path, &path).await;
+
+        // Files may linger after their sessions expire, until deleted.
+        if matches!(&linked, Err(err) if err.kind() == ErrorKind::AlreadyExists) {
+            let now = self.clock.now().unix_timestamp();
+            match self.remove_if_expired(&path, now).await {
+                Ok(true) => linked = fs::hard_link(&temporary_path, &path).await,
+                Ok(false) => {}
+                Err(err) => {
+                    let _ = fs::remove_file(&temporary_path).await;
+                    return Err(err);
+                }
+            }
+        }
+        fs::remove_file(&temporary_path).await?;
+
+        match linked {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => Err(FileStoreError::IdCollision),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let record = match fs::read(self.path(session_id)).await {
+            Ok(record) => record,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let (expiry_date, session): (i64, Session) = rmp_serde::from_slice(&record)?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        match fs::remove_file(self.path(session_id)).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use async_trait::async_trait;
+pub use firestore;
+use firestore::{errors::FirestoreError, FirestoreDb, FirestoreInstant, FirestoreTimestamp};
+use serde::{Deserialize, Serialize};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// The maximum number of expired sessions removed per batch by
+/// [`delete_expired`](ExpiredDeletion::delete_expired), which is also the
+/// maximum number of writes in a Firestore batch.
+const DELETE_EXPIRED_BATCH_SIZE: u32 = 500;
+
+/// An error type for `FirestoreStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum FirestoreStoreError {
+    /// A variant to map to `firestore::errors::FirestoreError` errors.
+    #[error("Firestore error: {0}")]
+    Firestore(#[from] FirestoreError),
+
+    /// A variant for sessions which could not be created because a document
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for expiry dates out of the range of Firestore timestamps.
+    #[error("Invalid expiry date")]
+    InvalidExpiryDate,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SessionDocument {
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+    expiry_date: FirestoreTimestamp,
+}
+
+/// A Firestore session store.
+///
+/// Sessions are stored as documents of the session collection, named after
+/// their ID, holding the serialized session and its expiry date as a
+/// timestamp. [`migrate`](FirestoreStore::migrate) enables a TTL policy on
+/// the expiry date, so Firestore removes expired sessions by itself.
+///
+/// TTL policies remove documents some time after they expire, typically
+/// within a day, so expired sessions are also ignored when loading, and may
+/// be removed promptly with
+/// [`delete_expired`](ExpiredDeletion::delete_expired). Sessions are created
+/// in transactions, which treat a document left behind by an expired session
+/// as free.
+#[derive(Clone)]
+pub struct FirestoreStore {
+    db: FirestoreDb,
+    collection_name: String,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for FirestoreStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FirestoreStore")
+            .field("collection_name", &self.collection_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FirestoreStore {
+    /// Create a new Firestore store with the provided client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_firestore_store::{firestore, FirestoreStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = firestore::FirestoreDb::new("my-project").await.unwrap();
+    /// let session_store = FirestoreStore::new(db);
+    /// # })
+    /// ```
+    pub fn new(db: FirestoreDb) -> Self {
+        Self {
+            db,
+            collection_name: "tower_sessions".to_string(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Set the session collection name with the provided name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_firestore_store::{firestore, FirestoreStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = firestore::FirestoreDb::new("my-project").await.unwrap();
+    /// let session_store = FirestoreStore::new(db).with_collection_name("sessions");
+    /// # })
+    /// ```
+    pub fn with_collection_name(mut self, collection_name: &str) -> Self {
+        self.collection_name = collection_name.to_string();
+        self
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session collection, enabling a TTL policy on the expiry
+    /// date of sessions.
+    ///
+    /// This uses the Firestore Admin API, and returns once the policy is
+    /// requested; Firestore may take a few minutes to activate it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_firestore_store::{firestore, FirestoreStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = firestore::FirestoreDb::new("my-project").await.unwrap();
+    /// let session_store = FirestoreStore::new(db);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), FirestoreStoreError> {
+        self.db
+            .fluent()
+            .indexes()
+            .collection_group(&self.collection_name)
+            .ttl(["expiry_date"])
+            .sync()
+            .await?;
+
+        Ok(())
+    }
+
+    fn now(&self) -> Result<FirestoreTimestamp, FirestoreStoreError> {
+        timestamp(self.clock.now().unix_timestamp())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for FirestoreStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let now = self.now()?;
+        loop {
+            let expired = self
+                .db
+                .fluent()
+                .select()
+                .fields(["expiry_date"])
+                .from(self.collection_name.as_str())
+                .filter(|q| q.for_all([q.field("expiry_date").less_than(now)]))
+                .limit(DELETE_EXPIRED_BATCH_SIZE)
+                .query()
+                .await?;
+
+            if expired.is_empty() {
+                break;
+            }
+
+            let writer = self.db.create_simple_batch_writer().await?;
+            let mut batch = writer.new_batch();
+            for document in &expired {
+                let document_id = document.name.rsplit('/').next().unwrap_or_default();
+                batch.delete_by_id(&self.collection_name, document_id, None)?;
+            }
+            batch.write().await?;
+
+            if expired.len() < DELETE_EXPIRED_BATCH_SIZE as usize {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for FirestoreStore {
+    type Error = FirestoreStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let document = SessionDocument {
+            data: rmp_serde::to_vec(session)?,
+            expiry_date: timestamp(session.expiry_date().unix_timestamp())?,
+        };
+        let _: SessionDocument = self
+            .db
+            .fluent()
+            .update()
+            .in_col(&self.collection_name)
+            .document_id(session.id().to_string())
+            .object(&document)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let document = SessionDocument {
+            data: rmp_serde::to_vec(session)?,
+            expiry_date: timestamp(session.expiry_date().unix_timestamp())?,
+        };
+        let document_id = session.id().to_string();
+        let now = self.now()?;
+
+        // Transactions may be retried, so each attempt reads the document anew.
+        let created = self
+            .db
+            .run_transaction(|db, transaction| {
+                let collection_name = self.collection_name.clone();
+                let document_id = document_id.clone();
+                let document = document.clone();
+                Box::pin(async move {
+                    let existing: Option<SessionDocument> = db
+                        .fluent()
+                        .select()
+                        .by_id_in(&collection_name)
+                        .obj()
+                        .one(&document_id)
+                        .await?;
+                    if existing.is_some_and(|existing| existing.expiry_date > now) {
+                        return Ok(false);
+                    }
+
+                    db.fluent()
+                        .update()
+                        .in_col(&collection_name)
+                        .document_id(&document_id)
+                        .object(&document)
+                        .add_to_transaction(transaction)?;
+
+                    Ok::<_, firestore::errors::BackoffError<FirestoreError>>(true)
+                })
+            })
+            .await?;
+
+        if created {
+            Ok(())
+        } else {
+            Err(FirestoreStoreError::IdCollision)
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let document: Option<SessionDocument> = self
+            .db
+            .fluent()
+            .select()
+            .by_id_in(&self.collection_name)
+            .obj()
+            .one(session_id.to_string())
+            .await?;
+
+        let now = self.now()?;
+        match document {
+            Some(document) if document.expiry_date > now => {
+                Ok(Some(rmp_serde::from_slice(&document.data)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.db
+            .fluent()
+            .delete()
+            .from(&self.collection_name)
+            .document_id(session_id.to_string())
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("firestore")
+    }
+}
+
+fn timestamp(unix_timestamp: i64) -> Result<FirestoreTimestamp, FirestoreStoreError> {
+    FirestoreInstant::from_second(unix_timestamp)
+        .map(FirestoreTimestamp)
+        .map_err(|_| FirestoreStoreError::InvalidExpiryDate)
+}
@@ -0,0 +1,394 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+pub use foundationdb;
+use foundationdb::{
+    options::MutationType,
+    tuple::{self, Bytes, Subspace, Versionstamp},
+    Database, FdbBindingError, RangeOption,
+};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// The number of expired sessions removed per transaction by
+/// [`delete_expired`](ExpiredDeletion::delete_expired), keeping transactions
+/// well within FoundationDB's size and duration limits.
+const DELETION_BATCH_SIZE: usize = 1000;
+
+/// An error type for `FoundationDbStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum FoundationDbStoreError {
+    /// A variant to map to `foundationdb::FdbBindingError` errors.
+    #[error("FoundationDB error: {0}")]
+    FoundationDb(#[source] FdbBindingError),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for sessions which could not be saved because they were
+    /// changed or deleted since they were loaded.
+    ///
+    /// This is only returned when optimistic concurrency is enabled.
+    #[error("Session was modified concurrently")]
+    Conflict,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+impl From<FdbBindingError> for FoundationDbStoreError {
+    fn from(err: FdbBindingError) -> Self {
+        // Errors of the store itself are passed through transactions as
+        // custom errors, see `abort`.
+        match err {
+            FdbBindingError::CustomError(err) => match err.downcast::<Self>() {
+                Ok(err) => *err,
+                Err(err) => Self::FoundationDb(FdbBindingError::CustomError(err)),
+            },
+            err => Self::FoundationDb(err),
+        }
+    }
+}
+
+/// Aborts a transaction with an error of the store.
+fn abort(err: FoundationDbStoreError) -> FdbBindingError {
+    FdbBindingError::CustomError(Box::new(err))
+}
+
+/// The outcome of writing a session record.
+enum Written<F> {
+    /// The record was written by a previous attempt whose commit outcome was
+    /// unknown, with the given versionstamp.
+    Previously(Versionstamp),
+
+    /// The record was written, with the versionstamp resolved by the future
+    /// once the transaction is committed.
+    Pending(F),
+}
+
+/// A FoundationDB session store.
+///
+/// Sessions are stored in the configured subspace, under their ID, alongside
+/// their expiry date and the versionstamp of the transaction which last wrote
+/// them. Expiry dates are indexed with versionstamped keys, so that
+/// [`delete_expired`](ExpiredDeletion::delete_expired) only visits expired
+/// sessions. With the `deletion-task` feature, this can be run periodically
+/// in the background with `continuously_delete_expired`.
+///
+/// Saving and creating sessions happen in a single serializable transaction,
+/// which is retried on conflicts with other transactions. Versionstamps also
+/// allow opting in to optimistic concurrency, see
+/// [`with_optimistic_concurrency`](FoundationDbStore::with_optimistic_concurrency).
+///
+/// The FoundationDB network thread must be started with
+/// [`foundationdb::boot`] before using the store, and stay running for as
+/// long as it's used. Building the bindings requires `libclang`, and linking
+/// them requires the FoundationDB client library, `libfdb_c`.
+#[derive(Clone)]
+pub struct FoundationDbStore {
+    db: Arc<Database>,
+    sessions: Subspace,
+    expiries: Subspace,
+    optimistic_concurrency: bool,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for FoundationDbStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FoundationDbStore")
+            .field("sessions", &self.sessions)
+            .field("expiries", &self.expiries)
+            .field("optimistic_concurrency", &self.optimistic_concurrency)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FoundationDbStore {
+    /// Create a new FoundationDB store with the provided database.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_foundationdb_store::{foundationdb, FoundationDbStore};
+    ///
+    /// let network = unsafe { foundationdb::boot() };
+    /// let db = foundationdb::Database::default().unwrap();
+    /// let session_store = FoundationDbStore::new(db);
+    /// ```
+    pub fn new(db: Database) -> Self {
+        Self::from_subspace(
+            Arc::new(db),
+            Subspace::all().subspace(&"tower_sessions"),
+            false,
+            SharedClock::default(),
+        )
+    }
+
+    fn from_subspace(
+        db: Arc<Database>,
+        subspace: Subspace,
+        optimistic_concurrency: bool,
+        clock: SharedClock,
+    ) -> Self {
+        Self {
+            db,
+            sessions: subspace.subspace(&"session"),
+            expiries: subspace.subspace(&"expiry"),
+            optimistic_concurrency,
+            clock,
+        }
+    }
+
+    /// Set the subspace sessions are stored in, which defaults to the
+    /// `tower_sessions` tuple.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_foundationdb_store::{
+    ///     foundationdb::{self, tuple::Subspace},
+    ///     FoundationDbStore,
+    /// };
+    ///
+    /// let network = unsafe { foundationdb::boot() };
+    /// let db = foundationdb::Database::default().unwrap();
+    /// let session_store =
+    ///     FoundationDbStore::new(db).with_subspace(Subspace::all().subspace(&("my-app", "sessions")));
+    /// ```
+    pub fn with_subspace(self, subspace: Subspace) -> Self {
+        Self::from_subspace(self.db, subspace, self.optimistic_concurrency, self.clock)
+    }
+
+    /// Enable optimistic concurrency using the versionstamps of session
+    /// records.
+    ///
+    /// When enabled, the commit version of the transaction which last wrote a
+    /// loaded session is kept as its [`version`](Session::version), and saving
+    /// it only succeeds if the record hasn't been changed or deleted in the
+    /// meantime. Otherwise, [`FoundationDbStoreError::Conflict`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_foundationdb_store::{foundationdb, FoundationDbStore};
+    ///
+    /// let network = unsafe { foundationdb::boot() };
+    /// let db = foundationdb::Database::default().unwrap();
+    /// let session_store = FoundationDbStore::new(db).with_optimistic_concurrency(true);
+    /// ```
+    pub fn with_optimistic_concurrency(mut self, optimistic_concurrency: bool) -> Self {
+        self.optimistic_concurrency = optimistic_concurrency;
+        self
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Writes the session record and its expiry index entry, failing if a
+    /// record exists when creating or, with optimistic concurrency, if the
+    /// record changed since the session was loaded.
+    async fn write(&self, session: &Session, create: bool) -> Result<(), FoundationDbStoreError> {
+        let key = self.sessions.pack(&session.id().to_string());
+        let expiry_date = session.expiry_date().unix_timestamp();
+        let record = rmp_serde::to_vec(session)?;
+        let expected_version = if self.optimistic_concurrency && !create {
+            session.version()
+        } else {
+            None
+        };
+
+        let (key, record) = (&key, &record);
+        let written = self
+            .db
+            .run(|trx, maybe_committed| async move {
+                if let Some(previous) = trx.get(key, false).await? {
+                    let (versionstamp, previous_expiry_date, previous_record) =
+                        unpack_record(&previous)?;
+
+                    // A previous attempt may have committed before failing.
+                    if bool::from(maybe_committed) && *previous_record == record[..] {
+                        return Ok(Written::Previously(versionstamp));
+                    }
+
+                    if create {
+                        return Err(abort(FoundationDbStoreError::IdCollision));
+                    }
+                    if let Some(expected_version) = expected_version {
+                        if expected_version != version(&versionstamp) {
+                            return Err(abort(FoundationDbStoreError::Conflict));
+                        }
+                    }
+
+                    trx.clear(&self.expiries.pack(&(previous_expiry_date, &versionstamp)));
+                } else if expected_version.is_some() {
+                    return Err(abort(FoundationDbStoreError::Conflict));
+                }
+
+                // Both the record and its index entry get the versionstamp of
+                // this transaction when it commits.
+                let versionstamp = Versionstamp::incomplete(0);
+                trx.atomic_op(
+                    key,
+                    &tuple::pack_with_versionstamp(&(
+                        &versionstamp,
+                        expiry_date,
+                        Bytes::from(&record[..]),
+                    )),
+                    MutationType::SetVersionstampedValue,
+                );
+                trx.atomic_op(
+                    &self
+                        .expiries
+                        .pack_with_versionstamp(&(expiry_date, &versionstamp)),
+                    key,
+                    MutationType::SetVersionstampedKey,
+                );
+
+                Ok(Written::Pending(trx.get_versionstamp()))
+            })
+            .await?;
+
+        let versionstamp = match written {
+            Written::Previously(versionstamp) => versionstamp,
+            Written::Pending(versionstamp) => {
+                versionstamp_from(versionstamp.await.map_err(FdbBindingError::from)?.as_ref())?
+            }
+        };
+        if self.optimistic_concurrency {
+            session.set_version(Some(version(&versionstamp)));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for FoundationDbStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let now = self.clock.now().unix_timestamp();
+        let (begin, _) = self.expiries.range();
+        let end = self.expiries.pack(&now.saturating_add(1));
+
+        let (begin, end) = (&begin, &end);
+        loop {
+            let deleted = self
+                .db
+                .run(|trx, _| async move {
+                    let range = RangeOption {
+                        limit: Some(DELETION_BATCH_SIZE),
+                        ..RangeOption::from((begin.as_slice(), end.as_slice()))
+                    };
+                    let expired = trx.get_range(&range, 1, false).await?;
+                    for entry in expired.iter() {
+                        trx.clear(entry.key());
+                        trx.clear(entry.value());
+                    }
+                    Ok(expired.len())
+                })
+                .await?;
+
+            if deleted < DELETION_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for FoundationDbStore {
+    type Error = FoundationDbStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, false).await
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, true).await
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let key = &self.sessions.pack(&session_id.to_string());
+        let record = self
+            .db
+            .run(|trx, _| async move {
+                let Some(record) = trx.get(key, false).await? else {
+                    return Ok(None);
+                };
+                let (versionstamp, expiry_date, record) = unpack_record(&record)?;
+                Ok(Some((versionstamp, expiry_date, record.to_vec())))
+            })
+            .await?;
+
+        let Some((versionstamp, expiry_date, record)) = record else {
+            return Ok(None);
+        };
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        let session: Session = rmp_serde::from_slice(&record)?;
+        if self.optimistic_concurrency {
+            session.set_version(Some(version(&versionstamp)));
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let key = &self.sessions.pack(&session_id.to_string());
+        self.db
+            .run(|trx, _| async move {
+                if let Some(previous) = trx.get(key, false).await? {
+                    let (versionstamp, expiry_date, _) = unpack_record(&previous)?;
+                    trx.clear(&self.expiries.pack(&(expiry_date, &versionstamp)));
+                    trx.clear(key);
+                }
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("foundationdb")
+    }
+}
+
+/// Unpacks a session record into the versionstamp of the transaction which
+/// wrote it, the session's expiry date, and the serialized session.
+fn unpack_record(record: &[u8]) -> Result<(Versionstamp, i64, Bytes<'_>), FdbBindingError> {
+    tuple::unpack(record).map_err(FdbBindingError::PackError)
+}
+
+/// Builds the versionstamp of a committed transaction from the value resolved
+/// by `get_versionstamp`.
+fn versionstamp_from(bytes: &[u8]) -> Result<Versionstamp, FdbBindingError> {
+    let transaction_version = bytes.try_into().map_err(|_| {
+        FdbBindingError::PackError(tuple::PackError::Message(
+            "invalid versionstamp length".into(),
+        ))
+    })?;
+    Ok(Versionstamp::complete(transaction_version, 0))
+}
+
+/// The commit version of the transaction a versionstamp was assigned by,
+/// which increases with every write of a record.
+fn version(versionstamp: &Versionstamp) -> u64 {
+    let mut commit_version = [0; 8];
+    commit_version.copy_from_slice(&versionstamp.transaction_version()[..8]);
+    u64::from_be_bytes(commit_version)
+}
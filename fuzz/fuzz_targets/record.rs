@@ -0,0 +1,27 @@
+//! Decodes arbitrary bytes as a stored session record, as the stores do when
+//! loading sessions, and exercises sessions which decode successfully.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tower_sessions::Session;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(session) = rmp_serde::from_slice::<Session>(data) else {
+        return;
+    };
+
+    let _ = session.expiry_date();
+    let _ = session.expiry_age();
+    let _ = session.expiry_limit();
+    let _ = session.principal();
+    let _ = session.is_empty();
+    for (key, _) in session.data() {
+        let _ = session.get::<serde_json::Value>(&key);
+    }
+
+    // Whatever decodes must survive being saved and loaded again.
+    let encoded = rmp_serde::to_vec(&session).expect("decoded sessions encode");
+    let decoded: Session = rmp_serde::from_slice(&encoded).expect("encoded sessions decode");
+    assert_eq!(decoded.id(), session.id());
+    assert_eq!(decoded.data(), session.data());
+});
@@ -0,0 +1,59 @@
+//! Sends requests carrying arbitrary `Cookie` headers through the session
+//! middleware, covering session ID parsing as well as the verification of
+//! signed and private session cookies.
+//!
+//! The first byte of the input selects the cookie jar and whether strict mode
+//! is enabled; the remaining bytes are the header value.
+#![no_main]
+
+use std::{convert::Infallible, sync::LazyLock};
+
+use http::{header, HeaderValue, Request, Response};
+use libfuzzer_sys::fuzz_target;
+use tokio::runtime::Runtime;
+use tower::{service_fn, Layer, ServiceExt};
+use tower_sessions::{cookie::Key, MemoryStore, Session, SessionManagerLayer};
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+});
+
+/// A fixed key, so that inputs may carry cookies which verify.
+static KEY: LazyLock<Key> = LazyLock::new(|| Key::from(&[7; 64]));
+
+async fn handler(req: Request<()>) -> Result<Response<()>, Infallible> {
+    let session = req
+        .extensions()
+        .get::<Session>()
+        .cloned()
+        .expect("the session is provided");
+    let _ = session.get_value("foo");
+    session.insert_value("foo", 42.into());
+
+    Ok(Response::new(()))
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&config, cookie)) = data.split_first() else {
+        return;
+    };
+    let Ok(cookie) = HeaderValue::from_bytes(cookie) else {
+        return;
+    };
+
+    let session_manager = SessionManagerLayer::new(MemoryStore::default());
+    let session_manager = match config % 3 {
+        0 => session_manager,
+        1 => session_manager.with_signed(KEY.clone()),
+        _ => session_manager.with_private(KEY.clone()),
+    };
+    let session_manager = session_manager.with_strict_mode(config & 0x80 != 0);
+
+    let mut req = Request::new(());
+    req.headers_mut().insert(header::COOKIE, cookie);
+
+    // Errors are fine, but the middleware must not panic.
+    let _ = RUNTIME.block_on(session_manager.layer(service_fn(handler)).oneshot(req));
+});
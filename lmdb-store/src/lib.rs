@@ -0,0 +1,214 @@
+use std::ops::Bound;
+
+use async_trait::async_trait;
+pub use heed;
+use heed::{
+    types::{Bytes, Str, Unit},
+    Database, Env, RwTxn,
+};
+use serde::de::IgnoredAny;
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// An error type for `LmdbStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum LmdbStoreError {
+    /// A variant to map to `heed::Error` errors.
+    #[error("LMDB error: {0}")]
+    Heed(#[from] heed::Error),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// An LMDB session store.
+///
+/// Sessions are stored in the `tower_sessions` database alongside their
+/// expiry date, which is also indexed in the `tower_sessions_expiry` database
+/// so that [`delete_expired`](ExpiredDeletion::delete_expired) only visits
+/// expired sessions. With the `deletion-task` feature, this can be run
+/// periodically in the background with `continuously_delete_expired`.
+///
+/// Each operation runs in its own transaction, which LMDB commits durably
+/// unless the environment was opened with flags relaxing syncs, so a crash
+/// never leaves sessions and their index out of step.
+///
+/// Note that LMDB operations are synchronous; reads are served from the
+/// memory map, while writes are serialized and may block on IO.
+#[derive(Clone, Debug)]
+pub struct LmdbStore {
+    env: Env,
+    sessions: Database<Str, Bytes>,
+    expiries: Database<Bytes, Unit>,
+    clock: SharedClock,
+}
+
+impl LmdbStore {
+    /// Create a new LMDB store with the provided environment, creating the
+    /// session databases if they don't exist.
+    ///
+    /// The environment must allow at least two named databases.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_lmdb_store::{heed::EnvOpenOptions, LmdbStore};
+    ///
+    /// let env = unsafe {
+    ///     EnvOpenOptions::new()
+    ///         .map_size(1024 * 1024 * 1024)
+    ///         .max_dbs(2)
+    ///         .open("sessions")
+    ///         .unwrap()
+    /// };
+    /// let session_store = LmdbStore::new(env).unwrap();
+    /// ```
+    pub fn new(env: Env) -> Result<Self, LmdbStoreError> {
+        let mut wtxn = env.write_txn()?;
+        let sessions = env.create_database(&mut wtxn, Some("tower_sessions"))?;
+        let expiries = env.create_database(&mut wtxn, Some("tower_sessions_expiry"))?;
+        wtxn.commit()?;
+
+        Ok(Self {
+            env,
+            sessions,
+            expiries,
+            clock: SharedClock::default(),
+        })
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    fn write(&self, session: &Session, overwrite: bool) -> Result<(), LmdbStoreError> {
+        let key = session.id().to_string();
+        let expiry_date = session.expiry_date().unix_timestamp();
+        let record = rmp_serde::to_vec(&(expiry_date, session))?;
+
+        let mut wtxn = self.env.write_txn()?;
+        if let Some(previous) = self.sessions.get(&wtxn, &key)? {
+            if !overwrite {
+                // Dropping the transaction without committing aborts it.
+                return Err(LmdbStoreError::IdCollision);
+            }
+            let previous_expiry_date = record_expiry_date(previous)?;
+            self.remove_expiry(&mut wtxn, &key, previous_expiry_date)?;
+        }
+
+        self.sessions.put(&mut wtxn, &key, &record)?;
+        self.expiries
+            .put(&mut wtxn, &expiry_key(expiry_date, &key), &())?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    fn remove_expiry(
+        &self,
+        wtxn: &mut RwTxn,
+        key: &str,
+        expiry_date: i64,
+    ) -> Result<(), LmdbStoreError> {
+        self.expiries.delete(wtxn, &expiry_key(expiry_date, key))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for LmdbStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let now = self.clock.now().unix_timestamp();
+        let end = expiry_key(now.saturating_add(1), "");
+        let range = (Bound::Unbounded, Bound::Excluded(end.as_slice()));
+
+        let mut wtxn = self.env.write_txn()?;
+        let expired = self
+            .expiries
+            .range(&wtxn, &range)?
+            .map(|entry| {
+                entry.map(|(index_key, _)| String::from_utf8_lossy(&index_key[8..]).into_owned())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for key in expired {
+            self.sessions.delete(&mut wtxn, &key)?;
+        }
+        self.expiries.delete_range(&mut wtxn, &range)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for LmdbStore {
+    type Error = LmdbStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, true)
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, false)
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let rtxn = self.env.read_txn()?;
+        let Some(record) = self.sessions.get(&rtxn, &session_id.to_string())? else {
+            return Ok(None);
+        };
+
+        let (expiry_date, session): (i64, Session) = rmp_serde::from_slice(record)?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let key = session_id.to_string();
+
+        let mut wtxn = self.env.write_txn()?;
+        if let Some(previous) = self.sessions.get(&wtxn, &key)? {
+            let previous_expiry_date = record_expiry_date(previous)?;
+            self.remove_expiry(&mut wtxn, &key, previous_expiry_date)?;
+            self.sessions.delete(&mut wtxn, &key)?;
+        }
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("lmdb")
+    }
+}
+
+fn record_expiry_date(record: &[u8]) -> Result<i64, LmdbStoreError> {
+    let (expiry_date, _): (i64, IgnoredAny) = rmp_serde::from_slice(record)?;
+    Ok(expiry_date)
+}
+
+/// Builds the key of a session in the expiry index: its expiry date, with the
+/// sign bit flipped so that keys sort by date bytewise, followed by its ID.
+fn expiry_key(expiry_date: i64, key: &str) -> Vec<u8> {
+    let mut index_key = ((expiry_date as u64) ^ (1 << 63)).to_be_bytes().to_vec();
+    index_key.extend_from_slice(key.as_bytes());
+    index_key
+}
@@ -0,0 +1,205 @@
+use std::{fmt, sync::Arc};
+
+pub use async_memcached;
+use async_memcached::{AsciiProtocol, Client, MetaProtocol, MetaValue, Status};
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tower_sessions_core::{session::Id, Session, SessionStore};
+
+/// An error type for `MemcachedStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum MemcachedStoreError {
+    /// A variant to map to `async_memcached::Error` errors.
+    #[error("Memcached error: {0}")]
+    Memcached(#[from] async_memcached::Error),
+
+    /// A variant for sessions which could not be created because an item with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for sessions which could not be saved because they were
+    /// changed or deleted since they were loaded.
+    ///
+    /// This is only returned when optimistic concurrency is enabled.
+    #[error("Session was modified concurrently")]
+    Conflict,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A Memcached session store.
+///
+/// Sessions are stored under their ID with the session's expiry date as the
+/// item's expiration time, so memcached evicts expired sessions itself.
+///
+/// Memcached connections handle one request at a time; the client is shared
+/// by clones of the store behind a mutex.
+#[derive(Clone)]
+pub struct MemcachedStore {
+    client: Arc<Mutex<Client>>,
+    optimistic_concurrency: bool,
+}
+
+impl fmt::Debug for MemcachedStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemcachedStore")
+            .field("optimistic_concurrency", &self.optimistic_concurrency)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemcachedStore {
+    /// Create a new Memcached store with the provided client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_memcached_store::{async_memcached::Client, MemcachedStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::new("tcp://localhost:11211").await.unwrap();
+    /// let session_store = MemcachedStore::new(client);
+    /// # })
+    /// ```
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+            optimistic_concurrency: false,
+        }
+    }
+
+    /// Enable optimistic concurrency using memcached's compare-and-swap.
+    ///
+    /// When enabled, the CAS value of a loaded session is kept as its
+    /// [`version`](Session::version) and saving it only succeeds if the item
+    /// hasn't been changed or deleted in the meantime. Otherwise,
+    /// [`MemcachedStoreError::Conflict`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_memcached_store::{async_memcached::Client, MemcachedStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::new("tcp://localhost:11211").await.unwrap();
+    /// let session_store = MemcachedStore::new(client).with_optimistic_concurrency(true);
+    /// # })
+    /// ```
+    pub fn with_optimistic_concurrency(mut self, optimistic_concurrency: bool) -> Self {
+        self.optimistic_concurrency = optimistic_concurrency;
+        self
+    }
+
+    async fn set(&self, session: &Session, mode: Option<&str>) -> Result<(), MemcachedStoreError> {
+        let key = session.id().to_string();
+        let data = rmp_serde::to_vec(session)?;
+
+        // Expiration times beyond 30 days are interpreted as Unix timestamps.
+        let ttl = format!("T{}", session.expiry_date().unix_timestamp());
+        let cas = session.version().map(|cas| format!("C{cas}"));
+        let mut meta_flags = vec![ttl.as_str()];
+        meta_flags.extend(mode);
+        if self.optimistic_concurrency {
+            meta_flags.push("c");
+            meta_flags.extend(cas.as_deref());
+        }
+
+        let result = self
+            .client
+            .lock()
+            .await
+            .meta_set(&key, data.as_slice(), false, None, Some(&meta_flags))
+            .await;
+
+        match result {
+            Ok(Some(MetaValue {
+                status: Some(Status::Stored) | None,
+                cas,
+                ..
+            })) => {
+                if self.optimistic_concurrency {
+                    session.set_version(cas);
+                }
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Ok(Some(MetaValue {
+                status: Some(status),
+                ..
+            }))
+            | Err(async_memcached::Error::Protocol(status)) => match status {
+                Status::NotStored => Err(MemcachedStoreError::IdCollision),
+                Status::Exists | Status::NotFound => Err(MemcachedStoreError::Conflict),
+                status => Err(async_memcached::Error::Protocol(status).into()),
+            },
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemcachedStore {
+    type Error = MemcachedStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.set(session, None).await
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // The "E" mode only stores the item if it doesn't exist yet.
+        session.set_version(None);
+        self.set(session, Some("ME")).await
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let key = session_id.to_string();
+        let mut client = self.client.lock().await;
+
+        let (data, cas) = if self.optimistic_concurrency {
+            match client
+                .meta_get(&key, false, None, Some(&["v", "c"]))
+                .await?
+            {
+                Some(value) => (value.data, value.cas),
+                None => return Ok(None),
+            }
+        } else {
+            match client.get(&key).await? {
+                Some(value) => (value.data, None),
+                None => return Ok(None),
+            }
+        };
+
+        let Some(data) = data else {
+            return Ok(None);
+        };
+        let session: Session = rmp_serde::from_slice(&data)?;
+        session.set_version(cas);
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        match self
+            .client
+            .lock()
+            .await
+            .delete(session_id.to_string())
+            .await
+        {
+            Ok(()) | Err(async_memcached::Error::Protocol(Status::NotFound)) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("memcached")
+    }
+}
@@ -1,16 +1,171 @@
-use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    sync::Arc,
+};
 
 use async_trait::async_trait;
 use parking_lot::Mutex;
 use time::OffsetDateTime;
 use tower_sessions_core::{
+    clock::{Clock, SharedClock},
     session::{Id, Session},
+    session_store::{ListSessions, TakeSession, UserSessions},
     SessionStore,
 };
 
+/// The reasons sessions are evicted for, as reported in metrics.
+#[derive(Debug, Clone, Copy)]
+enum Eviction {
+    Capacity,
+    Expired,
+}
+
+impl Eviction {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn record(self, count: usize) {
+        #[cfg(feature = "metrics")]
+        if count > 0 {
+            let reason = match self {
+                Self::Capacity => "capacity",
+                Self::Expired => "expired",
+            };
+            ::metrics::counter!(tower_sessions_core::metrics::MEMORY_STORE_EVICTIONS, "reason" => reason)
+                .increment(count as u64);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    session: Session,
+    expiry_date: OffsetDateTime,
+    /// The estimated size of the session, if the store has a memory budget.
+    size: usize,
+    /// The tick at which the session was last saved or loaded.
+    last_used: u64,
+    /// The tick at which the session was saved, keying it in `expiries`.
+    saved: u64,
+}
+
+#[derive(Debug, Default)]
+struct Sessions {
+    entries: HashMap<Id, Entry>,
+    /// The IDs of sessions by the tick of their last use, least recently used
+    /// first.
+    recency: BTreeMap<u64, Id>,
+    /// The IDs of sessions by their expiry date, soonest first, and the tick
+    /// at which they were saved, telling apart sessions expiring together.
+    expiries: BTreeMap<(OffsetDateTime, u64), Id>,
+    tick: u64,
+    size: usize,
+}
+
+impl Sessions {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn insert(&mut self, session: Session, expiry_date: OffsetDateTime, size: usize) {
+        let session_id = *session.id();
+        self.remove(&session_id);
+
+        let last_used = self.next_tick();
+        self.recency.insert(last_used, session_id);
+        self.expiries.insert((expiry_date, last_used), session_id);
+        self.size += size;
+        self.entries.insert(
+            session_id,
+            Entry {
+                session,
+                expiry_date,
+                size,
+                last_used,
+                saved: last_used,
+            },
+        );
+    }
+
+    /// Returns the session, marking it as used. An expired session is removed
+    /// instead, and returned only when `include_expired` is set.
+    fn get(
+        &mut self,
+        session_id: &Id,
+        now: OffsetDateTime,
+        include_expired: bool,
+    ) -> Option<Session> {
+        let expiry_date = self.entries.get(session_id)?.expiry_date;
+        if expiry_date <= now {
+            let entry = self.remove(session_id);
+            Eviction::Expired.record(1);
+            return entry.filter(|_| include_expired).map(|entry| entry.session);
+        }
+
+        let last_used = self.next_tick();
+        let entry = self.entries.get_mut(session_id)?;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(last_used, *session_id);
+        entry.last_used = last_used;
+        Some(entry.session.clone())
+    }
+
+    fn remove(&mut self, session_id: &Id) -> Option<Entry> {
+        let entry = self.entries.remove(session_id)?;
+        self.recency.remove(&entry.last_used);
+        self.expiries.remove(&(entry.expiry_date, entry.saved));
+        self.size -= entry.size;
+        Some(entry)
+    }
+
+    fn exceeds(&self, max_sessions: Option<usize>, max_size: Option<usize>) -> bool {
+        max_sessions.is_some_and(|max_sessions| self.entries.len() > max_sessions)
+            || max_size.is_some_and(|max_size| self.size > max_size)
+    }
+
+    /// Evicts sessions until the limits are met: expired sessions first, then
+    /// the least recently used ones.
+    fn evict(&mut self, now: OffsetDateTime, max_sessions: Option<usize>, max_size: Option<usize>) {
+        if !self.exceeds(max_sessions, max_size) {
+            return;
+        }
+
+        let mut expired = 0;
+        while let Some(entry) = self.expiries.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let session_id = entry.remove();
+            self.remove(&session_id);
+            expired += 1;
+        }
+        Eviction::Expired.record(expired);
+
+        let mut evicted = 0;
+        while self.exceeds(max_sessions, max_size) {
+            let Some((_, session_id)) = self.recency.pop_first() else {
+                break;
+            };
+            self.remove(&session_id);
+            evicted += 1;
+        }
+        Eviction::Capacity.record(evicted);
+    }
+}
+
 /// A session store that lives only in memory.
 ///
-/// This is useful for testing but not recommended for real applications.
+/// This is useful for testing and single-node deployments. Sessions aren't
+/// shared between processes, nor kept across restarts.
+///
+/// By default, the store grows with the sessions saved to it, which clients
+/// discarding their cookies, such as bots, may keep creating. A maximum
+/// number of sessions, set with [`with_max_sessions`](Self::with_max_sessions),
+/// and a memory budget, set with [`with_max_bytes`](Self::with_max_bytes),
+/// bound it: once a save exceeds either, expired sessions are evicted, and
+/// then the least recently used sessions, until both are met. With the
+/// `metrics` feature, evictions are counted by
+/// [`MEMORY_STORE_EVICTIONS`](tower_sessions_core::metrics::MEMORY_STORE_EVICTIONS).
 ///
 /// # Examples
 ///
@@ -19,35 +174,140 @@ use tower_sessions_core::{
 /// MemoryStore::default();
 /// ```
 #[derive(Clone, Debug, Default)]
-pub struct MemoryStore(Arc<Mutex<HashMap<Id, (Session, OffsetDateTime)>>>);
+pub struct MemoryStore {
+    sessions: Arc<Mutex<Sessions>>,
+    clock: SharedClock,
+    max_sessions: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl MemoryStore {
+    /// Configures the maximum number of sessions the store holds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    ///
+    /// let session_store = MemoryStore::default().with_max_sessions(100_000);
+    /// ```
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = Some(max_sessions);
+        self
+    }
+
+    /// Configures the memory budget of the store, in bytes.
+    ///
+    /// The memory sessions take is estimated by the size of their MessagePack
+    /// encoding, which leaves out the overhead of the store's own structures,
+    /// so the budget should be set with some margin.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    ///
+    /// // 64 MiB.
+    /// let session_store = MemoryStore::default().with_max_bytes(64 * 1024 * 1024);
+    /// ```
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, MemoryStore};
+    ///
+    /// let session_store = MemoryStore::default().with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+}
 
 #[async_trait]
 impl SessionStore for MemoryStore {
     type Error = Infallible;
 
     async fn save(&self, session: &Session) -> Result<(), Self::Error> {
-        self.0
-            .lock()
-            .insert(*session.id(), (session.clone(), session.expiry_date()));
+        // Sessions are only measured when there's a budget to measure them against.
+        let size = match self.max_bytes {
+            Some(_) => rmp_serde::to_vec(session).map_or(0, |encoded| encoded.len()),
+            None => 0,
+        };
+
+        let mut sessions = self.sessions.lock();
+        sessions.insert(session.clone(), session.expiry_date(), size);
+        sessions.evict(self.clock.now(), self.max_sessions, self.max_bytes);
         Ok(())
     }
 
     async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
         Ok(self
-            .0
+            .sessions
             .lock()
-            .get(session_id)
-            .filter(|(_, expiry_date)| is_active(*expiry_date))
-            .map(|(session, _)| session)
-            .cloned())
+            .get(session_id, self.clock.now(), false))
+    }
+
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        Ok(self.sessions.lock().get(session_id, self.clock.now(), true))
     }
 
     async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
-        self.0.lock().remove(session_id);
+        self.sessions.lock().remove(session_id);
         Ok(())
     }
 }
 
-fn is_active(expiry_date: OffsetDateTime) -> bool {
-    expiry_date > OffsetDateTime::now_utc()
+#[async_trait]
+impl ListSessions for MemoryStore {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        let now = self.clock.now();
+        Ok(self
+            .sessions
+            .lock()
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expiry_date > now)
+            .map(|(session_id, _)| *session_id)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl UserSessions for MemoryStore {
+    async fn user_sessions(&self, principal: &str) -> Result<Vec<Id>, Self::Error> {
+        let now = self.clock.now();
+        Ok(self
+            .sessions
+            .lock()
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.expiry_date > now && entry.session.principal().as_deref() == Some(principal)
+            })
+            .map(|(session_id, _)| *session_id)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl TakeSession for MemoryStore {
+    async fn take(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let now = self.clock.now();
+        Ok(self
+            .sessions
+            .lock()
+            .remove(session_id)
+            .filter(|entry| entry.expiry_date > now)
+            .map(|entry| entry.session))
+    }
 }
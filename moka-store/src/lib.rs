@@ -3,12 +3,17 @@ use std::convert::Infallible;
 use async_trait::async_trait;
 use moka::future::Cache;
 use time::OffsetDateTime;
-use tower_sessions_core::{session::Id, Session, SessionStore};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    Session, SessionStore,
+};
 
 /// A session store that uses Moka, a fast and concurrent caching library.
 #[derive(Debug, Clone)]
 pub struct MokaStore {
     cache: Cache<Id, (Session, OffsetDateTime)>,
+    clock: SharedClock,
 }
 
 impl MokaStore {
@@ -17,7 +22,7 @@ impl MokaStore {
     /// # Examples
     ///
     /// ```rust
-    /// use tower_sessions::{MemoryStore, MokaStore};
+    /// use tower_sessions_moka_store::MokaStore;
     /// let session_store = MokaStore::new(Some(2_000));
     /// ```
     pub fn new(max_capacity: Option<u64>) -> Self {
@@ -30,8 +35,24 @@ impl MokaStore {
 
         Self {
             cache: cache_builder.build(),
+            clock: SharedClock::default(),
         }
     }
+
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_core::clock::MockClock;
+    /// use tower_sessions_moka_store::MokaStore;
+    ///
+    /// let session_store = MokaStore::new(Some(2_000)).with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
 }
 
 #[async_trait]
@@ -50,7 +71,7 @@ impl SessionStore for MokaStore {
             .cache
             .get(session_id)
             .await
-            .filter(|(_, expiry_date)| is_active(*expiry_date))
+            .filter(|(_, expiry_date)| is_active(*expiry_date, &self.clock))
             .map(|(session, _)| session))
     }
 
@@ -63,6 +84,6 @@ impl SessionStore for MokaStore {
 // TODO: Moka supports expiry natively, but that interface is being overhauled
 // such that it's more accessible. When that work is done, we should replace
 // this with actual expiry.
-fn is_active(expiry_date: OffsetDateTime) -> bool {
-    expiry_date > OffsetDateTime::now_utc()
+fn is_active(expiry_date: OffsetDateTime, clock: &impl Clock) -> bool {
+    expiry_date > clock.now()
 }
@@ -1,10 +1,16 @@
 use async_trait::async_trait;
 use bson::{doc, to_document};
 pub use mongodb;
-use mongodb::{options::UpdateOptions, Client, Collection};
+use mongodb::{
+    options::{IndexOptions, UpdateOptions},
+    Client, Collection, IndexModel,
+};
 use serde::{Deserialize, Serialize};
-use time::OffsetDateTime;
-use tower_sessions_core::{session::Id, ExpiredDeletion, Session, SessionStore};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
 
 /// An error type for `MongoDBStore`.
 #[derive(thiserror::Error, Debug)]
@@ -39,9 +45,22 @@ struct MongoDBSessionRecord {
 }
 
 /// A MongoDB session store.
+///
+/// Sessions are stored as documents in the `sessions` collection of the
+/// provided database by default. Each document holds the serialized session as
+/// binary `data` alongside its `expireAt` date, on which
+/// [`migrate`](MongoDBStore::migrate) creates a TTL index.
+///
+/// # Client-side field level encryption
+///
+/// The store uses the provided client as is, so the `data` field may be
+/// encrypted by passing a client configured for automatic encryption, e.g. via
+/// `Client::encrypted_builder` with the `in-use-encryption-unstable` feature of
+/// the `mongodb` crate enabled.
 #[derive(Clone, Debug)]
 pub struct MongoDBStore {
     collection: Collection<MongoDBSessionRecord>,
+    clock: SharedClock,
 }
 
 impl MongoDBStore {
@@ -50,7 +69,7 @@ impl MongoDBStore {
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tower_sessions::{mongodb::Client, MongoDBStore};
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
     ///
     /// # tokio_test::block_on(async {
     /// let database_url = std::option_env!("DATABASE_URL").unwrap();
@@ -61,18 +80,89 @@ impl MongoDBStore {
     pub fn new(client: Client, database: String) -> Self {
         Self {
             collection: client.database(&database).collection("sessions"),
+            clock: SharedClock::default(),
         }
     }
+
+    /// Set the name of the collection sessions are stored in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store =
+    ///     MongoDBStore::new(client, "database".to_string()).with_collection_name("app_sessions");
+    /// # })
+    /// ```
+    pub fn with_collection_name(mut self, collection_name: &str) -> Self {
+        let database = self.collection.namespace().db;
+        self.collection = self
+            .collection
+            .client()
+            .database(&database)
+            .collection(collection_name);
+        self
+    }
+
+    /// Migrate the session collection, creating a TTL index so that MongoDB
+    /// removes expired sessions by itself.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store = MongoDBStore::new(client, "database".to_string());
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> mongodb::error::Result<()> {
+        let index = IndexModel::builder()
+            .keys(doc! { "expireAt": 1 })
+            .options(
+                IndexOptions::builder()
+                    .expire_after(std::time::Duration::ZERO)
+                    .build(),
+            )
+            .build();
+        self.collection.create_index(index, None).await?;
+
+        Ok(())
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_core::clock::MockClock;
+    /// use tower_sessions_mongodb_store::{mongodb::Client, MongoDBStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let client = Client::with_uri_str(database_url).await.unwrap();
+    /// let session_store =
+    ///     MongoDBStore::new(client, "database".to_string()).with_clock(MockClock::default());
+    /// # })
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
 }
 
 #[async_trait]
 impl ExpiredDeletion for MongoDBStore {
     async fn delete_expired(&self) -> Result<(), Self::Error> {
         self.collection
-            .delete_many(
-                doc! { "expireAt": {"$lt": OffsetDateTime::now_utc()} },
-                None,
-            )
+            .delete_many(doc! { "expireAt": {"$lt": self.clock.now()} }, None)
             .await?;
 
         Ok(())
@@ -109,7 +199,7 @@ impl SessionStore for MongoDBStore {
             .find_one(
                 doc! {
                     "_id": session_id.to_string(),
-                    "expireAt": {"$gt": OffsetDateTime::now_utc()}
+                    "expireAt": {"$gt": self.clock.now()}
                 },
                 None,
             )
@@ -129,4 +219,8 @@ impl SessionStore for MongoDBStore {
 
         Ok(())
     }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("mongodb")
+    }
 }
@@ -0,0 +1,312 @@
+use std::{sync::Arc, time::Duration};
+
+pub use async_nats;
+use async_nats::jetstream::{
+    context::{
+        CreateKeyValueError, KeyValueError, KeyValueErrorKind, PublishError, PublishErrorKind,
+    },
+    kv::{self, EntryError, Operation, PurgeError},
+    message::PublishMessage,
+    Context,
+};
+use async_trait::async_trait;
+use tokio::sync::OnceCell;
+use tower_sessions_core::{session::Id, Session, SessionStore};
+
+/// How long the server keeps the marker left when a session expires.
+const EXPIRY_MARKER_TTL: Duration = Duration::from_secs(1);
+
+/// An error type for `NatsStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum NatsStoreError {
+    /// A variant to map to `async_nats::jetstream::context::KeyValueError`
+    /// errors.
+    #[error("NATS bucket error: {0}")]
+    KeyValue(#[from] KeyValueError),
+
+    /// A variant to map to
+    /// `async_nats::jetstream::context::CreateKeyValueError` errors.
+    #[error("NATS bucket creation error: {0}")]
+    CreateKeyValue(#[from] CreateKeyValueError),
+
+    /// A variant to map to `async_nats::jetstream::context::PublishError`
+    /// errors.
+    #[error("NATS publish error: {0}")]
+    Publish(#[from] PublishError),
+
+    /// A variant to map to `async_nats::jetstream::kv::EntryError` errors.
+    #[error("NATS entry error: {0}")]
+    Entry(#[from] EntryError),
+
+    /// A variant to map to `async_nats::jetstream::kv::PurgeError` errors.
+    #[error("NATS purge error: {0}")]
+    Purge(#[from] PurgeError),
+
+    /// A variant for sessions which could not be created because a key with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for sessions which could not be saved because they were
+    /// changed or deleted since they were loaded.
+    ///
+    /// This is only returned when optimistic concurrency is enabled.
+    #[error("Session was modified concurrently")]
+    Conflict,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A NATS JetStream key-value session store.
+///
+/// Sessions are stored as keys of a key-value bucket, each published with a
+/// TTL matching the session's expiry so that the server removes expired
+/// sessions. This relies on per-message TTLs, which require NATS 2.11 and a
+/// bucket created with them enabled, see [`migrate`](NatsStore::migrate).
+///
+/// Buckets mirroring or sourcing other buckets aren't supported, since writes
+/// are published directly to the bucket's subjects.
+#[derive(Clone, Debug)]
+pub struct NatsStore {
+    context: Context,
+    bucket_name: String,
+    bucket: Arc<OnceCell<kv::Store>>,
+    optimistic_concurrency: bool,
+}
+
+impl NatsStore {
+    /// Create a new NATS store with the provided JetStream context.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_nats_store::{async_nats, NatsStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = async_nats::connect("localhost:4222").await.unwrap();
+    /// let session_store = NatsStore::new(async_nats::jetstream::new(client));
+    /// # })
+    /// ```
+    pub fn new(context: Context) -> Self {
+        Self {
+            context,
+            bucket_name: "tower_sessions".to_string(),
+            bucket: Arc::new(OnceCell::new()),
+            optimistic_concurrency: false,
+        }
+    }
+
+    /// Set the session bucket name with the provided name.
+    pub fn with_bucket_name(mut self, bucket_name: impl AsRef<str>) -> Result<Self, String> {
+        let bucket_name = bucket_name.as_ref();
+        if bucket_name.is_empty()
+            || !bucket_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!(
+                "Invalid bucket name '{}'. Bucket names must be non-empty and contain only \
+                 letters, digits, underscores, or hyphens.",
+                bucket_name
+            ));
+        }
+
+        bucket_name.clone_into(&mut self.bucket_name);
+        self.bucket = Arc::new(OnceCell::new());
+        Ok(self)
+    }
+
+    /// Enable optimistic concurrency using the revisions of bucket entries.
+    ///
+    /// When enabled, the revision of a loaded session is kept as its
+    /// [`version`](Session::version) and saving it only succeeds if the key
+    /// hasn't been changed or deleted in the meantime. Otherwise,
+    /// [`NatsStoreError::Conflict`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_nats_store::{async_nats, NatsStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = async_nats::connect("localhost:4222").await.unwrap();
+    /// let session_store =
+    ///     NatsStore::new(async_nats::jetstream::new(client)).with_optimistic_concurrency(true);
+    /// # })
+    /// ```
+    pub fn with_optimistic_concurrency(mut self, optimistic_concurrency: bool) -> Self {
+        self.optimistic_concurrency = optimistic_concurrency;
+        self
+    }
+
+    /// Migrate the session bucket, creating it with per-message TTLs enabled
+    /// if it doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_nats_store::{async_nats, NatsStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = async_nats::connect("localhost:4222").await.unwrap();
+    /// let session_store = NatsStore::new(async_nats::jetstream::new(client));
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), NatsStoreError> {
+        self.bucket
+            .get_or_try_init(|| async {
+                match self.context.get_key_value(&self.bucket_name).await {
+                    Err(err) if err.kind() == KeyValueErrorKind::GetBucket => Ok(self
+                        .context
+                        .create_key_value(kv::Config {
+                            bucket: self.bucket_name.clone(),
+                            history: 1,
+                            limit_markers: Some(EXPIRY_MARKER_TTL),
+                            ..Default::default()
+                        })
+                        .await?),
+                    result => Ok::<_, NatsStoreError>(result?),
+                }
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn bucket(&self) -> Result<&kv::Store, NatsStoreError> {
+        self.bucket
+            .get_or_try_init(|| async {
+                Ok::<_, NatsStoreError>(self.context.get_key_value(&self.bucket_name).await?)
+            })
+            .await
+    }
+
+    /// Publishes the session, expecting the given revision of its key if any,
+    /// and returns the new revision.
+    async fn publish(
+        &self,
+        session: &Session,
+        ttl: Duration,
+        revision: Option<u64>,
+    ) -> Result<u64, NatsStoreError> {
+        let bucket = self.bucket().await?;
+        let subject = format!(
+            "{}{}",
+            bucket.put_prefix.as_ref().unwrap_or(&bucket.prefix),
+            session.id()
+        );
+
+        let mut message = PublishMessage::build()
+            .payload(rmp_serde::to_vec(session)?.into())
+            .ttl(ttl);
+        if let Some(revision) = revision {
+            message = message.expected_last_subject_sequence(revision);
+        }
+
+        let ack = self.context.send_publish(subject, message).await?.await?;
+        Ok(ack.sequence)
+    }
+
+    fn ttl(session: &Session) -> Option<Duration> {
+        // Message TTLs have a granularity of seconds.
+        let seconds = session.expiry_age().whole_seconds();
+        (seconds > 0).then(|| Duration::from_secs(seconds as u64))
+    }
+}
+
+#[async_trait]
+impl SessionStore for NatsStore {
+    type Error = NatsStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let Some(ttl) = Self::ttl(session) else {
+            return self.delete(session.id()).await;
+        };
+
+        let revision = if self.optimistic_concurrency {
+            session.version()
+        } else {
+            None
+        };
+
+        match self.publish(session, ttl, revision).await {
+            Ok(revision) => {
+                if self.optimistic_concurrency {
+                    session.set_version(Some(revision));
+                }
+                Ok(())
+            }
+            Err(NatsStoreError::Publish(err))
+                if err.kind() == PublishErrorKind::WrongLastSequence =>
+            {
+                Err(NatsStoreError::Conflict)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // Expired sessions aren't stored, see `save`.
+        let Some(ttl) = Self::ttl(session) else {
+            return Ok(());
+        };
+
+        // A revision of zero means the key doesn't exist. Deleted keys
+        // leave a marker behind, whose revision must be expected instead.
+        let mut revision = 0;
+        loop {
+            match self.publish(session, ttl, Some(revision)).await {
+                Ok(revision) => {
+                    if self.optimistic_concurrency {
+                        session.set_version(Some(revision));
+                    }
+                    return Ok(());
+                }
+                Err(NatsStoreError::Publish(err))
+                    if err.kind() == PublishErrorKind::WrongLastSequence =>
+                {
+                    match self.bucket().await?.entry(session.id().to_string()).await? {
+                        Some(entry)
+                            if entry.operation != Operation::Put && entry.revision != revision =>
+                        {
+                            revision = entry.revision;
+                        }
+                        _ => return Err(NatsStoreError::IdCollision),
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let entry = self.bucket().await?.entry(session_id.to_string()).await?;
+
+        match entry {
+            Some(entry) if entry.operation == Operation::Put => {
+                let session: Session = rmp_serde::from_slice(&entry.value)?;
+                if self.optimistic_concurrency {
+                    session.set_version(Some(entry.revision));
+                }
+                Ok(Some(session))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.bucket().await?.purge(session_id.to_string()).await?;
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("nats")
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+pub use redb;
+use redb::{
+    CommitError, Database, ReadableDatabase, StorageError, Table, TableDefinition, TableError,
+    TransactionError,
+};
+use serde::de::IgnoredAny;
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+const SESSIONS: TableDefinition<&str, &[u8]> = TableDefinition::new("tower_sessions");
+const EXPIRIES: TableDefinition<(i64, &str), ()> = TableDefinition::new("tower_sessions_expiry");
+
+/// An error type for `RedbStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum RedbStoreError {
+    /// A variant to map to `redb::Error` errors.
+    #[error("redb error: {0}")]
+    Redb(#[from] redb::Error),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+macro_rules! impl_from_redb_error {
+    ($($err:ty),*) => {
+        $(
+            impl From<$err> for RedbStoreError {
+                fn from(err: $err) -> Self {
+                    Self::Redb(err.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_redb_error!(StorageError, TableError, TransactionError, CommitError);
+
+/// A redb session store.
+///
+/// Sessions are stored in the `tower_sessions` table alongside their expiry
+/// date, which is also indexed in the `tower_sessions_expiry` table so that
+/// [`delete_expired`](ExpiredDeletion::delete_expired) only visits expired
+/// sessions. With the `deletion-task` feature, this can be run periodically
+/// in the background with `continuously_delete_expired`.
+///
+/// Note that redb operations are synchronous and writes are committed durably
+/// by default, so they may block on IO.
+#[derive(Clone, Debug)]
+pub struct RedbStore {
+    db: Arc<Database>,
+    clock: SharedClock,
+}
+
+impl RedbStore {
+    /// Create a new redb store with the provided database.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redb_store::{redb::Database, RedbStore};
+    ///
+    /// let db = Database::create("sessions.redb").unwrap();
+    /// let session_store = RedbStore::new(db);
+    /// ```
+    pub fn new(db: Database) -> Self {
+        Self {
+            db: Arc::new(db),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session tables, creating them if they don't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_redb_store::{redb::Database, RedbStore};
+    ///
+    /// let db = Database::create("sessions.redb").unwrap();
+    /// let session_store = RedbStore::new(db);
+    /// session_store.migrate().unwrap();
+    /// ```
+    pub fn migrate(&self) -> Result<(), RedbStoreError> {
+        let write_txn = self.db.begin_write()?;
+        write_txn.open_table(SESSIONS)?;
+        write_txn.open_table(EXPIRIES)?;
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn write(&self, session: &Session, overwrite: bool) -> Result<(), RedbStoreError> {
+        let key = session.id().to_string();
+        let expiry_date = session.expiry_date().unix_timestamp();
+        let record = rmp_serde::to_vec(&(expiry_date, session))?;
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut sessions = write_txn.open_table(SESSIONS)?;
+            let mut expiries = write_txn.open_table(EXPIRIES)?;
+
+            let previous = sessions.insert(key.as_str(), record.as_slice())?;
+            if let Some(previous) = previous {
+                if !overwrite {
+                    // Dropping the transaction without committing aborts it.
+                    return Err(RedbStoreError::IdCollision);
+                }
+                remove_expiry(&mut expiries, &key, previous.value())?;
+            }
+
+            expiries.insert((expiry_date, key.as_str()), ())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for RedbStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let now = self.clock.now().unix_timestamp();
+        let end = (now.saturating_add(1), "");
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut sessions = write_txn.open_table(SESSIONS)?;
+            let mut expiries = write_txn.open_table(EXPIRIES)?;
+
+            let expired = expiries
+                .extract_from_if(..end, |_, _| true)?
+                .map(|entry| entry.map(|(index_key, _)| index_key.value().1.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            for key in expired {
+                sessions.remove(key.as_str())?;
+            }
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedbStore {
+    type Error = RedbStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, true)
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, false)
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let read_txn = self.db.begin_read()?;
+        let sessions = match read_txn.open_table(SESSIONS) {
+            Ok(sessions) => sessions,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let Some(record) = sessions.get(session_id.to_string().as_str())? else {
+            return Ok(None);
+        };
+
+        let (expiry_date, session): (i64, Session) = rmp_serde::from_slice(record.value())?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let key = session_id.to_string();
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut sessions = write_txn.open_table(SESSIONS)?;
+            let mut expiries = write_txn.open_table(EXPIRIES)?;
+
+            let previous = sessions.remove(key.as_str())?;
+            if let Some(previous) = previous {
+                remove_expiry(&mut expiries, &key, previous.value())?;
+            }
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("redb")
+    }
+}
+
+fn remove_expiry(
+    expiries: &mut Table<(i64, &str), ()>,
+    key: &str,
+    record: &[u8],
+) -> Result<(), RedbStoreError> {
+    let (expiry_date, _): (i64, IgnoredAny) = rmp_serde::from_slice(record)?;
+    expiries.remove((expiry_date, key))?;
+    Ok(())
+}
@@ -0,0 +1,221 @@
+//! Record formats and key naming schemes of `RedisStore`.
+//!
+//! By default, sessions are stored under their ID as MessagePack-encoded
+//! [`Session`]s. The other codecs store sessions the way Node and Python
+//! session frameworks do, so that services built on them may read and write
+//! the same sessions while a Rust service is introduced alongside them:
+//!
+//! - [`Codec::express_session`] follows express-session with connect-redis:
+//!   sessions are JSON objects holding the session data along with a `cookie`
+//!   object, under keys prefixed with `sess:`.
+//! - [`Codec::django`] follows Django's cache session backend with django-redis
+//!   and its `JSONSerializer`: sessions are JSON objects of the session data,
+//!   under keys prefixed with `:1:django.contrib.sessions.cache`.
+//!
+//! Sessions keep `tower-sessions` IDs, which the other frameworks accept as
+//! session keys. They must find them in the session cookie though: Django's
+//! `sessionid` cookie holds the key as is, so naming the cookie alike is
+//! enough, whereas express-session signs the ID in its cookie, which
+//! `tower-sessions` neither verifies nor produces.
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime, UtcOffset};
+use tower_sessions_core::{session::Id, Expiry, Session};
+
+use crate::RedisStoreError;
+
+/// A pattern matching session IDs.
+const SESSION_ID_PATTERN: &str = "????????-????-????-????-????????????";
+
+/// The key under which express-session keeps the session cookie's attributes.
+const EXPRESS_COOKIE_KEY: &str = "cookie";
+
+/// The key under which Django's authentication framework keeps the user.
+const DJANGO_USER_KEY: &str = "_auth_user_id";
+
+/// A record format and key naming scheme of `RedisStore`.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_redis_store::{codec::Codec, fred::prelude::RedisClient, RedisStore};
+///
+/// let session_store =
+///     RedisStore::new(RedisClient::default()).with_codec(Codec::express_session());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Codec {
+    /// MessagePack-encoded sessions stored under their ID.
+    #[default]
+    MessagePack,
+
+    /// The express-session format, under keys made of the prefix and the ID.
+    ///
+    /// The session data is stored at the top level of the JSON object, beside
+    /// the `cookie` object, whose `originalMaxAge` and `expires` fields hold
+    /// the expiry: an `originalMaxAge` is read as an inactivity expiry, as
+    /// with express-session's `rolling` option. Principals aren't part of this
+    /// format, and aren't stored.
+    ExpressSession {
+        /// The key prefix, `sess:` by default.
+        prefix: String,
+    },
+
+    /// The Django format, under keys made of the prefix and the ID.
+    ///
+    /// The session data is the JSON object itself, and the principal is
+    /// stored as the `_auth_user_id` of Django's authentication framework.
+    /// Django keeps the expiry as the key's expiration, which is read back as
+    /// the session's expiry date.
+    Django {
+        /// The key prefix, including the cache key prefix and version,
+        /// `:1:django.contrib.sessions.cache` by default.
+        prefix: String,
+    },
+}
+
+impl Codec {
+    /// The express-session format with connect-redis' default `sess:` prefix.
+    pub fn express_session() -> Self {
+        Self::ExpressSession {
+            prefix: "sess:".to_owned(),
+        }
+    }
+
+    /// The Django format with the default prefix of the cache session
+    /// backend, `:1:django.contrib.sessions.cache`.
+    pub fn django() -> Self {
+        Self::Django {
+            prefix: ":1:django.contrib.sessions.cache".to_owned(),
+        }
+    }
+
+    fn prefix(&self) -> &str {
+        match self {
+            Self::MessagePack => "",
+            Self::ExpressSession { prefix } | Self::Django { prefix } => prefix,
+        }
+    }
+
+    /// Whether the expiry of sessions is read from the expiration of their
+    /// key.
+    pub(crate) fn reads_expiration(&self) -> bool {
+        matches!(self, Self::Django { .. })
+    }
+
+    pub(crate) fn key(&self, session_id: &Id) -> String {
+        format!("{}{session_id}", self.prefix())
+    }
+
+    /// The `SCAN` pattern matching the keys of sessions.
+    pub(crate) fn pattern(&self) -> String {
+        let mut pattern = String::new();
+        for c in self.prefix().chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push_str(SESSION_ID_PATTERN);
+        pattern
+    }
+
+    pub(crate) fn session_id(&self, key: &str) -> Option<Id> {
+        key.strip_prefix(self.prefix())
+            .and_then(|session_id| Id::try_from(session_id).ok())
+    }
+
+    pub(crate) fn encode(&self, session: &Session) -> Result<Vec<u8>, RedisStoreError> {
+        match self {
+            Self::MessagePack => Ok(rmp_serde::to_vec(session)?),
+
+            Self::ExpressSession { .. } => {
+                let (original_max_age, expires) = match session.expiry() {
+                    Some(Expiry::OnInactivity(duration)) => (
+                        Some(duration.whole_milliseconds()),
+                        Some(to_js_date(session.expiry_date())),
+                    ),
+                    Some(Expiry::AtDateTime(datetime)) => (None, Some(to_js_date(datetime))),
+                    Some(Expiry::OnSessionEnd) | None => (None, None),
+                };
+
+                let mut record: Map<String, Value> = session.data().into_iter().collect();
+                record.insert(
+                    EXPRESS_COOKIE_KEY.to_owned(),
+                    json!({
+                        "originalMaxAge": original_max_age,
+                        "expires": expires,
+                        "httpOnly": true,
+                        "path": "/",
+                    }),
+                );
+                Ok(serde_json::to_vec(&record)?)
+            }
+
+            Self::Django { .. } => {
+                let mut record: Map<String, Value> = session.data().into_iter().collect();
+                if let Some(principal) = session.principal() {
+                    record.insert(DJANGO_USER_KEY.to_owned(), principal.into());
+                }
+                Ok(serde_json::to_vec(&record)?)
+            }
+        }
+    }
+
+    /// Decodes a session, given the remaining time to live of its key if the
+    /// codec [reads the expiration](Codec::reads_expiration).
+    pub(crate) fn decode(
+        &self,
+        session_id: &Id,
+        data: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<Session, RedisStoreError> {
+        match self {
+            Self::MessagePack => Ok(rmp_serde::from_slice(data)?),
+
+            Self::ExpressSession { .. } => {
+                let mut record: HashMap<String, Value> = serde_json::from_slice(data)?;
+                let cookie = record.remove(EXPRESS_COOKIE_KEY).unwrap_or_default();
+                let expiry = match cookie["originalMaxAge"].as_i64() {
+                    Some(original_max_age) => Some(Expiry::OnInactivity(Duration::milliseconds(
+                        original_max_age,
+                    ))),
+                    None => cookie["expires"]
+                        .as_str()
+                        .and_then(|expires| OffsetDateTime::parse(expires, &Rfc3339).ok())
+                        .map(Expiry::AtDateTime),
+                };
+                Ok(Session::from_parts(*session_id, record, None, expiry))
+            }
+
+            Self::Django { .. } => {
+                let mut record: HashMap<String, Value> = serde_json::from_slice(data)?;
+                // Django stores user IDs as strings, but integers are accepted as well.
+                let principal = record.remove(DJANGO_USER_KEY).and_then(|user| match user {
+                    Value::String(user) => Some(user),
+                    Value::Number(user) => Some(user.to_string()),
+                    _ => None,
+                });
+                let expiry = ttl.map(|ttl| Expiry::AtDateTime(OffsetDateTime::now_utc() + ttl));
+                Ok(Session::from_parts(*session_id, record, principal, expiry))
+            }
+        }
+    }
+}
+
+/// Formats the date as `Date.prototype.toISOString` does, with milliseconds.
+fn to_js_date(datetime: OffsetDateTime) -> String {
+    let datetime = datetime.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        datetime.year(),
+        u8::from(datetime.month()),
+        datetime.day(),
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+        datetime.millisecond(),
+    )
+}
@@ -1,11 +1,15 @@
 use async_trait::async_trait;
+use codec::Codec;
 pub use fred;
 use fred::{
-    prelude::{KeysInterface, RedisClient},
-    types::Expiration,
+    prelude::{ClientLike, KeysInterface, RedisClient, RedisPool},
+    types::{Expiration, RedisValue, Scanner, SetOptions},
 };
-use time::OffsetDateTime;
-use tower_sessions_core::{session::Id, Session, SessionStore};
+use futures::StreamExt;
+use time::{Duration, OffsetDateTime};
+use tower_sessions_core::{session::Id, ListSessions, Session, SessionStore, UserSessions};
+
+pub mod codec;
 
 /// An error type for `RedisStore`.
 #[derive(thiserror::Error, Debug)]
@@ -14,6 +18,11 @@ pub enum RedisStoreError {
     #[error("Redis error: {0}")]
     Redis(#[from] fred::error::RedisError),
 
+    /// A variant for sessions which could not be created because a key with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
     /// A variant to map `rmp_serde` encode errors.
     #[error("Rust MsgPack encode error: {0}")]
     RmpSerdeEncode(#[from] rmp_serde::encode::Error),
@@ -21,22 +30,36 @@ pub enum RedisStoreError {
     /// A variant to map `rmp_serde` decode errors.
     #[error("Rust MsgPack decode error: {0}")]
     RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+
+    /// A variant to map `serde_json` errors of the JSON codecs.
+    #[error("JSON error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
 }
 
 /// A Redis session store.
+///
+/// The store is built on [`fred`] and works with any of its clients which
+/// implement [`KeysInterface`]: besides a single [`RedisClient`], which may be
+/// configured for clustered or sentinel deployments and with a reconnection
+/// policy, a [`RedisPool`](fred::clients::RedisPool) may be used to spread
+/// commands over several connections.
+///
+/// Sessions are stored as MessagePack under their ID, unless another
+/// [`Codec`] is configured with [`with_codec`](RedisStore::with_codec).
 #[derive(Debug, Clone, Default)]
-pub struct RedisStore {
-    client: RedisClient,
+pub struct RedisStore<C: KeysInterface + Send + Sync = RedisClient> {
+    client: C,
+    codec: Codec,
 }
 
-impl RedisStore {
+impl<C: KeysInterface + Send + Sync> RedisStore<C> {
     /// Create a new Redis store with the provided client.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
     /// use fred::prelude::*;
-    /// use tower_sessions::RedisStore;
+    /// use tower_sessions_redis_store::RedisStore;
     ///
     /// # tokio_test::block_on(async {
     /// let client = RedisClient::default();
@@ -47,48 +70,165 @@ impl RedisStore {
     /// let session_store = RedisStore::new(client);
     /// })
     /// ```
-    pub fn new(client: RedisClient) -> Self {
-        Self { client }
+    ///
+    /// Using a connection pool:
+    ///
+    /// ```rust,no_run
+    /// use fred::prelude::*;
+    /// use tower_sessions_redis_store::RedisStore;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = RedisPool::new(RedisConfig::default(), None, None, None, 6).unwrap();
+    ///
+    /// let _ = pool.connect();
+    /// pool.wait_for_connect().await.unwrap();
+    ///
+    /// let session_store = RedisStore::new(pool);
+    /// })
+    /// ```
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            codec: Codec::default(),
+        }
     }
-}
 
-#[async_trait]
-impl SessionStore for RedisStore {
-    type Error = RedisStoreError;
+    /// Set the format and key naming scheme of stored sessions, e.g. to share
+    /// sessions with an express-session or Django service.
+    ///
+    /// See the [`codec`] module for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fred::prelude::*;
+    /// use tower_sessions_redis_store::{codec::Codec, RedisStore};
+    ///
+    /// let client = RedisClient::default();
+    /// let session_store = RedisStore::new(client).with_codec(Codec::django());
+    /// ```
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
 
-    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+    async fn set(
+        &self,
+        session: &Session,
+        options: Option<SetOptions>,
+    ) -> Result<RedisValue, RedisStoreError> {
         let expire = Some(Expiration::EXAT(OffsetDateTime::unix_timestamp(
             session.expiry_date(),
         )));
 
-        self.client
+        Ok(self
+            .client
             .set(
-                session.id().to_string(),
-                rmp_serde::to_vec(&session)?.as_slice(),
+                self.codec.key(session.id()),
+                self.codec.encode(session)?.as_slice(),
                 expire,
-                None,
+                options,
                 false,
             )
-            .await?;
+            .await?)
+    }
+}
+
+#[async_trait]
+impl<C> SessionStore for RedisStore<C>
+where
+    C: KeysInterface + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    type Error = RedisStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.set(session, None).await?;
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // With `NX`, nothing is set and nil is returned if the key exists.
+        if self.set(session, Some(SetOptions::NX)).await?.is_null() {
+            return Err(RedisStoreError::IdCollision);
+        }
 
         Ok(())
     }
 
     async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
-        let data = self
-            .client
-            .get::<Option<Vec<u8>>, _>(session_id.to_string())
-            .await?;
+        let key = self.codec.key(session_id);
+        let data = self.client.get::<Option<Vec<u8>>, _>(&key).await?;
+
+        let Some(data) = data else {
+            return Ok(None);
+        };
 
-        if let Some(data) = data {
-            Ok(Some(rmp_serde::from_slice(&data)?))
+        // A negative time to live means that the key has no expiration, or expired
+        // since it was read.
+        let ttl = if self.codec.reads_expiration() {
+            let ttl = self.client.pttl::<i64, _>(&key).await?;
+            (ttl >= 0).then(|| Duration::milliseconds(ttl))
         } else {
-            Ok(None)
-        }
+            None
+        };
+
+        Ok(Some(self.codec.decode(session_id, &data, ttl)?))
     }
 
     async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
-        self.client.del(session_id.to_string()).await?;
+        self.client.del::<(), _>(self.codec.key(session_id)).await?;
         Ok(())
     }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("redis")
+    }
+}
+
+/// Lists sessions by scanning the keyspace for session keys, so
+/// this visits every key of the database.
+#[async_trait]
+impl ListSessions for RedisStore<RedisClient> {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        scan_session_ids(&self.client, &self.codec).await
+    }
+}
+
+#[async_trait]
+impl UserSessions for RedisStore<RedisClient> {}
+
+/// Lists sessions by scanning the keyspace for session keys, so
+/// this visits every key of the database.
+#[async_trait]
+impl ListSessions for RedisStore<RedisPool> {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        scan_session_ids(self.client.next(), &self.codec).await
+    }
+}
+
+#[async_trait]
+impl UserSessions for RedisStore<RedisPool> {}
+
+async fn scan_session_ids(client: &RedisClient, codec: &Codec) -> Result<Vec<Id>, RedisStoreError> {
+    // Keys of clustered deployments are spread over several primaries.
+    let pattern = codec.pattern();
+    let mut pages = if client.is_clustered() {
+        client.scan_cluster(pattern, Some(100), None).boxed()
+    } else {
+        client.scan(pattern, Some(100), None).boxed()
+    };
+
+    let mut session_ids = Vec::new();
+    while let Some(page) = pages.next().await {
+        let mut page = page?;
+        let keys = page.take_results().unwrap_or_default();
+        session_ids.extend(
+            keys.iter()
+                .filter_map(|key| key.as_str())
+                .filter_map(|key| codec.session_id(key)),
+        );
+        page.next()?;
+    }
+
+    Ok(session_ids)
 }
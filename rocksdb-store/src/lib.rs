@@ -0,0 +1,255 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+pub use rocksdb;
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, CompactionDecision, Options, DB};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    Session, SessionStore,
+};
+
+/// The name of the column family holding sessions.
+const COLUMN_FAMILY_NAME: &str = "tower_sessions";
+
+/// The interval at which every file of the session column family goes
+/// through compaction, removing expired sessions.
+const PERIODIC_COMPACTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// An error type for `RocksDbStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum RocksDbStoreError {
+    /// A variant to map to `rocksdb::Error` errors.
+    #[error("RocksDB error: {0}")]
+    RocksDb(#[from] rocksdb::Error),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A RocksDB session store.
+///
+/// Sessions are stored in the `tower_sessions` column family, keyed by their
+/// ID, with their expiry date ahead of the serialized session. A compaction
+/// filter removes sessions past their expiry date whenever their files are
+/// compacted, which happens at least once a day, so expired sessions needn't
+/// be deleted separately; they're ignored when loading in the meantime.
+///
+/// The database is opened with a TTL for the column family, after which
+/// sessions that haven't been saved are removed regardless of their expiry
+/// date. This should exceed the longest lifetime of sessions, including
+/// sessions expiring on inactivity.
+///
+/// Note that RocksDB operations are synchronous; they are usually served from
+/// memory, but may block on IO.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_core::{Session, SessionStore};
+/// use tower_sessions_rocksdb_store::RocksDbStore;
+///
+/// # tokio_test::block_on(async {
+/// let path = std::env::temp_dir().join("tower_sessions_rocksdb_example");
+/// let session_store = RocksDbStore::open(&path).unwrap();
+///
+/// let session = Session::default();
+/// session_store.create(&session).await.unwrap();
+/// assert!(session_store.load(session.id()).await.unwrap().is_some());
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct RocksDbStore {
+    db: Arc<DB>,
+    create_lock: Arc<Mutex<()>>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for RocksDbStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RocksDbStore")
+            .field("path", &self.db.path())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RocksDbStore {
+    /// Open a RocksDB store at the provided path, creating the database if
+    /// it doesn't exist, with a TTL of 30 days.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_rocksdb_store::RocksDbStore;
+    ///
+    /// let path = std::env::temp_dir().join("tower_sessions_rocksdb_open");
+    /// let session_store = RocksDbStore::open(&path).unwrap();
+    /// ```
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RocksDbStoreError> {
+        Self::open_with_ttl(path, Duration::from_secs(30 * 24 * 60 * 60))
+    }
+
+    /// Open a RocksDB store at the provided path, creating the database if
+    /// it doesn't exist, removing sessions which haven't been saved for the
+    /// provided TTL.
+    ///
+    /// The TTL is enforced when compacting, so sessions may outlive it for a
+    /// while.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions_rocksdb_store::RocksDbStore;
+    ///
+    /// let path = std::env::temp_dir().join("tower_sessions_rocksdb_open_with_ttl");
+    /// let session_store =
+    ///     RocksDbStore::open_with_ttl(&path, Duration::from_secs(90 * 24 * 60 * 60)).unwrap();
+    /// ```
+    pub fn open_with_ttl(path: impl AsRef<Path>, ttl: Duration) -> Result<Self, RocksDbStoreError> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let mut cf_options = Options::default();
+        cf_options.set_compaction_filter("tower_sessions_expiry", expiry_filter);
+        cf_options.set_periodic_compaction_seconds(PERIODIC_COMPACTION.as_secs());
+
+        let db = DB::open_cf_descriptors_with_ttl(
+            &options,
+            path,
+            [ColumnFamilyDescriptor::new(COLUMN_FAMILY_NAME, cf_options)],
+            ttl,
+        )?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            create_lock: Arc::new(Mutex::new(())),
+            clock: SharedClock::default(),
+        })
+    }
+
+    /// Configures the clock used to determine whether a session has expired
+    /// when loading.
+    ///
+    /// The compaction filter always uses the system clock.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    fn sessions(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(COLUMN_FAMILY_NAME)
+            .expect("the session column family is created when opening the database")
+    }
+
+    fn is_expired(&self, record: &[u8]) -> bool {
+        record_expiry(record) <= self.clock.now().unix_timestamp()
+    }
+}
+
+#[async_trait]
+impl SessionStore for RocksDbStore {
+    type Error = RocksDbStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.db
+            .put_cf(self.sessions(), session.id().to_string(), record(session)?)?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let key = session.id().to_string();
+        let record = record(session)?;
+
+        // Records may linger after their sessions expire, until compacted.
+        let _guard = self
+            .create_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(existing) = self.db.get_pinned_cf(self.sessions(), &key)? {
+            if !self.is_expired(&existing) {
+                return Err(RocksDbStoreError::IdCollision);
+            }
+        }
+        self.db.put_cf(self.sessions(), key, record)?;
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let Some(record) = self
+            .db
+            .get_pinned_cf(self.sessions(), session_id.to_string())?
+        else {
+            return Ok(None);
+        };
+
+        if self.is_expired(&record) {
+            return Ok(None);
+        }
+
+        // Records too short to hold an expiry date count as expired, but are
+        // never sliced past their end: an empty session fails to decode.
+        let session = record.get(8..).unwrap_or_default();
+        Ok(Some(rmp_serde::from_slice(session)?))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.db.delete_cf(self.sessions(), session_id.to_string())?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("rocksdb")
+    }
+}
+
+/// Builds a session record, prefixing the serialized session with its expiry
+/// date so the compaction filter needn't decode it.
+fn record(session: &Session) -> Result<Vec<u8>, RocksDbStoreError> {
+    let mut record = session
+        .expiry_date()
+        .unix_timestamp()
+        .to_be_bytes()
+        .to_vec();
+    rmp_serde::encode::write(&mut record, session)?;
+    Ok(record)
+}
+
+fn record_expiry(record: &[u8]) -> i64 {
+    record
+        .get(..8)
+        .and_then(|prefix| prefix.try_into().ok())
+        .map_or(i64::MIN, i64::from_be_bytes)
+}
+
+fn expiry_filter(_level: u32, _key: &[u8], value: &[u8]) -> CompactionDecision {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+
+    if record_expiry(value) <= now {
+        CompactionDecision::Remove
+    } else {
+        CompactionDecision::Keep
+    }
+}
@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+pub use aws_sdk_s3;
+use aws_sdk_s3::{
+    error::{ProvideErrorMetadata, SdkError},
+    primitives::{ByteStream, ByteStreamError},
+    types::{
+        BucketLifecycleConfiguration, ExpirationStatus, LifecycleExpiration, LifecycleRule,
+        LifecycleRuleFilter,
+    },
+    Client,
+};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    Session, SessionStore,
+};
+
+/// The ID of the lifecycle rule managed by [`S3Store::migrate`].
+const LIFECYCLE_RULE_ID: &str = "tower-sessions-expiry";
+
+/// An error type for `S3Store`.
+#[derive(thiserror::Error, Debug)]
+pub enum S3StoreError {
+    /// A variant to map to `aws_sdk_s3::Error` errors.
+    #[error("S3 error: {0}")]
+    S3(#[from] aws_sdk_s3::Error),
+
+    /// A variant to map to `aws_sdk_s3::primitives::ByteStreamError` errors.
+    #[error("S3 body error: {0}")]
+    ByteStream(#[from] ByteStreamError),
+
+    /// A variant for sessions which could not be created because an object
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// An S3 session store, also compatible with other object storage services
+/// implementing the S3 API.
+///
+/// Sessions are stored as objects named after their ID under the configured
+/// prefix, holding their expiry date alongside the serialized session, so
+/// expired sessions are ignored when loading. Object storage can't expire
+/// individual objects at a given date; instead, [`migrate`](S3Store::migrate)
+/// installs a lifecycle rule removing session objects which haven't been
+/// written to for a number of days.
+///
+/// Sessions are created with conditional writes, which requires a service
+/// supporting `If-None-Match` on `PutObject`.
+#[derive(Clone, Debug)]
+pub struct S3Store {
+    client: Client,
+    bucket: String,
+    prefix: String,
+    lifecycle_expiration_days: i32,
+    clock: SharedClock,
+}
+
+impl S3Store {
+    /// Create a new S3 store with the provided client, storing sessions in
+    /// the provided bucket.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_s3_store::{aws_sdk_s3, S3Store};
+    ///
+    /// let config = aws_sdk_s3::Config::builder()
+    ///     .behavior_version_latest()
+    ///     .build();
+    /// let client = aws_sdk_s3::Client::from_conf(config);
+    /// let session_store = S3Store::new(client, "my-app");
+    /// ```
+    pub fn new(client: Client, bucket: &str) -> Self {
+        Self {
+            client,
+            bucket: bucket.to_string(),
+            prefix: "tower_sessions/".to_string(),
+            lifecycle_expiration_days: 30,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Set the prefix of session object keys, which defaults to
+    /// `tower_sessions/`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_s3_store::{aws_sdk_s3, S3Store};
+    ///
+    /// # let config = aws_sdk_s3::Config::builder().behavior_version_latest().build();
+    /// # let client = aws_sdk_s3::Client::from_conf(config);
+    /// let session_store = S3Store::new(client, "my-app").with_prefix("sessions/");
+    /// ```
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Set the number of days after which the lifecycle rule installed by
+    /// [`migrate`](S3Store::migrate) removes session objects that haven't
+    /// been written to, which defaults to 30.
+    ///
+    /// This should exceed the longest lifetime of sessions, including
+    /// sessions expiring on inactivity, as sessions are removed regardless
+    /// of their expiry date.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_s3_store::{aws_sdk_s3, S3Store};
+    ///
+    /// # let config = aws_sdk_s3::Config::builder().behavior_version_latest().build();
+    /// # let client = aws_sdk_s3::Client::from_conf(config);
+    /// let session_store = S3Store::new(client, "my-app").with_lifecycle_expiration_days(90);
+    /// ```
+    pub fn with_lifecycle_expiration_days(mut self, days: i32) -> Self {
+        self.lifecycle_expiration_days = days;
+        self
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the bucket's lifecycle configuration, adding or updating the
+    /// rule removing stale session objects while keeping any other rules.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_s3_store::{aws_sdk_s3, S3Store};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let config = aws_sdk_s3::Config::builder().behavior_version_latest().build();
+    /// # let client = aws_sdk_s3::Client::from_conf(config);
+    /// let session_store = S3Store::new(client, "my-app");
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), S3StoreError> {
+        let existing = self
+            .client
+            .get_bucket_lifecycle_configuration()
+            .bucket(&self.bucket)
+            .send()
+            .await;
+        let mut rules = match existing {
+            Ok(output) => output.rules.unwrap_or_default(),
+            Err(err) if err.code() == Some("NoSuchLifecycleConfiguration") => Vec::new(),
+            Err(err) => return Err(aws_sdk_s3::Error::from(err).into()),
+        };
+        rules.retain(|rule| rule.id() != Some(LIFECYCLE_RULE_ID));
+
+        let rule = LifecycleRule::builder()
+            .id(LIFECYCLE_RULE_ID)
+            .filter(LifecycleRuleFilter::builder().prefix(&self.prefix).build())
+            .status(ExpirationStatus::Enabled)
+            .expiration(
+                LifecycleExpiration::builder()
+                    .days(self.lifecycle_expiration_days)
+                    .build(),
+            )
+            .build()
+            .map_err(aws_sdk_s3::Error::from)?;
+        rules.push(rule);
+
+        let configuration = BucketLifecycleConfiguration::builder()
+            .set_rules(Some(rules))
+            .build()
+            .map_err(aws_sdk_s3::Error::from)?;
+        self.client
+            .put_bucket_lifecycle_configuration()
+            .bucket(&self.bucket)
+            .lifecycle_configuration(configuration)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+
+        Ok(())
+    }
+
+    fn key(&self, session_id: &Id) -> String {
+        format!("{}{}", self.prefix, session_id)
+    }
+
+    fn body(session: &Session) -> Result<ByteStream, rmp_serde::encode::Error> {
+        let record = rmp_serde::to_vec(&(session.expiry_date().unix_timestamp(), session))?;
+        Ok(ByteStream::from(record))
+    }
+}
+
+#[async_trait]
+impl SessionStore for S3Store {
+    type Error = S3StoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.key(session.id()))
+            .body(Self::body(session)?)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let created = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.key(session.id()))
+            .body(Self::body(session)?)
+            .if_none_match("*")
+            .send()
+            .await;
+
+        match created {
+            Ok(_) => Ok(()),
+            Err(SdkError::ServiceError(err)) if err.raw().status().as_u16() == 412 => {
+                Err(S3StoreError::IdCollision)
+            }
+            Err(err) => Err(aws_sdk_s3::Error::from(err).into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.key(session_id))
+            .send()
+            .await;
+
+        let output = match output {
+            Ok(output) => output,
+            Err(SdkError::ServiceError(err)) if err.err().is_no_such_key() => return Ok(None),
+            Err(err) => return Err(aws_sdk_s3::Error::from(err).into()),
+        };
+
+        let record = output.body.collect().await?.into_bytes();
+        let (expiry_date, session): (i64, Session) = rmp_serde::from_slice(&record)?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.key(session_id))
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("s3")
+    }
+}
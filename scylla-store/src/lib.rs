@@ -0,0 +1,298 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+pub use scylla;
+use scylla::{
+    client::{
+        caching_session::{CachingSession, CachingSessionBuilder},
+        session::Session as ScyllaSession,
+    },
+    errors::{ExecutionError, IntoRowsResultError, MaybeFirstRowError, SchemaAgreementError},
+    value::{CqlValue, Row},
+};
+use tower_sessions_core::{session::Id, Session, SessionStore};
+
+/// The maximum TTL supported by ScyllaDB and Cassandra, i.e. 20 years.
+const MAX_TTL_SECONDS: i64 = 630_720_000;
+
+/// An error type for `ScyllaStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum ScyllaStoreError {
+    /// A variant to map to `scylla::errors::ExecutionError` errors.
+    #[error("Scylla execution error: {0}")]
+    Execution(#[from] ExecutionError),
+
+    /// A variant to map to `scylla::errors::SchemaAgreementError` errors.
+    #[error("Scylla schema agreement error: {0}")]
+    SchemaAgreement(#[from] SchemaAgreementError),
+
+    /// A variant to map to `scylla::errors::IntoRowsResultError` errors.
+    #[error("Scylla result error: {0}")]
+    IntoRowsResult(#[from] IntoRowsResultError),
+
+    /// A variant to map to `scylla::errors::MaybeFirstRowError` errors.
+    #[error("Scylla row error: {0}")]
+    MaybeFirstRow(#[from] MaybeFirstRowError),
+
+    /// A variant for sessions which could not be created because a row with
+    /// the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A ScyllaDB session store, also compatible with Apache Cassandra.
+///
+/// Sessions are stored as rows partitioned by their ID, written with a TTL
+/// matching the session's expiry so that expired sessions are removed by the
+/// database. All statements are prepared and cached, which allows the driver
+/// to route them to the replicas owning a session's partition.
+#[derive(Clone, Debug)]
+pub struct ScyllaStore {
+    session: Arc<CachingSession>,
+    keyspace_name: String,
+    table_name: String,
+    replication: String,
+}
+
+impl ScyllaStore {
+    /// Create a new Scylla store with the provided session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_scylla_store::{
+    ///     scylla::client::session_builder::SessionBuilder, ScyllaStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .build()
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = ScyllaStore::new(session.into());
+    /// # })
+    /// ```
+    pub fn new(session: Arc<ScyllaSession>) -> Self {
+        Self {
+            session: Arc::new(CachingSessionBuilder::new_shared(session).build()),
+            keyspace_name: "tower_sessions".to_string(),
+            table_name: "session".to_string(),
+            replication: "{'class': 'SimpleStrategy', 'replication_factor': 1}".to_string(),
+        }
+    }
+
+    /// Set the session keyspace name with the provided name.
+    pub fn with_keyspace_name(mut self, keyspace_name: impl AsRef<str>) -> Result<Self, String> {
+        let keyspace_name = keyspace_name.as_ref();
+        if !is_valid_identifier(keyspace_name) {
+            return Err(format!(
+                "Invalid keyspace name '{}'. Keyspace names must start with a letter and contain \
+                 at most 48 letters, digits, or underscores.",
+                keyspace_name
+            ));
+        }
+
+        self.keyspace_name = keyspace_name.to_owned();
+        Ok(self)
+    }
+
+    /// Set the session table name with the provided name.
+    pub fn with_table_name(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
+        let table_name = table_name.as_ref();
+        if !is_valid_identifier(table_name) {
+            return Err(format!(
+                "Invalid table name '{}'. Table names must start with a letter and contain at \
+                 most 48 letters, digits, or underscores.",
+                table_name
+            ));
+        }
+
+        self.table_name = table_name.to_owned();
+        Ok(self)
+    }
+
+    /// Set the replication options used when [`migrate`](ScyllaStore::migrate)
+    /// creates the keyspace, as a CQL map literal.
+    ///
+    /// Defaults to `SimpleStrategy` with a replication factor of one, which is
+    /// only suitable for development; production clusters should use
+    /// `NetworkTopologyStrategy`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_scylla_store::{scylla::client::session_builder::SessionBuilder, ScyllaStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let session = SessionBuilder::new().known_node("127.0.0.1:9042").build().await.unwrap();
+    /// let session_store = ScyllaStore::new(session.into())
+    ///     .with_replication("{'class': 'NetworkTopologyStrategy', 'dc1': 3}");
+    /// # })
+    /// ```
+    pub fn with_replication(mut self, replication: &str) -> Self {
+        self.replication = replication.to_string();
+        self
+    }
+
+    /// Migrate the session schema, creating the keyspace and table if they
+    /// don't exist and waiting for the cluster to agree on the schema.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_scylla_store::{scylla::client::session_builder::SessionBuilder, ScyllaStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # let session = SessionBuilder::new().known_node("127.0.0.1:9042").build().await.unwrap();
+    /// let session_store = ScyllaStore::new(session.into());
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), ScyllaStoreError> {
+        let session = self.session.get_session();
+
+        let create_keyspace_query = format!(
+            "create keyspace if not exists {keyspace_name} with replication = {replication}",
+            keyspace_name = self.keyspace_name,
+            replication = self.replication,
+        );
+        session.query_unpaged(create_keyspace_query, ()).await?;
+
+        let create_table_query = format!(
+            "create table if not exists {keyspace_name}.{table_name} (id text primary key, data \
+             blob)",
+            keyspace_name = self.keyspace_name,
+            table_name = self.table_name,
+        );
+        session.query_unpaged(create_table_query, ()).await?;
+
+        session.await_schema_agreement().await?;
+
+        Ok(())
+    }
+
+    fn ttl(session: &Session) -> i32 {
+        session
+            .expiry_age()
+            .whole_seconds()
+            .clamp(0, MAX_TTL_SECONDS) as i32
+    }
+}
+
+#[async_trait]
+impl SessionStore for ScyllaStore {
+    type Error = ScyllaStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        // A TTL of zero means the row never expires.
+        let ttl = Self::ttl(session);
+        if ttl == 0 {
+            return self.delete(session.id()).await;
+        }
+
+        let query = format!(
+            "insert into {keyspace_name}.{table_name} (id, data) values (?, ?) using ttl ?",
+            keyspace_name = self.keyspace_name,
+            table_name = self.table_name,
+        );
+        self.session
+            .execute_unpaged(
+                query,
+                (session.id().to_string(), rmp_serde::to_vec(session)?, ttl),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // Expired sessions aren't stored, see `save`.
+        let ttl = Self::ttl(session);
+        if ttl == 0 {
+            return Ok(());
+        }
+
+        let query = format!(
+            "insert into {keyspace_name}.{table_name} (id, data) values (?, ?) if not exists \
+             using ttl ?",
+            keyspace_name = self.keyspace_name,
+            table_name = self.table_name,
+        );
+        let result = self
+            .session
+            .execute_unpaged(
+                query,
+                (session.id().to_string(), rmp_serde::to_vec(session)?, ttl),
+            )
+            .await?
+            .into_rows_result()?;
+
+        // Conditional statements return whether they were applied in the first column.
+        let applied = result
+            .maybe_first_row::<Row>()?
+            .and_then(|row| row.columns.into_iter().next().flatten());
+        if !matches!(applied, Some(CqlValue::Boolean(true))) {
+            return Err(ScyllaStoreError::IdCollision);
+        }
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let query = format!(
+            "select data from {keyspace_name}.{table_name} where id = ?",
+            keyspace_name = self.keyspace_name,
+            table_name = self.table_name,
+        );
+        let row = self
+            .session
+            .execute_unpaged(query, (session_id.to_string(),))
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<(Vec<u8>,)>()?;
+
+        if let Some((data,)) = row {
+            Ok(Some(rmp_serde::from_slice(&data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let query = format!(
+            "delete from {keyspace_name}.{table_name} where id = ?",
+            keyspace_name = self.keyspace_name,
+            table_name = self.table_name,
+        );
+        self.session
+            .execute_unpaged(query, (session_id.to_string(),))
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("scylladb")
+    }
+}
+
+/// A valid CQL identifier, unquoted, starts with a letter and contains at
+/// most 48 alphanumeric characters or underscores.
+fn is_valid_identifier(name: &str) -> bool {
+    name.len() <= 48
+        && name
+            .chars()
+            .next()
+            .map(|c| c.is_ascii_alphabetic())
+            .unwrap_or_default()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+pub use sea_orm;
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseBackend,
+    DatabaseConnection, DbErr, EntityTrait, QueryFilter, Schema, SqlErr,
+};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// The SeaORM entity for the session table.
+///
+/// The entity may be used with `sea-orm-migration` in place of
+/// [`SeaOrmStore::migrate`].
+pub mod entity {
+    use sea_orm::entity::prelude::*;
+
+    /// A session record.
+    #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+    #[sea_orm(table_name = "tower_sessions")]
+    pub struct Model {
+        /// The session ID.
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+
+        /// The MessagePack-serialized session.
+        pub data: Vec<u8>,
+
+        /// The date and time at which the session expires.
+        pub expiry_date: TimeDateTimeWithTimeZone,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+use entity::{ActiveModel, Column, Entity};
+
+/// An error type for `SeaOrmStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum SeaOrmStoreError {
+    /// A variant to map to `sea_orm::DbErr` errors.
+    #[error("SeaORM error: {0}")]
+    SeaOrm(#[from] DbErr),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A SeaORM session store.
+///
+/// Sessions are stored in the `tower_sessions` table, as described by
+/// [`entity`], over any database supported by the provided connection.
+#[derive(Clone, Debug)]
+pub struct SeaOrmStore {
+    db: DatabaseConnection,
+    clock: SharedClock,
+}
+
+impl SeaOrmStore {
+    /// Create a new SeaORM store with the provided database connection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sea_orm_store::{sea_orm::Database, SeaOrmStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = Database::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SeaOrmStore::new(db);
+    /// # })
+    /// ```
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            db,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session schema, creating the session table if it doesn't
+    /// exist.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sea_orm_store::{sea_orm::Database, SeaOrmStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = Database::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SeaOrmStore::new(db);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), DbErr> {
+        let backend = self.db.get_database_backend();
+        let mut create_table = Schema::new(backend).create_table_from_entity(Entity);
+        self.db
+            .execute(backend.build(create_table.if_not_exists()))
+            .await?;
+
+        Ok(())
+    }
+
+    fn active_model(session: &Session) -> Result<ActiveModel, rmp_serde::encode::Error> {
+        Ok(ActiveModel {
+            id: Set(session.id().to_string()),
+            data: Set(rmp_serde::to_vec(session)?),
+            expiry_date: Set(session.expiry_date()),
+        })
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for SeaOrmStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        Entity::delete_many()
+            .filter(Column::ExpiryDate.lt(self.clock.now()))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for SeaOrmStore {
+    type Error = SeaOrmStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        Entity::insert(Self::active_model(session)?)
+            .on_conflict(
+                OnConflict::column(Column::Id)
+                    .update_columns([Column::Data, Column::ExpiryDate])
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let inserted = Entity::insert(Self::active_model(session)?)
+            .exec_without_returning(&self.db)
+            .await;
+
+        match inserted {
+            Ok(_) => Ok(()),
+            Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                Err(SeaOrmStoreError::IdCollision)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let model = Entity::find_by_id(session_id.to_string())
+            .filter(Column::ExpiryDate.gt(self.clock.now()))
+            .one(&self.db)
+            .await?;
+
+        if let Some(model) = model {
+            Ok(Some(rmp_serde::from_slice(&model.data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        Entity::delete_by_id(session_id.to_string())
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some(match self.db.get_database_backend() {
+            DatabaseBackend::MySql => "mysql",
+            DatabaseBackend::Postgres => "postgresql",
+            DatabaseBackend::Sqlite => "sqlite",
+        })
+    }
+}
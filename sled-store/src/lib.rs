@@ -0,0 +1,199 @@
+use async_trait::async_trait;
+use serde::de::IgnoredAny;
+pub use sled;
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionError, TransactionalTree},
+    Db, Transactional, Tree,
+};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// An error type for `SledStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum SledStoreError {
+    /// A variant to map to `sled::Error` errors.
+    #[error("sled error: {0}")]
+    Sled(#[from] sled::Error),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+impl From<TransactionError<SledStoreError>> for SledStoreError {
+    fn from(err: TransactionError<SledStoreError>) -> Self {
+        match err {
+            TransactionError::Abort(err) => err,
+            TransactionError::Storage(err) => err.into(),
+        }
+    }
+}
+
+/// A sled session store.
+///
+/// Sessions are stored in the `tower_sessions` tree alongside their expiry
+/// date, which is also indexed in the `tower_sessions_expiry` tree so that
+/// [`delete_expired`](ExpiredDeletion::delete_expired) only visits expired
+/// sessions. With the `deletion-task` feature, this can be run periodically
+/// in the background with `continuously_delete_expired`.
+///
+/// Note that sled operations are synchronous; they are usually served from
+/// memory, but may block on IO.
+#[derive(Clone, Debug)]
+pub struct SledStore {
+    sessions: Tree,
+    expiries: Tree,
+    clock: SharedClock,
+}
+
+impl SledStore {
+    /// Create a new sled store with the provided database.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_sled_store::{sled, SledStore};
+    ///
+    /// let db = sled::Config::new().temporary(true).open().unwrap();
+    /// let session_store = SledStore::new(db).unwrap();
+    /// ```
+    pub fn new(db: Db) -> Result<Self, SledStoreError> {
+        Ok(Self {
+            sessions: db.open_tree("tower_sessions")?,
+            expiries: db.open_tree("tower_sessions_expiry")?,
+            clock: SharedClock::default(),
+        })
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    fn write(&self, session: &Session, overwrite: bool) -> Result<(), SledStoreError> {
+        let key = session.id().to_string();
+        let expiry_date = session.expiry_date().unix_timestamp();
+        let record = rmp_serde::to_vec(&(expiry_date, session))?;
+
+        (&self.sessions, &self.expiries).transaction(|(sessions, expiries)| {
+            if let Some(previous) = sessions.get(&key)? {
+                if !overwrite {
+                    return Err(ConflictableTransactionError::Abort(
+                        SledStoreError::IdCollision,
+                    ));
+                }
+                remove_expiry(expiries, &key, &previous)?;
+            }
+
+            sessions.insert(key.as_bytes(), record.as_slice())?;
+            expiries.insert(expiry_key(expiry_date, &key), &[])?;
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for SledStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let now = self.clock.now().unix_timestamp();
+        let end = expiry_key(now.saturating_add(1), "");
+
+        for entry in self.expiries.range(..end) {
+            let (index_key, _) = entry?;
+            let key = String::from_utf8_lossy(&index_key[8..]).into_owned();
+
+            (&self.sessions, &self.expiries).transaction(|(sessions, expiries)| {
+                expiries.remove(&index_key)?;
+
+                // The session may have been saved again since it was indexed.
+                if let Some(record) = sessions.get(&key)? {
+                    if record_expiry(&record)? <= now {
+                        sessions.remove(key.as_bytes())?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for SledStore {
+    type Error = SledStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, true)
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.write(session, false)
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let Some(record) = self.sessions.get(session_id.to_string())? else {
+            return Ok(None);
+        };
+
+        let (expiry_date, session): (i64, Session) = rmp_serde::from_slice(&record)?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let key = session_id.to_string();
+        (&self.sessions, &self.expiries).transaction(|(sessions, expiries)| {
+            if let Some(previous) = sessions.remove(key.as_bytes())? {
+                remove_expiry(expiries, &key, &previous)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("sled")
+    }
+}
+
+/// Builds the expiry index key, ordering entries by expiry date.
+fn expiry_key(expiry_date: i64, key: &str) -> Vec<u8> {
+    let mut index_key = expiry_date.max(0).to_be_bytes().to_vec();
+    index_key.extend_from_slice(key.as_bytes());
+    index_key
+}
+
+fn record_expiry(record: &[u8]) -> Result<i64, ConflictableTransactionError<SledStoreError>> {
+    let (expiry_date, _): (i64, IgnoredAny) = rmp_serde::from_slice(record)
+        .map_err(|err| ConflictableTransactionError::Abort(err.into()))?;
+    Ok(expiry_date)
+}
+
+fn remove_expiry(
+    expiries: &TransactionalTree,
+    key: &str,
+    record: &[u8],
+) -> Result<(), ConflictableTransactionError<SledStoreError>> {
+    expiries.remove(expiry_key(record_expiry(record)?, key))?;
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use async_trait::async_trait;
 use sqlx::MySqlPool;
-use time::OffsetDateTime;
-use tower_sessions_core::{session::Id, ExpiredDeletion, Session, SessionStore};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, ListSessions, Session, SessionStore, UserSessions,
+};
 
 use crate::SqlxStoreError;
 
@@ -11,6 +14,7 @@ pub struct MySqlStore {
     pool: MySqlPool,
     schema_name: String,
     table_name: String,
+    clock: SharedClock,
 }
 
 impl MySqlStore {
@@ -19,7 +23,7 @@ impl MySqlStore {
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tower_sessions::{sqlx::MySqlPool, MySqlStore};
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
     ///
     /// # tokio_test::block_on(async {
     /// let database_url = std::option_env!("DATABASE_URL").unwrap();
@@ -32,15 +36,89 @@ impl MySqlStore {
             pool,
             schema_name: "tower_sessions".to_string(),
             table_name: "session".to_string(),
+            clock: SharedClock::default(),
         }
     }
 
+    /// Set the session table schema name with the provided name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool).with_schema_name("app").unwrap();
+    /// # })
+    /// ```
+    pub fn with_schema_name(mut self, schema_name: impl AsRef<str>) -> Result<Self, String> {
+        let schema_name = schema_name.as_ref();
+        if !is_valid_identifier(schema_name) {
+            return Err(format!(
+                "Invalid schema name '{}'. Schema names must be at most 64 characters long and \
+                 may only contain letters, digits, underscores, or dollar signs ($).",
+                schema_name
+            ));
+        }
+
+        self.schema_name = schema_name.to_owned();
+        Ok(self)
+    }
+
+    /// Set the session table name with the provided name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool).with_table_name("sessions").unwrap();
+    /// # })
+    /// ```
+    pub fn with_table_name(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
+        let table_name = table_name.as_ref();
+        if !is_valid_identifier(table_name) {
+            return Err(format!(
+                "Invalid table name '{}'. Table names must be at most 64 characters long and may \
+                 only contain letters, digits, underscores, or dollar signs ($).",
+                table_name
+            ));
+        }
+
+        self.table_name = table_name.to_owned();
+        Ok(self)
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::clock::MockClock;
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = MySqlPool::connect(database_url).await.unwrap();
+    /// let session_store = MySqlStore::new(pool).with_clock(MockClock::default());
+    /// # })
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
     /// Migrate the session schema.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tower_sessions::{sqlx::MySqlPool, MySqlStore};
+    /// use tower_sessions_sqlx_store::{sqlx::MySqlPool, MySqlStore};
     ///
     /// # tokio_test::block_on(async {
     /// let database_url = std::option_env!("DATABASE_URL").unwrap();
@@ -53,7 +131,7 @@ impl MySqlStore {
         let mut tx = self.pool.begin().await?;
 
         let create_schema_query = format!(
-            "create schema if not exists {schema_name}",
+            "create schema if not exists `{schema_name}`",
             schema_name = self.schema_name,
         );
         sqlx::query(&create_schema_query).execute(&mut *tx).await?;
@@ -84,16 +162,42 @@ impl ExpiredDeletion for MySqlStore {
         let query = format!(
             r#"
             delete from `{schema_name}`.`{table_name}`
-            where expiry_date < utc_timestamp()
+            where expiry_date < ?
             "#,
             schema_name = self.schema_name,
             table_name = self.table_name
         );
-        sqlx::query(&query).execute(&self.pool).await?;
+        sqlx::query(&query)
+            .bind(self.clock.now())
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 }
 
+#[async_trait]
+impl ListSessions for MySqlStore {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        let query = format!(
+            r#"
+            select id from `{schema_name}`.`{table_name}`
+            where expiry_date > ?
+            "#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        let ids: Vec<(String,)> = sqlx::query_as(&query)
+            .bind(self.clock.now())
+            .fetch_all(&self.pool)
+            .await?;
+
+        ids.into_iter().map(|(id,)| Ok(Id::try_from(id)?)).collect()
+    }
+}
+
+#[async_trait]
+impl UserSessions for MySqlStore {}
+
 #[async_trait]
 impl SessionStore for MySqlStore {
     type Error = SqlxStoreError;
@@ -111,7 +215,7 @@ impl SessionStore for MySqlStore {
             table_name = self.table_name
         );
         sqlx::query(&query)
-            .bind(&session.id().to_string())
+            .bind(session.id().to_string())
             .bind(rmp_serde::to_vec(&session)?)
             .bind(session.expiry_date())
             .execute(&self.pool)
@@ -131,7 +235,7 @@ impl SessionStore for MySqlStore {
         );
         let data: Option<(Vec<u8>,)> = sqlx::query_as(&query)
             .bind(session_id.to_string())
-            .bind(OffsetDateTime::now_utc())
+            .bind(self.clock.now())
             .fetch_optional(&self.pool)
             .await?;
 
@@ -149,10 +253,26 @@ impl SessionStore for MySqlStore {
             table_name = self.table_name
         );
         sqlx::query(&query)
-            .bind(&session_id.to_string())
+            .bind(session_id.to_string())
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("mysql")
+    }
+}
+
+/// A valid MySQL identifier is at most 64 characters long and, so that it
+/// never needs escaping beyond quoting, is limited to letters, digits,
+/// underscores, and dollar signs ($).
+/// See https://dev.mysql.com/doc/refman/8.0/en/identifiers.html for details.
+fn is_valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
 }
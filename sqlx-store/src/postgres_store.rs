@@ -1,7 +1,10 @@
 use async_trait::async_trait;
 use sqlx::PgPool;
-use time::OffsetDateTime;
-use tower_sessions_core::{session::Id, ExpiredDeletion, Session, SessionStore};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, ListSessions, Session, SessionStore, UserSessions,
+};
 
 use crate::SqlxStoreError;
 
@@ -11,6 +14,7 @@ pub struct PostgresStore {
     pool: PgPool,
     schema_name: String,
     table_name: String,
+    clock: SharedClock,
 }
 
 impl PostgresStore {
@@ -19,7 +23,7 @@ impl PostgresStore {
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tower_sessions::{sqlx::PgPool, PostgresStore};
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
     ///
     /// # tokio_test::block_on(async {
     /// let database_url = std::option_env!("DATABASE_URL").unwrap();
@@ -32,6 +36,7 @@ impl PostgresStore {
             pool,
             schema_name: "tower_sessions".to_string(),
             table_name: "session".to_string(),
+            clock: SharedClock::default(),
         }
     }
 
@@ -65,12 +70,31 @@ impl PostgresStore {
         Ok(self)
     }
 
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::clock::MockClock;
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let pool = PgPool::connect(database_url).await.unwrap();
+    /// let session_store = PostgresStore::new(pool).with_clock(MockClock::default());
+    /// # })
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
     /// Migrate the session schema.
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tower_sessions::{sqlx::PgPool, PostgresStore};
+    /// use tower_sessions_sqlx_store::{sqlx::PgPool, PostgresStore};
     ///
     /// # tokio_test::block_on(async {
     /// let database_url = std::option_env!("DATABASE_URL").unwrap();
@@ -80,8 +104,6 @@ impl PostgresStore {
     /// # })
     /// ```
     pub async fn migrate(&self) -> sqlx::Result<()> {
-        let mut tx = self.pool.begin().await?;
-
         let create_schema_query = format!(
             r#"create schema if not exists "{schema_name}""#,
             schema_name = self.schema_name,
@@ -89,17 +111,19 @@ impl PostgresStore {
         // Concurrent create schema may fail due to duplicate key violations.
         //
         // This works around that by assuming the schema must exist on such an error.
-        if let Err(err) = sqlx::query(&create_schema_query).execute(&mut *tx).await {
+        // The schema is created outside of the transaction below so that such an
+        // error doesn't abort it, and the table is still created.
+        if let Err(err) = sqlx::query(&create_schema_query).execute(&self.pool).await {
             if !err
                 .to_string()
                 .contains("duplicate key value violates unique constraint")
             {
                 return Err(err);
             }
-
-            return Ok(());
         }
 
+        let mut tx = self.pool.begin().await?;
+
         let create_table_query = format!(
             r#"
             create table if not exists "{schema_name}"."{table_name}"
@@ -126,16 +150,42 @@ impl ExpiredDeletion for PostgresStore {
         let query = format!(
             r#"
             delete from "{schema_name}"."{table_name}"
-            where expiry_date < (now() at time zone 'utc')
+            where expiry_date < $1
             "#,
             schema_name = self.schema_name,
             table_name = self.table_name
         );
-        sqlx::query(&query).execute(&self.pool).await?;
+        sqlx::query(&query)
+            .bind(self.clock.now())
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 }
 
+#[async_trait]
+impl ListSessions for PostgresStore {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        let query = format!(
+            r#"
+            select id from "{schema_name}"."{table_name}"
+            where expiry_date > $1
+            "#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        let ids: Vec<(String,)> = sqlx::query_as(&query)
+            .bind(self.clock.now())
+            .fetch_all(&self.pool)
+            .await?;
+
+        ids.into_iter().map(|(id,)| Ok(Id::try_from(id)?)).collect()
+    }
+}
+
+#[async_trait]
+impl UserSessions for PostgresStore {}
+
 #[async_trait]
 impl SessionStore for PostgresStore {
     type Error = SqlxStoreError;
@@ -154,7 +204,7 @@ impl SessionStore for PostgresStore {
             table_name = self.table_name
         );
         sqlx::query(&query)
-            .bind(&session.id().to_string())
+            .bind(session.id().to_string())
             .bind(rmp_serde::to_vec(&session)?)
             .bind(session.expiry_date())
             .execute(&self.pool)
@@ -174,7 +224,7 @@ impl SessionStore for PostgresStore {
         );
         let record_value: Option<(Vec<u8>,)> = sqlx::query_as(&query)
             .bind(session_id.to_string())
-            .bind(OffsetDateTime::now_utc())
+            .bind(self.clock.now())
             .fetch_optional(&self.pool)
             .await?;
 
@@ -192,12 +242,16 @@ impl SessionStore for PostgresStore {
             table_name = self.table_name
         );
         sqlx::query(&query)
-            .bind(&session_id.to_string())
+            .bind(session_id.to_string())
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("postgresql")
+    }
 }
 
 /// A valid PostreSQL identifier must start with a letter or underscore (including letters with diacritical marks and non-Latin letters).
@@ -1,15 +1,46 @@
 use async_trait::async_trait;
 use sqlx::sqlite::SqlitePool;
-use time::OffsetDateTime;
-use tower_sessions_core::{session::Id, session_store::ExpiredDeletion, Session, SessionStore};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    session_store::{ExpiredDeletion, ListSessions, UserSessions},
+    Session, SessionStore,
+};
 
 use crate::SqlxStoreError;
 
 /// A SQLite session store.
+///
+/// Both in-memory and file-backed databases are supported. Sessions are saved
+/// with a single upsert statement, so file-backed databases work well in WAL
+/// mode, which allows sessions to be loaded while another connection writes.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions_sqlx_store::{
+///     sqlx::{
+///         sqlite::{SqliteConnectOptions, SqliteJournalMode},
+///         SqlitePool,
+///     },
+///     SqliteStore,
+/// };
+///
+/// # tokio_test::block_on(async {
+/// let options = SqliteConnectOptions::new()
+///     .filename("sessions.db")
+///     .create_if_missing(true)
+///     .journal_mode(SqliteJournalMode::Wal);
+/// let pool = SqlitePool::connect_with(options).await.unwrap();
+/// let session_store = SqliteStore::new(pool);
+/// session_store.migrate().await.unwrap();
+/// # })
+/// ```
 #[derive(Clone, Debug)]
 pub struct SqliteStore {
     pool: SqlitePool,
     table_name: String,
+    clock: SharedClock,
 }
 
 impl SqliteStore {
@@ -18,7 +49,7 @@ impl SqliteStore {
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tower_sessions::{sqlx::SqlitePool, SqliteStore};
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore};
     ///
     /// # tokio_test::block_on(async {
     /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
@@ -29,6 +60,7 @@ impl SqliteStore {
         Self {
             pool,
             table_name: "tower_sessions".into(),
+            clock: SharedClock::default(),
         }
     }
 
@@ -47,6 +79,24 @@ impl SqliteStore {
         Ok(self)
     }
 
+    /// Configures the clock used to determine whether a session has expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::clock::MockClock;
+    /// use tower_sessions_sqlx_store::{sqlx::SqlitePool, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SqliteStore::new(pool).with_clock(MockClock::default());
+    /// # })
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
     /// Migrate the session schema.
     pub async fn migrate(&self) -> sqlx::Result<()> {
         let query = format!(
@@ -71,15 +121,40 @@ impl ExpiredDeletion for SqliteStore {
         let query = format!(
             r#"
             delete from {table_name}
-            where expiry_date < datetime('now', 'utc')
+            where expiry_date < ?
             "#,
             table_name = self.table_name
         );
-        sqlx::query(&query).execute(&self.pool).await?;
+        sqlx::query(&query)
+            .bind(self.clock.now())
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 }
 
+#[async_trait]
+impl ListSessions for SqliteStore {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        let query = format!(
+            r#"
+            select id from {}
+            where expiry_date > ?
+            "#,
+            self.table_name
+        );
+        let ids: Vec<(String,)> = sqlx::query_as(&query)
+            .bind(self.clock.now())
+            .fetch_all(&self.pool)
+            .await?;
+
+        ids.into_iter().map(|(id,)| Ok(Id::try_from(id)?)).collect()
+    }
+}
+
+#[async_trait]
+impl UserSessions for SqliteStore {}
+
 #[async_trait]
 impl SessionStore for SqliteStore {
     type Error = SqlxStoreError;
@@ -96,7 +171,7 @@ impl SessionStore for SqliteStore {
             self.table_name
         );
         sqlx::query(&query)
-            .bind(&session.id().to_string())
+            .bind(session.id().to_string())
             .bind(rmp_serde::to_vec(session)?)
             .bind(session.expiry_date())
             .execute(&self.pool)
@@ -115,7 +190,7 @@ impl SessionStore for SqliteStore {
         );
         let data: Option<(Vec<u8>,)> = sqlx::query_as(&query)
             .bind(session_id.to_string())
-            .bind(OffsetDateTime::now_utc())
+            .bind(self.clock.now())
             .fetch_optional(&self.pool)
             .await?;
 
@@ -134,12 +209,16 @@ impl SessionStore for SqliteStore {
             self.table_name
         );
         sqlx::query(&query)
-            .bind(&session_id.to_string())
+            .bind(session_id.to_string())
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("sqlite")
+    }
 }
 
 fn is_valid_table_name(name: &str) -> bool {
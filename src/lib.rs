@@ -12,7 +12,12 @@
 //!   can use `Session` as an extractor directly in their handlers. This makes
 //!   using sessions as easy as including `Session` in your handler.
 //! - **Common Backends Out-of-the-Box:** [`RedisStore`], SQLx ([`SqliteStore`],
-//!   [`PostgresStore`], [`MySqlStore`]), and [`MongoDBStore`] stores are
+//!   [`PostgresStore`], [`MySqlStore`]), [`MongoDBStore`], [`DynamoDBStore`],
+//!   [`MemcachedStore`], [`SeaOrmStore`], [`DieselPostgresStore`],
+//!   [`DieselMySqlStore`], [`ScyllaStore`], [`FileStore`], [`SledStore`],
+//!   [`RedbStore`], [`EtcdStore`], [`NatsStore`], [`WorkersKvStore`],
+//!   [`S3Store`], [`TokioPostgresStore`], [`SurrealStore`], [`FirestoreStore`],
+//!   [`CosmosStore`], [`LmdbStore`], and [`AerospikeStore`] stores are
 //!   available via their respective feature flags.
 //! - **Layered Caching:** With [`CachingSessionStore`], applications can
 //!   leverage a cache, such as [`MokaStore`], to reduce roundtrips to the store
@@ -120,7 +125,7 @@
 //! # use axum::extract::FromRequestParts;
 //! # use http::{request::Parts, StatusCode};
 //! # use serde::{Deserialize, Serialize};
-//! # use tower_sessions::Session;
+//! # use tower_sessions::{extract::SessionRejection, Session};
 //! const COUNTER_KEY: &str = "counter";
 //!
 //! #[derive(Default, Deserialize, Serialize)]
@@ -131,7 +136,7 @@
 //! where
 //!     S: Send + Sync,
 //! {
-//!     type Rejection = (http::StatusCode, &'static str);
+//!     type Rejection = SessionRejection;
 //!
 //!     async fn from_request_parts(req: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
 //!         let session = Session::from_request_parts(req, state).await?;
@@ -160,7 +165,7 @@
 //! # use http::{request::Parts, StatusCode};
 //! # use serde::{Deserialize, Serialize};
 //! # use time::OffsetDateTime;
-//! # use tower_sessions::Session;
+//! # use tower_sessions::{extract::SessionRejection, Session};
 //! # use uuid::Uuid;
 //! #[derive(Clone, Deserialize, Serialize)]
 //! struct GuestData {
@@ -222,7 +227,7 @@
 //! where
 //!     S: Send + Sync,
 //! {
-//!     type Rejection = (StatusCode, &'static str);
+//!     type Rejection = SessionRejection;
 //!
 //!     async fn from_request_parts(req: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
 //!         let session = Session::from_request_parts(req, state).await?;
@@ -413,6 +418,11 @@
 //!    the session will have been marked as modified and so this will also set a
 //!    `Set-Cookie` header on the response.
 //!
+//! These transitions may be observed by subscribing to the
+//! [`SessionEvents`](events::SessionEvents) configured with
+//! [`with_events`](SessionManagerLayer::with_events), which reports sessions
+//! being created, renewed, destroyed, and found expired.
+//!
 //! [^getrandom]: `uuid` uses `getrandom` which varies by platform; the crucial
 //!   assumption `tower-sessions` makes is that your platform is secure.
 //! However, you **must** verify this for yourself.
@@ -440,13 +450,99 @@
 #![forbid(unsafe_code)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-pub use tower_sessions_core::{cookie, service, session, session_store};
+#[cfg(feature = "aerospike-store")]
+pub use tower_sessions_aerospike_store::aerospike;
+#[cfg(feature = "aerospike-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "aerospike-store")))]
+#[doc(inline)]
+pub use tower_sessions_aerospike_store::{AerospikeStore, AerospikeStoreError};
+#[cfg(feature = "blocking")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+pub use tower_sessions_core::blocking;
+#[cfg(feature = "compressed-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compressed-store")))]
+pub use tower_sessions_core::compressed_store;
+#[cfg(feature = "encrypted-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encrypted-store")))]
+pub use tower_sessions_core::encrypted_store;
+#[cfg(feature = "axum-core")]
+#[cfg_attr(docsrs, doc(cfg(feature = "axum-core")))]
+pub use tower_sessions_core::extract;
+#[cfg(feature = "maintenance")]
+#[cfg_attr(docsrs, doc(cfg(feature = "maintenance")))]
+pub use tower_sessions_core::maintenance;
+#[cfg(feature = "private")]
+#[cfg_attr(docsrs, doc(cfg(feature = "private")))]
+pub use tower_sessions_core::stateless;
+#[cfg(feature = "timeout-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "timeout-store")))]
+pub use tower_sessions_core::timeout_store;
+pub use tower_sessions_core::{
+    audit, clock, cookie, csrf, events, key_provider, limit, metrics, one_time, persistence,
+    remember, scope, service, session, session_store, telemetry, transfer,
+};
 #[doc(inline)]
 pub use tower_sessions_core::{
     service::{SessionManager, SessionManagerLayer},
     session::{Expiry, Session},
-    session_store::{CachingSessionStore, ExpiredDeletion, SessionStore},
+    session_store::{
+        CachingSessionStore, ExpiredDeletion, ListSessions, SessionStore, TakeSession, UserSessions,
+    },
 };
+#[cfg(feature = "cosmos-store")]
+pub use tower_sessions_cosmos_store::azure_data_cosmos;
+#[cfg(feature = "cosmos-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cosmos-store")))]
+#[doc(inline)]
+pub use tower_sessions_cosmos_store::{CosmosStore, CosmosStoreError};
+#[cfg(feature = "diesel-mysql-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "diesel-mysql-store")))]
+#[doc(inline)]
+pub use tower_sessions_diesel_store::DieselMySqlStore;
+#[cfg(feature = "diesel-postgres-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "diesel-postgres-store")))]
+#[doc(inline)]
+pub use tower_sessions_diesel_store::DieselPostgresStore;
+#[cfg(feature = "diesel-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "diesel-store")))]
+#[doc(inline)]
+pub use tower_sessions_diesel_store::DieselStoreError;
+#[cfg(feature = "diesel-store")]
+pub use tower_sessions_diesel_store::{diesel, diesel_async};
+#[cfg(feature = "dynamodb-store")]
+pub use tower_sessions_dynamodb_store::aws_sdk_dynamodb;
+#[cfg(feature = "dynamodb-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dynamodb-store")))]
+#[doc(inline)]
+pub use tower_sessions_dynamodb_store::DynamoDBStore;
+#[cfg(feature = "etcd-store")]
+pub use tower_sessions_etcd_store::etcd_client;
+#[cfg(feature = "etcd-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "etcd-store")))]
+#[doc(inline)]
+pub use tower_sessions_etcd_store::{EtcdStore, EtcdStoreError};
+#[cfg(feature = "file-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "file-store")))]
+#[doc(inline)]
+pub use tower_sessions_file_store::{FileStore, FileStoreError};
+#[cfg(feature = "firestore-store")]
+pub use tower_sessions_firestore_store::firestore;
+#[cfg(feature = "firestore-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "firestore-store")))]
+#[doc(inline)]
+pub use tower_sessions_firestore_store::{FirestoreStore, FirestoreStoreError};
+#[cfg(feature = "lmdb-store")]
+pub use tower_sessions_lmdb_store::heed;
+#[cfg(feature = "lmdb-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lmdb-store")))]
+#[doc(inline)]
+pub use tower_sessions_lmdb_store::{LmdbStore, LmdbStoreError};
+#[cfg(feature = "memcached-store")]
+pub use tower_sessions_memcached_store::async_memcached;
+#[cfg(feature = "memcached-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "memcached-store")))]
+#[doc(inline)]
+pub use tower_sessions_memcached_store::{MemcachedStore, MemcachedStoreError};
 #[cfg(feature = "memory-store")]
 #[cfg_attr(docsrs, doc(cfg(feature = "memory-store")))]
 #[doc(inline)]
@@ -461,12 +557,52 @@ pub use tower_sessions_mongodb_store::mongodb;
 #[cfg_attr(docsrs, doc(cfg(feature = "mongodb-store")))]
 #[doc(inline)]
 pub use tower_sessions_mongodb_store::MongoDBStore;
+#[cfg(feature = "nats-store")]
+pub use tower_sessions_nats_store::async_nats;
+#[cfg(feature = "nats-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nats-store")))]
+#[doc(inline)]
+pub use tower_sessions_nats_store::{NatsStore, NatsStoreError};
+#[cfg(feature = "redb-store")]
+pub use tower_sessions_redb_store::redb;
+#[cfg(feature = "redb-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "redb-store")))]
+#[doc(inline)]
+pub use tower_sessions_redb_store::{RedbStore, RedbStoreError};
+#[cfg(feature = "redis-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "redis-store")))]
+#[doc(inline)]
+pub use tower_sessions_redis_store::codec::Codec as RedisCodec;
 #[cfg(feature = "redis-store")]
 pub use tower_sessions_redis_store::fred;
 #[cfg(feature = "redis-store")]
 #[cfg_attr(docsrs, doc(cfg(feature = "redis-store")))]
 #[doc(inline)]
-pub use tower_sessions_redis_store::RedisStore;
+pub use tower_sessions_redis_store::{RedisStore, RedisStoreError};
+#[cfg(feature = "s3-store")]
+pub use tower_sessions_s3_store::aws_sdk_s3;
+#[cfg(feature = "s3-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "s3-store")))]
+#[doc(inline)]
+pub use tower_sessions_s3_store::{S3Store, S3StoreError};
+#[cfg(feature = "scylla-store")]
+pub use tower_sessions_scylla_store::scylla;
+#[cfg(feature = "scylla-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "scylla-store")))]
+#[doc(inline)]
+pub use tower_sessions_scylla_store::{ScyllaStore, ScyllaStoreError};
+#[cfg(feature = "sea-orm-store")]
+pub use tower_sessions_sea_orm_store::sea_orm;
+#[cfg(feature = "sea-orm-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sea-orm-store")))]
+#[doc(inline)]
+pub use tower_sessions_sea_orm_store::{SeaOrmStore, SeaOrmStoreError};
+#[cfg(feature = "sled-store")]
+pub use tower_sessions_sled_store::sled;
+#[cfg(feature = "sled-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sled-store")))]
+#[doc(inline)]
+pub use tower_sessions_sled_store::{SledStore, SledStoreError};
 #[cfg(feature = "sqlx-store")]
 pub use tower_sessions_sqlx_store::sqlx;
 #[cfg(feature = "mysql-store")]
@@ -481,3 +617,23 @@ pub use tower_sessions_sqlx_store::PostgresStore;
 #[cfg_attr(docsrs, doc(cfg(feature = "sqlite-store")))]
 #[doc(inline)]
 pub use tower_sessions_sqlx_store::SqliteStore;
+#[cfg(feature = "surrealdb-store")]
+pub use tower_sessions_surrealdb_store::surrealdb;
+#[cfg(feature = "surrealdb-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "surrealdb-store")))]
+#[doc(inline)]
+pub use tower_sessions_surrealdb_store::{SurrealStore, SurrealStoreError};
+#[cfg(feature = "tokio-postgres-store")]
+pub use tower_sessions_tokio_postgres_store::deadpool_postgres;
+#[cfg(feature = "tokio-postgres-store")]
+pub use tower_sessions_tokio_postgres_store::tokio_postgres;
+#[cfg(feature = "tokio-postgres-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-postgres-store")))]
+#[doc(inline)]
+pub use tower_sessions_tokio_postgres_store::{TokioPostgresStore, TokioPostgresStoreError};
+#[cfg(feature = "workers-kv-store")]
+pub use tower_sessions_workers_kv_store::worker;
+#[cfg(feature = "workers-kv-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "workers-kv-store")))]
+#[doc(inline)]
+pub use tower_sessions_workers_kv_store::{WorkersKvStore, WorkersKvStoreError};
@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+pub use surrealdb;
+use surrealdb::{
+    types::{Bytes, Datetime, RecordId},
+    Connection, Surreal,
+};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// An error type for `SurrealStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum SurrealStoreError {
+    /// A variant to map to `surrealdb::Error` errors.
+    #[error("SurrealDB error: {0}")]
+    Surreal(#[from] surrealdb::Error),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for expiry dates out of the range of SurrealDB datetimes.
+    #[error("Invalid expiry date")]
+    InvalidExpiryDate,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A SurrealDB session store.
+///
+/// Sessions are stored as records of the session table, keyed by their ID,
+/// holding the serialized session and its expiry date. Any engine supported
+/// by the SDK may be used, and the namespace and database must be selected
+/// on the client beforehand.
+///
+/// Expired sessions are ignored when loading, and may be removed with
+/// [`delete_expired`](ExpiredDeletion::delete_expired). With the
+/// `deletion-task` feature, this can be run periodically in the background
+/// with `continuously_delete_expired`. Alternatively,
+/// [`with_expiry_event`](SurrealStore::with_expiry_event) lets the database
+/// remove expired sessions whenever a session is created.
+pub struct SurrealStore<C: Connection> {
+    db: Surreal<C>,
+    table_name: String,
+    expiry_event: bool,
+    clock: SharedClock,
+}
+
+impl<C: Connection> Clone for SurrealStore<C> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            table_name: self.table_name.clone(),
+            expiry_event: self.expiry_event,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<C: Connection> std::fmt::Debug for SurrealStore<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SurrealStore")
+            .field("table_name", &self.table_name)
+            .field("expiry_event", &self.expiry_event)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Connection> SurrealStore<C> {
+    /// Create a new SurrealDB store with the provided client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_surrealdb_store::{surrealdb, SurrealStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = surrealdb::engine::any::connect("ws://localhost:8000")
+    ///     .await
+    ///     .unwrap();
+    /// db.use_ns("my_app").use_db("my_app").await.unwrap();
+    /// let session_store = SurrealStore::new(db);
+    /// # })
+    /// ```
+    pub fn new(db: Surreal<C>) -> Self {
+        Self {
+            db,
+            table_name: "tower_sessions".to_string(),
+            expiry_event: false,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Set the session table name with the provided name.
+    pub fn with_table_name(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
+        let table_name = table_name.as_ref();
+        if !is_valid_table_name(table_name) {
+            return Err(format!(
+                "Invalid table name '{}'. Table names must start with a letter or underscore and \
+                 contain only letters, digits, or underscores.",
+                table_name
+            ));
+        }
+
+        table_name.clone_into(&mut self.table_name);
+        Ok(self)
+    }
+
+    /// Define an event on the session table removing expired sessions
+    /// whenever a session is created, when migrating.
+    ///
+    /// Saves creating a record fire the event too, so a session saved with
+    /// an expiry date already past is removed right away. The event compares
+    /// expiry dates with the database's time rather than the store's clock.
+    /// Disabling it afterwards and migrating again removes the event.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_surrealdb_store::{surrealdb, SurrealStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = surrealdb::engine::any::connect("ws://localhost:8000")
+    ///     .await
+    ///     .unwrap();
+    /// db.use_ns("my_app").use_db("my_app").await.unwrap();
+    /// let session_store = SurrealStore::new(db).with_expiry_event(true);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub fn with_expiry_event(mut self, expiry_event: bool) -> Self {
+        self.expiry_event = expiry_event;
+        self
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session schema, defining the session table, its fields,
+    /// and an index on expiry dates.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_surrealdb_store::{surrealdb, SurrealStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let db = surrealdb::engine::any::connect("ws://localhost:8000")
+    ///     .await
+    ///     .unwrap();
+    /// db.use_ns("my_app").use_db("my_app").await.unwrap();
+    /// let session_store = SurrealStore::new(db);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), SurrealStoreError> {
+        let expiry_event = if self.expiry_event {
+            format!(
+                r#"
+                define event overwrite {table_name}_expiry on table {table_name}
+                when $event = "CREATE"
+                then (delete {table_name} where expiry_date < time::now());
+                "#,
+                table_name = self.table_name
+            )
+        } else {
+            format!(
+                "remove event if exists {table_name}_expiry on table {table_name};",
+                table_name = self.table_name
+            )
+        };
+
+        let query = format!(
+            r#"
+            define table if not exists {table_name} schemafull;
+            define field if not exists data on table {table_name} type bytes;
+            define field if not exists expiry_date on table {table_name} type datetime;
+            define index if not exists {table_name}_expiry_date on table {table_name}
+                fields expiry_date;
+            {expiry_event}
+            "#,
+            table_name = self.table_name
+        );
+        self.db.query(query).await?.check()?;
+
+        Ok(())
+    }
+
+    fn record_id(&self, session_id: &Id) -> RecordId {
+        RecordId::new(self.table_name.as_str(), session_id.to_string())
+    }
+
+    fn now(&self) -> Result<Datetime, SurrealStoreError> {
+        datetime(self.clock.now().unix_timestamp())
+    }
+}
+
+#[async_trait]
+impl<C: Connection> ExpiredDeletion for SurrealStore<C> {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let query = format!(
+            "delete {table_name} where expiry_date < $now",
+            table_name = self.table_name
+        );
+        self.db
+            .query(query)
+            .bind(("now", self.now()?))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<C: Connection> SessionStore for SurrealStore<C> {
+    type Error = SurrealStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.db
+            .query("upsert $record content { data: $data, expiry_date: $expiry_date } return none")
+            .bind(("record", self.record_id(session.id())))
+            .bind(("data", Bytes::from(rmp_serde::to_vec(session)?)))
+            .bind((
+                "expiry_date",
+                datetime(session.expiry_date().unix_timestamp())?,
+            ))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let created = self
+            .db
+            .query("create $record content { data: $data, expiry_date: $expiry_date } return none")
+            .bind(("record", self.record_id(session.id())))
+            .bind(("data", Bytes::from(rmp_serde::to_vec(session)?)))
+            .bind((
+                "expiry_date",
+                datetime(session.expiry_date().unix_timestamp())?,
+            ))
+            .await?
+            .check();
+
+        match created {
+            Ok(_) => Ok(()),
+            Err(err) if err.is_already_exists() => Err(SurrealStoreError::IdCollision),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let data: Option<Bytes> = self
+            .db
+            .query("select data from $record where expiry_date > $now")
+            .bind(("record", self.record_id(session_id)))
+            .bind(("now", self.now()?))
+            .await?
+            .take((0, "data"))?;
+
+        if let Some(data) = data {
+            Ok(Some(rmp_serde::from_slice(&data.into_inner())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.db
+            .query("delete $record")
+            .bind(("record", self.record_id(session_id)))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("surrealdb")
+    }
+}
+
+fn datetime(unix_timestamp: i64) -> Result<Datetime, SurrealStoreError> {
+    Datetime::from_timestamp(unix_timestamp, 0).ok_or(SurrealStoreError::InvalidExpiryDate)
+}
+
+/// Table names are used as SurrealQL identifiers, which may contain letters,
+/// digits, and underscores, and must not start with a digit.
+fn is_valid_table_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -2,10 +2,15 @@ use axum::{error_handling::HandleErrorLayer, routing::get, Router};
 use axum_core::{body::Body, BoxError};
 use http::{header, HeaderMap, StatusCode};
 use http_body_util::BodyExt;
+use proptest::prelude::*;
 use time::Duration;
 use tower::ServiceBuilder;
 use tower_cookies::{cookie, Cookie};
-use tower_sessions::{Expiry, Session, SessionManagerLayer, SessionStore};
+use tower_sessions::{
+    clock::{Clock, MockClock},
+    session::Id,
+    Expiry, Session, SessionManagerLayer, SessionStore,
+};
 
 fn routes() -> Router {
     Router::new()
@@ -44,12 +49,25 @@ fn routes() -> Router {
                 session.cycle_id();
             }),
         )
+        .route(
+            "/promote",
+            get(|session: Session| async move {
+                session.promote("alice", &["foo"]);
+            }),
+        )
         .route(
             "/delete",
             get(|session: Session| async move {
                 session.delete();
             }),
         )
+        .route(
+            "/grant_consent",
+            get(|session: Session| async move {
+                session.grant_consent();
+                session.insert("foo", 42).unwrap();
+            }),
+        )
 }
 
 pub fn build_app<Store: SessionStore>(
@@ -84,6 +102,133 @@ pub fn get_session_cookie(headers: &HeaderMap) -> Result<Cookie<'_>, cookie::Par
         .and_then(Cookie::parse_encoded)
 }
 
+/// The number of sessions the store property tests juggle at once.
+const SLOTS: usize = 4;
+
+/// An operation applied to a store by the store property tests.
+///
+/// Sessions are addressed by slot, and lifetimes as well as clock advances are
+/// given in minutes. Sessions expire half a minute past their lifetime, so
+/// they never expire at the very instant the clock is set to.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// Create a new session in the slot, replacing any session held there.
+    Create {
+        slot: usize,
+        lifetime: i64,
+        value: i64,
+    },
+    /// Modify the session in the slot and save it, extending its lifetime.
+    Save {
+        slot: usize,
+        lifetime: i64,
+        value: i64,
+    },
+    /// Load the session in the slot.
+    Load { slot: usize },
+    /// Delete the session in the slot, emptying the slot.
+    Delete { slot: usize },
+    /// Move the clock forward.
+    Advance { minutes: i64 },
+}
+
+/// A strategy yielding sequences of store operations.
+pub fn operations() -> impl Strategy<Value = Vec<Operation>> {
+    let slot = 0..SLOTS;
+    let lifetime = 1..120_i64;
+
+    let operation = prop_oneof![
+        2 => (slot.clone(), lifetime.clone(), any::<i64>())
+            .prop_map(|(slot, lifetime, value)| Operation::Create { slot, lifetime, value }),
+        2 => (slot.clone(), lifetime, any::<i64>())
+            .prop_map(|(slot, lifetime, value)| Operation::Save { slot, lifetime, value }),
+        3 => slot.clone().prop_map(|slot| Operation::Load { slot }),
+        1 => slot.prop_map(|slot| Operation::Delete { slot }),
+        2 => (1..90_i64).prop_map(|minutes| Operation::Advance { minutes }),
+    ];
+
+    prop::collection::vec(operation, 1..32)
+}
+
+fn expiry(clock: &MockClock, lifetime: i64) -> Expiry {
+    let expiry_date = clock.now() + Duration::minutes(lifetime) + Duration::seconds(30);
+    Expiry::AtDateTime(expiry_date)
+}
+
+/// Applies the operations to the store, whose clock must be the provided
+/// clock, asserting that it behaves like a map of unexpired sessions.
+///
+/// In particular, sessions load as last saved until they expire, expired
+/// sessions stay expired as time moves on, and deleted sessions are never
+/// resurrected.
+pub async fn check_operations<Store: SessionStore>(
+    store: &Store,
+    clock: &MockClock,
+    operations: Vec<Operation>,
+) {
+    let mut slots: [Option<Session>; SLOTS] = Default::default();
+    let mut deleted: Vec<Id> = Vec::new();
+
+    for operation in operations {
+        match operation {
+            Operation::Create {
+                slot,
+                lifetime,
+                value,
+            } => {
+                let session = Session::new(Some(expiry(clock, lifetime)));
+                session.insert("value", value).unwrap();
+                store.create(&session).await.unwrap();
+                slots[slot] = Some(session);
+            }
+
+            Operation::Save {
+                slot,
+                lifetime,
+                value,
+            } => {
+                if let Some(session) = &slots[slot] {
+                    session.set_expiry(Some(expiry(clock, lifetime)));
+                    session.insert("value", value).unwrap();
+                    store.save(session).await.unwrap();
+                }
+            }
+
+            Operation::Load { slot } => {
+                if let Some(session) = &slots[slot] {
+                    let loaded = store.load(session.id()).await.unwrap();
+                    if session.expiry_date() > clock.now() {
+                        let loaded = loaded.expect("active sessions load");
+                        assert_eq!(
+                            loaded.get::<i64>("value").unwrap(),
+                            session.get::<i64>("value").unwrap()
+                        );
+                        // Stores may round expiry dates, e.g. to microseconds.
+                        let drift = (loaded.expiry_date() - session.expiry_date()).abs();
+                        assert!(drift < Duration::seconds(1));
+                    } else {
+                        assert!(loaded.is_none(), "expired sessions don't load");
+                    }
+                }
+            }
+
+            Operation::Delete { slot } => {
+                if let Some(session) = slots[slot].take() {
+                    store.delete(session.id()).await.unwrap();
+                    deleted.push(*session.id());
+                }
+            }
+
+            Operation::Advance { minutes } => clock.advance(Duration::minutes(minutes)),
+        }
+
+        for session_id in &deleted {
+            let loaded = store.load(session_id).await.unwrap();
+            assert!(loaded.is_none(), "deleted sessions aren't resurrected");
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! route_tests {
     ($create_app:expr) => {
@@ -285,6 +430,56 @@ macro_rules! route_tests {
             assert_eq!(body_string(res.into_body()).await, "42");
         }
 
+        #[tokio::test]
+        async fn promote_session() {
+            let app = $create_app(Some(Duration::hours(1))).await;
+
+            let req = Request::builder()
+                .uri("/insert")
+                .body(Body::empty())
+                .unwrap();
+            let res = app.clone().oneshot(req).await.unwrap();
+            let anonymous_session_cookie = get_session_cookie(res.headers()).unwrap();
+
+            let req = Request::builder()
+                .uri("/promote")
+                .header(
+                    header::COOKIE,
+                    anonymous_session_cookie.encoded().to_string(),
+                )
+                .body(Body::empty())
+                .unwrap();
+            let res = app.clone().oneshot(req).await.unwrap();
+            let promoted_session_cookie = get_session_cookie(res.headers()).unwrap();
+            assert_ne!(
+                anonymous_session_cookie.value(),
+                promoted_session_cookie.value()
+            );
+
+            let req = Request::builder()
+                .uri("/get")
+                .header(
+                    header::COOKIE,
+                    promoted_session_cookie.encoded().to_string(),
+                )
+                .body(Body::empty())
+                .unwrap();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(body_string(res.into_body()).await, "42");
+
+            // The anonymous session no longer exists.
+            let req = Request::builder()
+                .uri("/get_value")
+                .header(
+                    header::COOKIE,
+                    anonymous_session_cookie.encoded().to_string(),
+                )
+                .body(Body::empty())
+                .unwrap();
+            let res = app.oneshot(req).await.unwrap();
+            assert_eq!(body_string(res.into_body()).await, "None");
+        }
+
         #[tokio::test]
         async fn delete_session() {
             let app = $create_app(Some(Duration::hours(1))).await;
@@ -310,3 +505,28 @@ macro_rules! route_tests {
         }
     };
 }
+
+#[macro_export]
+macro_rules! store_property_tests {
+    ($create_store:expr) => {
+        use proptest::prelude::*;
+        use $crate::common::{check_operations, operations};
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(32))]
+
+            #[test]
+            fn store_behaves_like_a_map_of_unexpired_sessions(operations in operations()) {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                rt.block_on(async {
+                    let clock = tower_sessions::clock::MockClock::default();
+                    let session_store = $create_store(clock.clone()).await;
+                    check_operations(&session_store, &clock, operations).await;
+                });
+            }
+        }
+    };
+}
@@ -17,6 +17,116 @@ mod memory_store_tests {
     route_tests!(app);
 }
 
+#[cfg(all(test, feature = "memory-store"))]
+mod property_memory_store_tests {
+    use tower_sessions::{clock::MockClock, MemoryStore};
+
+    async fn store(clock: MockClock) -> MemoryStore {
+        MemoryStore::default().with_clock(clock)
+    }
+
+    store_property_tests!(store);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod bounded_memory_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::MockClock, Expiry, MemoryStore, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store = MemoryStore::default()
+            .with_max_sessions(1_000)
+            .with_max_bytes(1024 * 1024);
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    async fn save_session(session_store: &MemoryStore, expiry: Duration) -> Session {
+        let session = Session::new(Some(Expiry::OnInactivity(expiry)));
+        session.insert("foo", "bar").unwrap();
+        session_store.save(&session).await.unwrap();
+        session
+    }
+
+    async fn is_stored(session_store: &MemoryStore, session: &Session) -> bool {
+        session_store.load(session.id()).await.unwrap().is_some()
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let session_store = MemoryStore::default().with_max_sessions(2);
+        let first = save_session(&session_store, Duration::hours(1)).await;
+        let second = save_session(&session_store, Duration::hours(1)).await;
+
+        // Loading the first session makes the second one the least recently used.
+        assert!(is_stored(&session_store, &first).await);
+        let third = save_session(&session_store, Duration::hours(1)).await;
+
+        assert!(is_stored(&session_store, &first).await);
+        assert!(!is_stored(&session_store, &second).await);
+        assert!(is_stored(&session_store, &third).await);
+    }
+
+    #[tokio::test]
+    async fn evicts_expired_first() {
+        let clock = MockClock::default();
+        let session_store = MemoryStore::default()
+            .with_clock(clock.clone())
+            .with_max_sessions(2);
+        let expiring = save_session(&session_store, Duration::minutes(1)).await;
+        let lasting = save_session(&session_store, Duration::hours(1)).await;
+
+        clock.advance(Duration::minutes(2));
+        let latest = save_session(&session_store, Duration::hours(1)).await;
+
+        assert!(!is_stored(&session_store, &expiring).await);
+        assert!(is_stored(&session_store, &lasting).await);
+        assert!(is_stored(&session_store, &latest).await);
+    }
+
+    #[tokio::test]
+    async fn evicts_past_memory_budget() {
+        let sessions = MemoryStore::default();
+        let session = save_session(&sessions, Duration::hours(1)).await;
+        let size = rmp_serde::to_vec(&session).unwrap().len();
+
+        let session_store = MemoryStore::default().with_max_bytes(size * 3);
+        let mut saved = Vec::new();
+        for _ in 0..5 {
+            saved.push(save_session(&session_store, Duration::hours(1)).await);
+        }
+
+        let mut stored = 0;
+        for session in &saved {
+            if is_stored(&session_store, session).await {
+                stored += 1;
+            }
+        }
+        assert_eq!(stored, 3);
+        assert!(is_stored(&session_store, &saved[4]).await);
+    }
+
+    #[tokio::test]
+    async fn resaving_replaces_session() {
+        let session_store = MemoryStore::default().with_max_sessions(2);
+        let first = save_session(&session_store, Duration::hours(1)).await;
+        let second = save_session(&session_store, Duration::hours(1)).await;
+
+        first.insert("foo", "baz").unwrap();
+        session_store.save(&first).await.unwrap();
+        session_store.save(&second).await.unwrap();
+
+        assert!(is_stored(&session_store, &first).await);
+        assert!(is_stored(&session_store, &second).await);
+    }
+}
+
 #[cfg(all(test, feature = "axum-core", feature = "moka-store"))]
 mod moka_store_tests {
     use axum::Router;
@@ -33,6 +143,17 @@ mod moka_store_tests {
     route_tests!(app);
 }
 
+#[cfg(all(test, feature = "moka-store"))]
+mod property_moka_store_tests {
+    use tower_sessions::{clock::MockClock, MokaStore};
+
+    async fn store(clock: MockClock) -> MokaStore {
+        MokaStore::new(None).with_clock(clock)
+    }
+
+    store_property_tests!(store);
+}
+
 #[cfg(all(test, feature = "axum-core", feature = "redis-store"))]
 mod redis_store_tests {
     use axum::Router;
@@ -77,6 +198,131 @@ mod sqlite_store_tests {
     route_tests!(app);
 }
 
+#[cfg(all(test, feature = "sqlite-store"))]
+mod property_sqlite_store_tests {
+    use tower_sessions::{clock::MockClock, sqlx::SqlitePool, SqliteStore};
+
+    async fn store(clock: MockClock) -> SqliteStore {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let session_store = SqliteStore::new(pool).with_clock(clock);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    store_property_tests!(store);
+}
+
+#[cfg(all(test, feature = "sqlite-store"))]
+mod expired_deletion_sqlite_store_tests {
+    use time::Duration;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        session_store::ExpiredDeletion,
+        sqlx::SqlitePool,
+        Expiry, Session, SessionStore, SqliteStore,
+    };
+
+    async fn store(clock: &MockClock) -> SqliteStore {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let session_store = SqliteStore::new(pool).with_clock(clock.clone());
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn save_session(session_store: &SqliteStore, clock: &MockClock) -> Session {
+        let expired_at = clock.now().saturating_add(Duration::hours(1));
+        let session = Session::new(Some(Expiry::AtDateTime(expired_at)));
+        session.insert("foo", 42).unwrap();
+        session_store.save(&session).await.unwrap();
+        session
+    }
+
+    #[tokio::test]
+    async fn delete_expired_removes_expired_sessions() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store(&clock).await;
+        let session = save_session(&session_store, &clock).await;
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(session.id()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn delete_expired_retains_active_sessions() {
+        let clock = MockClock::default();
+        let session_store = store(&clock).await;
+        let session = save_session(&session_store, &clock).await;
+
+        clock.advance(Duration::minutes(30));
+        session_store.delete_expired().await.unwrap();
+
+        assert!(session_store.load(session.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "sqlite-store"))]
+mod list_sessions_sqlite_store_tests {
+    use time::Duration;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        sqlx::SqlitePool,
+        Expiry, ListSessions, Session, SessionStore, SqliteStore, UserSessions,
+    };
+
+    async fn store(clock: &MockClock) -> SqliteStore {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let session_store = SqliteStore::new(pool).with_clock(clock.clone());
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn save_session(
+        session_store: &SqliteStore,
+        clock: &MockClock,
+        lifetime: Duration,
+        principal: &str,
+    ) -> Session {
+        let expired_at = clock.now().saturating_add(lifetime);
+        let session = Session::new(Some(Expiry::AtDateTime(expired_at)));
+        session.set_principal(Some(principal));
+        session_store.save(&session).await.unwrap();
+        session
+    }
+
+    #[tokio::test]
+    async fn list_sessions_omits_expired_sessions() {
+        let clock = MockClock::default();
+        let session_store = store(&clock).await;
+        let active = save_session(&session_store, &clock, Duration::hours(2), "alice").await;
+        save_session(&session_store, &clock, Duration::hours(1), "alice").await;
+
+        clock.advance(Duration::minutes(90));
+
+        let session_ids = session_store.list_sessions().await.unwrap();
+        assert_eq!(session_ids, vec![*active.id()]);
+    }
+
+    #[tokio::test]
+    async fn delete_user_sessions_retains_other_principals() {
+        let clock = MockClock::default();
+        let session_store = store(&clock).await;
+        let alice = save_session(&session_store, &clock, Duration::hours(1), "alice").await;
+        let bob = save_session(&session_store, &clock, Duration::hours(1), "bob").await;
+
+        let session_ids = session_store.user_sessions("alice").await.unwrap();
+        assert_eq!(session_ids, vec![*alice.id()]);
+
+        session_store.delete_user_sessions("alice").await.unwrap();
+        assert!(session_store.load(alice.id()).await.unwrap().is_none());
+        assert!(session_store.load(bob.id()).await.unwrap().is_some());
+    }
+}
+
 #[cfg(all(test, feature = "axum-core", feature = "postgres-store"))]
 mod postgres_store_tests {
     use axum::Router;
@@ -97,6 +343,21 @@ mod postgres_store_tests {
     route_tests!(app);
 }
 
+#[cfg(all(test, feature = "postgres-store"))]
+mod property_postgres_store_tests {
+    use tower_sessions::{clock::MockClock, sqlx::PgPool, PostgresStore};
+
+    async fn store(clock: MockClock) -> PostgresStore {
+        let database_url = std::option_env!("POSTGRES_URL").unwrap();
+        let pool = PgPool::connect(database_url).await.unwrap();
+        let session_store = PostgresStore::new(pool).with_clock(clock);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    store_property_tests!(store);
+}
+
 #[cfg(all(test, feature = "axum-core", feature = "mysql-store"))]
 mod mysql_store_tests {
     use axum::Router;
@@ -118,6 +379,241 @@ mod mysql_store_tests {
     route_tests!(app);
 }
 
+#[cfg(all(test, feature = "mysql-store"))]
+mod property_mysql_store_tests {
+    use tower_sessions::{clock::MockClock, sqlx::MySqlPool, MySqlStore};
+
+    async fn store(clock: MockClock) -> MySqlStore {
+        let database_url = std::option_env!("MYSQL_URL").unwrap();
+        let pool = MySqlPool::connect(database_url).await.unwrap();
+        let session_store = MySqlStore::new(pool).with_clock(clock);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    store_property_tests!(store);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "mysql-store"))]
+mod custom_table_mysql_store_tests {
+    use axum::Router;
+    use tower_sessions::{sqlx::MySqlPool, MySqlStore, SessionManagerLayer};
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let database_url = std::option_env!("MYSQL_URL").unwrap();
+
+        let pool = MySqlPool::connect(database_url).await.unwrap();
+        let session_store = MySqlStore::new(pool)
+            .with_schema_name("custom_schema")
+            .unwrap()
+            .with_table_name("custom_sessions")
+            .unwrap();
+        session_store.migrate().await.unwrap();
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "redis-store"))]
+mod pooled_redis_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        fred::prelude::*, RedisStore, RedisStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> RedisStore<RedisPool> {
+        let database_url = std::option_env!("REDIS_URL").unwrap();
+
+        let config = RedisConfig::from_url(database_url).unwrap();
+        let pool = RedisPool::new(config, None, None, None, 2).unwrap();
+
+        pool.connect();
+        pool.wait_for_connect().await.unwrap();
+
+        RedisStore::new(pool)
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(RedisStoreError::IdCollision)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "redis-store"))]
+mod express_redis_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        fred::prelude::*, session::Id, Expiry, RedisCodec, RedisStore, Session,
+        SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn client() -> RedisClient {
+        let database_url = std::option_env!("REDIS_URL").unwrap();
+
+        let config = RedisConfig::from_url(database_url).unwrap();
+        let client = RedisClient::new(config, None, None, None);
+
+        client.connect();
+        client.wait_for_connect().await.unwrap();
+
+        client
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store =
+            RedisStore::new(client().await).with_codec(RedisCodec::express_session());
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn save_express_session_record() {
+        let client = client().await;
+        let session_store =
+            RedisStore::new(client.clone()).with_codec(RedisCodec::express_session());
+
+        let session = Session::new(Some(Expiry::OnInactivity(Duration::hours(1))));
+        session.insert("foo", 42).unwrap();
+        session_store.save(&session).await.unwrap();
+
+        let record: String = client.get(format!("sess:{}", session.id())).await.unwrap();
+        let record: serde_json::Value = serde_json::from_str(&record).unwrap();
+        assert_eq!(record["foo"], 42);
+        assert_eq!(record["cookie"]["originalMaxAge"], 3_600_000);
+        assert!(record["cookie"]["expires"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[tokio::test]
+    async fn load_express_session_record() {
+        let client = client().await;
+        let session_store =
+            RedisStore::new(client.clone()).with_codec(RedisCodec::express_session());
+
+        let session_id = Id::default();
+        let record = serde_json::json!({
+            "cookie": {
+                "originalMaxAge": null,
+                "expires": "2099-01-01T00:00:00.000Z",
+                "httpOnly": true,
+                "path": "/",
+            },
+            "foo": "bar",
+        });
+        client
+            .set::<(), _, _>(
+                format!("sess:{session_id}"),
+                record.to_string(),
+                None,
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let session = session_store.load(&session_id).await.unwrap().unwrap();
+        assert_eq!(
+            session.get::<String>("foo").unwrap().as_deref(),
+            Some("bar")
+        );
+        assert_eq!(session.get_value("cookie"), None);
+        assert_eq!(
+            session.expiry(),
+            Some(Expiry::AtDateTime(
+                time::OffsetDateTime::from_unix_timestamp(4_070_908_800).unwrap()
+            ))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "redis-store"))]
+mod django_redis_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        fred::{prelude::*, types::Expiration},
+        session::Id,
+        RedisCodec, RedisStore, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn client() -> RedisClient {
+        let database_url = std::option_env!("REDIS_URL").unwrap();
+
+        let config = RedisConfig::from_url(database_url).unwrap();
+        let client = RedisClient::new(config, None, None, None);
+
+        client.connect();
+        client.wait_for_connect().await.unwrap();
+
+        client
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store = RedisStore::new(client().await).with_codec(RedisCodec::django());
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn load_django_session_record() {
+        let client = client().await;
+        let session_store = RedisStore::new(client.clone()).with_codec(RedisCodec::django());
+
+        let session_id = Id::default();
+        let record = serde_json::json!({ "_auth_user_id": "42", "foo": "bar" });
+        client
+            .set::<(), _, _>(
+                format!(":1:django.contrib.sessions.cache{session_id}"),
+                record.to_string(),
+                Some(Expiration::EX(3600)),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let session = session_store.load(&session_id).await.unwrap().unwrap();
+        assert_eq!(session.principal().as_deref(), Some("42"));
+        assert_eq!(
+            session.get::<String>("foo").unwrap().as_deref(),
+            Some("bar")
+        );
+        assert_eq!(session.get_value("_auth_user_id"), None);
+        assert!(session.expiry_age() <= Duration::hours(1));
+        assert!(session.expiry_age() > Duration::minutes(59));
+    }
+}
+
 #[cfg(all(test, feature = "axum-core", feature = "mongodb-store"))]
 mod mongodb_store_tests {
     use axum::Router;
@@ -129,6 +625,7 @@ mod mongodb_store_tests {
         let database_url = std::option_env!("MONGODB_URL").unwrap();
         let client = mongodb::Client::with_uri_str(database_url).await.unwrap();
         let session_store = MongoDBStore::new(client, "tower-sessions".to_string());
+        session_store.migrate().await.unwrap();
         let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
 
         build_app(session_manager, max_age)
@@ -137,32 +634,5754 @@ mod mongodb_store_tests {
     route_tests!(app);
 }
 
-#[cfg(all(
-    test,
-    feature = "axum-core",
-    feature = "sqlite-store",
-    feature = "moka-store"
-))]
-mod caching_store_tests {
+#[cfg(all(test, feature = "axum-core", feature = "mongodb-store"))]
+mod custom_collection_mongodb_store_tests {
+    use axum::Router;
+    use tower_sessions::{mongodb, MongoDBStore, SessionManagerLayer};
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let database_url = std::option_env!("MONGODB_URL").unwrap();
+        let client = mongodb::Client::with_uri_str(database_url).await.unwrap();
+        let session_store = MongoDBStore::new(client, "tower-sessions".to_string())
+            .with_collection_name("custom_sessions");
+        session_store.migrate().await.unwrap();
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "dynamodb-store"))]
+mod dynamodb_store_tests {
     use axum::Router;
     use tower_sessions::{
-        sqlx::SqlitePool, CachingSessionStore, MokaStore, SessionManagerLayer, SqliteStore,
+        aws_sdk_dynamodb::{
+            config::{Credentials, Region},
+            Client, Config,
+        },
+        DynamoDBStore, SessionManagerLayer,
     };
 
     use crate::common::build_app;
 
     async fn app(max_age: Option<Duration>) -> Router {
-        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
-        let sqlite_store = SqliteStore::new(pool);
-        sqlite_store.migrate().await.unwrap();
+        let database_url = std::option_env!("DYNAMODB_URL").unwrap();
 
-        let moka_store = MokaStore::new(None);
-        let caching_store = CachingSessionStore::new(moka_store, sqlite_store);
+        let config = Config::builder()
+            .behavior_version_latest()
+            .endpoint_url(database_url)
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        let client = Client::from_conf(config);
 
-        let session_manager = SessionManagerLayer::new(caching_store).with_secure(true);
+        let session_store = DynamoDBStore::new(client);
+        session_store.migrate().await.unwrap();
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
 
         build_app(session_manager, max_age)
     }
 
     route_tests!(app);
 }
+
+#[cfg(all(test, feature = "axum-core", feature = "memcached-store"))]
+mod memcached_store_tests {
+    use axum::Router;
+    use tower_sessions::{async_memcached::Client, MemcachedStore, SessionManagerLayer};
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let database_url = std::option_env!("MEMCACHED_URL").unwrap();
+
+        let client = Client::new(database_url).await.unwrap();
+        let session_store = MemcachedStore::new(client);
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memcached-store"))]
+mod optimistic_memcached_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        async_memcached::Client, MemcachedStore, MemcachedStoreError, Session, SessionManagerLayer,
+        SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> MemcachedStore {
+        let database_url = std::option_env!("MEMCACHED_URL").unwrap();
+
+        let client = Client::new(database_url).await.unwrap();
+        MemcachedStore::new(client).with_optimistic_concurrency(true)
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn concurrent_save_conflicts() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session.insert("foo", 42).unwrap();
+        session_store.create(&session).await.unwrap();
+
+        let first = session_store.load(session.id()).await.unwrap().unwrap();
+        let second = session_store.load(session.id()).await.unwrap().unwrap();
+
+        first.insert("foo", 43).unwrap();
+        session_store.save(&first).await.unwrap();
+
+        second.insert("foo", 44).unwrap();
+        assert!(matches!(
+            session_store.save(&second).await,
+            Err(MemcachedStoreError::Conflict)
+        ));
+
+        // The first session's version was updated when it was saved.
+        first.insert("foo", 45).unwrap();
+        session_store.save(&first).await.unwrap();
+
+        session_store.delete(session.id()).await.unwrap();
+        assert!(matches!(
+            session_store.save(&first).await,
+            Err(MemcachedStoreError::Conflict)
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(MemcachedStoreError::IdCollision)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "sea-orm-store"))]
+mod sea_orm_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        sea_orm::Database,
+        session_store::ExpiredDeletion,
+        Expiry, SeaOrmStore, SeaOrmStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> SeaOrmStore {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let session_store = SeaOrmStore::new(db);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(SeaOrmStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().await.with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "diesel-postgres-store"))]
+mod diesel_postgres_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        diesel_async::{
+            pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+            AsyncPgConnection,
+        },
+        DieselPostgresStore, DieselStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> DieselPostgresStore {
+        let database_url = std::option_env!("POSTGRES_URL").unwrap();
+
+        let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url);
+        let pool = Pool::builder(manager).build().unwrap();
+        let session_store = DieselPostgresStore::new(pool);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(DieselStoreError::IdCollision)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "diesel-mysql-store"))]
+mod diesel_mysql_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        diesel_async::{
+            pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+            AsyncMysqlConnection,
+        },
+        DieselMySqlStore, DieselStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> DieselMySqlStore {
+        let database_url = std::option_env!("MYSQL_URL").unwrap();
+
+        let manager = AsyncDieselConnectionManager::<AsyncMysqlConnection>::new(database_url);
+        let pool = Pool::builder(manager).build().unwrap();
+        let session_store = DieselMySqlStore::new(pool);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(DieselStoreError::IdCollision)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "scylla-store"))]
+mod scylla_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        scylla::client::session_builder::SessionBuilder, ScyllaStore, ScyllaStoreError, Session,
+        SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> ScyllaStore {
+        let database_url = std::option_env!("SCYLLA_URL").unwrap();
+
+        let session = SessionBuilder::new()
+            .known_node(database_url)
+            .build()
+            .await
+            .unwrap();
+        let session_store = ScyllaStore::new(session.into());
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(ScyllaStoreError::IdCollision)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "file-store"))]
+mod file_store_tests {
+    use std::path::PathBuf;
+
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        session_store::ExpiredDeletion,
+        Expiry, FileStore, FileStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    fn dir() -> PathBuf {
+        std::env::temp_dir().join(format!("tower-sessions-{}", uuid::Uuid::new_v4()))
+    }
+
+    async fn store() -> FileStore {
+        let session_store = FileStore::new(dir());
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(FileStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_replaces_expired_file() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().await.with_clock(clock.clone());
+
+        let session = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.create(&session).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session.set_expiry(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session.insert("foo", 42).unwrap();
+        session_store.create(&session).await.unwrap();
+
+        let loaded = session_store.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(loaded.get::<i32>("foo").unwrap(), Some(42));
+    }
+
+    #[tokio::test]
+    async fn delete_expired_skips_undecodable_files() {
+        let clock = MockClock::default();
+        let dir = dir();
+        let session_store = FileStore::new(&dir).with_clock(clock.clone());
+        session_store.migrate().await.unwrap();
+
+        let undecodable = dir.join("not-a-session");
+        std::fs::write(&undecodable, b"not msgpack").unwrap();
+        let expired = Session::new(Some(Expiry::AtDateTime(clock.now() + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        assert!(!dir.join(expired.id().to_string()).exists());
+        assert!(undecodable.exists());
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().await.with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "file-store"))]
+mod property_file_store_tests {
+    use tower_sessions::{clock::MockClock, FileStore};
+
+    async fn store(clock: MockClock) -> FileStore {
+        let dir = std::env::temp_dir().join(format!("tower-sessions-{}", uuid::Uuid::new_v4()));
+        let session_store = FileStore::new(dir).with_clock(clock);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    store_property_tests!(store);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "sled-store"))]
+mod sled_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        session_store::ExpiredDeletion,
+        sled, Expiry, Session, SessionManagerLayer, SessionStore, SledStore, SledStoreError,
+    };
+
+    use crate::common::build_app;
+
+    fn store() -> SledStore {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        SledStore::new(db).unwrap()
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store()).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store();
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(SledStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        // Extending the session before it expires keeps it from being deleted.
+        let extended = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&extended).await.unwrap();
+        extended.set_expiry(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&extended).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+        assert!(session_store.load(extended.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "sled-store"))]
+mod property_sled_store_tests {
+    use tower_sessions::{clock::MockClock, sled, SledStore};
+
+    async fn store(clock: MockClock) -> SledStore {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        SledStore::new(db).unwrap().with_clock(clock)
+    }
+
+    store_property_tests!(store);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "redb-store"))]
+mod redb_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        redb::{backends::InMemoryBackend, Database},
+        session_store::ExpiredDeletion,
+        Expiry, RedbStore, RedbStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    fn store() -> RedbStore {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        let session_store = RedbStore::new(db);
+        session_store.migrate().unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store()).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store();
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(RedbStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        // Extending the session before it expires keeps it from being deleted.
+        let extended = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&extended).await.unwrap();
+        extended.set_expiry(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&extended).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+        assert!(session_store.load(extended.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "redb-store"))]
+mod property_redb_store_tests {
+    use tower_sessions::{
+        clock::MockClock,
+        redb::{backends::InMemoryBackend, Database},
+        RedbStore,
+    };
+
+    async fn store(clock: MockClock) -> RedbStore {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        let session_store = RedbStore::new(db).with_clock(clock);
+        session_store.migrate().unwrap();
+        session_store
+    }
+
+    store_property_tests!(store);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "etcd-store"))]
+mod etcd_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        etcd_client::Client, EtcdStore, EtcdStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> EtcdStore {
+        let etcd_url = std::option_env!("ETCD_URL").unwrap();
+
+        let client = Client::connect([etcd_url], None).await.unwrap();
+        EtcdStore::new(client)
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(EtcdStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_revokes_lease() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.save(&session).await.unwrap();
+        session_store.save(&session).await.unwrap();
+        session_store.delete(session.id()).await.unwrap();
+        assert!(session_store.load(session.id()).await.unwrap().is_none());
+
+        // Deleting a missing session is a no-op.
+        session_store.delete(session.id()).await.unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "nats-store"))]
+mod nats_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        async_nats, NatsStore, NatsStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> NatsStore {
+        let nats_url = std::option_env!("NATS_URL").unwrap();
+
+        let client = async_nats::connect(nats_url).await.unwrap();
+        let session_store = NatsStore::new(async_nats::jetstream::new(client));
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(NatsStoreError::IdCollision)
+        ));
+
+        // Deleted sessions leave a marker, which doesn't count as a collision.
+        session_store.delete(session.id()).await.unwrap();
+        session_store.create(&session).await.unwrap();
+        assert!(session_store.load(session.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "nats-store"))]
+mod optimistic_nats_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        async_nats, NatsStore, NatsStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> NatsStore {
+        let nats_url = std::option_env!("NATS_URL").unwrap();
+
+        let client = async_nats::connect(nats_url).await.unwrap();
+        let session_store =
+            NatsStore::new(async_nats::jetstream::new(client)).with_optimistic_concurrency(true);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn concurrent_save_conflicts() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session.insert("foo", 42).unwrap();
+        session_store.create(&session).await.unwrap();
+
+        let first = session_store.load(session.id()).await.unwrap().unwrap();
+        let second = session_store.load(session.id()).await.unwrap().unwrap();
+
+        first.insert("foo", 43).unwrap();
+        session_store.save(&first).await.unwrap();
+
+        second.insert("foo", 44).unwrap();
+        assert!(matches!(
+            session_store.save(&second).await,
+            Err(NatsStoreError::Conflict)
+        ));
+
+        // The first session's version was updated when it was saved.
+        first.insert("foo", 45).unwrap();
+        session_store.save(&first).await.unwrap();
+
+        session_store.delete(session.id()).await.unwrap();
+        assert!(matches!(
+            session_store.save(&first).await,
+            Err(NatsStoreError::Conflict)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "s3-store"))]
+mod s3_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        aws_sdk_s3::{
+            config::{Credentials, Region},
+            Client, Config,
+        },
+        S3Store, S3StoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> S3Store {
+        let s3_url = std::option_env!("S3_URL").unwrap();
+
+        let config = Config::builder()
+            .behavior_version_latest()
+            .endpoint_url(s3_url)
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new(
+                "minioadmin",
+                "minioadmin",
+                None,
+                None,
+                "test",
+            ))
+            .force_path_style(true)
+            .build();
+        let client = Client::from_conf(config);
+
+        // The bucket is shared by all tests, so it may exist already.
+        let _ = client.create_bucket().bucket("tower-sessions").send().await;
+
+        let session_store = S3Store::new(client, "tower-sessions");
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(S3StoreError::IdCollision)
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "tokio-postgres-store"))]
+mod tokio_postgres_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        deadpool_postgres::{Manager, Pool},
+        session_store::ExpiredDeletion,
+        tokio_postgres::{self, NoTls},
+        Session, SessionManagerLayer, SessionStore, TokioPostgresStore, TokioPostgresStoreError,
+    };
+
+    use crate::common::build_app;
+
+    fn config() -> tokio_postgres::Config {
+        std::option_env!("POSTGRES_URL").unwrap().parse().unwrap()
+    }
+
+    async fn store() -> TokioPostgresStore {
+        let pool = Pool::builder(Manager::new(config(), NoTls))
+            .build()
+            .unwrap();
+        let session_store = TokioPostgresStore::new(pool);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(TokioPostgresStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_expired_notifies_listeners() {
+        let session_store = store()
+            .await
+            .with_channel_name("tower_sessions_test_expired_deletion")
+            .unwrap();
+
+        let (client, mut connection) = config().connect(NoTls).await.unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(Ok(message)) =
+                std::future::poll_fn(|cx| connection.poll_message(cx)).await
+            {
+                if let tokio_postgres::AsyncMessage::Notification(notification) = message {
+                    let _ = tx.send(notification.channel().to_string());
+                }
+            }
+        });
+        client
+            .batch_execute("listen tower_sessions_test_expired_deletion")
+            .await
+            .unwrap();
+
+        session_store.delete_expired().await.unwrap();
+
+        assert_eq!(
+            rx.recv().await.as_deref(),
+            Some("tower_sessions_test_expired_deletion")
+        );
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "surrealdb-store"))]
+mod surrealdb_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        session_store::ExpiredDeletion,
+        surrealdb::{
+            self,
+            engine::any::{self, Any},
+        },
+        Expiry, Session, SessionManagerLayer, SessionStore, SurrealStore, SurrealStoreError,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> SurrealStore<Any> {
+        let surrealdb_url = std::option_env!("SURREALDB_URL").unwrap();
+        let db: surrealdb::Surreal<Any> = any::connect(surrealdb_url).await.unwrap();
+        db.use_ns("tower_sessions")
+            .use_db("tower_sessions")
+            .await
+            .unwrap();
+
+        let session_store = SurrealStore::new(db);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(SurrealStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().await.with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "firestore-store"))]
+mod firestore_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        firestore::FirestoreDb,
+        session_store::ExpiredDeletion,
+        Expiry, FirestoreStore, FirestoreStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> FirestoreStore {
+        // The client connects to the emulator named by `FIRESTORE_EMULATOR_HOST`.
+        let db = FirestoreDb::new("tower-sessions").await.unwrap();
+
+        let session_store = FirestoreStore::new(db);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(FirestoreStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_over_expired_session() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().await.with_clock(clock.clone());
+
+        let session = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.create(&session).await.unwrap();
+
+        // The expired document may linger until its TTL removes it.
+        clock.advance(Duration::hours(2));
+        session_store.create(&session).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().await.with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "cosmos-store"))]
+mod cosmos_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        azure_data_cosmos::{
+            models::ContainerProperties, options::BinaryEncodingOptions, AccountReference,
+            CosmosClient, RoutingStrategy,
+        },
+        CosmosStore, CosmosStoreError, Expiry, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    // The well-known key of the Cosmos DB emulator.
+    const EMULATOR_KEY: &str =
+        "C2y6yDjf5/R+ob0N8A7Cgv30VRDJIWEHLM+4QDU5DE2nQ9nDuVTqobD4b8mGGyPMbIZnqyMsEcaGQy67XIw/Jw==";
+
+    async fn store() -> CosmosStore {
+        let cosmos_url = std::option_env!("COSMOS_URL").unwrap();
+        let account = AccountReference::with_authentication_key(
+            cosmos_url.parse().unwrap(),
+            EMULATOR_KEY.to_string(),
+        );
+        let client = CosmosClient::builder()
+            .with_binary_encoding_options(BinaryEncodingOptions::new().with_enabled(false))
+            .build(account, RoutingStrategy::ProximityTo("West US".into()))
+            .await
+            .unwrap();
+
+        // The database and container are shared by all tests, so they may exist
+        // already.
+        match client.create_database("tower_sessions", None).await {
+            Ok(_) => {}
+            Err(err) if err.status().is_conflict() => {}
+            Err(err) => panic!("{err}"),
+        }
+        let database = client.database_client("tower_sessions");
+        let properties = ContainerProperties::new("tower_sessions", "/id".into());
+        match database.create_container(properties, None).await {
+            Ok(_) => {}
+            Err(err) if err.status().is_conflict() => {}
+            Err(err) => panic!("{err}"),
+        }
+        let container = database
+            .container_client("tower_sessions", None)
+            .await
+            .unwrap();
+
+        let session_store = CosmosStore::new(container);
+        session_store.migrate().await.unwrap();
+        session_store
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(CosmosStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn save_expired_session() {
+        let session_store = store().await;
+
+        // Item TTLs must be positive, so expired sessions aren't stored.
+        let expired = Session::new(Some(Expiry::AtDateTime(
+            time::OffsetDateTime::now_utc() - Duration::hours(1),
+        )));
+        session_store.save(&expired).await.unwrap();
+        session_store.create(&expired).await.unwrap();
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "lmdb-store"))]
+mod lmdb_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        clock::{Clock, MockClock},
+        heed::EnvOpenOptions,
+        session_store::ExpiredDeletion,
+        Expiry, LmdbStore, LmdbStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    fn store() -> LmdbStore {
+        let dir = std::env::temp_dir().join(format!("tower-sessions-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let env = unsafe { EnvOpenOptions::new().max_dbs(2).open(dir).unwrap() };
+        LmdbStore::new(env).unwrap()
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store()).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store();
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(LmdbStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_expired() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let session_store = store().with_clock(clock.clone());
+
+        let expired = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&expired).await.unwrap();
+        let active = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&active).await.unwrap();
+
+        // Extending the session before it expires keeps it from being deleted.
+        let extended = Session::new(Some(Expiry::AtDateTime(start + Duration::hours(1))));
+        session_store.save(&extended).await.unwrap();
+        extended.set_expiry(Some(Expiry::AtDateTime(start + Duration::hours(3))));
+        session_store.save(&extended).await.unwrap();
+
+        clock.advance(Duration::hours(2));
+        session_store.delete_expired().await.unwrap();
+
+        // Going back in time shows the session is gone rather than filtered.
+        clock.set(start);
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+        assert!(session_store.load(active.id()).await.unwrap().is_some());
+        assert!(session_store.load(extended.id()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_expired_before_epoch() {
+        let clock = MockClock::default();
+        let session_store = store().with_clock(clock.clone());
+
+        // Expiry dates before the epoch must sort before later ones.
+        let epoch = time::OffsetDateTime::UNIX_EPOCH;
+        let expired = Session::new(Some(Expiry::AtDateTime(epoch - Duration::days(1))));
+        session_store.save(&expired).await.unwrap();
+
+        clock.set(epoch - Duration::days(2));
+        assert!(session_store.load(expired.id()).await.unwrap().is_some());
+        clock.set(epoch);
+        session_store.delete_expired().await.unwrap();
+        clock.set(epoch - Duration::days(2));
+        assert!(session_store.load(expired.id()).await.unwrap().is_none());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "aerospike-store"))]
+mod aerospike_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        aerospike::{Client, ClientPolicy},
+        AerospikeStore, AerospikeStoreError, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn store() -> AerospikeStore {
+        let aerospike_url = std::option_env!("AEROSPIKE_URL").unwrap();
+        let client = Client::new(&ClientPolicy::default(), &aerospike_url)
+            .await
+            .unwrap();
+        AerospikeStore::new(client, "test")
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_manager = SessionManagerLayer::new(store().await).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    #[tokio::test]
+    async fn create_id_collision() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        assert!(matches!(
+            session_store.create(&session).await,
+            Err(AerospikeStoreError::IdCollision)
+        ));
+    }
+
+    #[tokio::test]
+    async fn save_with_generation() {
+        let session_store = store().await;
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        let (loaded, generation) = session_store
+            .load_with_generation(session.id())
+            .await
+            .unwrap()
+            .unwrap();
+
+        loaded.insert("foo", 42).unwrap();
+        session_store
+            .save_with_generation(&loaded, generation)
+            .await
+            .unwrap();
+
+        // The record moved on to another generation with the previous save.
+        assert!(matches!(
+            session_store
+                .save_with_generation(&loaded, generation)
+                .await,
+            Err(AerospikeStoreError::GenerationMismatch)
+        ));
+
+        session_store.delete(session.id()).await.unwrap();
+        assert!(matches!(
+            session_store
+                .save_with_generation(&loaded, generation + 1)
+                .await,
+            Err(AerospikeStoreError::GenerationMismatch)
+        ));
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "sqlite-store",
+    feature = "moka-store"
+))]
+mod caching_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        sqlx::SqlitePool, CachingSessionStore, MokaStore, SessionManagerLayer, SqliteStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let sqlite_store = SqliteStore::new(pool);
+        sqlite_store.migrate().await.unwrap();
+
+        let moka_store = MokaStore::new(None);
+        let caching_store = CachingSessionStore::new(moka_store, sqlite_store);
+
+        let session_manager = SessionManagerLayer::new(caching_store).with_secure(true);
+
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod consent_tests {
+    use axum::{body::Body, Router};
+    use http::{header, Request};
+    use time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_sessions::{service::Consent, MemoryStore, SessionManagerLayer};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    fn app() -> Router {
+        let session_store = MemoryStore::default();
+        let session_manager = SessionManagerLayer::new(session_store).with_consent_required(true);
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    #[tokio::test]
+    async fn no_cookie_without_consent() {
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app().oneshot(req).await.unwrap();
+
+        assert!(res.headers().get(header::SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn cookie_with_session_consent() {
+        let req = Request::builder()
+            .uri("/grant_consent")
+            .body(Body::empty())
+            .unwrap();
+        let res = app().oneshot(req).await.unwrap();
+
+        assert!(get_session_cookie(res.headers()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn cookie_with_consent_extension() {
+        let app = ServiceBuilder::new()
+            .map_request(|mut req: Request<Body>| {
+                req.extensions_mut().insert(Consent);
+                req
+            })
+            .service(app());
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert!(get_session_cookie(res.headers()).is_ok());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod strict_mode_tests {
+    use axum::{body::Body, Router};
+    use http::{header, Request, StatusCode};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_cookies::Cookie;
+    use tower_sessions::{MemoryStore, SessionManagerLayer};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    fn app(session_manager: SessionManagerLayer<MemoryStore>) -> Router {
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    fn unknown_session_request() -> Request<Body> {
+        let unknown_cookie = Cookie::new("id", "00000000-0000-0000-0000-000000000000");
+        Request::builder()
+            .uri("/insert")
+            .header(header::COOKIE, unknown_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn unknown_session_is_rejected() {
+        let session_manager =
+            SessionManagerLayer::new(MemoryStore::default()).with_strict_mode(true);
+        let res = app(session_manager)
+            .oneshot(unknown_session_request())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+        assert_eq!(session_cookie.max_age(), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn unknown_session_is_rejected_with_status() {
+        let session_manager = SessionManagerLayer::new(MemoryStore::default())
+            .with_strict_mode_status(StatusCode::FORBIDDEN);
+        let res = app(session_manager)
+            .oneshot(unknown_session_request())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn unknown_session_is_replaced_by_default() {
+        let session_manager = SessionManagerLayer::new(MemoryStore::default());
+        let res = app(session_manager)
+            .oneshot(unknown_session_request())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+        assert_ne!(
+            session_cookie.value(),
+            "00000000-0000-0000-0000-000000000000"
+        );
+    }
+
+    #[tokio::test]
+    async fn known_session_is_accepted() {
+        let session_manager =
+            SessionManagerLayer::new(MemoryStore::default()).with_strict_mode(true);
+        let app = app(session_manager);
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "signed")]
+    #[tokio::test]
+    async fn tampered_session_is_rejected() {
+        use tower_cookies::cookie::Key;
+
+        let session_manager = SessionManagerLayer::new(MemoryStore::default())
+            .with_signed(Key::generate())
+            .with_strict_mode(true);
+        let app = app(session_manager);
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        let tampered_cookie = Cookie::new("id", format!("{}x", session_cookie.value()));
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, tampered_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    fn assert_removes_session_cookie(res: &http::Response<Body>) {
+        let removal_cookie = get_session_cookie(res.headers()).unwrap();
+        assert_eq!(removal_cookie.value(), "");
+        assert_eq!(removal_cookie.path(), Some("/app"));
+        assert_eq!(removal_cookie.domain(), Some("example.com"));
+    }
+
+    #[tokio::test]
+    async fn unknown_session_cookie_is_removed_with_its_attributes() {
+        let session_manager = SessionManagerLayer::new(MemoryStore::default())
+            .with_path("/app".to_owned())
+            .with_domain("example.com".to_owned())
+            .with_strict_mode(true);
+        let res = app(session_manager)
+            .oneshot(unknown_session_request())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_removes_session_cookie(&res);
+    }
+
+    #[cfg(feature = "signed")]
+    #[tokio::test]
+    async fn tampered_session_cookie_is_removed_with_its_attributes() {
+        use tower_cookies::cookie::Key;
+
+        let session_manager = SessionManagerLayer::new(MemoryStore::default())
+            .with_signed(Key::generate())
+            .with_path("/app".to_owned())
+            .with_domain("example.com".to_owned())
+            .with_strict_mode(true);
+
+        let tampered_cookie = Cookie::new("id", "tampered");
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, tampered_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app(session_manager).oneshot(req).await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_removes_session_cookie(&res);
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod partitioned_tests {
+    use axum::body::Body;
+    use http::Request;
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_sessions::{cookie::SameSite, MemoryStore, SessionManagerLayer};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    #[tokio::test]
+    async fn partitioned_cookie() {
+        let session_store = MemoryStore::default();
+        let session_manager = SessionManagerLayer::new(session_store)
+            .with_same_site(SameSite::None)
+            .with_secure(true)
+            .with_partitioned(true);
+        let app = build_app(session_manager, Some(Duration::hours(1)));
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        assert_eq!(session_cookie.partitioned(), Some(true));
+        assert_eq!(session_cookie.same_site(), Some(SameSite::None));
+        assert_eq!(session_cookie.secure(), Some(true));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod refresh_threshold_tests {
+    use axum::{body::Body, Router};
+    use http::{header, Request};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_sessions::{MemoryStore, SessionManagerLayer};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    fn app(refresh_threshold: Option<Duration>) -> Router {
+        let session_store = MemoryStore::default();
+        let mut session_manager = SessionManagerLayer::new(session_store);
+        if let Some(refresh_threshold) = refresh_threshold {
+            session_manager = session_manager.with_refresh_threshold(refresh_threshold);
+        }
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    async fn read_after_insert(app: Router) -> http::Response<Body> {
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn no_refresh_above_threshold() {
+        let res = read_after_insert(app(Some(Duration::minutes(30)))).await;
+
+        assert!(res.headers().get(header::SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn refresh_on_every_request_without_threshold() {
+        let res = read_after_insert(app(None)).await;
+
+        assert!(get_session_cookie(res.headers()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn refresh_below_threshold() {
+        let res = read_after_insert(app(Some(Duration::hours(2)))).await;
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        assert!(session_cookie
+            .max_age()
+            .is_some_and(|max_age| max_age > Duration::minutes(59)));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod absolute_timeout_tests {
+    use axum::{
+        body::Body, error_handling::HandleErrorLayer, routing::get, BoxError, Extension, Router,
+    };
+    use http::{header, Request, StatusCode};
+    use time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_sessions::{
+        clock::MockClock, service::SessionExpired, Expiry, MemoryStore, Session,
+        SessionManagerLayer,
+    };
+
+    use crate::common::{body_string, build_app, get_session_cookie};
+
+    fn app(absolute_timeout: Duration) -> Router {
+        let session_store = MemoryStore::default();
+        let session_manager =
+            SessionManagerLayer::new(session_store).with_absolute_timeout(absolute_timeout);
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    #[tokio::test]
+    async fn absolute_timeout_bounds_max_age() {
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app(Duration::minutes(30)).oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        assert!(session_cookie
+            .max_age()
+            .is_some_and(|max_age| max_age <= Duration::minutes(30)));
+    }
+
+    #[tokio::test]
+    async fn absolute_timeout_ends_session() {
+        let app = app(Duration::milliseconds(100));
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        let req = Request::builder()
+            .uri("/get_value")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(body_string(res.into_body()).await, "None");
+    }
+
+    #[tokio::test]
+    async fn expired_session_reports_limit() {
+        // The store keeps the system's time, leaving expiry to the middleware.
+        let clock = MockClock::default();
+        let session_manager = SessionManagerLayer::new(MemoryStore::default())
+            .with_clock(clock.clone())
+            .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+            .with_absolute_timeout(Duration::minutes(30));
+        let app = Router::new()
+            .route(
+                "/insert",
+                get(|session: Session| async move {
+                    session.insert("foo", 42).unwrap();
+                }),
+            )
+            .route(
+                "/limit",
+                get(|expired: Option<Extension<SessionExpired>>| async move {
+                    format!("{:?}", expired.map(|Extension(expired)| expired.limit()))
+                }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(session_manager),
+            );
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+
+        let limit = |app: Router| {
+            let req = Request::builder()
+                .uri("/limit")
+                .header(header::COOKIE, session_cookie.encoded().to_string())
+                .body(Body::empty())
+                .unwrap();
+            async move { body_string(app.oneshot(req).await.unwrap().into_body()).await }
+        };
+        assert_eq!(limit(app.clone()).await, "None");
+
+        clock.advance(Duration::minutes(31));
+        assert_eq!(limit(app).await, "Some(AbsoluteTimeout)");
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod clock_tests {
+    use axum::{body::Body, Router};
+    use http::{header, Request};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_cookies::Cookie;
+    use tower_sessions::{clock::MockClock, MemoryStore, SessionManagerLayer};
+
+    use crate::common::{body_string, build_app, get_session_cookie};
+
+    fn app(clock: &MockClock, absolute_timeout: Option<Duration>) -> Router {
+        let session_store = MemoryStore::default().with_clock(clock.clone());
+        let mut session_manager = SessionManagerLayer::new(session_store).with_clock(clock.clone());
+        if let Some(absolute_timeout) = absolute_timeout {
+            session_manager = session_manager.with_absolute_timeout(absolute_timeout);
+        }
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    async fn insert(app: &Router) -> Cookie<'static> {
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        get_session_cookie(res.headers()).unwrap().into_owned()
+    }
+
+    async fn get_value(app: &Router, session_cookie: &Cookie<'_>) -> String {
+        let req = Request::builder()
+            .uri("/get_value")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        body_string(res.into_body()).await
+    }
+
+    #[tokio::test]
+    async fn inactivity_expiry_follows_clock() {
+        let clock = MockClock::default();
+        let app = app(&clock, None);
+        let session_cookie = insert(&app).await;
+
+        clock.advance(Duration::minutes(59));
+        assert_eq!(get_value(&app, &session_cookie).await, "Some(Number(42))");
+
+        // Reading the session extended it.
+        clock.advance(Duration::minutes(59));
+        assert_eq!(get_value(&app, &session_cookie).await, "Some(Number(42))");
+
+        clock.advance(Duration::minutes(61));
+        assert_eq!(get_value(&app, &session_cookie).await, "None");
+    }
+
+    #[tokio::test]
+    async fn absolute_timeout_follows_clock() {
+        let clock = MockClock::default();
+        let app = app(&clock, Some(Duration::minutes(30)));
+        let session_cookie = insert(&app).await;
+
+        clock.advance(Duration::minutes(20));
+        assert_eq!(get_value(&app, &session_cookie).await, "Some(Number(42))");
+
+        clock.advance(Duration::minutes(15));
+        assert_eq!(get_value(&app, &session_cookie).await, "None");
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod events_tests {
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, Router};
+    use axum_core::BoxError;
+    use http::{header, Request, StatusCode};
+    use time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_cookies::Cookie;
+    use tower_sessions::{
+        clock::MockClock,
+        events::{SessionEvent, SessionEventKind, SessionEvents},
+        session::Id,
+        Expiry, MemoryStore, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::get_session_cookie;
+
+    fn app(events: SessionEvents, clock: &MockClock) -> Router {
+        // The store keeps to the system time, so sessions which the clock has been
+        // advanced past still load and are then found expired by the middleware.
+        app_with_store(MemoryStore::default(), events, clock)
+    }
+
+    fn app_with_store(
+        session_store: MemoryStore,
+        events: SessionEvents,
+        clock: &MockClock,
+    ) -> Router {
+        let session_manager = SessionManagerLayer::new(session_store)
+            .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+            .with_clock(clock.clone())
+            .with_events(events);
+        let session_service = ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|_: BoxError| async {
+                StatusCode::BAD_REQUEST
+            }))
+            .layer(session_manager);
+
+        Router::new()
+            .route(
+                "/login",
+                get(|session: Session| async move {
+                    session.set_principal(Some("alice"));
+                }),
+            )
+            .route("/", get(|_: Session| async move {}))
+            .route(
+                "/cycle_id",
+                get(|session: Session| async move {
+                    session.cycle_id();
+                }),
+            )
+            .route(
+                "/logout",
+                get(|session: Session| async move {
+                    session.delete();
+                }),
+            )
+            .layer(session_service)
+    }
+
+    async fn request(
+        app: &Router,
+        uri: &str,
+        session_cookie: Option<&Cookie<'_>>,
+    ) -> Option<Cookie<'static>> {
+        let mut req = Request::builder().uri(uri);
+        if let Some(session_cookie) = session_cookie {
+            req = req.header(header::COOKIE, session_cookie.encoded().to_string());
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        get_session_cookie(res.headers())
+            .ok()
+            .map(Cookie::into_owned)
+    }
+
+    fn event(kind: SessionEventKind, session_cookie: &Cookie<'_>) -> SessionEvent {
+        SessionEvent {
+            kind,
+            session_id: Id::try_from(session_cookie.value()).unwrap().fingerprint(),
+            principal: Some("alice".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn lifecycle_events() {
+        let events = SessionEvents::default();
+        let mut subscriber = events.subscribe();
+        let app = app(events, &MockClock::default());
+
+        let session_cookie = request(&app, "/login", None).await.unwrap();
+        let created = subscriber.try_recv().unwrap();
+        assert_eq!(created, event(SessionEventKind::Created, &session_cookie));
+
+        request(&app, "/", Some(&session_cookie)).await;
+        assert!(subscriber.try_recv().is_err());
+
+        let session_cookie = request(&app, "/cycle_id", Some(&session_cookie))
+            .await
+            .unwrap();
+        let renewed = subscriber.try_recv().unwrap();
+        assert_eq!(renewed, event(SessionEventKind::Renewed, &session_cookie));
+
+        request(&app, "/logout", Some(&session_cookie)).await;
+        let destroyed = subscriber.try_recv().unwrap();
+        assert_eq!(
+            destroyed,
+            event(SessionEventKind::Destroyed, &session_cookie)
+        );
+        assert!(subscriber.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn expired_event() {
+        let events = SessionEvents::default();
+        let mut subscriber = events.subscribe();
+        let clock = MockClock::default();
+        let app = app(events, &clock);
+
+        let session_cookie = request(&app, "/login", None).await.unwrap();
+        subscriber.try_recv().unwrap();
+
+        clock.advance(Duration::hours(2));
+        request(&app, "/", Some(&session_cookie)).await;
+        let expired = subscriber.try_recv().unwrap();
+        assert_eq!(expired, event(SessionEventKind::Expired, &session_cookie));
+        assert!(subscriber.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn expired_event_from_store() {
+        let events = SessionEvents::default();
+        let mut subscriber = events.subscribe();
+        let clock = MockClock::default();
+        let session_store = MemoryStore::default().with_clock(clock.clone());
+        let app = app_with_store(session_store.clone(), events, &clock);
+
+        let session_cookie = request(&app, "/login", None).await.unwrap();
+        subscriber.try_recv().unwrap();
+
+        // The store finds the session expired itself, and so never loads it again.
+        clock.advance(Duration::hours(2));
+        request(&app, "/", Some(&session_cookie)).await;
+        let expired = subscriber.try_recv().unwrap();
+        assert_eq!(expired, event(SessionEventKind::Expired, &session_cookie));
+
+        let session_id = Id::try_from(session_cookie.value()).unwrap();
+        assert!(session_store
+            .load_including_expired(&session_id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn unknown_session_cookie_emits_nothing() {
+        let events = SessionEvents::default();
+        let mut subscriber = events.subscribe();
+        let app = app(events, &MockClock::default());
+
+        let session_cookie = Cookie::new("id", "00000000-0000-0000-0000-000000000000");
+        request(&app, "/logout", Some(&session_cookie)).await;
+        assert!(subscriber.try_recv().is_err());
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod audit_tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use tower_sessions::{
+        audit::{AuditOperation, AuditRecord, AuditSink, AuditStore, AuditStoreError, FileSink},
+        clock::{Clock, MockClock},
+        ListSessions, MemoryStore, Session, SessionStore,
+    };
+
+    #[derive(Debug, Clone, Default)]
+    struct VecSink(Arc<Mutex<Vec<AuditRecord>>>);
+
+    #[async_trait]
+    impl AuditSink for VecSink {
+        type Error = io::Error;
+
+        async fn record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct FailingSink;
+
+    #[async_trait]
+    impl AuditSink for FailingSink {
+        type Error = io::Error;
+
+        async fn record(&self, _: &AuditRecord) -> Result<(), Self::Error> {
+            Err(io::Error::other("sink unavailable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn records_every_access() {
+        let clock = MockClock::default();
+        let sink = VecSink::default();
+        let session_store = AuditStore::new(MemoryStore::default(), sink.clone())
+            .with_context(|| Some("tests".to_owned()))
+            .with_clock(clock.clone());
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        session_store.save(&session).await.unwrap();
+        session_store.load(session.id()).await.unwrap();
+        session_store.delete(session.id()).await.unwrap();
+        session_store.list_sessions().await.unwrap();
+
+        let fingerprint = Some(session.id().fingerprint());
+        let record = |operation, session_id| AuditRecord {
+            operation,
+            session_id,
+            context: Some("tests".to_owned()),
+            timestamp: clock.now(),
+            succeeded: true,
+        };
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            vec![
+                record(AuditOperation::Create, fingerprint.clone()),
+                record(AuditOperation::Save, fingerprint.clone()),
+                record(AuditOperation::Load, fingerprint.clone()),
+                record(AuditOperation::Delete, fingerprint),
+                record(AuditOperation::List, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unrecorded_access_fails() {
+        let session_store = AuditStore::new(MemoryStore::default(), FailingSink);
+
+        let session = Session::default();
+        let res = session_store.create(&session).await;
+        assert!(matches!(res, Err(AuditStoreError::Sink(_))));
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("tower-sessions-{}", uuid::Uuid::new_v4()));
+        let session_store = AuditStore::new(MemoryStore::default(), FileSink::open(&path).unwrap());
+
+        let session = Session::default();
+        session_store.create(&session).await.unwrap();
+        session_store.load(session.id()).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let records = contents
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["operation"], "create");
+        assert_eq!(records[1]["operation"], "load");
+        assert_eq!(records[1]["session_id"], session.id().fingerprint());
+        assert_eq!(records[1]["succeeded"], true);
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod external_cookie_manager_tests {
+    use axum::Router;
+    use tower_cookies::CookieManagerLayer;
+    use tower_sessions::{MemoryStore, SessionManagerLayer};
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store = MemoryStore::default();
+        let session_manager = SessionManagerLayer::new(session_store)
+            .with_secure(true)
+            .with_external_cookie_manager(true);
+        build_app(session_manager, max_age).layer(CookieManagerLayer::new())
+    }
+
+    route_tests!(app);
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "signed",
+    feature = "private"
+))]
+mod signed_cookie_tests {
+    use axum::{body::Body, Router};
+    use http::{header, Request};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_cookies::{cookie::Key, Cookie};
+    use tower_sessions::{MemoryStore, SessionManagerLayer};
+
+    use crate::common::{body_string, build_app, get_session_cookie};
+
+    fn signed_app() -> Router {
+        let session_store = MemoryStore::default();
+        let session_manager = SessionManagerLayer::new(session_store).with_signed(Key::generate());
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    fn private_app() -> Router {
+        let session_store = MemoryStore::default();
+        let session_manager = SessionManagerLayer::new(session_store).with_private(Key::generate());
+        build_app(session_manager, Some(Duration::hours(1)))
+    }
+
+    async fn insert_then_get(app: Router) {
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        assert!(session_cookie.value().len() > 36);
+
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+
+        assert_eq!(body_string(res.into_body()).await, "42");
+
+        // An unverifiable cookie is treated as absent.
+        let tampered_cookie = Cookie::new("id", format!("{}x", session_cookie.value()));
+        let req = Request::builder()
+            .uri("/get_value")
+            .header(header::COOKIE, tampered_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(body_string(res.into_body()).await, "None");
+    }
+
+    #[tokio::test]
+    async fn signed_session_cookie() {
+        insert_then_get(signed_app()).await;
+    }
+
+    #[tokio::test]
+    async fn private_session_cookie() {
+        insert_then_get(private_app()).await;
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "signed"
+))]
+mod managed_key_tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use axum::{body::Body, Router};
+    use http::{header, Request};
+    use time::Duration;
+    use tokio::sync::Semaphore;
+    use tower::ServiceExt;
+    use tower_sessions::{
+        clock::MockClock,
+        key_provider::{FileKeyProvider, KeyProvider, KeyProviderError, ManagedKey},
+        MemoryStore, SessionManagerLayer,
+    };
+
+    use crate::common::{body_string, build_app, get_session_cookie};
+
+    /// A provider whose key tests may rotate, or make unavailable.
+    #[derive(Debug, Clone, Default)]
+    struct RotatingKeyProvider(Arc<Mutex<Option<Vec<u8>>>>);
+
+    impl RotatingKeyProvider {
+        fn set(&self, fill: Option<u8>) {
+            *self.0.lock().unwrap() = fill.map(|fill| vec![fill; 64]);
+        }
+    }
+
+    #[async_trait]
+    impl KeyProvider for RotatingKeyProvider {
+        async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+            self.0
+                .lock()
+                .unwrap()
+                .clone()
+                .ok_or_else(|| KeyProviderError::NotFound("rotating key".to_owned()))
+        }
+    }
+
+    /// A provider which yields its key once per permit, stalling until then.
+    #[derive(Debug, Clone)]
+    struct StalledKeyProvider(Arc<Semaphore>);
+
+    #[async_trait]
+    impl KeyProvider for StalledKeyProvider {
+        async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+            self.0.acquire().await.unwrap().forget();
+            Ok(vec![1; 64])
+        }
+    }
+
+    async fn app(key_provider: impl KeyProvider, clock: MockClock) -> Router {
+        let key = ManagedKey::new(key_provider)
+            .await
+            .unwrap()
+            .with_refresh_interval(Duration::minutes(1));
+        let session_manager = SessionManagerLayer::new(MemoryStore::default())
+            .with_signed_managed_key(key)
+            .with_clock(clock);
+        build_app(session_manager, None)
+    }
+
+    /// Lets the refresh spawned by a request replace the key.
+    async fn refreshed() {
+        tokio::task::yield_now().await;
+    }
+
+    async fn insert(app: &Router) -> String {
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        get_session_cookie(res.headers())
+            .unwrap()
+            .encoded()
+            .to_string()
+    }
+
+    async fn get_value(app: &Router, cookie: &str) -> String {
+        let req = Request::builder()
+            .uri("/get_value")
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        body_string(res.into_body()).await
+    }
+
+    #[tokio::test]
+    async fn rotated_key_verifies_previous_cookies() {
+        let key_provider = RotatingKeyProvider::default();
+        key_provider.set(Some(1));
+        let clock = MockClock::default();
+        let app = app(key_provider.clone(), clock.clone()).await;
+
+        let first_cookie = insert(&app).await;
+
+        key_provider.set(Some(2));
+        clock.advance(Duration::minutes(2));
+        assert_eq!(get_value(&app, &first_cookie).await, "Some(Number(42))");
+        refreshed().await;
+
+        let second_cookie = insert(&app).await;
+        assert_eq!(get_value(&app, &second_cookie).await, "Some(Number(42))");
+        assert_eq!(get_value(&app, &first_cookie).await, "Some(Number(42))");
+
+        // Past a second rotation, the first key is no longer accepted.
+        key_provider.set(Some(3));
+        clock.advance(Duration::minutes(2));
+        assert_eq!(get_value(&app, &second_cookie).await, "Some(Number(42))");
+        refreshed().await;
+        assert_eq!(get_value(&app, &second_cookie).await, "Some(Number(42))");
+        assert_eq!(get_value(&app, &first_cookie).await, "None");
+    }
+
+    #[tokio::test]
+    async fn key_is_refreshed_after_interval() {
+        let key_provider = RotatingKeyProvider::default();
+        key_provider.set(Some(1));
+        let clock = MockClock::default();
+        let app = app(key_provider.clone(), clock.clone()).await;
+
+        let first_cookie = insert(&app).await;
+
+        // Within the refresh interval, the key in use is kept.
+        key_provider.set(Some(2));
+        let second_cookie = insert(&app).await;
+        key_provider.set(Some(3));
+        clock.advance(Duration::minutes(2));
+        assert_eq!(get_value(&app, &second_cookie).await, "Some(Number(42))");
+        refreshed().await;
+        assert_eq!(get_value(&app, &first_cookie).await, "Some(Number(42))");
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_key() {
+        let key_provider = RotatingKeyProvider::default();
+        key_provider.set(Some(1));
+        let clock = MockClock::default();
+        let app = app(key_provider.clone(), clock.clone()).await;
+
+        let cookie = insert(&app).await;
+
+        key_provider.set(None);
+        clock.advance(Duration::minutes(2));
+        assert_eq!(get_value(&app, &cookie).await, "Some(Number(42))");
+        refreshed().await;
+        assert_eq!(get_value(&app, &cookie).await, "Some(Number(42))");
+    }
+
+    #[tokio::test]
+    async fn requests_do_not_wait_for_refresh() {
+        let key_provider = StalledKeyProvider(Arc::new(Semaphore::new(1)));
+        let clock = MockClock::default();
+        let app = app(key_provider.clone(), clock.clone()).await;
+
+        let cookie = insert(&app).await;
+
+        // The request due to refresh the key carries on with the current one while
+        // the provider stalls.
+        clock.advance(Duration::minutes(2));
+        let value =
+            tokio::time::timeout(std::time::Duration::from_secs(5), get_value(&app, &cookie)).await;
+        assert_eq!(value.unwrap(), "Some(Number(42))");
+
+        key_provider.0.add_permits(1);
+        refreshed().await;
+        assert_eq!(get_value(&app, &cookie).await, "Some(Number(42))");
+    }
+
+    #[tokio::test]
+    async fn file_key_provider_reads_replaced_file() {
+        let path = std::env::temp_dir().join(format!("session-key-{}", uuid::Uuid::new_v4()));
+        let key_provider = FileKeyProvider::new(&path);
+
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::NotFound(_))
+        ));
+
+        // Trailing whitespace, such as a final newline, is ignored.
+        std::fs::write(&path, format!("{}\n", "A".repeat(88))).unwrap();
+        assert_eq!(key_provider.fetch().await.unwrap(), vec![0; 66]);
+
+        std::fs::write(&path, "c2hvcnQ=").unwrap();
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::TooShort { len: 5 })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "encrypted-store"
+))]
+mod encrypted_store_tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use axum::Router;
+    use tower_sessions::{
+        clock::MockClock,
+        encrypted_store::{EncryptedStore, EncryptedStoreError, MemoryDataKeyStore},
+        key_provider::{KeyProvider, KeyProviderError},
+        MemoryStore, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    #[derive(Debug, Clone)]
+    struct MasterKeyProvider(Arc<Mutex<Vec<u8>>>);
+
+    impl MasterKeyProvider {
+        fn new(fill: u8) -> Self {
+            Self(Arc::new(Mutex::new(vec![fill; 64])))
+        }
+
+        fn rotate(&self, fill: u8) {
+            *self.0.lock().unwrap() = vec![fill; 64];
+        }
+    }
+
+    #[async_trait]
+    impl KeyProvider for MasterKeyProvider {
+        async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    /// Lets the refresh of the master key spawned by a store call replace it.
+    async fn refreshed() {
+        tokio::task::yield_now().await;
+    }
+
+    async fn store(
+        inner: MemoryStore,
+        master_key_provider: MasterKeyProvider,
+    ) -> EncryptedStore<MemoryStore, MemoryDataKeyStore> {
+        EncryptedStore::new(inner, MemoryDataKeyStore::default(), master_key_provider)
+            .await
+            .unwrap()
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store = store(MemoryStore::default(), MasterKeyProvider::new(1)).await;
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    async fn save_session(
+        session_store: &EncryptedStore<MemoryStore, MemoryDataKeyStore>,
+        principal: Option<&str>,
+    ) -> Session {
+        let session = Session::default();
+        session.insert("foo", 42).unwrap();
+        session.set_principal(principal);
+        session_store.create(&session).await.unwrap();
+        session
+    }
+
+    async fn loads(
+        session_store: &EncryptedStore<MemoryStore, MemoryDataKeyStore>,
+        session: &Session,
+    ) -> bool {
+        session_store
+            .load(session.id())
+            .await
+            .unwrap()
+            .is_some_and(|loaded| loaded.get::<i32>("foo").unwrap() == Some(42))
+    }
+
+    #[tokio::test]
+    async fn records_are_encrypted() {
+        let inner = MemoryStore::default();
+        let session_store = store(inner.clone(), MasterKeyProvider::new(1)).await;
+
+        for principal in [Some("alice"), None] {
+            let session = save_session(&session_store, principal).await;
+
+            let record = inner.load(session.id()).await.unwrap().unwrap();
+            assert!(record.get_value("foo").is_none());
+            assert_eq!(record.principal().as_deref(), principal);
+            assert!(loads(&session_store, &session).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn shred_makes_sessions_of_principal_unreadable() {
+        let session_store = store(MemoryStore::default(), MasterKeyProvider::new(1)).await;
+        let alice_sessions = [
+            save_session(&session_store, Some("alice")).await,
+            save_session(&session_store, Some("alice")).await,
+        ];
+        let bob_session = save_session(&session_store, Some("bob")).await;
+        let anonymous_session = save_session(&session_store, None).await;
+
+        session_store.shred("alice").await.unwrap();
+
+        for session in &alice_sessions {
+            assert!(!loads(&session_store, session).await);
+        }
+        assert!(loads(&session_store, &bob_session).await);
+        assert!(loads(&session_store, &anonymous_session).await);
+
+        // New sessions of the principal get a new data key.
+        let session = save_session(&session_store, Some("alice")).await;
+        assert!(loads(&session_store, &session).await);
+        assert!(!loads(&session_store, &alice_sessions[0]).await);
+    }
+
+    #[tokio::test]
+    async fn master_key_rotation_rewraps_data_keys() {
+        let master_key_provider = MasterKeyProvider::new(1);
+        let session_store = store(MemoryStore::default(), master_key_provider.clone())
+            .await
+            .with_refresh_interval(Duration::ZERO);
+        let alice_session = save_session(&session_store, Some("alice")).await;
+        let anonymous_session = save_session(&session_store, None).await;
+
+        master_key_provider.rotate(2);
+        assert!(loads(&session_store, &anonymous_session).await);
+        refreshed().await;
+        assert!(loads(&session_store, &alice_session).await);
+        assert!(loads(&session_store, &anonymous_session).await);
+
+        // The data key was wrapped again with the second master key, whereas the
+        // anonymous session is still encrypted with the first.
+        master_key_provider.rotate(3);
+        assert!(loads(&session_store, &alice_session).await);
+        refreshed().await;
+        assert!(loads(&session_store, &alice_session).await);
+        assert!(!loads(&session_store, &anonymous_session).await);
+    }
+
+    #[tokio::test]
+    async fn data_key_left_behind_two_rotations_fails() {
+        let master_key_provider = MasterKeyProvider::new(1);
+        let session_store = store(MemoryStore::default(), master_key_provider.clone())
+            .await
+            .with_refresh_interval(Duration::ZERO);
+        let alice_session = save_session(&session_store, Some("alice")).await;
+
+        // Alice's data key isn't used while the master key is rotated twice, and so
+        // is never wrapped again.
+        master_key_provider.rotate(2);
+        save_session(&session_store, Some("bob")).await;
+        refreshed().await;
+        master_key_provider.rotate(3);
+        save_session(&session_store, Some("bob")).await;
+        refreshed().await;
+        assert!(matches!(
+            session_store.load(alice_session.id()).await,
+            Err(EncryptedStoreError::Unwrap)
+        ));
+        assert!(matches!(
+            session_store.save(&alice_session).await,
+            Err(EncryptedStoreError::Unwrap)
+        ));
+
+        // The key is only replaced once shredded.
+        session_store.shred("alice").await.unwrap();
+        let session = save_session(&session_store, Some("alice")).await;
+        assert!(loads(&session_store, &session).await);
+    }
+
+    #[tokio::test]
+    async fn lagging_instance_fetches_master_key_again() {
+        let master_key_provider = MasterKeyProvider::new(1);
+        let inner = MemoryStore::default();
+        let data_keys = MemoryDataKeyStore::default();
+        let instance = |refresh_interval| {
+            let inner = inner.clone();
+            let data_keys = data_keys.clone();
+            let master_key_provider = master_key_provider.clone();
+            async move {
+                EncryptedStore::new(inner, data_keys, master_key_provider)
+                    .await
+                    .unwrap()
+                    .with_refresh_interval(refresh_interval)
+            }
+        };
+        let rotating = instance(Duration::ZERO).await;
+        let lagging = instance(Duration::hours(1)).await;
+        let alice_session = save_session(&rotating, Some("alice")).await;
+
+        // The rotating instance wraps the data key again with the second master key,
+        // which the lagging instance isn't yet due to fetch.
+        master_key_provider.rotate(2);
+        assert!(loads(&rotating, &alice_session).await);
+        refreshed().await;
+        assert!(loads(&rotating, &alice_session).await);
+        assert!(loads(&lagging, &alice_session).await);
+        assert!(loads(&rotating, &alice_session).await);
+    }
+
+    #[tokio::test]
+    async fn master_key_refreshes_on_clock() {
+        let clock = MockClock::default();
+        let master_key_provider = MasterKeyProvider::new(1);
+        let session_store = store(MemoryStore::default(), master_key_provider.clone())
+            .await
+            .with_refresh_interval(Duration::hours(1))
+            .with_clock(clock.clone());
+        let anonymous_session = save_session(&session_store, None).await;
+
+        master_key_provider.rotate(2);
+        clock.advance(Duration::hours(2));
+        assert!(loads(&session_store, &anonymous_session).await);
+        refreshed().await;
+
+        // Until the clock is advanced again, the third master key isn't fetched, and
+        // the first is still the previous one.
+        master_key_provider.rotate(3);
+        assert!(loads(&session_store, &anonymous_session).await);
+        clock.advance(Duration::hours(2));
+        assert!(loads(&session_store, &anonymous_session).await);
+        refreshed().await;
+        assert!(!loads(&session_store, &anonymous_session).await);
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod transfer_tests {
+    use futures::{stream, TryStreamExt};
+    use time::Duration;
+    use tower_sessions::{
+        clock::MockClock,
+        transfer::{self, Transfer, TransferError},
+        Expiry, MemoryStore, Session, SessionStore,
+    };
+
+    async fn save_session(session_store: &MemoryStore, principal: Option<&str>) -> Session {
+        let session = Session::new(Some(Expiry::OnInactivity(Duration::hours(1))));
+        session.insert("foo", 42).unwrap();
+        session.set_principal(principal);
+        session_store.save(&session).await.unwrap();
+        session
+    }
+
+    fn lines(
+        lines: Vec<String>,
+    ) -> impl futures::Stream<Item = Result<String, std::io::Error>> + Send {
+        stream::iter(lines.into_iter().map(Ok))
+    }
+
+    #[tokio::test]
+    async fn export_then_import() {
+        let source = MemoryStore::default();
+        let sessions = [
+            save_session(&source, Some("alice")).await,
+            save_session(&source, None).await,
+        ];
+
+        let exported: Vec<String> = transfer::export(&source).try_collect().await.unwrap();
+        assert_eq!(exported.len(), 2);
+        assert!(exported.iter().all(|line| line.ends_with('\n')));
+
+        let destination = MemoryStore::default();
+        let imported = transfer::import(&destination, lines(exported))
+            .await
+            .unwrap();
+        assert_eq!(imported, 2);
+
+        for session in &sessions {
+            let loaded = destination.load(session.id()).await.unwrap().unwrap();
+            assert_eq!(loaded.get::<i32>("foo").unwrap(), Some(42));
+            assert_eq!(loaded.principal(), session.principal());
+            assert_eq!(loaded.expiry_date(), session.expiry_date());
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_sessions_are_skipped() {
+        let source = MemoryStore::default();
+        save_session(&source, None).await;
+
+        let clock = MockClock::default();
+        clock.advance(Duration::hours(2));
+
+        let transfer = Transfer::new().with_clock(clock.clone());
+        let exported: Vec<String> = transfer.export(&source).try_collect().await.unwrap();
+        assert!(exported.is_empty());
+
+        let transfer = transfer.with_expired(true);
+        let exported: Vec<String> = transfer.export(&source).try_collect().await.unwrap();
+        assert_eq!(exported.len(), 1);
+
+        let destination = MemoryStore::default();
+        let imported = Transfer::new()
+            .with_clock(clock)
+            .import(&destination, lines(exported))
+            .await
+            .unwrap();
+        assert_eq!(imported, 0);
+    }
+
+    #[tokio::test]
+    async fn transform_anonymizes_sessions() {
+        let source = MemoryStore::default();
+        let session = save_session(&source, Some("alice")).await;
+        save_session(&source, None).await;
+
+        let transfer = Transfer::new().with_transform(|session| {
+            session.principal()?;
+            session.set_principal(Some("user"));
+            session.remove_value("foo");
+            Some(session)
+        });
+        let exported: Vec<String> = transfer.export(&source).try_collect().await.unwrap();
+        assert_eq!(exported.len(), 1);
+
+        let destination = MemoryStore::default();
+        transfer::import(&destination, lines(exported))
+            .await
+            .unwrap();
+        let loaded = destination.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(loaded.principal().as_deref(), Some("user"));
+        assert!(loaded.get_value("foo").is_none());
+    }
+
+    #[tokio::test]
+    async fn import_reports_malformed_line() {
+        let source = MemoryStore::default();
+        save_session(&source, None).await;
+        let mut exported: Vec<String> = transfer::export(&source).try_collect().await.unwrap();
+        exported.push(String::new());
+        exported.push("{".to_owned());
+
+        let destination = MemoryStore::default();
+        let err = transfer::import(&destination, lines(exported))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TransferError::Deserialize { line: 3, .. }));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod shutdown_tests {
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use async_trait::async_trait;
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, BoxError, Router};
+    use http::{Request, StatusCode};
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_sessions::{session::Id, MemoryStore, Session, SessionManagerLayer, SessionStore};
+
+    use crate::common::build_app;
+
+    #[derive(Debug, Clone, Default)]
+    struct SlowStore {
+        inner: MemoryStore,
+        saves: Arc<AtomicUsize>,
+        flushed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl SessionStore for SlowStore {
+        type Error = Infallible;
+
+        async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            self.inner.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            self.inner.load(session_id).await
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            self.inner.delete(session_id).await
+        }
+
+        async fn flush(&self) -> Result<(), Self::Error> {
+            self.flushed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_pending_writes() {
+        let session_store = SlowStore::default();
+        let session_manager = SessionManagerLayer::new(session_store.clone());
+        let shutdown_handle = session_manager.clone();
+        let app = build_app(session_manager, None);
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let request = tokio::spawn(app.oneshot(req));
+
+        // Give the request time to reach the store.
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        shutdown_handle.shutdown().await.unwrap();
+
+        assert_eq!(session_store.saves.load(Ordering::SeqCst), 1);
+        assert!(session_store.flushed.load(Ordering::SeqCst));
+
+        request.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_running_handlers() {
+        let session_store = SlowStore::default();
+        let session_manager = SessionManagerLayer::new(session_store.clone());
+        let shutdown_handle = session_manager.clone();
+        let app = Router::new()
+            .route(
+                "/slow_insert",
+                get(|session: Session| async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    session.insert("foo", 42).unwrap();
+                }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(session_manager),
+            );
+
+        let req = Request::builder()
+            .uri("/slow_insert")
+            .body(Body::empty())
+            .unwrap();
+        let request = tokio::spawn(app.oneshot(req));
+
+        // Give the request time to reach the handler.
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        shutdown_handle.shutdown().await.unwrap();
+
+        assert_eq!(session_store.saves.load(Ordering::SeqCst), 1);
+        assert!(session_store.flushed.load(Ordering::SeqCst));
+
+        request.await.unwrap().unwrap();
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod session_store_create_tests {
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use async_trait::async_trait;
+    use axum::body::Body;
+    use http::{header, Request};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_sessions::{session::Id, MemoryStore, Session, SessionManagerLayer, SessionStore};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    #[derive(Debug, Clone, Default)]
+    struct CountingStore {
+        inner: MemoryStore,
+        creates: Arc<AtomicUsize>,
+        saves: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl SessionStore for CountingStore {
+        type Error = Infallible;
+
+        async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            self.inner.save(session).await
+        }
+
+        async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+            self.creates.fetch_add(1, Ordering::SeqCst);
+            self.inner.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            self.inner.load(session_id).await
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            self.inner.delete(session_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn new_ids_are_created_and_existing_ids_saved() {
+        let session_store = CountingStore::default();
+        // Sessions expiring within the threshold are saved on every request.
+        let session_manager = SessionManagerLayer::new(session_store.clone())
+            .with_refresh_threshold(Duration::hours(2));
+        let app = build_app(session_manager, Some(Duration::hours(1)));
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+        assert_eq!(session_store.creates.load(Ordering::SeqCst), 1);
+        assert_eq!(session_store.saves.load(Ordering::SeqCst), 0);
+
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap();
+        assert_eq!(session_store.creates.load(Ordering::SeqCst), 1);
+        assert_eq!(session_store.saves.load(Ordering::SeqCst), 1);
+
+        let req = Request::builder()
+            .uri("/cycle_id")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap();
+        assert_eq!(session_store.creates.load(Ordering::SeqCst), 2);
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "metrics"
+))]
+mod metrics_tests {
+    use axum::body::Body;
+    use http::{header, Request};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_cookies::Cookie;
+    use tower_sessions::{metrics, MemoryStore, Session, SessionManagerLayer, SessionStore};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    #[test]
+    fn records_session_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let session_manager = SessionManagerLayer::new(MemoryStore::default());
+                let app = build_app(session_manager, Some(Duration::hours(1)));
+
+                let req = Request::builder()
+                    .uri("/insert")
+                    .body(Body::empty())
+                    .unwrap();
+                let res = app.clone().oneshot(req).await.unwrap();
+                let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+                let req = Request::builder()
+                    .uri("/get")
+                    .header(header::COOKIE, session_cookie.encoded().to_string())
+                    .body(Body::empty())
+                    .unwrap();
+                app.clone().oneshot(req).await.unwrap();
+
+                let unknown_cookie = Cookie::new("id", "00000000-0000-0000-0000-000000000000");
+                let req = Request::builder()
+                    .uri("/")
+                    .header(header::COOKIE, unknown_cookie.encoded().to_string())
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(req).await.unwrap();
+            })
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let value = |name: &str| {
+            snapshot
+                .iter()
+                .find(|(key, _, _, _)| key.key().name() == name)
+                .map(|(_, _, _, value)| value)
+        };
+
+        assert_eq!(
+            value(metrics::SESSIONS_CREATED),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(metrics::SESSIONS_MISSING),
+            Some(&DebugValue::Counter(1))
+        );
+        assert!(matches!(
+            value(metrics::LOAD_DURATION),
+            Some(DebugValue::Histogram(loads)) if loads.len() == 2
+        ));
+        assert!(matches!(
+            value(metrics::SAVE_DURATION),
+            Some(DebugValue::Histogram(saves)) if saves.len() == 1
+        ));
+        assert_eq!(value(metrics::SAVE_FAILURES), None);
+    }
+
+    #[test]
+    fn records_memory_store_evictions() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let session_store = MemoryStore::default().with_max_sessions(1);
+                for _ in 0..3 {
+                    session_store.save(&Session::default()).await.unwrap();
+                }
+            })
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let evictions = snapshot
+            .iter()
+            .find(|(key, _, _, _)| {
+                key.key().name() == metrics::MEMORY_STORE_EVICTIONS
+                    && key
+                        .key()
+                        .labels()
+                        .any(|label| label.key() == "reason" && label.value() == "capacity")
+            })
+            .map(|(_, _, _, value)| value);
+        assert_eq!(evictions, Some(&DebugValue::Counter(2)));
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "opentelemetry"
+))]
+mod telemetry_tests {
+    use std::{
+        convert::Infallible,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use axum::body::Body;
+    use http::{header, Request};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_sessions::{
+        session::{Id, Session},
+        telemetry, MemoryStore, SessionManagerLayer, SessionStore,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+    use crate::common::{build_app, get_session_cookie};
+
+    #[derive(Debug, Clone, Default)]
+    struct TestStore(MemoryStore);
+
+    #[async_trait]
+    impl SessionStore for TestStore {
+        type Error = Infallible;
+
+        async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            self.0.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            self.0.load(session_id).await
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            self.0.delete(session_id).await
+        }
+
+        fn db_system(&self) -> Option<&'static str> {
+            Some("test")
+        }
+    }
+
+    /// Spans by name, with their fields.
+    type Spans = Arc<Mutex<Vec<(&'static str, Vec<(&'static str, String)>)>>>;
+
+    struct Fields<'a>(&'a mut Vec<(&'static str, String)>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push((field.name(), format!("{value:?}")));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name(), value.to_string()));
+        }
+    }
+
+    struct SpanRecorder(Spans);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = Vec::new();
+            attrs.record(&mut Fields(&mut fields));
+            ctx.span(id).unwrap().extensions_mut().insert(spans.len());
+            spans.push((attrs.metadata().name(), fields));
+        }
+
+        fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+            let index = *ctx.span(id).unwrap().extensions().get::<usize>().unwrap();
+            values.record(&mut Fields(&mut self.0.lock().unwrap()[index].1));
+        }
+    }
+
+    fn field<'a>(fields: &'a [(&'static str, String)], name: &str) -> Option<&'a str> {
+        fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[tokio::test]
+    async fn records_store_spans() {
+        let spans = Spans::default();
+        let _guard = tracing_subscriber::registry()
+            .with(SpanRecorder(spans.clone()))
+            .set_default();
+
+        let session_manager = SessionManagerLayer::new(TestStore::default());
+        let app = build_app(session_manager, Some(Duration::hours(1)));
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap();
+
+        let spans = spans.lock().unwrap();
+        let middleware_spans = spans
+            .iter()
+            .filter(|(name, _)| *name == "session_middleware")
+            .map(|(_, fields)| field(fields, telemetry::SESSION_NEW))
+            .collect::<Vec<_>>();
+        assert_eq!(middleware_spans, [Some("true"), Some("false")]);
+
+        let store_spans = spans
+            .iter()
+            .filter(|(name, _)| *name == telemetry::STORE_SPAN)
+            .collect::<Vec<_>>();
+        let operations = store_spans
+            .iter()
+            .map(|(_, fields)| field(fields, telemetry::DB_OPERATION_NAME))
+            .collect::<Vec<_>>();
+        assert_eq!(operations, [Some("create"), Some("load")]);
+        for (_, fields) in store_spans {
+            assert_eq!(field(fields, telemetry::DB_SYSTEM), Some("test"));
+            assert_eq!(field(fields, "otel.kind"), Some("client"));
+            assert_eq!(field(fields, "otel.status_code"), None);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod metrics_store_tests {
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, BoxError, Router};
+    use http::{header, Request, StatusCode};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_cookies::Cookie;
+    use tower_sessions::{MemoryStore, Session, SessionManagerLayer};
+    use tower_sessions_metrics::{
+        MetricsLayer, MetricsStore, ACTIVE_SESSIONS, LOADS, LOAD_MISSES, OPERATION_DURATION,
+        PAYLOAD_SIZE, REQUESTS, SAVES, SAVE_ERRORS,
+    };
+
+    use crate::common::get_session_cookie;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", get(|_: Session| async move { "Hello, world!" }))
+            .route(
+                "/insert",
+                get(|session: Session| async move {
+                    session.insert("foo", 42).unwrap();
+                }),
+            )
+            .route(
+                "/delete",
+                get(|session: Session| async move {
+                    session.delete();
+                }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(SessionManagerLayer::new(MetricsStore::new(
+                        MemoryStore::default(),
+                    )))
+                    .layer(MetricsLayer::new()),
+            )
+    }
+
+    #[test]
+    fn records_store_and_request_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let app = app();
+
+                let req = Request::builder()
+                    .uri("/insert")
+                    .body(Body::empty())
+                    .unwrap();
+                let res = app.clone().oneshot(req).await.unwrap();
+                let session_cookie = get_session_cookie(res.headers()).unwrap();
+
+                for uri in ["/", "/delete"] {
+                    let req = Request::builder()
+                        .uri(uri)
+                        .header(header::COOKIE, session_cookie.encoded().to_string())
+                        .body(Body::empty())
+                        .unwrap();
+                    app.clone().oneshot(req).await.unwrap();
+                }
+
+                let unknown_cookie = Cookie::new("id", "00000000-0000-0000-0000-000000000000");
+                let req = Request::builder()
+                    .uri("/")
+                    .header(header::COOKIE, unknown_cookie.encoded().to_string())
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(req).await.unwrap();
+            })
+        });
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let value = |name: &str, label: Option<(&str, &str)>| {
+            snapshot
+                .iter()
+                .find(|(key, _, _, _)| {
+                    key.key().name() == name
+                        && label.is_none_or(|(label_key, label_value)| {
+                            key.key()
+                                .labels()
+                                .any(|l| l.key() == label_key && l.value() == label_value)
+                        })
+                })
+                .map(|(_, _, _, value)| value)
+        };
+
+        assert_eq!(value(LOADS, None), Some(&DebugValue::Counter(3)));
+        assert_eq!(value(LOAD_MISSES, None), Some(&DebugValue::Counter(1)));
+        assert_eq!(value(SAVES, None), Some(&DebugValue::Counter(1)));
+        assert_eq!(value(SAVE_ERRORS, None), None);
+        // The session of the unknown cookie is deleted as well.
+        assert!(matches!(
+            value(ACTIVE_SESSIONS, None),
+            Some(DebugValue::Gauge(active)) if active.into_inner() == -1.0
+        ));
+        assert!(matches!(
+            value(PAYLOAD_SIZE, None),
+            Some(DebugValue::Histogram(sizes)) if sizes.len() == 1
+        ));
+        assert!(matches!(
+            value(OPERATION_DURATION, Some(("operation", "load"))),
+            Some(DebugValue::Histogram(loads)) if loads.len() == 3
+        ));
+        assert!(matches!(
+            value(OPERATION_DURATION, Some(("operation", "delete"))),
+            Some(DebugValue::Histogram(deletes)) if deletes.len() == 2
+        ));
+
+        for (outcome, count) in [("modified", 1), ("unchanged", 2), ("deleted", 1)] {
+            assert_eq!(
+                value(REQUESTS, Some(("outcome", outcome))),
+                Some(&DebugValue::Counter(count)),
+                "{outcome}"
+            );
+        }
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod admin_tests {
+    use axum::{body::Body, Router};
+    use http::{header, Method, Request, StatusCode};
+    use serde_json::Value;
+    use tower::ServiceExt;
+    use tower_sessions::{one_time::OneTimeTokens, MemoryStore, Session, SessionStore};
+
+    use crate::common::body_string;
+
+    const TOKEN: &str = "an admin token";
+
+    async fn session(store: &MemoryStore, principal: Option<&str>) -> Session {
+        let session = Session::default();
+        session.insert("foo", 42).unwrap();
+        session.set_principal(principal);
+        store.create(&session).await.unwrap();
+        session
+    }
+
+    async fn request(app: &Router, method: Method, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        (status, body_string(res.into_body()).await)
+    }
+
+    fn session_ids(body: &str) -> Vec<String> {
+        let body: Value = serde_json::from_str(body).unwrap();
+        let mut session_ids = body["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|session_id| session_id.as_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        session_ids.sort();
+        session_ids
+    }
+
+    #[tokio::test]
+    async fn requires_token() {
+        let app = tower_sessions_admin::router(MemoryStore::default(), TOKEN);
+
+        let req = Request::builder()
+            .uri("/sessions")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = Request::builder()
+            .uri("/sessions")
+            .header(header::AUTHORIZATION, "Bearer not the admin token")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn list_and_inspect_sessions() {
+        let store = MemoryStore::default();
+        let alice = session(&store, Some("alice")).await;
+        let anonymous = session(&store, None).await;
+        let app = tower_sessions_admin::router(store, TOKEN);
+
+        let (status, body) = request(&app, Method::GET, "/sessions").await;
+        assert_eq!(status, StatusCode::OK);
+        let mut expected = vec![alice.id().to_string(), anonymous.id().to_string()];
+        expected.sort();
+        assert_eq!(session_ids(&body), expected);
+
+        let (_, body) = request(&app, Method::GET, "/sessions?limit=1").await;
+        assert_eq!(session_ids(&body).len(), 1);
+
+        let (status, body) = request(&app, Method::GET, &format!("/sessions/{}", alice.id())).await;
+        assert_eq!(status, StatusCode::OK);
+        let record: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(record["id"], alice.id().to_string());
+        assert_eq!(record["principal"], "alice");
+        assert_eq!(record["data"]["foo"], 42);
+
+        let (status, _) = request(&app, Method::GET, "/sessions/not-a-session-id").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn revoke_sessions() {
+        let store = MemoryStore::default();
+        let alice = session(&store, Some("alice")).await;
+        let other_alice = session(&store, Some("alice")).await;
+        let bob = session(&store, Some("bob")).await;
+        let app = tower_sessions_admin::router(store.clone(), TOKEN);
+
+        let (status, body) = request(&app, Method::GET, "/principals/alice/sessions").await;
+        assert_eq!(status, StatusCode::OK);
+        let mut expected = vec![alice.id().to_string(), other_alice.id().to_string()];
+        expected.sort();
+        assert_eq!(session_ids(&body), expected);
+
+        let (status, _) = request(&app, Method::DELETE, &format!("/sessions/{}", bob.id())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(store.load(bob.id()).await.unwrap().is_none());
+
+        let (status, _) = request(&app, Method::DELETE, "/principals/alice/sessions").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(store.load(alice.id()).await.unwrap().is_none());
+        assert!(store.load(other_alice.id()).await.unwrap().is_none());
+
+        let (_, body) = request(&app, Method::GET, "/sessions").await;
+        assert!(session_ids(&body).is_empty());
+    }
+
+    #[tokio::test]
+    async fn token_records_are_not_sessions() {
+        let store = MemoryStore::default();
+        let tokens = OneTimeTokens::new(store.clone());
+        let token = tokens.issue_for("alice", "magic-link", &42).await.unwrap();
+        let (record_id, _) = token.split_once('.').unwrap();
+        let alice = session(&store, Some("alice")).await;
+        let app = tower_sessions_admin::router(store, TOKEN);
+
+        let (_, body) = request(&app, Method::GET, "/sessions").await;
+        assert_eq!(session_ids(&body), vec![alice.id().to_string()]);
+
+        let (_, body) = request(&app, Method::GET, "/sessions?limit=1").await;
+        assert_eq!(session_ids(&body), vec![alice.id().to_string()]);
+
+        let (_, body) = request(&app, Method::GET, "/principals/alice/sessions").await;
+        assert_eq!(session_ids(&body), vec![alice.id().to_string()]);
+
+        // The record of an unspent token isn't returned either.
+        let (status, _) = request(&app, Method::GET, &format!("/sessions/{record_id}")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod limit_tests {
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, Router};
+    use axum_core::BoxError;
+    use http::{header, Request, StatusCode};
+    use time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_cookies::Cookie;
+    use tower_sessions::{
+        clock::MockClock, limit::CreationLimit, ListSessions, MemoryStore, Session,
+        SessionManagerLayer,
+    };
+
+    use crate::common::{body_string, get_session_cookie};
+
+    fn app(session_store: MemoryStore, clock: &MockClock) -> Router {
+        let creation_limit = CreationLimit::new(2, Duration::minutes(1), |parts| {
+            let real_ip = parts.headers.get("x-real-ip")?;
+            real_ip.to_str().ok().map(str::to_owned)
+        });
+        let session_manager = SessionManagerLayer::new(session_store)
+            .with_clock(clock.clone())
+            .with_creation_limit(creation_limit);
+        let session_service = ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|_: BoxError| async {
+                StatusCode::BAD_REQUEST
+            }))
+            .layer(session_manager);
+
+        Router::new()
+            .route(
+                "/increment",
+                get(|session: Session| async move {
+                    let counter = session.get::<usize>("counter").unwrap().unwrap_or_default();
+                    session.insert("counter", counter + 1).unwrap();
+                }),
+            )
+            .route(
+                "/get",
+                get(|session: Session| async move {
+                    format!("{:?}", session.get::<usize>("counter").unwrap())
+                }),
+            )
+            .layer(session_service)
+    }
+
+    async fn increment(
+        app: &Router,
+        real_ip: Option<&str>,
+        session_cookie: Option<&Cookie<'_>>,
+    ) -> Option<Cookie<'static>> {
+        let mut req = Request::builder().uri("/increment");
+        if let Some(real_ip) = real_ip {
+            req = req.header("x-real-ip", real_ip);
+        }
+        if let Some(session_cookie) = session_cookie {
+            req = req.header(header::COOKIE, session_cookie.encoded().to_string());
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        get_session_cookie(res.headers())
+            .ok()
+            .map(Cookie::into_owned)
+    }
+
+    #[tokio::test]
+    async fn limits_sessions_created_per_client() {
+        let session_store = MemoryStore::default();
+        let clock = MockClock::default();
+        let app = app(session_store.clone(), &clock);
+
+        assert!(increment(&app, Some("10.0.0.1"), None).await.is_some());
+        assert!(increment(&app, Some("10.0.0.1"), None).await.is_some());
+        assert!(increment(&app, Some("10.0.0.1"), None).await.is_none());
+        assert_eq!(session_store.list_sessions().await.unwrap().len(), 2);
+
+        // Other clients, and requests without a key, aren't affected.
+        assert!(increment(&app, Some("10.0.0.2"), None).await.is_some());
+        assert!(increment(&app, None, None).await.is_some());
+
+        clock.advance(Duration::minutes(1));
+        assert!(increment(&app, Some("10.0.0.1"), None).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn stored_sessions_are_not_limited() {
+        let session_store = MemoryStore::default();
+        let clock = MockClock::default();
+        let app = app(session_store, &clock);
+
+        let session_cookie = increment(&app, Some("10.0.0.1"), None).await.unwrap();
+        assert!(increment(&app, Some("10.0.0.1"), None).await.is_some());
+        assert!(increment(&app, Some("10.0.0.1"), None).await.is_none());
+
+        // Sessions the client already holds are still saved.
+        assert!(increment(&app, Some("10.0.0.1"), Some(&session_cookie))
+            .await
+            .is_some());
+
+        let req = Request::builder()
+            .uri("/get")
+            .header("x-real-ip", "10.0.0.1")
+            .header(header::COOKIE, session_cookie.encoded().to_string())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(body_string(res.into_body()).await, "Some(2)");
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod remember_tests {
+    use std::collections::HashMap;
+
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, Router};
+    use axum_core::BoxError;
+    use http::{header, HeaderMap, Request, StatusCode};
+    use time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_cookies::Cookie;
+    use tower_sessions::{
+        clock::MockClock, remember::RememberMe, Expiry, MemoryStore, Session, SessionManagerLayer,
+    };
+
+    use crate::common::body_string;
+
+    fn app(clock: &MockClock) -> Router {
+        let session_store = MemoryStore::default().with_clock(clock.clone());
+        let session_manager = SessionManagerLayer::new(session_store)
+            .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+            .with_clock(clock.clone())
+            .with_remember_me(RememberMe::new(Duration::days(30)));
+        let session_service = ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|_: BoxError| async {
+                StatusCode::BAD_REQUEST
+            }))
+            .layer(session_manager);
+
+        Router::new()
+            .route(
+                "/login",
+                get(|session: Session| async move {
+                    session.promote("alice", &[]);
+                    session.remember();
+                }),
+            )
+            .route(
+                "/whoami",
+                get(|session: Session| async move { format!("{:?}", session.principal()) }),
+            )
+            .route(
+                "/logout",
+                get(|session: Session| async move {
+                    session.delete();
+                }),
+            )
+            .layer(session_service)
+    }
+
+    fn response_cookies(headers: &HeaderMap) -> HashMap<String, Cookie<'static>> {
+        headers
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .flat_map(|header| header.to_str())
+            .flat_map(Cookie::parse_encoded)
+            .map(|cookie| (cookie.name().to_owned(), cookie.into_owned()))
+            .collect()
+    }
+
+    async fn request(
+        app: &Router,
+        uri: &str,
+        cookies: &[&Cookie<'_>],
+    ) -> (String, HashMap<String, Cookie<'static>>) {
+        let mut req = Request::builder().uri(uri);
+        for cookie in cookies {
+            req = req.header(header::COOKIE, cookie.encoded().to_string());
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let cookies = response_cookies(res.headers());
+        (body_string(res.into_body()).await, cookies)
+    }
+
+    #[tokio::test]
+    async fn restores_expired_session() {
+        let clock = MockClock::default();
+        let app = app(&clock);
+
+        let (_, cookies) = request(&app, "/login", &[]).await;
+        let session_cookie = &cookies["id"];
+        let remember_cookie = &cookies["remember"];
+        assert_eq!(remember_cookie.http_only(), Some(true));
+        assert_eq!(remember_cookie.max_age(), Some(Duration::days(30)));
+
+        clock.advance(Duration::hours(2));
+        let (body, cookies) = request(&app, "/whoami", &[session_cookie, remember_cookie]).await;
+        assert_eq!(body, r#"Some("alice")"#);
+
+        // A new session is established and the token rotated.
+        assert_ne!(cookies["id"].value(), session_cookie.value());
+        assert_ne!(cookies["remember"].value(), remember_cookie.value());
+
+        let (body, _) = request(&app, "/whoami", &[&cookies["id"]]).await;
+        assert_eq!(body, r#"Some("alice")"#);
+    }
+
+    #[tokio::test]
+    async fn reused_token_revokes_family() {
+        let clock = MockClock::default();
+        let app = app(&clock);
+
+        let (_, cookies) = request(&app, "/login", &[]).await;
+        let stolen_cookie = &cookies["remember"];
+
+        let (body, cookies) = request(&app, "/whoami", &[stolen_cookie]).await;
+        assert_eq!(body, r#"Some("alice")"#);
+        let rotated_cookie = &cookies["remember"];
+
+        let (body, cookies) = request(&app, "/whoami", &[stolen_cookie]).await;
+        assert_eq!(body, "None");
+        assert_eq!(cookies["remember"].value(), "");
+
+        // The rightful token was revoked along with the stolen one.
+        let (body, _) = request(&app, "/whoami", &[rotated_cookie]).await;
+        assert_eq!(body, "None");
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_token() {
+        let clock = MockClock::default();
+        let app = app(&clock);
+
+        let (_, cookies) = request(&app, "/login", &[]).await;
+        let session_cookie = &cookies["id"];
+        let remember_cookie = &cookies["remember"];
+
+        let (_, cookies) = request(&app, "/logout", &[session_cookie, remember_cookie]).await;
+        assert_eq!(cookies["remember"].value(), "");
+        assert_eq!(cookies["remember"].max_age(), Some(Duration::ZERO));
+
+        let (body, _) = request(&app, "/whoami", &[remember_cookie]).await;
+        assert_eq!(body, "None");
+    }
+
+    #[tokio::test]
+    async fn records_are_not_sessions() {
+        let clock = MockClock::default();
+        let app = app(&clock);
+
+        let (_, cookies) = request(&app, "/login", &[]).await;
+        let (family, _) = cookies["remember"].value().split_once('.').unwrap();
+
+        let (body, _) = request(&app, "/whoami", &[&Cookie::new("id", family.to_owned())]).await;
+        assert_eq!(body, "None");
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod extract_tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, Router};
+    use axum_core::BoxError;
+    use http::{header, Request, StatusCode};
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_sessions::{session::Id, Session, SessionManagerLayer, SessionStore};
+
+    use crate::common::body_string;
+
+    #[derive(Debug, Clone)]
+    struct UnavailableStore;
+
+    #[async_trait]
+    impl SessionStore for UnavailableStore {
+        type Error = io::Error;
+
+        async fn save(&self, _session: &Session) -> Result<(), Self::Error> {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        }
+
+        async fn load(&self, _session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        }
+
+        async fn delete(&self, _session_id: &Id) -> Result<(), Self::Error> {
+            Err(io::ErrorKind::ConnectionRefused.into())
+        }
+    }
+
+    fn routes() -> Router {
+        Router::new()
+            .route("/session", get(|_: Session| async move { "ok" }))
+            .route("/static", get(|| async move { "ok" }))
+    }
+
+    fn app(defer_load_errors: bool) -> Router {
+        let session_manager =
+            SessionManagerLayer::new(UnavailableStore).with_deferred_load_errors(defer_load_errors);
+        let session_service = ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|_: BoxError| async {
+                StatusCode::BAD_REQUEST
+            }))
+            .layer(session_manager);
+
+        routes().layer(session_service)
+    }
+
+    async fn request(app: Router, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::COOKIE, format!("id={}", Id::default()))
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        (res.status(), body_string(res.into_body()).await)
+    }
+
+    #[tokio::test]
+    async fn missing_layer_rejection() {
+        let (status, body) = request(routes(), "/session").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body,
+            "Can't extract session. Is `SessionManagerLayer` enabled?"
+        );
+    }
+
+    #[tokio::test]
+    async fn deferred_load_error_rejection() {
+        let (status, body) = request(app(true), "/session").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, "Session unavailable");
+
+        // Requests which don't use the session are still served.
+        let (status, body) = request(app(true), "/static").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "ok");
+    }
+
+    #[tokio::test]
+    async fn load_error_fails_request() {
+        let (status, _) = request(app(false), "/static").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod actix_tests {
+    use actix_web::{cookie::Cookie, dev::ServiceResponse, test, web, App};
+    use axum::body::Body;
+    use http::{header, Request};
+    use time::Duration;
+    use tower::ServiceExt;
+    use tower_sessions::{clock::MockClock, Expiry, MemoryStore, SessionManagerLayer};
+    use tower_sessions_actix::{ActixSession, SessionMiddleware};
+
+    use crate::common::{body_string, build_app, get_session_cookie};
+
+    // The initialized service's type can't be named, so the app is built by a
+    // macro.
+    macro_rules! actix_app {
+        ($session_store:expr) => {
+            actix_app!(middleware: SessionMiddleware::new($session_store)
+                .with_expiry(Expiry::OnInactivity(Duration::hours(1))))
+        };
+        (middleware: $session_middleware:expr) => {
+            test::init_service(
+                App::new()
+                    .wrap($session_middleware)
+                    .route(
+                        "/insert",
+                        web::get().to(|session: ActixSession| async move {
+                            session.insert("foo", 42).unwrap();
+                            ""
+                        }),
+                    )
+                    .route(
+                        "/get",
+                        web::get().to(|session: ActixSession| async move {
+                            format!("{:?}", session.get::<usize>("foo").unwrap())
+                        }),
+                    )
+                    .route(
+                        "/cycle_id",
+                        web::get().to(|session: ActixSession| async move {
+                            session.cycle_id();
+                            ""
+                        }),
+                    )
+                    .route(
+                        "/delete",
+                        web::get().to(|session: ActixSession| async move {
+                            session.delete();
+                            ""
+                        }),
+                    ),
+            )
+            .await
+        };
+    }
+
+    fn session_cookie<B>(res: &ServiceResponse<B>) -> Option<Cookie<'static>> {
+        res.response()
+            .cookies()
+            .find(|cookie| cookie.name() == "id")
+            .map(Cookie::into_owned)
+    }
+
+    #[actix_web::test]
+    async fn actix_session_round_trip() {
+        let app = actix_app!(MemoryStore::default());
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/insert").to_request()).await;
+        let first_cookie = session_cookie(&res).unwrap();
+        assert_eq!(first_cookie.http_only(), Some(true));
+        assert!(first_cookie
+            .max_age()
+            .is_some_and(|max_age| max_age <= Duration::hours(1)));
+
+        let req = test::TestRequest::get()
+            .uri("/cycle_id")
+            .cookie(first_cookie.clone())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let second_cookie = session_cookie(&res).unwrap();
+        assert_ne!(first_cookie.value(), second_cookie.value());
+
+        let req = test::TestRequest::get()
+            .uri("/get")
+            .cookie(second_cookie.clone())
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "Some(42)");
+
+        let req = test::TestRequest::get()
+            .uri("/get")
+            .cookie(first_cookie)
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "None");
+
+        let req = test::TestRequest::get()
+            .uri("/delete")
+            .cookie(second_cookie.clone())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(session_cookie(&res).unwrap().value(), "");
+
+        let req = test::TestRequest::get()
+            .uri("/get")
+            .cookie(second_cookie)
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "None");
+    }
+
+    #[actix_web::test]
+    async fn inactivity_expiry_is_extended_on_clock() {
+        let clock = MockClock::default();
+        let app = actix_app!(middleware: SessionMiddleware::new(MemoryStore::default())
+            .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+            .with_clock(clock.clone()));
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/insert").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        let get = || {
+            test::TestRequest::get()
+                .uri("/get")
+                .cookie(cookie.clone())
+                .to_request()
+        };
+
+        // Read-only requests extend the session, and send its cookie again.
+        clock.advance(Duration::minutes(59));
+        let res = test::call_service(&app, get()).await;
+        assert!(session_cookie(&res).is_some());
+        clock.advance(Duration::minutes(59));
+        assert_eq!(test::call_and_read_body(&app, get()).await, "Some(42)");
+
+        clock.advance(Duration::minutes(61));
+        assert_eq!(test::call_and_read_body(&app, get()).await, "None");
+    }
+
+    #[actix_web::test]
+    async fn extension_within_refresh_threshold_only() {
+        let clock = MockClock::default();
+        let app = actix_app!(middleware: SessionMiddleware::new(MemoryStore::default())
+            .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+            .with_refresh_threshold(Duration::minutes(15))
+            .with_clock(clock.clone()));
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/insert").to_request()).await;
+        let cookie = session_cookie(&res).unwrap();
+        let get = || {
+            test::TestRequest::get()
+                .uri("/get")
+                .cookie(cookie.clone())
+                .to_request()
+        };
+
+        clock.advance(Duration::minutes(30));
+        let res = test::call_service(&app, get()).await;
+        assert!(session_cookie(&res).is_none());
+
+        clock.advance(Duration::minutes(20));
+        let res = test::call_service(&app, get()).await;
+        assert!(session_cookie(&res).is_some());
+    }
+
+    #[actix_web::test]
+    async fn sessions_are_shared_with_tower() {
+        let session_store = MemoryStore::default();
+        let actix_app = actix_app!(session_store.clone());
+        let axum_app = build_app(
+            SessionManagerLayer::new(session_store),
+            Some(Duration::hours(1)),
+        );
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = axum_app.oneshot(req).await.unwrap();
+        let axum_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+
+        let req = test::TestRequest::get()
+            .uri("/get")
+            .cookie(Cookie::new("id", axum_cookie.value().to_owned()))
+            .to_request();
+        assert_eq!(test::call_and_read_body(&actix_app, req).await, "Some(42)");
+    }
+
+    #[actix_web::test]
+    async fn sessions_are_shared_with_axum() {
+        let session_store = MemoryStore::default();
+        let actix_app = actix_app!(session_store.clone());
+        let axum_app = build_app(
+            SessionManagerLayer::new(session_store),
+            Some(Duration::hours(1)),
+        );
+
+        let res = test::call_service(
+            &actix_app,
+            test::TestRequest::get().uri("/insert").to_request(),
+        )
+        .await;
+        let actix_cookie = session_cookie(&res).unwrap();
+
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, format!("id={}", actix_cookie.value()))
+            .body(Body::empty())
+            .unwrap();
+        let res = axum_app.oneshot(req).await.unwrap();
+        assert_eq!(body_string(res.into_body()).await, "42");
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod tonic_tests {
+    use std::convert::Infallible;
+
+    use axum::body::Body;
+    use http::{header, Request, Response, StatusCode};
+    use time::Duration;
+    use tonic::service::Interceptor;
+    use tower::{service_fn, Layer, ServiceExt};
+    use tower_sessions::{
+        clock::MockClock, one_time::OneTimeTokens, session::Id, Expiry, MemoryStore, Session,
+        SessionManagerLayer, SessionStore,
+    };
+    use tower_sessions_tonic::{GrpcSessionLayer, RequestSessionExt, SessionInterceptor};
+
+    use crate::common::{body_string, build_app, get_session_cookie};
+
+    async fn handler(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let path = req.uri().path().to_owned();
+        let session = match tonic::Request::from_http(req).session() {
+            Ok(session) => session,
+            Err(status) => return Ok(status.into_http()),
+        };
+        let body = match path.as_str() {
+            "/insert" => {
+                session.insert("foo", 42).unwrap();
+                String::new()
+            }
+            "/get" => format!("{:?}", session.get::<usize>("foo").unwrap()),
+            "/cycle_id" => {
+                session.cycle_id();
+                String::new()
+            }
+            "/delete" => {
+                session.delete();
+                String::new()
+            }
+            _ => unreachable!(),
+        };
+        Ok(Response::new(Body::from(body)))
+    }
+
+    async fn call(
+        session_layer: &GrpcSessionLayer<MemoryStore>,
+        uri: &str,
+        token: Option<&str>,
+    ) -> (Option<String>, String) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(token) = token {
+            req = req.header("x-session-id", token);
+        }
+        let res = session_layer
+            .layer(service_fn(handler))
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let token = res
+            .headers()
+            .get("x-session-id")
+            .map(|token| token.to_str().unwrap().to_owned());
+        (token, body_string(res.into_body()).await)
+    }
+
+    fn grpc_session_layer(session_store: MemoryStore) -> GrpcSessionLayer<MemoryStore> {
+        GrpcSessionLayer::new(session_store).with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+    }
+
+    #[tokio::test]
+    async fn grpc_session_round_trip() {
+        let session_layer = grpc_session_layer(MemoryStore::default());
+
+        let (first_token, _) = call(&session_layer, "/insert", None).await;
+        let first_token = first_token.unwrap();
+
+        let (_, body) = call(&session_layer, "/get", Some(&first_token)).await;
+        assert_eq!(body, "Some(42)");
+
+        let (second_token, _) = call(&session_layer, "/cycle_id", Some(&first_token)).await;
+        let second_token = second_token.unwrap();
+        assert_ne!(first_token, second_token);
+
+        let (_, body) = call(&session_layer, "/get", Some(&second_token)).await;
+        assert_eq!(body, "Some(42)");
+        let (_, body) = call(&session_layer, "/get", Some(&first_token)).await;
+        assert_eq!(body, "None");
+
+        let (token, _) = call(&session_layer, "/delete", Some(&second_token)).await;
+        assert_eq!(token.as_deref(), Some(""));
+        let (_, body) = call(&session_layer, "/get", Some(&second_token)).await;
+        assert_eq!(body, "None");
+    }
+
+    #[tokio::test]
+    async fn inactivity_expiry_is_extended_on_clock() {
+        let clock = MockClock::default();
+        let session_layer = grpc_session_layer(MemoryStore::default()).with_clock(clock.clone());
+
+        let (token, _) = call(&session_layer, "/insert", None).await;
+        let token = token.unwrap();
+
+        // Read-only calls extend the session, and send its ID again.
+        clock.advance(Duration::minutes(59));
+        let (sent_token, _) = call(&session_layer, "/get", Some(&token)).await;
+        assert_eq!(sent_token.as_deref(), Some(token.as_str()));
+        clock.advance(Duration::minutes(59));
+        let (_, body) = call(&session_layer, "/get", Some(&token)).await;
+        assert_eq!(body, "Some(42)");
+
+        clock.advance(Duration::minutes(61));
+        let (_, body) = call(&session_layer, "/get", Some(&token)).await;
+        assert_eq!(body, "None");
+    }
+
+    #[tokio::test]
+    async fn extension_within_refresh_threshold_only() {
+        let clock = MockClock::default();
+        let session_layer = grpc_session_layer(MemoryStore::default())
+            .with_refresh_threshold(Duration::minutes(15))
+            .with_clock(clock.clone());
+
+        let (token, _) = call(&session_layer, "/insert", None).await;
+        let token = token.unwrap();
+
+        clock.advance(Duration::minutes(30));
+        let (sent_token, _) = call(&session_layer, "/get", Some(&token)).await;
+        assert!(sent_token.is_none());
+
+        clock.advance(Duration::minutes(20));
+        let (sent_token, _) = call(&session_layer, "/get", Some(&token)).await;
+        assert_eq!(sent_token.as_deref(), Some(token.as_str()));
+    }
+
+    #[tokio::test]
+    async fn malformed_token_is_replaced() {
+        let session_layer = grpc_session_layer(MemoryStore::default());
+
+        // Clients are told to drop the token.
+        let (token, body) = call(&session_layer, "/get", Some("not-a-session-id")).await;
+        assert_eq!(body, "None");
+        assert_eq!(token.as_deref(), Some(""));
+
+        let (token, _) = call(&session_layer, "/insert", Some("not-a-session-id")).await;
+        assert_ne!(token.as_deref(), Some("not-a-session-id"));
+    }
+
+    #[tokio::test]
+    async fn internal_records_are_not_loaded() {
+        let session_store = MemoryStore::default();
+        let session_layer = grpc_session_layer(session_store.clone());
+        let tokens = OneTimeTokens::new(session_store);
+        let token = tokens.issue("verify-email", &42).await.unwrap();
+        let (record_id, _) = token.split_once('.').unwrap();
+
+        let (_, body) = call(&session_layer, "/get", Some(record_id)).await;
+        assert_eq!(body, "None");
+    }
+
+    #[tokio::test]
+    async fn missing_layer_is_an_internal_status() {
+        let res = service_fn(handler)
+            .oneshot(Request::builder().uri("/get").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = tonic::Status::from_header_map(res.headers()).unwrap();
+        assert_eq!(status.code(), tonic::Code::Internal);
+    }
+
+    #[tokio::test]
+    async fn sessions_are_shared_with_axum() {
+        let session_store = MemoryStore::default();
+        let session_layer = grpc_session_layer(session_store.clone());
+        let axum_app = build_app(
+            SessionManagerLayer::new(session_store),
+            Some(Duration::hours(1)),
+        );
+
+        let req = Request::builder()
+            .uri("/insert")
+            .body(Body::empty())
+            .unwrap();
+        let res = axum_app.clone().oneshot(req).await.unwrap();
+        let axum_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+        let (_, body) = call(&session_layer, "/get", Some(axum_cookie.value())).await;
+        assert_eq!(body, "Some(42)");
+
+        let (token, _) = call(&session_layer, "/cycle_id", Some(axum_cookie.value())).await;
+        let req = Request::builder()
+            .uri("/get")
+            .header(header::COOKIE, format!("id={}", token.unwrap()))
+            .body(Body::empty())
+            .unwrap();
+        let res = axum_app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body_string(res.into_body()).await, "42");
+    }
+
+    #[tokio::test]
+    async fn interceptor_propagates_session() {
+        let session_store = MemoryStore::default();
+        let session_layer = grpc_session_layer(session_store.clone());
+        let (token, _) = call(&session_layer, "/insert", None).await;
+        let session_id = Id::try_from(token.unwrap().as_str()).unwrap();
+        let session = session_store.load(&session_id).await.unwrap().unwrap();
+
+        let mut interceptor = SessionInterceptor::new(session);
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        let token = request.metadata().get("x-session-id").unwrap();
+
+        let (_, body) = call(&session_layer, "/get", Some(token.to_str().unwrap())).await;
+        assert_eq!(body, "Some(42)");
+    }
+
+    #[test]
+    fn interceptor_uses_metadata_key() {
+        let session = Session::new(None);
+        let mut interceptor = SessionInterceptor::new(session.clone()).with_metadata_key("my-sid");
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+        assert_eq!(
+            request.metadata().get("my-sid").unwrap(),
+            session.id().to_string().as_str()
+        );
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "private"))]
+mod stateless_tests {
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, BoxError, Router};
+    use http::{header, Request, StatusCode};
+    use time::{Duration, OffsetDateTime};
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_cookies::{cookie::Key, Cookie};
+    use tower_sessions::{
+        clock::MockClock, stateless::StatelessSessionManagerLayer, Expiry, Session,
+    };
+
+    use crate::common::{body_string, get_session_cookie};
+
+    fn stateless_app(session_manager: StatelessSessionManagerLayer) -> Router {
+        let session_service = ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|_: BoxError| async {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }))
+            .layer(session_manager.with_expiry(Expiry::OnInactivity(Duration::hours(1))));
+
+        Router::new()
+            .route(
+                "/insert",
+                get(|session: Session| async move {
+                    session.insert("foo", 42).unwrap();
+                }),
+            )
+            .route(
+                "/insert_large",
+                get(|session: Session| async move {
+                    session.insert("foo", "x".repeat(8192)).unwrap();
+                }),
+            )
+            .route(
+                "/get_value",
+                get(|session: Session| async move { format!("{:?}", session.get_value("foo")) }),
+            )
+            .route(
+                "/cycle_id",
+                get(|session: Session| async move {
+                    session.cycle_id();
+                }),
+            )
+            .route(
+                "/delete",
+                get(|session: Session| async move {
+                    session.delete();
+                }),
+            )
+            .layer(session_service)
+    }
+
+    async fn request(app: &Router, uri: &str, cookie: Option<&Cookie<'_>>) -> http::Response<Body> {
+        let mut req = Request::builder().uri(uri);
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie.encoded().to_string());
+        }
+        app.clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// Returns the cookies set by the response, ordered by name so that chunks
+    /// are in order.
+    fn set_cookies(res: &http::Response<Body>) -> Vec<Cookie<'static>> {
+        let mut cookies: Vec<_> = res
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|header| Cookie::parse(header.to_str().unwrap().to_owned()).unwrap())
+            .collect();
+        cookies.sort_by(|a, b| a.name().cmp(b.name()));
+        cookies
+    }
+
+    async fn request_with_cookies(
+        app: &Router,
+        uri: &str,
+        cookies: &[Cookie<'_>],
+    ) -> http::Response<Body> {
+        let cookie_header = cookies
+            .iter()
+            .map(|cookie| cookie.stripped().encoded().to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::COOKIE, cookie_header)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn session_is_kept_in_cookie() {
+        let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+
+        let res = request(&app, "/insert", None).await;
+        let session_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+        assert_eq!(session_cookie.name(), "id");
+        assert_eq!(session_cookie.http_only(), Some(true));
+        assert!(session_cookie
+            .max_age()
+            .is_some_and(|max_age| max_age <= Duration::hours(1)));
+        assert!(!session_cookie.value().contains("foo"));
+
+        let res = request(&app, "/get_value", Some(&session_cookie)).await;
+        assert_eq!(body_string(res.into_body()).await, "Some(Number(42))");
+    }
+
+    #[tokio::test]
+    async fn unchanged_session_sets_no_cookie() {
+        let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+
+        let res = request(&app, "/insert", None).await;
+        let session_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+
+        let res = request(&app, "/get_value", Some(&session_cookie)).await;
+        assert!(res.headers().get(header::SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn foreign_cookie_is_replaced() {
+        let session_cookie = {
+            let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+            let res = request(&app, "/insert", None).await;
+            get_session_cookie(res.headers()).unwrap().into_owned()
+        };
+
+        let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+        let res = request(&app, "/get_value", Some(&session_cookie)).await;
+        let removal_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+        assert_eq!(removal_cookie.max_age(), Some(Duration::ZERO));
+        assert_eq!(body_string(res.into_body()).await, "None");
+
+        let tampered_cookie = Cookie::new("id", "tampered");
+        let res = request(&app, "/get_value", Some(&tampered_cookie)).await;
+        assert_eq!(body_string(res.into_body()).await, "None");
+    }
+
+    #[tokio::test]
+    async fn expired_cookie_is_rejected() {
+        let clock = MockClock::new(OffsetDateTime::now_utc());
+        let app = stateless_app(
+            StatelessSessionManagerLayer::new(Key::generate()).with_clock(clock.clone()),
+        );
+
+        let res = request(&app, "/insert", None).await;
+        let session_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+
+        // The browser would have dropped the cookie, but a copy of it is replayed.
+        clock.advance(Duration::hours(2));
+        let res = request(&app, "/get_value", Some(&session_cookie)).await;
+        assert_eq!(body_string(res.into_body()).await, "None");
+    }
+
+    #[tokio::test]
+    async fn cycle_session_id() {
+        let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+
+        let res = request(&app, "/insert", None).await;
+        let first_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+
+        let res = request(&app, "/cycle_id", Some(&first_cookie)).await;
+        let second_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+        assert_ne!(first_cookie.value(), second_cookie.value());
+
+        let res = request(&app, "/get_value", Some(&second_cookie)).await;
+        assert_eq!(body_string(res.into_body()).await, "Some(Number(42))");
+    }
+
+    #[tokio::test]
+    async fn delete_session() {
+        let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+
+        let res = request(&app, "/insert", None).await;
+        let session_cookie = get_session_cookie(res.headers()).unwrap().into_owned();
+
+        let res = request(&app, "/delete", Some(&session_cookie)).await;
+        let removal_cookie = get_session_cookie(res.headers()).unwrap();
+        assert_eq!(removal_cookie.value(), "");
+        assert_eq!(removal_cookie.max_age(), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn oversized_session_fails() {
+        let app = stateless_app(StatelessSessionManagerLayer::new(Key::generate()));
+
+        let res = request(&app, "/insert_large", None).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(res.headers().get(header::SET_COOKIE).is_none());
+
+        let app = stateless_app(
+            StatelessSessionManagerLayer::new(Key::generate()).with_max_size(16 * 1024),
+        );
+        let res = request(&app, "/insert_large", None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(get_session_cookie(res.headers()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn oversized_session_is_chunked() {
+        let app =
+            stateless_app(StatelessSessionManagerLayer::new(Key::generate()).with_max_chunks(4));
+
+        let res = request(&app, "/insert_large", None).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let chunks = set_cookies(&res);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].name(), "id");
+        assert_eq!(chunks[1].name(), "id.1");
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.name().len() + chunk.value().len() <= 4096));
+
+        let res = request_with_cookies(&app, "/get_value", &chunks).await;
+        let body = body_string(res.into_body()).await;
+        assert!(body.starts_with("Some(String(\"xxx"));
+    }
+
+    #[tokio::test]
+    async fn incomplete_chunks_fail_verification() {
+        let app =
+            stateless_app(StatelessSessionManagerLayer::new(Key::generate()).with_max_chunks(4));
+
+        let res = request(&app, "/insert_large", None).await;
+        let mut chunks = set_cookies(&res);
+        chunks.pop();
+
+        let res = request_with_cookies(&app, "/get_value", &chunks).await;
+        let removal_cookies = set_cookies(&res);
+        assert_eq!(removal_cookies.len(), chunks.len());
+        assert!(removal_cookies
+            .iter()
+            .all(|cookie| cookie.max_age() == Some(Duration::ZERO)));
+        assert_eq!(body_string(res.into_body()).await, "None");
+    }
+
+    #[tokio::test]
+    async fn shrunk_session_removes_stale_chunks() {
+        let app =
+            stateless_app(StatelessSessionManagerLayer::new(Key::generate()).with_max_chunks(4));
+
+        let res = request(&app, "/insert_large", None).await;
+        let chunks = set_cookies(&res);
+
+        let res = request_with_cookies(&app, "/insert", &chunks).await;
+        let cookies = set_cookies(&res);
+        assert_eq!(cookies.len(), chunks.len());
+        assert_eq!(cookies[0].name(), "id");
+        assert!(cookies[0]
+            .max_age()
+            .is_some_and(|max_age| max_age > Duration::ZERO));
+        assert!(cookies[1..]
+            .iter()
+            .all(|cookie| cookie.max_age() == Some(Duration::ZERO)));
+
+        let res = request_with_cookies(&app, "/get_value", &cookies[..1]).await;
+        assert_eq!(body_string(res.into_body()).await, "Some(Number(42))");
+    }
+
+    #[tokio::test]
+    async fn session_exceeding_chunks_fails() {
+        let app =
+            stateless_app(StatelessSessionManagerLayer::new(Key::generate()).with_max_chunks(2));
+
+        let res = request(&app, "/insert_large", None).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(res.headers().get(header::SET_COOKIE).is_none());
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod csrf_tests {
+    use axum::{
+        body::Body,
+        error_handling::HandleErrorLayer,
+        routing::{get, post},
+        BoxError, Router,
+    };
+    use http::{header, Method, Request, StatusCode};
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_cookies::Cookie;
+    use tower_sessions::{
+        csrf::{CsrfLayer, CsrfToken},
+        MemoryStore, Session, SessionManagerLayer,
+    };
+
+    use crate::common::body_string;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/token",
+                get(|csrf_token: CsrfToken| async move { csrf_token.token() }),
+            )
+            .route("/submit", post(|| async { "Submitted!" }))
+            .route(
+                "/logout",
+                post(|session: Session| async move {
+                    session.delete();
+                }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(SessionManagerLayer::new(MemoryStore::default()))
+                    .layer(CsrfLayer::new()),
+            )
+    }
+
+    fn set_cookie<'c>(res: &'c http::Response<Body>, name: &str) -> Option<Cookie<'c>> {
+        res.headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .flat_map(|header| header.to_str())
+            .flat_map(Cookie::parse)
+            .find(|cookie| cookie.name() == name)
+    }
+
+    /// Requests a token, returning it along with the request's cookies.
+    async fn token(app: &Router) -> (String, String) {
+        let req = Request::builder()
+            .uri("/token")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let session_cookie = set_cookie(&res, "id").unwrap().stripped().to_string();
+        let csrf_cookie = set_cookie(&res, "csrf-token")
+            .unwrap()
+            .stripped()
+            .to_string();
+        let token = body_string(res.into_body()).await;
+        (token, format!("{session_cookie}; {csrf_cookie}"))
+    }
+
+    async fn submit(app: &Router, cookies: &str, token: Option<&str>) -> StatusCode {
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri("/submit")
+            .header(header::COOKIE, cookies);
+        if let Some(token) = token {
+            req = req.header("x-csrf-token", token);
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        res.status()
+    }
+
+    #[tokio::test]
+    async fn token_is_mirrored_in_cookie() {
+        let req = Request::builder()
+            .uri("/token")
+            .body(Body::empty())
+            .unwrap();
+        let res = app().oneshot(req).await.unwrap();
+
+        assert!(set_cookie(&res, "id").is_some());
+        let csrf_cookie = set_cookie(&res, "csrf-token").unwrap().into_owned();
+        assert_eq!(csrf_cookie.http_only(), None);
+        assert_eq!(body_string(res.into_body()).await, csrf_cookie.value());
+    }
+
+    #[tokio::test]
+    async fn token_is_stable() {
+        let app = app();
+        let (token, cookies) = token(&app).await;
+
+        let req = Request::builder()
+            .uri("/token")
+            .header(header::COOKIE, &cookies)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert!(res.headers().get(header::SET_COOKIE).is_none());
+        assert_eq!(body_string(res.into_body()).await, token);
+    }
+
+    #[tokio::test]
+    async fn unsafe_request_with_token() {
+        let app = app();
+        let (token, cookies) = token(&app).await;
+
+        assert_eq!(submit(&app, &cookies, Some(&token)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unsafe_request_without_token() {
+        let app = app();
+        let (_, cookies) = token(&app).await;
+
+        assert_eq!(submit(&app, &cookies, None).await, StatusCode::FORBIDDEN);
+        assert_eq!(
+            submit(&app, &cookies, Some("forged")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn token_is_bound_to_session() {
+        let app = app();
+        let (token, _) = token(&app).await;
+        let (_, other_cookies) = self::token(&app).await;
+
+        assert_eq!(
+            submit(&app, &other_cookies, Some(&token)).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn unsafe_request_without_session() {
+        assert_eq!(
+            submit(&app(), "", Some("token")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn logout_removes_cookie() {
+        let app = app();
+        let (token, cookies) = token(&app).await;
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/logout")
+            .header(header::COOKIE, &cookies)
+            .header("x-csrf-token", &token)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let csrf_cookie = set_cookie(&res, "csrf-token").unwrap();
+        assert_eq!(csrf_cookie.value(), "");
+        assert_eq!(csrf_cookie.max_age(), Some(time::Duration::ZERO));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod scope_tests {
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, BoxError, Router};
+    use http::{header, Request, StatusCode};
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_sessions::{scope::Scope, MemoryStore, Session, SessionManagerLayer};
+
+    use crate::common::{body_string, get_session_cookie};
+
+    fn subdomain_app(session_store: MemoryStore, scope: &str) -> Router {
+        let session_manager = SessionManagerLayer::new(session_store)
+            .with_domain("example.com".to_string())
+            .with_scope(scope);
+
+        Router::new()
+            .route(
+                "/login",
+                get(|session: Session| async move {
+                    session.set_principal(Some("alice"));
+                }),
+            )
+            .route(
+                "/principal",
+                get(|session: Session| async move { format!("{:?}", session.principal()) }),
+            )
+            .route(
+                "/scope/insert",
+                get(|scope: Scope| async move {
+                    scope.insert("foo", scope.name()).unwrap();
+                }),
+            )
+            .route(
+                "/scope/get",
+                get(|scope: Scope| async move { format!("{:?}", scope.get::<String>("foo")) }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(session_manager),
+            )
+    }
+
+    async fn request(app: &Router, uri: &str, cookie: &str) -> http::Response<Body> {
+        let req = Request::builder()
+            .uri(uri)
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn login_is_shared_across_subdomains() {
+        let session_store = MemoryStore::default();
+        let app = subdomain_app(session_store.clone(), "app");
+        let admin = subdomain_app(session_store, "admin");
+
+        let res = request(&app, "/login", "").await;
+        let session_cookie = get_session_cookie(res.headers()).unwrap();
+        assert_eq!(session_cookie.domain(), Some("example.com"));
+        let cookie = session_cookie.stripped().to_string();
+
+        let res = request(&admin, "/principal", &cookie).await;
+        assert_eq!(body_string(res.into_body()).await, r#"Some("alice")"#);
+    }
+
+    #[tokio::test]
+    async fn scopes_are_partitioned() {
+        let session_store = MemoryStore::default();
+        let app = subdomain_app(session_store.clone(), "app");
+        let admin = subdomain_app(session_store, "admin");
+
+        let res = request(&app, "/login", "").await;
+        let cookie = get_session_cookie(res.headers())
+            .unwrap()
+            .stripped()
+            .to_string();
+
+        request(&admin, "/scope/insert", &cookie).await;
+
+        let res = request(&admin, "/scope/get", &cookie).await;
+        assert_eq!(body_string(res.into_body()).await, r#"Ok(Some("admin"))"#);
+        let res = request(&app, "/scope/get", &cookie).await;
+        assert_eq!(body_string(res.into_body()).await, "Ok(None)");
+    }
+
+    #[tokio::test]
+    async fn scope_requires_configuration() {
+        let session_manager = SessionManagerLayer::new(MemoryStore::default());
+        let app = Router::new()
+            .route("/scope", get(|_: Scope| async {}))
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(session_manager),
+            );
+
+        let res = request(&app, "/scope", "").await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
+
+#[cfg(all(test, feature = "maintenance", feature = "memory-store"))]
+mod maintenance_tests {
+    use std::{
+        convert::Infallible,
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use tower_sessions::{
+        maintenance::Maintenance, session::Id, MemoryStore, Session, SessionStore,
+    };
+
+    #[derive(Debug, Clone, Default)]
+    struct FlushCountingStore {
+        inner: MemoryStore,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl SessionStore for FlushCountingStore {
+        type Error = Infallible;
+
+        async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            self.inner.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            self.inner.load(session_id).await
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            self.inner.delete(session_id).await
+        }
+
+        async fn flush(&self) -> Result<(), Self::Error> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn counting(runs: &Arc<AtomicUsize>) -> impl Fn() -> Arc<AtomicUsize> {
+        let runs = runs.clone();
+        move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            runs.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_tasks_periodically() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let count = counting(&runs);
+        let maintenance = Maintenance::new()
+            .with_task("count", Duration::from_millis(10), move || {
+                count();
+                async { Ok::<_, io::Error>(()) }
+            })
+            .spawn();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        maintenance.shutdown().await;
+
+        let ran = runs.load(Ordering::SeqCst);
+        assert!(ran >= 3, "ran {ran} times");
+
+        // Nothing runs once shut down.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), ran);
+    }
+
+    fn explode() -> io::Result<()> {
+        panic!("maintenance task panicking")
+    }
+
+    #[tokio::test]
+    async fn failures_and_panics_are_isolated() {
+        let failures = Arc::new(AtomicUsize::new(0));
+        let fail = counting(&failures);
+        let panics = Arc::new(AtomicUsize::new(0));
+        let panic = counting(&panics);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let run = counting(&runs);
+
+        let maintenance = Maintenance::new()
+            .with_task("fail", Duration::from_millis(10), move || {
+                fail();
+                async { Err(io::Error::other("unavailable")) }
+            })
+            .with_task("panic", Duration::from_millis(10), move || {
+                panic();
+                async { explode() }
+            })
+            .with_task("run", Duration::from_millis(10), move || {
+                run();
+                async { Ok::<_, io::Error>(()) }
+            })
+            .spawn();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        maintenance.shutdown().await;
+
+        assert!(failures.load(Ordering::SeqCst) >= 2);
+        assert!(panics.load(Ordering::SeqCst) >= 2);
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_runs_in_progress() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let maintenance = {
+            let started = started.clone();
+            let finished = finished.clone();
+            Maintenance::new()
+                .with_jitter(0.0)
+                .with_task("slow", Duration::from_millis(10), move || {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    let finished = finished.clone();
+                    async move {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        finished.fetch_add(1, Ordering::SeqCst);
+                        Ok::<_, io::Error>(())
+                    }
+                })
+                .spawn()
+        };
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        maintenance.shutdown().await;
+
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn shutdown_flushes_once_more() {
+        let session_store = FlushCountingStore::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let count = counting(&runs);
+        let maintenance = Maintenance::new()
+            .with_flush(session_store.clone(), Duration::from_secs(3600))
+            .with_task("count", Duration::from_secs(3600), move || {
+                count();
+                async { Ok::<_, io::Error>(()) }
+            })
+            .spawn();
+
+        // Only flush tasks run on shutdown.
+        maintenance.shutdown().await;
+        assert_eq!(session_store.flushes.load(Ordering::SeqCst), 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn runs_until_shutdown_signal() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let count = counting(&runs);
+        let maintenance =
+            Maintenance::new().with_task("count", Duration::from_millis(10), move || {
+                count();
+                async { Ok::<_, io::Error>(()) }
+            });
+
+        maintenance
+            .run_until(tokio::time::sleep(Duration::from_millis(100)))
+            .await;
+
+        assert!(runs.load(Ordering::SeqCst) >= 3);
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "blocking"))]
+mod blocking_tests {
+    use std::{collections::HashMap, convert::Infallible, sync::Mutex, thread};
+
+    use axum::Router;
+    use tower_sessions::{
+        blocking::{
+            BlockingExpiredDeletion, BlockingSessionStore, BlockingStoreAdapter, BlockingStoreError,
+        },
+        clock::{Clock, MockClock, SharedClock},
+        session::Id,
+        ExpiredDeletion, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    /// A store keeping serialized sessions, which blocks on each operation.
+    #[derive(Debug, Default)]
+    struct SyncStore {
+        sessions: Mutex<HashMap<Id, String>>,
+        clock: SharedClock,
+        latency: std::time::Duration,
+    }
+
+    impl SyncStore {
+        fn with_clock(clock: impl Clock) -> Self {
+            Self {
+                clock: SharedClock::new(clock),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl BlockingSessionStore for SyncStore {
+        type Error = Infallible;
+
+        fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            thread::sleep(self.latency);
+            let record = serde_json::to_string(session).unwrap();
+            self.sessions.lock().unwrap().insert(*session.id(), record);
+            Ok(())
+        }
+
+        fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            thread::sleep(self.latency);
+            Ok(self
+                .sessions
+                .lock()
+                .unwrap()
+                .get(session_id)
+                .map(|record| serde_json::from_str::<Session>(record).unwrap())
+                .filter(|session| session.expiry_date() > self.clock.now()))
+        }
+
+        fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            thread::sleep(self.latency);
+            self.sessions.lock().unwrap().remove(session_id);
+            Ok(())
+        }
+    }
+
+    impl BlockingExpiredDeletion for SyncStore {
+        fn delete_expired(&self) -> Result<(), Self::Error> {
+            let now = self.clock.now();
+            self.sessions.lock().unwrap().retain(|_, record| {
+                serde_json::from_str::<Session>(record)
+                    .unwrap()
+                    .expiry_date()
+                    > now
+            });
+            Ok(())
+        }
+    }
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store = BlockingStoreAdapter::new(SyncStore::default());
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    mod properties {
+        use tower_sessions::{blocking::BlockingStoreAdapter, clock::MockClock};
+
+        use super::SyncStore;
+
+        async fn store(clock: MockClock) -> BlockingStoreAdapter<SyncStore> {
+            BlockingStoreAdapter::new(SyncStore::with_clock(clock))
+        }
+
+        store_property_tests!(store);
+    }
+
+    #[tokio::test]
+    async fn operations_do_not_block_the_runtime() {
+        let session_store = BlockingStoreAdapter::new(SyncStore {
+            latency: std::time::Duration::from_millis(100),
+            ..Default::default()
+        });
+        let session = Session::default();
+
+        // On a single-threaded runtime, the timer only fires first if the save isn't
+        // blocking the thread.
+        let save = session_store.save(&session);
+        let timer = tokio::time::sleep(std::time::Duration::from_millis(10));
+        tokio::pin!(save);
+        tokio::select! {
+            _ = &mut save => panic!("save completed before the timer"),
+            _ = timer => {}
+        }
+        save.await.unwrap();
+
+        assert!(session_store.load(session.id()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn limits_concurrency() {
+        let session_store = BlockingStoreAdapter::new(SyncStore {
+            latency: std::time::Duration::from_millis(50),
+            ..Default::default()
+        })
+        .with_max_concurrency(1);
+
+        let sessions = [Session::default(), Session::default()];
+        let start = std::time::Instant::now();
+        let (first, second) = tokio::join!(
+            session_store.save(&sessions[0]),
+            session_store.save(&sessions[1])
+        );
+        first.unwrap();
+        second.unwrap();
+
+        assert!(start.elapsed() >= std::time::Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn surfaces_panics() {
+        #[derive(Debug)]
+        struct PanickingStore;
+
+        impl BlockingSessionStore for PanickingStore {
+            type Error = Infallible;
+
+            fn save(&self, _: &Session) -> Result<(), Self::Error> {
+                panic!("store panicking")
+            }
+
+            fn load(&self, _: &Id) -> Result<Option<Session>, Self::Error> {
+                Ok(None)
+            }
+
+            fn delete(&self, _: &Id) -> Result<(), Self::Error> {
+                Ok(())
+            }
+        }
+
+        let session_store = BlockingStoreAdapter::new(PanickingStore);
+        let err = session_store.save(&Session::default()).await.unwrap_err();
+        assert!(matches!(err, BlockingStoreError::Join(err) if err.is_panic()));
+    }
+
+    #[tokio::test]
+    async fn deletes_expired() {
+        let clock = MockClock::default();
+        let session_store = BlockingStoreAdapter::new(SyncStore::with_clock(clock.clone()));
+        let session = Session::new(Some(tower_sessions::Expiry::OnInactivity(
+            Duration::minutes(1),
+        )));
+        session_store.save(&session).await.unwrap();
+
+        clock.advance(Duration::minutes(2));
+        session_store.delete_expired().await.unwrap();
+
+        assert!(session_store.store().sessions.lock().unwrap().is_empty());
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "timeout-store"
+))]
+mod timeout_store_tests {
+    use async_trait::async_trait;
+    use axum::Router;
+    use tower_sessions::{
+        session::Id,
+        timeout_store::{Operation, TimeoutStore, TimeoutStoreError},
+        MemoryStore, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store =
+            TimeoutStore::new(MemoryStore::default(), std::time::Duration::from_secs(1));
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    /// A store which takes its time to save sessions.
+    #[derive(Debug, Clone, Default)]
+    struct SlowStore(MemoryStore);
+
+    #[async_trait]
+    impl SessionStore for SlowStore {
+        type Error = std::convert::Infallible;
+
+        async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            self.0.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            self.0.load(session_id).await
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            self.0.delete(session_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_operations() {
+        let session_store =
+            TimeoutStore::new(SlowStore::default(), std::time::Duration::from_secs(1))
+                .with_save_timeout(std::time::Duration::from_millis(10));
+        let session = Session::default();
+
+        let err = session_store.save(&session).await.unwrap_err();
+        assert!(err.is_timeout());
+        assert!(matches!(
+            err,
+            TimeoutStoreError::Timeout {
+                operation: Operation::Save,
+                timeout,
+            } if timeout == std::time::Duration::from_millis(10)
+        ));
+
+        let err = session_store.create(&session).await.unwrap_err();
+        assert!(matches!(
+            err,
+            TimeoutStoreError::Timeout {
+                operation: Operation::Create,
+                ..
+            }
+        ));
+
+        // Other operations keep their own deadline.
+        assert!(session_store.load(session.id()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn passes_operations_within_deadline() {
+        let session_store =
+            TimeoutStore::new(SlowStore::default(), std::time::Duration::from_secs(1));
+        let session = Session::default();
+
+        session_store.save(&session).await.unwrap();
+        assert!(session_store.load(session.id()).await.unwrap().is_some());
+        session_store.delete(session.id()).await.unwrap();
+        assert!(session_store.load(session.id()).await.unwrap().is_none());
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "axum-core",
+    feature = "memory-store",
+    feature = "compressed-store"
+))]
+mod compressed_store_tests {
+    use axum::Router;
+    use tower_sessions::{
+        compressed_store::{CompressedStore, CompressedStoreError, Compression},
+        session::Id,
+        MemoryStore, Session, SessionManagerLayer, SessionStore,
+    };
+
+    use crate::common::build_app;
+
+    async fn app(max_age: Option<Duration>) -> Router {
+        let session_store = CompressedStore::new(MemoryStore::default()).with_threshold(0);
+        let session_manager = SessionManagerLayer::new(session_store).with_secure(true);
+        build_app(session_manager, max_age)
+    }
+
+    route_tests!(app);
+
+    fn large_session() -> Session {
+        let expiry_date = time::OffsetDateTime::now_utc() + Duration::hours(1);
+        let session = Session::new(Some(tower_sessions::Expiry::AtDateTime(expiry_date)));
+        session.insert("foo", "bar".repeat(1_000)).unwrap();
+        session
+    }
+
+    async fn is_compressed(inner: &MemoryStore, session_id: &Id) -> bool {
+        let record = inner.load(session_id).await.unwrap().unwrap();
+        record.get_value("__tower_sessions_compressed").is_some()
+    }
+
+    #[tokio::test]
+    async fn round_trips_compressions() {
+        for compression in [Compression::Zstd(3), Compression::Brotli(5)] {
+            let inner = MemoryStore::default();
+            let session_store = CompressedStore::new(inner.clone()).with_compression(compression);
+            let session = large_session();
+            session.set_principal(Some("alice"));
+            session_store.save(&session).await.unwrap();
+
+            assert!(is_compressed(&inner, session.id()).await);
+            let record = inner.load(session.id()).await.unwrap().unwrap();
+            assert_eq!(record.principal().as_deref(), Some("alice"));
+            assert_eq!(record.expiry_date(), session.expiry_date());
+
+            let loaded = session_store.load(session.id()).await.unwrap().unwrap();
+            assert_eq!(
+                loaded.get::<String>("foo").unwrap(),
+                session.get::<String>("foo").unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn passes_small_sessions_through() {
+        let inner = MemoryStore::default();
+        let session_store = CompressedStore::new(inner.clone());
+        let session = Session::default();
+        session.insert("foo", 42).unwrap();
+        session_store.save(&session).await.unwrap();
+
+        assert!(!is_compressed(&inner, session.id()).await);
+        let loaded = session_store.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(loaded.get::<usize>("foo").unwrap(), Some(42));
+    }
+
+    #[tokio::test]
+    async fn reads_records_of_other_compressions() {
+        let inner = MemoryStore::default();
+        let session = large_session();
+        CompressedStore::new(inner.clone())
+            .with_compression(Compression::Brotli(11))
+            .save(&session)
+            .await
+            .unwrap();
+
+        let session_store = CompressedStore::new(inner).with_compression(Compression::Zstd(1));
+        let loaded = session_store.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(
+            loaded.get::<String>("foo").unwrap(),
+            session.get::<String>("foo").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_formats() {
+        let inner = MemoryStore::default();
+        let record = Session::default();
+        // A header of format version 1 with an unknown compression.
+        record
+            .insert("__tower_sessions_compressed", "AX8=")
+            .unwrap();
+        inner.save(&record).await.unwrap();
+
+        let session_store = CompressedStore::new(inner);
+        let err = session_store.load(record.id()).await.unwrap_err();
+        assert!(matches!(err, CompressedStoreError::Format(_)));
+    }
+}
+
+#[cfg(all(test, feature = "axum-core", feature = "memory-store"))]
+mod one_time_tests {
+    use std::convert::Infallible;
+
+    use async_trait::async_trait;
+    use axum::{body::Body, error_handling::HandleErrorLayer, routing::get, Router};
+    use axum_core::BoxError;
+    use http::{header, Request, StatusCode};
+    use serde::{Deserialize, Serialize};
+    use time::Duration;
+    use tower::{ServiceBuilder, ServiceExt};
+    use tower_sessions::{
+        clock::MockClock,
+        one_time::{OneTimeError, OneTimeTokens},
+        session::Id,
+        MemoryStore, Session, SessionManagerLayer, SessionStore, TakeSession,
+    };
+
+    use crate::common::body_string;
+
+    /// A store yielding before each operation, so that concurrent consumptions
+    /// interleave, and copying the sessions it loads, as stores backed by a
+    /// database do.
+    #[derive(Debug, Clone, Default)]
+    struct YieldingStore(MemoryStore);
+
+    fn copy(session: Option<Session>) -> Option<Session> {
+        session
+            .map(|session| serde_json::from_value(serde_json::to_value(session).unwrap()).unwrap())
+    }
+
+    #[async_trait]
+    impl SessionStore for YieldingStore {
+        type Error = Infallible;
+
+        async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+            tokio::task::yield_now().await;
+            self.0.save(session).await
+        }
+
+        async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            tokio::task::yield_now().await;
+            self.0.load(session_id).await.map(copy)
+        }
+
+        async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+            tokio::task::yield_now().await;
+            self.0.delete(session_id).await
+        }
+    }
+
+    #[async_trait]
+    impl TakeSession for YieldingStore {
+        async fn take(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+            tokio::task::yield_now().await;
+            self.0.take(session_id).await.map(copy)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reset {
+        email: String,
+    }
+
+    fn reset() -> Reset {
+        Reset {
+            email: "alice@example.com".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn consumes_once() {
+        let tokens = OneTimeTokens::new(MemoryStore::default());
+        let token = tokens.issue("reset-password", &reset()).await.unwrap();
+
+        let payload: Reset = tokens.consume("reset-password", &token).await.unwrap();
+        assert_eq!(payload, reset());
+
+        let replay = tokens.consume::<Reset>("reset-password", &token).await;
+        assert!(matches!(replay, Err(OneTimeError::Replayed)));
+    }
+
+    #[tokio::test]
+    async fn concurrent_consumptions_consume_once() {
+        let tokens = OneTimeTokens::new(YieldingStore::default());
+        let token = tokens.issue("reset-password", &reset()).await.unwrap();
+
+        let (first, second) = tokio::join!(
+            tokens.consume::<Reset>("reset-password", &token),
+            tokens.consume::<Reset>("reset-password", &token),
+        );
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Err(OneTimeError::Replayed))));
+
+        let replay = tokens.consume::<Reset>("reset-password", &token).await;
+        assert!(matches!(replay, Err(OneTimeError::Replayed)));
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_tokens() {
+        let tokens = OneTimeTokens::new(MemoryStore::default());
+        let token = tokens.issue("reset-password", &reset()).await.unwrap();
+
+        let other_purpose = tokens.consume::<Reset>("verify-email", &token).await;
+        assert!(matches!(other_purpose, Err(OneTimeError::Invalid)));
+
+        let (record_id, _) = token.split_once('.').unwrap();
+        let forged = format!("{record_id}.{}", tower_sessions::session::Id::default());
+        let forged = tokens.consume::<Reset>("reset-password", &forged).await;
+        assert!(matches!(forged, Err(OneTimeError::Invalid)));
+
+        let malformed = tokens.consume::<Reset>("reset-password", "token").await;
+        assert!(matches!(malformed, Err(OneTimeError::Invalid)));
+
+        // Failed attempts don't spend the token.
+        assert!(tokens
+            .consume::<Reset>("reset-password", &token)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn tokens_expire() {
+        let clock = MockClock::default();
+        let tokens = OneTimeTokens::new(MemoryStore::default().with_clock(clock.clone()))
+            .with_duration(Duration::minutes(15))
+            .with_clock(clock.clone());
+        let token = tokens.issue("reset-password", &reset()).await.unwrap();
+
+        clock.advance(Duration::minutes(16));
+        let expired = tokens.consume::<Reset>("reset-password", &token).await;
+        assert!(matches!(expired, Err(OneTimeError::Invalid)));
+    }
+
+    #[tokio::test]
+    async fn revokes_tokens() {
+        let tokens = OneTimeTokens::new(MemoryStore::default());
+        let token = tokens.issue("reset-password", &reset()).await.unwrap();
+
+        tokens.revoke(&token).await.unwrap();
+        let revoked = tokens.consume::<Reset>("reset-password", &token).await;
+        assert!(matches!(revoked, Err(OneTimeError::Invalid)));
+    }
+
+    #[tokio::test]
+    async fn records_are_not_sessions() {
+        let session_store = MemoryStore::default();
+        let tokens = OneTimeTokens::new(session_store.clone());
+        let token = tokens.issue_for("alice", "magic-link", &()).await.unwrap();
+        let (record_id, _) = token.split_once('.').unwrap();
+
+        let app = Router::new()
+            .route(
+                "/whoami",
+                get(|session: Session| async move { format!("{:?}", session.principal()) }),
+            )
+            .layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(|_: BoxError| async {
+                        StatusCode::BAD_REQUEST
+                    }))
+                    .layer(SessionManagerLayer::new(session_store)),
+            );
+        let req = Request::builder()
+            .uri("/whoami")
+            .header(header::COOKIE, format!("id={record_id}"))
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+
+        assert_eq!(body_string(res.into_body()).await, "None");
+    }
+}
+
+#[cfg(all(test, feature = "axum-core"))]
+mod key_providers_tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{body::Body, extract::Request, Router};
+    use http::{header, HeaderMap, StatusCode};
+    use serde_json::{json, Value};
+    use tower_sessions::key_provider::{KeyProvider, KeyProviderError};
+    use tower_sessions_key_providers::{
+        aws_sdk_kms::{
+            self,
+            config::{BehaviorVersion, Credentials, Region},
+        },
+        KmsKeyProvider, VaultKeyProvider,
+    };
+
+    /// A request received by a mocked endpoint.
+    #[derive(Debug, Clone)]
+    struct Received {
+        path: String,
+        headers: HeaderMap,
+        body: Value,
+    }
+
+    /// Serves the response to every request, returning the address of the
+    /// endpoint and the requests it received.
+    async fn mock(
+        status: StatusCode,
+        content_type: &'static str,
+        response: String,
+    ) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let router = Router::new().fallback({
+            let received = received.clone();
+            move |req: Request| async move {
+                let (parts, body) = req.into_parts();
+                let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+                received.lock().unwrap().push(Received {
+                    path: parts.uri.path().to_owned(),
+                    headers: parts.headers,
+                    body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+                });
+                (
+                    status,
+                    [(header::CONTENT_TYPE, content_type)],
+                    Body::from(response.clone()),
+                )
+            }
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        (format!("http://{address}"), received)
+    }
+
+    /// 64 bytes of zeros, encoded as KMS and Vault return key material.
+    const KEY: &str =
+        "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA==";
+
+    /// 32 bytes of zeros, too short for a key.
+    const SHORT_KEY: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+    async fn kms(
+        status: StatusCode,
+        response: Value,
+    ) -> (KmsKeyProvider, Arc<Mutex<Vec<Received>>>) {
+        let (address, received) =
+            mock(status, "application/x-amz-json-1.1", response.to_string()).await;
+        let config = aws_sdk_kms::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("eu-west-1"))
+            .credentials_provider(Credentials::new(
+                "access-key",
+                "secret-key",
+                None,
+                None,
+                "test",
+            ))
+            .endpoint_url(address)
+            .build();
+        let key_provider = KmsKeyProvider::new(
+            aws_sdk_kms::Client::from_conf(config),
+            b"ciphertext".to_vec(),
+        );
+        (key_provider, received)
+    }
+
+    #[tokio::test]
+    async fn kms_decrypts_data_key() {
+        let (key_provider, received) = kms(
+            StatusCode::OK,
+            json!({ "KeyId": "alias/sessions", "Plaintext": KEY }),
+        )
+        .await;
+        let key_provider = key_provider
+            .with_key_id("alias/sessions")
+            .with_encryption_context("purpose", "sessions");
+
+        assert_eq!(key_provider.fetch().await.unwrap(), vec![0; 64]);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].headers["x-amz-target"], "TrentService.Decrypt");
+        assert_eq!(
+            received[0].body,
+            json!({
+                "CiphertextBlob": "Y2lwaGVydGV4dA==",
+                "KeyId": "alias/sessions",
+                "EncryptionContext": { "purpose": "sessions" },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn kms_short_data_key_fails() {
+        let (key_provider, _) = kms(StatusCode::OK, json!({ "Plaintext": SHORT_KEY })).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::TooShort { len: 32 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn kms_missing_plaintext_is_not_found() {
+        let (key_provider, _) = kms(StatusCode::OK, json!({ "KeyId": "alias/sessions" })).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn kms_errors_are_backend_errors() {
+        let (key_provider, _) = kms(
+            StatusCode::BAD_REQUEST,
+            json!({ "__type": "DisabledException", "message": "alias/sessions is disabled." }),
+        )
+        .await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::Backend(_))
+        ));
+    }
+
+    async fn vault(
+        status: StatusCode,
+        response: &str,
+    ) -> (VaultKeyProvider, Arc<Mutex<Vec<Received>>>) {
+        let (address, received) = mock(status, "application/json", response.to_owned()).await;
+        let key_provider = VaultKeyProvider::new(&format!("{address}/"), "hvs.token", "/sessions/");
+        (key_provider, received)
+    }
+
+    fn secret(field: &str, value: &str) -> String {
+        json!({ "data": { "data": { field: value }, "metadata": { "version": 2 } } }).to_string()
+    }
+
+    #[tokio::test]
+    async fn vault_reads_key_field() {
+        let (key_provider, received) = vault(StatusCode::OK, &secret("cookie-key", KEY)).await;
+        let key_provider = key_provider.with_mount("/kv/").with_field("cookie-key");
+
+        assert_eq!(key_provider.fetch().await.unwrap(), vec![0; 64]);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/v1/kv/data/sessions");
+        assert_eq!(received[0].headers["x-vault-token"], "hvs.token");
+    }
+
+    #[tokio::test]
+    async fn vault_missing_secret_is_not_found() {
+        let (key_provider, _) = vault(StatusCode::NOT_FOUND, r#"{"errors":[]}"#).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn vault_missing_field_is_not_found() {
+        let (key_provider, _) = vault(StatusCode::OK, &secret("other", KEY)).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn vault_errors_are_backend_errors() {
+        let (key_provider, _) =
+            vault(StatusCode::FORBIDDEN, r#"{"errors":["permission denied"]}"#).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::Backend(_))
+        ));
+
+        // Responses which aren't JSON, e.g. of a proxy in front of Vault, fail too.
+        let (key_provider, _) = vault(StatusCode::OK, "<html></html>").await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::Backend(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn vault_key_material_is_decoded() {
+        let (key_provider, _) = vault(StatusCode::OK, &secret("key", "not base64!")).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::Decode(_))
+        ));
+
+        let (key_provider, _) = vault(StatusCode::OK, &secret("key", SHORT_KEY)).await;
+        assert!(matches!(
+            key_provider.fetch().await,
+            Err(KeyProviderError::TooShort { len: 32 })
+        ));
+    }
+}
@@ -0,0 +1,453 @@
+use std::future::poll_fn;
+
+use async_trait::async_trait;
+pub use deadpool_postgres;
+use deadpool_postgres::{Pool, PoolError};
+use time::OffsetDateTime;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
+    time::Duration,
+};
+pub use tokio_postgres;
+use tokio_postgres::{AsyncMessage, Client, Connection};
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, Session, SessionStore,
+};
+
+/// An error type for `TokioPostgresStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum TokioPostgresStoreError {
+    /// A variant to map `tokio_postgres` errors.
+    #[error("Postgres error: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+
+    /// A variant to map connection pool errors.
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] PoolError),
+
+    /// A variant for sessions which could not be created because a record
+    /// with the same ID already exists.
+    #[error("Session ID collision")]
+    IdCollision,
+
+    /// A variant for when the connection listening for notifications of
+    /// expired deletion closes.
+    #[error("Notification listener connection closed")]
+    ListenerClosed,
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A PostgreSQL session store using `tokio-postgres` directly, with
+/// connections from a `deadpool-postgres` pool.
+///
+/// Queries are issued as prepared statements cached per connection. Sessions
+/// are stored in the same schema as the SQLx `PostgresStore`, so either store
+/// may be used with the same table.
+///
+/// Expired sessions may be deleted with
+/// [`delete_expired`](ExpiredDeletion::delete_expired), which also notifies
+/// other instances of the store through `NOTIFY`, so that
+/// [`continuously_delete_expired_with_listener`](TokioPostgresStore::continuously_delete_expired_with_listener)
+/// can skip redundant deletions.
+#[derive(Clone)]
+pub struct TokioPostgresStore {
+    pool: Pool,
+    schema_name: String,
+    table_name: String,
+    channel_name: String,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for TokioPostgresStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokioPostgresStore")
+            .field("schema_name", &self.schema_name)
+            .field("table_name", &self.table_name)
+            .field("channel_name", &self.channel_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokioPostgresStore {
+    /// Create a new PostgreSQL store with the provided connection pool.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_tokio_postgres_store::{
+    ///     deadpool_postgres::{Manager, Pool},
+    ///     tokio_postgres::NoTls,
+    ///     TokioPostgresStore,
+    /// };
+    ///
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let manager = Manager::new(database_url.parse().unwrap(), NoTls);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = TokioPostgresStore::new(pool);
+    /// ```
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            pool,
+            schema_name: "tower_sessions".to_string(),
+            table_name: "session".to_string(),
+            channel_name: "tower_sessions_expired_deletion".to_string(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Set the session table schema name with the provided name.
+    pub fn with_schema_name(mut self, schema_name: impl AsRef<str>) -> Result<Self, String> {
+        let schema_name = schema_name.as_ref();
+        if !is_valid_identifier(schema_name) {
+            return Err(format!(
+                "Invalid schema name '{}'. Schema names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters).Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                schema_name
+            ));
+        }
+
+        self.schema_name = schema_name.to_owned();
+        Ok(self)
+    }
+
+    /// Set the session table name with the provided name.
+    pub fn with_table_name(mut self, table_name: impl AsRef<str>) -> Result<Self, String> {
+        let table_name = table_name.as_ref();
+        if !is_valid_identifier(table_name) {
+            return Err(format!(
+                "Invalid table name '{}'. Table names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters).Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                table_name
+            ));
+        }
+
+        self.table_name = table_name.to_owned();
+        Ok(self)
+    }
+
+    /// Set the name of the channel expired deletion is notified on, which
+    /// defaults to `tower_sessions_expired_deletion`.
+    pub fn with_channel_name(mut self, channel_name: impl AsRef<str>) -> Result<Self, String> {
+        let channel_name = channel_name.as_ref();
+        if !is_valid_identifier(channel_name) {
+            return Err(format!(
+                "Invalid channel name '{}'. Channel names must start with a letter or underscore \
+                 (including letters with diacritical marks and non-Latin letters).Subsequent \
+                 characters can be letters, underscores, digits (0-9), or dollar signs ($).",
+                channel_name
+            ));
+        }
+
+        self.channel_name = channel_name.to_owned();
+        Ok(self)
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Migrate the session schema.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_tokio_postgres_store::{
+    ///     deadpool_postgres::{Manager, Pool},
+    ///     tokio_postgres::NoTls,
+    ///     TokioPostgresStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let manager = Manager::new(database_url.parse().unwrap(), NoTls);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = TokioPostgresStore::new(pool);
+    /// session_store.migrate().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn migrate(&self) -> Result<(), TokioPostgresStoreError> {
+        let mut client = self.pool.get().await?;
+
+        let create_schema_query = format!(
+            r#"create schema if not exists "{schema_name}""#,
+            schema_name = self.schema_name,
+        );
+        // Concurrent create schema may fail due to duplicate key violations.
+        //
+        // This works around that by assuming the schema must exist on such an error.
+        // The schema is created outside of the transaction below so that such an
+        // error doesn't abort it, and the table is still created.
+        if let Err(err) = client.batch_execute(&create_schema_query).await {
+            if err.code() != Some(&tokio_postgres::error::SqlState::UNIQUE_VIOLATION) {
+                return Err(err.into());
+            }
+        }
+
+        let tx = client.transaction().await?;
+
+        let create_table_query = format!(
+            r#"
+            create table if not exists "{schema_name}"."{table_name}"
+            (
+                id text primary key not null,
+                data bytea not null,
+                expiry_date timestamptz not null
+            )
+            "#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        tx.batch_execute(&create_table_query).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Continuously delete expired sessions, coordinating with other
+    /// instances of the store through notifications.
+    ///
+    /// Expired sessions are deleted every `period`, and every successful
+    /// deletion is notified on the store's channel. The provided client
+    /// listens on that channel, and whenever another instance notifies a
+    /// deletion, the next deletion here is postponed by a full `period`, so
+    /// that expired sessions are deleted about once per `period` overall
+    /// rather than once per instance.
+    ///
+    /// The client and its connection must be dedicated to listening, as
+    /// pooled connections don't surface notifications.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_tokio_postgres_store::{
+    ///     deadpool_postgres::{Manager, Pool},
+    ///     tokio_postgres::{self, NoTls},
+    ///     TokioPostgresStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let database_url = std::option_env!("DATABASE_URL").unwrap();
+    /// let config: tokio_postgres::Config = database_url.parse().unwrap();
+    /// let manager = Manager::new(config.clone(), NoTls);
+    /// let pool = Pool::builder(manager).build().unwrap();
+    /// let session_store = TokioPostgresStore::new(pool);
+    ///
+    /// let (client, connection) = config.connect(NoTls).await.unwrap();
+    /// let deletion_task = tokio::task::spawn(
+    ///     session_store
+    ///         .clone()
+    ///         .continuously_delete_expired_with_listener(
+    ///             client,
+    ///             connection,
+    ///             tokio::time::Duration::from_secs(60),
+    ///         ),
+    /// );
+    /// # })
+    /// ```
+    pub async fn continuously_delete_expired_with_listener<S, T>(
+        self,
+        client: Client,
+        mut connection: Connection<S, T>,
+        period: Duration,
+    ) -> Result<(), TokioPostgresStoreError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        // Notifications are only delivered while the connection is polled,
+        // so it's driven by its own task forwarding them.
+        let (tx, mut notifications) = mpsc::unbounded_channel();
+        let channel_name = self.channel_name.clone();
+        tokio::spawn(async move {
+            while let Some(message) = poll_fn(|cx| connection.poll_message(cx)).await {
+                match message {
+                    Ok(AsyncMessage::Notification(notification))
+                        if notification.channel() == channel_name =>
+                    {
+                        if tx.send(Ok(())).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        let _ = tx.send(Err(err));
+                        return;
+                    }
+                }
+            }
+        });
+
+        client
+            .batch_execute(&format!(
+                r#"listen "{channel_name}""#,
+                channel_name = self.channel_name
+            ))
+            .await?;
+
+        let mut interval = tokio::time::interval(period);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.delete_expired().await?,
+                notification = notifications.recv() => match notification {
+                    Some(Ok(())) => interval.reset(),
+                    Some(Err(err)) => return Err(err.into()),
+                    None => return Err(TokioPostgresStoreError::ListenerClosed),
+                },
+            }
+        }
+    }
+
+    async fn insert(
+        &self,
+        session: &Session,
+        on_conflict: &str,
+    ) -> Result<u64, TokioPostgresStoreError> {
+        let query = format!(
+            r#"
+            insert into "{schema_name}"."{table_name}" (id, data, expiry_date)
+            values ($1, $2, $3)
+            on conflict (id) {on_conflict}
+            "#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        let client = self.pool.get().await?;
+        let statement = client.prepare_cached(&query).await?;
+        let rows = client
+            .execute(
+                &statement,
+                &[
+                    &session.id().to_string(),
+                    &rmp_serde::to_vec(&session)?,
+                    &session.expiry_date(),
+                ],
+            )
+            .await?;
+
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for TokioPostgresStore {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let query = format!(
+            r#"
+            delete from "{schema_name}"."{table_name}"
+            where expiry_date < $1
+            "#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        let client = self.pool.get().await?;
+        let statement = client.prepare_cached(&query).await?;
+        client.execute(&statement, &[&self.clock.now()]).await?;
+
+        let statement = client.prepare_cached("select pg_notify($1, '')").await?;
+        client.execute(&statement, &[&self.channel_name]).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for TokioPostgresStore {
+    type Error = TokioPostgresStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.insert(
+            session,
+            r#"
+            do update
+            set
+              data = excluded.data,
+              expiry_date = excluded.expiry_date
+            "#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        if self.insert(session, "do nothing").await? == 0 {
+            return Err(TokioPostgresStoreError::IdCollision);
+        }
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let query = format!(
+            r#"
+            select data from "{schema_name}"."{table_name}"
+            where id = $1 and expiry_date > $2
+            "#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        let client = self.pool.get().await?;
+        let statement = client.prepare_cached(&query).await?;
+        let now: OffsetDateTime = self.clock.now();
+        let row = client
+            .query_opt(&statement, &[&session_id.to_string(), &now])
+            .await?;
+
+        if let Some(row) = row {
+            let data: &[u8] = row.try_get(0)?;
+            Ok(Some(rmp_serde::from_slice(data)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let query = format!(
+            r#"delete from "{schema_name}"."{table_name}" where id = $1"#,
+            schema_name = self.schema_name,
+            table_name = self.table_name
+        );
+        let client = self.pool.get().await?;
+        let statement = client.prepare_cached(&query).await?;
+        client
+            .execute(&statement, &[&session_id.to_string()])
+            .await?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("postgresql")
+    }
+}
+
+/// A valid PostreSQL identifier must start with a letter or underscore
+/// (including letters with diacritical marks and non-Latin letters). Subsequent
+/// characters in an identifier or key word can be letters, underscores, digits
+/// (0-9), or dollar signs ($). See https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-SYNTAX-IDENTIFIERS for details.
+fn is_valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .next()
+            .map(|c| c.is_alphabetic() || c == '_')
+            .unwrap_or_default()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
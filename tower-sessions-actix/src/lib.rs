@@ -0,0 +1,434 @@
+//! An [`actix-web`](https://docs.rs/actix-web) middleware for sessions backed
+//! by `tower-sessions` stores.
+//!
+//! [`SessionMiddleware`] loads and persists the same [`Session`] records as
+//! `tower-sessions`' own middleware, through any [`SessionStore`], so that
+//! `actix-web` and `axum` or other `tower` services may share a store, and
+//! with it their visitors' sessions. Handlers access the session with the
+//! [`ActixSession`] extractor, which dereferences to [`Session`].
+//!
+//! The middleware supports the session cookie's attributes, expiry, and
+//! refresh threshold, loading and saving sessions as `SessionManagerLayer`
+//! does through [`SessionPersistence`]. Other features of
+//! `SessionManagerLayer`, such as signed cookies, consent, and strict mode,
+//! aren't supported.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use actix_web::{web, App};
+//! use tower_sessions::MemoryStore;
+//! use tower_sessions_actix::{ActixSession, SessionMiddleware};
+//!
+//! async fn counter(session: ActixSession) -> String {
+//!     let counter: usize = session.get("counter").unwrap().unwrap_or_default();
+//!     session.insert("counter", counter + 1).unwrap();
+//!     format!("Current count: {counter}")
+//! }
+//!
+//! let session_store = MemoryStore::default();
+//! let app = App::new()
+//!     .wrap(SessionMiddleware::new(session_store))
+//!     .route("/", web::get().to(counter));
+//! ```
+#![warn(
+    clippy::all,
+    nonstandard_style,
+    future_incompatible,
+    missing_debug_implementations
+)]
+#![deny(missing_docs)]
+#![forbid(unsafe_code)]
+
+use std::{ops::Deref, rc::Rc, sync::Arc};
+
+use actix_web::{
+    body::MessageBody,
+    cookie::{Cookie, SameSite},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorInternalServerError,
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use time::Duration;
+use tower_sessions_core::{
+    clock::Clock,
+    persistence::{Saved, SessionPersistence},
+    session::Id,
+    Expiry, Session, SessionStore,
+};
+
+#[derive(Debug, Clone)]
+struct SessionConfig {
+    name: String,
+    http_only: bool,
+    same_site: SameSite,
+    persistence: SessionPersistence,
+    secure: bool,
+    path: String,
+    domain: Option<String>,
+}
+
+impl SessionConfig {
+    fn build_cookie(&self, session: &Session) -> Cookie<'static> {
+        let mut cookie_builder = Cookie::build(self.name.clone(), session.id().to_string())
+            .http_only(self.http_only)
+            .same_site(self.same_site)
+            .secure(self.secure)
+            .path(self.path.clone());
+
+        cookie_builder = cookie_builder.max_age(session.expiry_age());
+
+        if let Some(domain) = &self.domain {
+            cookie_builder = cookie_builder.domain(domain.clone());
+        }
+
+        cookie_builder.finish()
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            name: String::from("id"),
+            http_only: true,
+            same_site: SameSite::Strict,
+            persistence: SessionPersistence::default(),
+            secure: false,
+            path: String::from("/"),
+            domain: None,
+        }
+    }
+}
+
+/// A middleware providing [`Session`] to `actix-web` handlers.
+///
+/// Its defaults, such as the cookie name `id`, match those of
+/// `SessionManagerLayer`, so that both middleware read each other's cookies.
+#[derive(Debug, Clone)]
+pub struct SessionMiddleware<Store: SessionStore> {
+    session_store: Store,
+    session_config: Arc<SessionConfig>,
+}
+
+impl<Store: SessionStore> SessionMiddleware<Store> {
+    /// Create a new middleware persisting sessions with the provided store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_middleware = SessionMiddleware::new(session_store);
+    /// ```
+    pub fn new(session_store: Store) -> Self {
+        Self {
+            session_store,
+            session_config: Arc::default(),
+        }
+    }
+
+    fn config_mut(&mut self) -> &mut SessionConfig {
+        Arc::make_mut(&mut self.session_config)
+    }
+
+    /// Configures the name of the cookie used for the session.
+    /// The default value is `"id"`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware = SessionMiddleware::new(MemoryStore::default()).with_name("my.sid");
+    /// ```
+    pub fn with_name<N: Into<String>>(mut self, name: N) -> Self {
+        self.config_mut().name = name.into();
+        self
+    }
+
+    /// Configures the `"HttpOnly"` attribute of the cookie used for the
+    /// session.
+    ///
+    /// # ⚠️ **Warning: Cross-site scripting risk**
+    ///
+    /// Applications should generally **not** override the default value of
+    /// `true`. If you do, you are exposing your application to increased risk
+    /// of cookie theft via techniques like cross-site scripting.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware = SessionMiddleware::new(MemoryStore::default()).with_http_only(true);
+    /// ```
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.config_mut().http_only = http_only;
+        self
+    }
+
+    /// Configures the `"SameSite"` attribute of the cookie used for the
+    /// session. The default value is [`SameSite::Strict`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use actix_web::cookie::SameSite;
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware =
+    ///     SessionMiddleware::new(MemoryStore::default()).with_same_site(SameSite::Lax);
+    /// ```
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.config_mut().same_site = same_site;
+        self
+    }
+
+    /// Configures the expiry of new sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{Expiry, MemoryStore};
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware = SessionMiddleware::new(MemoryStore::default())
+    ///     .with_expiry(Expiry::OnInactivity(Duration::hours(1)));
+    /// ```
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        let session_config = self.config_mut();
+        session_config.persistence = session_config.persistence.clone().with_expiry(expiry);
+        self
+    }
+
+    /// Configures the remaining lifetime below which sessions expiring on
+    /// inactivity are extended. Without a threshold, such sessions are
+    /// extended by every request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{Expiry, MemoryStore};
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware = SessionMiddleware::new(MemoryStore::default())
+    ///     .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+    ///     .with_refresh_threshold(Duration::minutes(15));
+    /// ```
+    pub fn with_refresh_threshold(mut self, refresh_threshold: Duration) -> Self {
+        let session_config = self.config_mut();
+        session_config.persistence = session_config
+            .persistence
+            .clone()
+            .with_refresh_threshold(refresh_threshold);
+        self
+    }
+
+    /// Configures the clock against which sessions expire.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, MemoryStore};
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware =
+    ///     SessionMiddleware::new(MemoryStore::default()).with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        let session_config = self.config_mut();
+        session_config.persistence = session_config.persistence.clone().with_clock(clock);
+        self
+    }
+
+    /// Configures the `"Secure"` attribute of the cookie used for the session.
+    /// The default value is `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware = SessionMiddleware::new(MemoryStore::default()).with_secure(true);
+    /// ```
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.config_mut().secure = secure;
+        self
+    }
+
+    /// Configures the `"Path"` attribute of the cookie used for the session.
+    /// The default value is `"/"`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware = SessionMiddleware::new(MemoryStore::default()).with_path("/app");
+    /// ```
+    pub fn with_path<P: Into<String>>(mut self, path: P) -> Self {
+        self.config_mut().path = path.into();
+        self
+    }
+
+    /// Configures the `"Domain"` attribute of the cookie used for the session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_actix::SessionMiddleware;
+    ///
+    /// let session_middleware =
+    ///     SessionMiddleware::new(MemoryStore::default()).with_domain("example.com");
+    /// ```
+    pub fn with_domain<D: Into<String>>(mut self, domain: D) -> Self {
+        self.config_mut().domain = Some(domain.into());
+        self
+    }
+}
+
+impl<S, B, Store> Transform<S, ServiceRequest> for SessionMiddleware<Store>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    Store: SessionStore,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SessionMiddlewareService<S, Store>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SessionMiddlewareService {
+            service: Rc::new(service),
+            session_store: self.session_store.clone(),
+            session_config: self.session_config.clone(),
+        }))
+    }
+}
+
+/// The service produced by [`SessionMiddleware`].
+#[derive(Debug)]
+pub struct SessionMiddlewareService<S, Store: SessionStore> {
+    service: Rc<S>,
+    session_store: Store,
+    session_config: Arc<SessionConfig>,
+}
+
+impl<S, B, Store> Service<ServiceRequest> for SessionMiddlewareService<S, Store>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    Store: SessionStore,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let session_store = self.session_store.clone();
+        let session_config = self.session_config.clone();
+
+        Box::pin(async move {
+            let session_cookie = req.cookie(&session_config.name);
+            let persistence = &session_config.persistence;
+
+            // Unknown and malformed cookies alike are replaced by a new session.
+            let session_id = session_cookie
+                .as_ref()
+                .and_then(|session_cookie| Id::try_from(session_cookie.value()).ok());
+            let loaded = persistence
+                .load(&session_store, session_id.as_ref())
+                .await
+                .map_err(ErrorInternalServerError)?;
+
+            let is_loaded = loaded.is_some();
+            let mut session = loaded.unwrap_or_else(|| persistence.new_session());
+
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = service.call(req).await?;
+
+            match persistence
+                .save(&session_store, &mut session, is_loaded)
+                .await
+                .map_err(ErrorInternalServerError)?
+            {
+                Saved::Deleted if session_cookie.is_some() => {
+                    res.response_mut()
+                        .add_removal_cookie(&session_config.build_cookie(&session))?;
+                }
+                Saved::Persisted => {
+                    res.response_mut()
+                        .add_cookie(&session_config.build_cookie(&session))?;
+                }
+                Saved::Deleted | Saved::Unchanged => {}
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+/// An extractor providing the [`Session`] of the request.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_actix::ActixSession;
+///
+/// async fn handler(session: ActixSession) -> String {
+///     format!("{:?}", session.principal())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ActixSession(Session);
+
+impl ActixSession {
+    /// Consumes the extractor, returning the session.
+    pub fn into_inner(self) -> Session {
+        self.0
+    }
+}
+
+impl Deref for ActixSession {
+    type Target = Session;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for ActixSession {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Session>()
+                .cloned()
+                .map(ActixSession)
+                .ok_or_else(|| {
+                    ErrorInternalServerError(
+                        "Can't extract session. Is `SessionMiddleware` enabled?",
+                    )
+                }),
+        )
+    }
+}
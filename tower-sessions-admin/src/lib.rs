@@ -0,0 +1,244 @@
+//! An embeddable [`axum`](https://docs.rs/axum) router for managing sessions.
+//!
+//! [`router`] exposes the following endpoints, which respond with JSON and
+//! require the configured token as a bearer token in the `Authorization`
+//! header:
+//!
+//! - `GET /sessions` lists the IDs of active sessions, at most `limit` of them
+//!   when given as a query parameter.
+//! - `GET /sessions/:session_id` returns the record of a session: its ID,
+//!   expiry date, principal and data.
+//! - `DELETE /sessions/:session_id` revokes a session.
+//! - `GET /principals/:principal/sessions` lists the IDs of the sessions of a
+//!   principal.
+//! - `DELETE /principals/:principal/sessions` revokes every session of a
+//!   principal.
+//!
+//! Records of remember-me and one-time tokens, which share the store with
+//! sessions, are neither listed nor returned.
+//!
+//! Sessions are associated with principals with
+//! [`Session::set_principal`](tower_sessions_core::Session::set_principal),
+//! and the store must implement [`ListSessions`] and [`UserSessions`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use axum::Router;
+//! use tower_sessions::MemoryStore;
+//!
+//! let session_store = MemoryStore::default();
+//! let app: Router = Router::new().nest(
+//!     "/admin",
+//!     tower_sessions_admin::router(session_store, "an admin token"),
+//! );
+//! ```
+#![warn(
+    clippy::all,
+    nonstandard_style,
+    future_incompatible,
+    missing_debug_implementations
+)]
+#![deny(missing_docs)]
+#![forbid(unsafe_code)]
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::Id, session_store::is_internal_record, ListSessions, Session, SessionStore,
+    UserSessions,
+};
+
+/// Create a router exposing the admin endpoints for the provided store,
+/// guarded by the provided bearer token.
+///
+/// The router is meant to be nested in an application, e.g. under `/admin`,
+/// and served over TLS only, as the token grants control over every session.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::MemoryStore;
+///
+/// let admin_router = tower_sessions_admin::router(MemoryStore::default(), "an admin token");
+/// ```
+pub fn router<Store>(store: Store, token: impl Into<String>) -> Router
+where
+    Store: ListSessions + UserSessions,
+{
+    let token: Arc<str> = token.into().into();
+
+    Router::new()
+        .route("/sessions", get(list_sessions::<Store>))
+        .route(
+            "/sessions/:session_id",
+            get(get_session::<Store>).delete(delete_session::<Store>),
+        )
+        .route(
+            "/principals/:principal/sessions",
+            get(principal_sessions::<Store>).delete(delete_principal_sessions::<Store>),
+        )
+        .route_layer(middleware::from_fn_with_state(token, authenticate))
+        .with_state(store)
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionIds {
+    sessions: Vec<Id>,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionRecord {
+    id: Id,
+    #[serde(with = "time::serde::rfc3339")]
+    expiry_date: OffsetDateTime,
+    principal: Option<String>,
+    data: HashMap<String, Value>,
+}
+
+impl From<Session> for SessionRecord {
+    fn from(session: Session) -> Self {
+        Self {
+            id: *session.id(),
+            expiry_date: session.expiry_date(),
+            principal: session.principal(),
+            data: session.data(),
+        }
+    }
+}
+
+async fn authenticate(State(token): State<Arc<str>>, req: Request, next: Next) -> Response {
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|presented| tokens_match(presented.as_bytes(), token.as_bytes()));
+
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Compares tokens in time independent of where they differ.
+fn tokens_match(presented: &[u8], expected: &[u8]) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn store_error(err: impl std::error::Error) -> StatusCode {
+    tracing::error!(err = %err, "failed to access the session store");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+fn parse_id(session_id: &str) -> Result<Id, StatusCode> {
+    Id::try_from(session_id).map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Returns the IDs of the listed records which are sessions, at most `limit`
+/// of them.
+///
+/// Stores list the records of remember-me and one-time tokens along with
+/// sessions, so each record is loaded to tell them apart.
+async fn sessions_only<Store: SessionStore>(
+    store: &Store,
+    session_ids: Vec<Id>,
+    limit: Option<usize>,
+) -> Result<Vec<Id>, StatusCode> {
+    let mut sessions = Vec::new();
+    for session_id in session_ids {
+        if limit.is_some_and(|limit| sessions.len() >= limit) {
+            break;
+        }
+        let session = store.load(&session_id).await.map_err(store_error)?;
+        if session.is_some_and(|session| !is_internal_record(&session)) {
+            sessions.push(session_id);
+        }
+    }
+
+    Ok(sessions)
+}
+
+async fn list_sessions<Store: ListSessions>(
+    State(store): State<Store>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<SessionIds>, StatusCode> {
+    let session_ids = store.list_sessions().await.map_err(store_error)?;
+    let sessions = sessions_only(&store, session_ids, query.limit).await?;
+
+    Ok(Json(SessionIds { sessions }))
+}
+
+async fn get_session<Store: ListSessions>(
+    State(store): State<Store>,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionRecord>, StatusCode> {
+    let session_id = parse_id(&session_id)?;
+    let session = store
+        .load(&session_id)
+        .await
+        .map_err(store_error)?
+        // Token records hold unspent tokens' hashes and payloads.
+        .filter(|session| !is_internal_record(session))
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(session.into()))
+}
+
+async fn delete_session<Store: ListSessions>(
+    State(store): State<Store>,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let session_id = parse_id(&session_id)?;
+    store.delete(&session_id).await.map_err(store_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn principal_sessions<Store: UserSessions>(
+    State(store): State<Store>,
+    Path(principal): Path<String>,
+) -> Result<Json<SessionIds>, StatusCode> {
+    let session_ids = store.user_sessions(&principal).await.map_err(store_error)?;
+    let sessions = sessions_only(&store, session_ids, None).await?;
+
+    Ok(Json(SessionIds { sessions }))
+}
+
+async fn delete_principal_sessions<Store: UserSessions>(
+    State(store): State<Store>,
+    Path(principal): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    store
+        .delete_user_sessions(&principal)
+        .await
+        .map_err(store_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
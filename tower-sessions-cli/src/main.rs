@@ -0,0 +1,298 @@
+//! A command line tool for inspecting and cleaning up the sessions of a
+//! `tower-sessions` store.
+//!
+//! The store is selected by the scheme of its URL, given with `--url` or the
+//! `TOWER_SESSIONS_URL` environment variable:
+//!
+//! - `redis://` and `rediss://` for `RedisStore`
+//! - `postgres://` and `postgresql://` for `PostgresStore`
+//! - `mysql://` for `MySqlStore`
+//! - `sqlite:` for `SqliteStore`
+//!
+//! ```sh
+//! export TOWER_SESSIONS_URL=postgres://localhost/app
+//! tower-sessions-cli list
+//! tower-sessions-cli dump 6d7e9a3b-a4b2-4a4e-bc91-1f1b27e0c84d
+//! tower-sessions-cli delete 6d7e9a3b-a4b2-4a4e-bc91-1f1b27e0c84d
+//! tower-sessions-cli purge-expired
+//! ```
+//!
+//! Sessions may be backed up and restored, or moved between stores, as
+//! newline-delimited JSON:
+//!
+//! ```sh
+//! tower-sessions-cli export > sessions.ndjson
+//! tower-sessions-cli --url redis://localhost:6379 import < sessions.ndjson
+//! ```
+#![warn(
+    clippy::all,
+    nonstandard_style,
+    future_incompatible,
+    missing_debug_implementations
+)]
+#![forbid(unsafe_code)]
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{self, Write},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use futures::{stream, TryStreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+use tower_sessions_core::{
+    session::Id,
+    session_store::{is_internal_record, ExpiredDeletion, ListSessions},
+    transfer::Transfer,
+    Session,
+};
+use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+use tower_sessions_sqlx_store::{
+    sqlx::{MySqlPool, PgPool, SqlitePool},
+    MySqlStore, PostgresStore, SqliteStore,
+};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Inspect and clean up the sessions of a tower-sessions store.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// The URL of the store, e.g. `redis://localhost:6379` or
+    /// `postgres://localhost/app`.
+    #[arg(long, env = "TOWER_SESSIONS_URL")]
+    url: String,
+
+    /// The name of the sessions table of SQL stores.
+    #[arg(long)]
+    table: Option<String>,
+
+    /// The name of the schema of the sessions table of Postgres and MySQL
+    /// stores.
+    #[arg(long)]
+    schema: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the IDs of the active sessions.
+    List,
+
+    /// Print the decoded record of a session as JSON.
+    Dump {
+        /// The ID of the session.
+        session_id: String,
+    },
+
+    /// Delete a session.
+    Delete {
+        /// The ID of the session.
+        session_id: String,
+    },
+
+    /// Delete the sessions past their expiry date.
+    PurgeExpired,
+
+    /// Print every session as a line of JSON, for `import` to read.
+    Export {
+        /// Include sessions past their expiry date.
+        #[arg(long)]
+        include_expired: bool,
+    },
+
+    /// Save the sessions read from standard input, as printed by `export`.
+    Import {
+        /// Include sessions past their expiry date.
+        #[arg(long)]
+        include_expired: bool,
+    },
+}
+
+/// A store selected by the scheme of its URL.
+#[derive(Debug)]
+enum Store {
+    Redis(RedisStore<RedisClient>),
+    Postgres(PostgresStore),
+    MySql(MySqlStore),
+    Sqlite(SqliteStore),
+}
+
+impl Store {
+    async fn connect(cli: &Cli) -> Result<Self, BoxError> {
+        let url = cli.url.as_str();
+
+        if url.starts_with("redis://") || url.starts_with("rediss://") {
+            let client = RedisClient::new(RedisConfig::from_url(url)?, None, None, None);
+            client.connect();
+            client.wait_for_connect().await?;
+            Ok(Self::Redis(RedisStore::new(client)))
+        } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            let mut store = PostgresStore::new(PgPool::connect(url).await?);
+            if let Some(schema) = &cli.schema {
+                store = store.with_schema_name(schema)?;
+            }
+            if let Some(table) = &cli.table {
+                store = store.with_table_name(table)?;
+            }
+            Ok(Self::Postgres(store))
+        } else if url.starts_with("mysql://") {
+            let mut store = MySqlStore::new(MySqlPool::connect(url).await?);
+            if let Some(schema) = &cli.schema {
+                store = store.with_schema_name(schema)?;
+            }
+            if let Some(table) = &cli.table {
+                store = store.with_table_name(table)?;
+            }
+            Ok(Self::MySql(store))
+        } else if url.starts_with("sqlite:") {
+            let mut store = SqliteStore::new(SqlitePool::connect(url).await?);
+            if let Some(table) = &cli.table {
+                store = store.with_table_name(table)?;
+            }
+            Ok(Self::Sqlite(store))
+        } else {
+            Err(format!("unsupported store URL `{url}`").into())
+        }
+    }
+
+    async fn run(&self, command: &Command) -> Result<(), BoxError> {
+        match (self, command) {
+            (Self::Redis(_), Command::PurgeExpired) => {
+                println!("Redis expires sessions itself; nothing to purge");
+            }
+            (Self::Postgres(store), Command::PurgeExpired) => purge_expired(store).await?,
+            (Self::MySql(store), Command::PurgeExpired) => purge_expired(store).await?,
+            (Self::Sqlite(store), Command::PurgeExpired) => purge_expired(store).await?,
+            (Self::Redis(store), command) => inspect(store, command).await?,
+            (Self::Postgres(store), command) => inspect(store, command).await?,
+            (Self::MySql(store), command) => inspect(store, command).await?,
+            (Self::Sqlite(store), command) => inspect(store, command).await?,
+        }
+
+        Ok(())
+    }
+}
+
+/// The decoded record of a session.
+#[derive(Debug, Serialize)]
+struct SessionRecord {
+    id: Id,
+    #[serde(with = "time::serde::rfc3339")]
+    expiry_date: OffsetDateTime,
+    principal: Option<String>,
+    data: HashMap<String, Value>,
+}
+
+impl From<Session> for SessionRecord {
+    fn from(session: Session) -> Self {
+        Self {
+            id: *session.id(),
+            expiry_date: session.expiry_date(),
+            principal: session.principal(),
+            data: session.data(),
+        }
+    }
+}
+
+async fn inspect<S>(store: &S, command: &Command) -> Result<(), BoxError>
+where
+    S: ListSessions,
+    S::Error: 'static,
+{
+    match command {
+        Command::List => {
+            // Listings are often piped, so write errors such as closed pipes
+            // are reported rather than panicking.
+            let mut stdout = io::stdout().lock();
+            for session_id in store.list_sessions().await? {
+                // Records of remember-me and one-time tokens aren't sessions.
+                let is_session = store
+                    .load(&session_id)
+                    .await?
+                    .is_some_and(|session| !is_internal_record(&session));
+                if is_session {
+                    writeln!(stdout, "{session_id}")?;
+                }
+            }
+        }
+
+        Command::Dump { session_id } => {
+            let session_id = parse_id(session_id)?;
+            let session = store
+                .load(&session_id)
+                .await?
+                .filter(|session| !is_internal_record(session))
+                .ok_or_else(|| format!("session `{session_id}` not found"))?;
+            let record = SessionRecord::from(session);
+            println!("{}", serde_json::to_string_pretty(&record)?);
+        }
+
+        Command::Delete { session_id } => {
+            let session_id = parse_id(session_id)?;
+            store.delete(&session_id).await?;
+            println!("deleted session `{session_id}`");
+        }
+
+        Command::Export { include_expired } => {
+            let transfer = Transfer::new().with_expired(*include_expired);
+            let mut lines = std::pin::pin!(transfer.export(store));
+            let mut stdout = io::stdout().lock();
+            while let Some(line) = lines.try_next().await? {
+                stdout.write_all(line.as_bytes())?;
+            }
+        }
+
+        Command::Import { include_expired } => {
+            let lines = stream::iter(io::stdin().lines());
+            let imported = Transfer::new()
+                .with_expired(*include_expired)
+                .import(store, lines)
+                .await?;
+            println!("imported {imported} sessions");
+        }
+
+        Command::PurgeExpired => unreachable!("expired sessions are purged by `Store::run`"),
+    }
+
+    Ok(())
+}
+
+async fn purge_expired<S>(store: &S) -> Result<(), BoxError>
+where
+    S: ExpiredDeletion,
+    S::Error: 'static,
+{
+    store.delete_expired().await?;
+    println!("deleted expired sessions");
+
+    Ok(())
+}
+
+fn parse_id(session_id: &str) -> Result<Id, BoxError> {
+    Id::try_from(session_id).map_err(|_| format!("invalid session ID `{session_id}`").into())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match Store::connect(&cli).await {
+        Ok(store) => store.run(&cli.command).await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
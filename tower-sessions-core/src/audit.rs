@@ -0,0 +1,417 @@
+//! A session store wrapper recording every access to the store it wraps.
+//!
+//! [`AuditStore`] records an [`AuditRecord`] of each store operation to an
+//! [`AuditSink`]. Provided sinks emit records as [`tracing`](https://docs.rs/tracing)
+//! events with [`TracingSink`] or append them to a file with [`FileSink`],
+//! while others, e.g. forwarding records to a database, may be implemented
+//! with the `AuditSink` trait.
+//!
+//! Records identify sessions by their
+//! [fingerprint](crate::session::Id::fingerprint), so the audit trail itself
+//! doesn't expose session IDs.
+use std::{
+    convert::Infallible,
+    fmt::{self, Debug},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    ExpiredDeletion, ListSessions, Session, SessionStore, UserSessions,
+};
+
+/// A store operation recorded by [`AuditStore`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// A session was saved for the first time.
+    Create,
+
+    /// A session was saved.
+    Save,
+
+    /// A session was loaded.
+    Load,
+
+    /// A session was deleted.
+    Delete,
+
+    /// The sessions of the store were listed.
+    List,
+
+    /// The expired sessions of the store were deleted.
+    DeleteExpired,
+}
+
+/// A record of an access to a session store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditRecord {
+    /// The operation performed.
+    pub operation: AuditOperation,
+
+    /// The fingerprint of the ID of the session accessed, unless the operation
+    /// concerns the whole store.
+    pub session_id: Option<String>,
+
+    /// The context provided with
+    /// [`AuditStore::with_context`](AuditStore::with_context), e.g. the
+    /// service or user performing the access.
+    pub context: Option<String>,
+
+    /// When the operation completed.
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+
+    /// Whether the operation succeeded.
+    pub succeeded: bool,
+}
+
+/// A destination for [`AuditRecord`]s.
+///
+/// # Examples
+///
+/// A sink forwarding records to a channel, e.g. for a task writing them to a
+/// database:
+///
+/// ```rust
+/// use async_trait::async_trait;
+/// use tokio::sync::mpsc::{error::SendError, UnboundedSender};
+/// use tower_sessions::audit::{AuditRecord, AuditSink};
+///
+/// #[derive(Debug)]
+/// struct ChannelSink(UnboundedSender<AuditRecord>);
+///
+/// #[async_trait]
+/// impl AuditSink for ChannelSink {
+///     type Error = SendError<AuditRecord>;
+///
+///     async fn record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+///         self.0.send(record.clone())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait AuditSink: Debug + Send + Sync + 'static {
+    /// An error that occurs when recording.
+    type Error: std::error::Error + Send + Sync;
+
+    /// A method for recording an access to the store.
+    async fn record(&self, record: &AuditRecord) -> Result<(), Self::Error>;
+}
+
+/// A sink emitting records as `tracing` events with the
+/// `tower_sessions::audit` target, at the info level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+#[async_trait]
+impl AuditSink for TracingSink {
+    type Error = Infallible;
+
+    async fn record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        tracing::info!(
+            target: "tower_sessions::audit",
+            operation = ?record.operation,
+            session.id = record.session_id.as_deref(),
+            context = record.context.as_deref(),
+            timestamp = %record.timestamp,
+            succeeded = record.succeeded,
+            "session store accessed"
+        );
+
+        Ok(())
+    }
+}
+
+/// A sink appending records to a file as JSON, one record per line.
+///
+/// Records are written with blocking I/O as they're recorded, and aren't
+/// buffered, so that the file is complete should the process exit.
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    file: Arc<Mutex<File>>,
+}
+
+impl FileSink {
+    /// Open the file at the provided path for appending records, creating it
+    /// if it doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::audit::FileSink;
+    ///
+    /// let sink = FileSink::open("sessions-audit.log").unwrap();
+    /// ```
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+}
+
+#[async_trait]
+impl AuditSink for FileSink {
+    type Error = io::Error;
+
+    async fn record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.lock().write_all(&line)
+    }
+}
+
+/// An enumeration of both the store and the sink error types.
+#[derive(thiserror::Error)]
+pub enum AuditStoreError<Store: SessionStore, Sink: AuditSink> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(Store::Error),
+
+    /// A sink-related error.
+    #[error(transparent)]
+    Sink(Sink::Error),
+}
+
+impl<Store: SessionStore, Sink: AuditSink> Debug for AuditStoreError<Store, Sink> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditStoreError::Store(err) => write!(f, "{:?}", err)?,
+            AuditStoreError::Sink(err) => write!(f, "{:?}", err)?,
+        };
+
+        Ok(())
+    }
+}
+
+type ContextFn = dyn Fn() -> Option<String> + Send + Sync;
+
+/// A session store recording every access to the store it wraps to an
+/// [`AuditSink`].
+///
+/// Each operation is recorded once it completes, whether it succeeded or not.
+/// Should recording fail, the operation fails with
+/// [`AuditStoreError::Sink`], so that no access goes unrecorded unnoticed.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::{
+///     audit::{AuditStore, TracingSink},
+///     MemoryStore, SessionManagerLayer,
+/// };
+///
+/// let session_store = AuditStore::new(MemoryStore::default(), TracingSink)
+///     .with_context(|| Some("web".to_owned()));
+/// let session_service = SessionManagerLayer::new(session_store);
+/// ```
+pub struct AuditStore<Store: SessionStore, Sink: AuditSink> {
+    inner: Store,
+    sink: Arc<Sink>,
+    context: Option<Arc<ContextFn>>,
+    clock: SharedClock,
+}
+
+// Sinks are shared rather than cloned, so they needn't be `Clone`.
+impl<Store: SessionStore, Sink: AuditSink> Clone for AuditStore<Store, Sink> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            sink: self.sink.clone(),
+            context: self.context.clone(),
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<Store: SessionStore + Debug, Sink: AuditSink> Debug for AuditStore<Store, Sink> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditStore")
+            .field("inner", &self.inner)
+            .field("sink", &self.sink)
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Store: SessionStore, Sink: AuditSink> AuditStore<Store, Sink> {
+    /// Create a new `AuditStore` recording the accesses to the provided store
+    /// to the provided sink.
+    pub fn new(inner: Store, sink: Sink) -> Self {
+        Self {
+            inner,
+            sink: Arc::new(sink),
+            context: None,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures a function providing the context recorded with each access.
+    ///
+    /// The function is called as each operation completes, so it may read
+    /// state local to the caller, such as a task-local holding the user on
+    /// whose behalf the store is accessed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{
+    ///     audit::{AuditStore, TracingSink},
+    ///     MemoryStore,
+    /// };
+    ///
+    /// let session_store = AuditStore::new(MemoryStore::default(), TracingSink)
+    ///     .with_context(|| Some("session-cleanup".to_owned()));
+    /// ```
+    pub fn with_context(
+        mut self,
+        context: impl Fn() -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.context = Some(Arc::new(context));
+        self
+    }
+
+    /// Configures the clock used to timestamp records.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{
+    ///     audit::{AuditStore, TracingSink},
+    ///     clock::MockClock,
+    ///     MemoryStore,
+    /// };
+    ///
+    /// let session_store =
+    ///     AuditStore::new(MemoryStore::default(), TracingSink).with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Returns a reference to the wrapped store.
+    pub fn get_ref(&self) -> &Store {
+        &self.inner
+    }
+
+    /// Consumes the `AuditStore`, returning the wrapped store.
+    pub fn into_inner(self) -> Store {
+        self.inner
+    }
+
+    async fn audit<T>(
+        &self,
+        operation: AuditOperation,
+        session_id: Option<&Id>,
+        res: Result<T, Store::Error>,
+    ) -> Result<T, AuditStoreError<Store, Sink>> {
+        let record = AuditRecord {
+            operation,
+            session_id: session_id.map(Id::fingerprint),
+            context: self.context.as_ref().and_then(|context| context()),
+            timestamp: self.clock.now(),
+            succeeded: res.is_ok(),
+        };
+        self.sink
+            .record(&record)
+            .await
+            .map_err(AuditStoreError::Sink)?;
+
+        res.map_err(AuditStoreError::Store)
+    }
+}
+
+#[async_trait]
+impl<Store, Sink> SessionStore for AuditStore<Store, Sink>
+where
+    Store: SessionStore,
+    Sink: AuditSink,
+{
+    type Error = AuditStoreError<Store, Sink>;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let res = self.inner.save(session).await;
+        self.audit(AuditOperation::Save, Some(session.id()), res)
+            .await
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let res = self.inner.create(session).await;
+        self.audit(AuditOperation::Create, Some(session.id()), res)
+            .await
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let res = self.inner.load(session_id).await;
+        self.audit(AuditOperation::Load, Some(session_id), res)
+            .await
+    }
+
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        let res = self.inner.load_including_expired(session_id).await;
+        self.audit(AuditOperation::Load, Some(session_id), res)
+            .await
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let res = self.inner.delete(session_id).await;
+        self.audit(AuditOperation::Delete, Some(session_id), res)
+            .await
+    }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        self.inner.flush().await.map_err(AuditStoreError::Store)
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.inner.db_system()
+    }
+}
+
+#[async_trait]
+impl<Store, Sink> ExpiredDeletion for AuditStore<Store, Sink>
+where
+    Store: ExpiredDeletion,
+    Sink: AuditSink,
+{
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        let res = self.inner.delete_expired().await;
+        self.audit(AuditOperation::DeleteExpired, None, res).await
+    }
+}
+
+#[async_trait]
+impl<Store, Sink> ListSessions for AuditStore<Store, Sink>
+where
+    Store: ListSessions,
+    Sink: AuditSink,
+{
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        let res = self.inner.list_sessions().await;
+        self.audit(AuditOperation::List, None, res).await
+    }
+}
+
+// The provided methods go through the store's `load` and `delete`, so each
+// session they access is recorded.
+#[async_trait]
+impl<Store, Sink> UserSessions for AuditStore<Store, Sink>
+where
+    Store: ListSessions,
+    Sink: AuditSink,
+{
+}
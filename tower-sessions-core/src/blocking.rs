@@ -0,0 +1,231 @@
+//! Adapting synchronous session stores.
+//!
+//! Some backends only offer blocking APIs, e.g. synchronous Diesel
+//! connections, embedded key-value stores, or bindings to C libraries.
+//! Calling them from an async store would block the runtime's worker threads
+//! and stall every other request they serve. Instead, such backends implement
+//! [`BlockingSessionStore`], and [`BlockingStoreAdapter`] turns them into a
+//! [`SessionStore`], running each operation on Tokio's blocking thread pool
+//! with `spawn_blocking`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::{collections::HashMap, convert::Infallible};
+//!
+//! use parking_lot::Mutex;
+//! use tower_sessions::{
+//!     blocking::{BlockingSessionStore, BlockingStoreAdapter},
+//!     session::Id,
+//!     Session, SessionManagerLayer,
+//! };
+//!
+//! #[derive(Debug, Default)]
+//! pub struct TestingStore(Mutex<HashMap<Id, Session>>);
+//!
+//! impl BlockingSessionStore for TestingStore {
+//!     type Error = Infallible;
+//!
+//!     fn save(&self, session: &Session) -> Result<(), Self::Error> {
+//!         self.0.lock().insert(*session.id(), session.clone());
+//!         Ok(())
+//!     }
+//!
+//!     fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+//!         Ok(self.0.lock().get(session_id).cloned())
+//!     }
+//!
+//!     fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+//!         self.0.lock().remove(session_id);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let session_store = BlockingStoreAdapter::new(TestingStore::default());
+//! let session_layer = SessionManagerLayer::new(session_store);
+//! ```
+use std::{fmt::Debug, sync::Arc};
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+
+use crate::{
+    session::{Id, Session},
+    session_store::ExpiredDeletion,
+    SessionStore,
+};
+
+/// A synchronous store which houses the session data, made a
+/// [`SessionStore`] by [`BlockingStoreAdapter`].
+///
+/// Methods mirror those of [`SessionStore`], and are called from Tokio's
+/// blocking thread pool, so they may block.
+pub trait BlockingSessionStore: Debug + Send + Sync + 'static {
+    /// An error that occurs when interacting with the store.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// A method for saving a session in a store.
+    fn save(&self, session: &Session) -> Result<(), Self::Error>;
+
+    /// A method for saving a session in a store for the first time.
+    ///
+    /// By default, this defers to `save`, see [`SessionStore::create`].
+    fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.save(session)
+    }
+
+    /// A method for loading a session from a store.
+    fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error>;
+
+    /// A method for deleting a session from a store.
+    fn delete(&self, session_id: &Id) -> Result<(), Self::Error>;
+
+    /// A method for flushing buffered writes to a store.
+    ///
+    /// By default, this does nothing, see [`SessionStore::flush`].
+    fn flush(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The database system backing the store, e.g. `sqlite` or `lmdb`.
+    fn db_system(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// A synchronous store providing a method for deleting expired sessions,
+/// making its adapter implement [`ExpiredDeletion`].
+pub trait BlockingExpiredDeletion: BlockingSessionStore {
+    /// A method for deleting expired sessions from the store.
+    fn delete_expired(&self) -> Result<(), Self::Error>;
+}
+
+/// An error running an operation of a [`BlockingSessionStore`].
+#[derive(thiserror::Error, Debug)]
+pub enum BlockingStoreError<StoreError: std::error::Error> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(StoreError),
+
+    /// The operation panicked, or was cancelled as the runtime shut down.
+    #[error("Blocking store operation didn't complete: {0}")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+/// A [`SessionStore`] running the operations of a [`BlockingSessionStore`]
+/// on Tokio's blocking thread pool.
+#[derive(Debug)]
+pub struct BlockingStoreAdapter<S> {
+    store: Arc<S>,
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl<S> Clone for BlockingStoreAdapter<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            permits: self.permits.clone(),
+        }
+    }
+}
+
+impl<S: BlockingSessionStore> BlockingStoreAdapter<S> {
+    /// Create a new adapter for the synchronous store.
+    ///
+    /// # Examples
+    ///
+    /// See the [module documentation](self).
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            permits: None,
+        }
+    }
+
+    /// Configures the maximum number of operations running on the blocking
+    /// thread pool at once, e.g. to match the size of the store's connection
+    /// pool, queueing the rest. By default, operations aren't limited, beyond
+    /// the size of the pool.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::{convert::Infallible, sync::Arc};
+    /// # use tower_sessions::{blocking::{BlockingSessionStore, BlockingStoreAdapter}, session::Id, Session};
+    /// # #[derive(Debug)]
+    /// # struct DieselStore;
+    /// # impl BlockingSessionStore for DieselStore {
+    /// #     type Error = Infallible;
+    /// #     fn save(&self, _: &Session) -> Result<(), Self::Error> { Ok(()) }
+    /// #     fn load(&self, _: &Id) -> Result<Option<Session>, Self::Error> { Ok(None) }
+    /// #     fn delete(&self, _: &Id) -> Result<(), Self::Error> { Ok(()) }
+    /// # }
+    /// let session_store = BlockingStoreAdapter::new(DieselStore).with_max_concurrency(8);
+    /// ```
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.permits = Some(Arc::new(Semaphore::new(max_concurrency)));
+        self
+    }
+
+    /// Returns the synchronous store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    async fn run<T, F>(&self, operation: F) -> Result<T, BlockingStoreError<S::Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&S) -> Result<T, S::Error> + Send + 'static,
+    {
+        // The semaphore is never closed, so acquiring a permit can't fail.
+        let _permit = match &self.permits {
+            Some(permits) => permits.acquire().await.ok(),
+            None => None,
+        };
+
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || operation(&store))
+            .await?
+            .map_err(BlockingStoreError::Store)
+    }
+}
+
+#[async_trait]
+impl<S: BlockingSessionStore> SessionStore for BlockingStoreAdapter<S> {
+    type Error = BlockingStoreError<S::Error>;
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let session = session.clone();
+        self.run(move |store| store.create(&session)).await
+    }
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let session = session.clone();
+        self.run(move |store| store.save(&session)).await
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let session_id = *session_id;
+        self.run(move |store| store.load(&session_id)).await
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let session_id = *session_id;
+        self.run(move |store| store.delete(&session_id)).await
+    }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        self.run(|store| store.flush()).await
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.store.db_system()
+    }
+}
+
+#[async_trait]
+impl<S: BlockingExpiredDeletion> ExpiredDeletion for BlockingStoreAdapter<S> {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        self.run(|store| store.delete_expired()).await
+    }
+}
@@ -0,0 +1,120 @@
+//! Sources of the current time used to compute session expiry.
+//!
+//! Expiry computations in the middleware and in session stores go through a
+//! [`Clock`] rather than calling [`OffsetDateTime::now_utc`] directly. This
+//! allows tests to fast-forward time with a [`MockClock`] and verify expiry
+//! behavior without sleeping.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use time::Duration;
+//! use tower_sessions::{clock::MockClock, Expiry, MemoryStore, SessionManagerLayer};
+//!
+//! let clock = MockClock::default();
+//! let session_store = MemoryStore::default().with_clock(clock.clone());
+//! let session_service = SessionManagerLayer::new(session_store)
+//!     .with_expiry(Expiry::OnInactivity(Duration::minutes(10)))
+//!     .with_clock(clock.clone());
+//!
+//! // Later, in a test: sessions are now considered idle.
+//! clock.advance(Duration::minutes(11));
+//! ```
+use std::{fmt::Debug, sync::Arc};
+
+use parking_lot::Mutex;
+use time::{Duration, OffsetDateTime};
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync + 'static {
+    /// Returns the current date and time.
+    fn now(&self) -> OffsetDateTime;
+}
+
+/// A clock backed by the system time, i.e. [`OffsetDateTime::now_utc`].
+///
+/// This is the default clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock which only moves when told to.
+///
+/// Clones share the same time, so a clone may be handed to the layer and the
+/// store while the original is advanced by the test.
+///
+/// # Examples
+///
+/// ```rust
+/// use time::Duration;
+/// use tower_sessions::clock::{Clock, MockClock};
+///
+/// let clock = MockClock::default();
+/// let start = clock.now();
+///
+/// clock.advance(Duration::hours(1));
+/// assert_eq!(clock.now() - start, Duration::hours(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Mutex<OffsetDateTime>>);
+
+impl MockClock {
+    /// Create a new mock clock starting at the provided date and time.
+    pub fn new(now: OffsetDateTime) -> Self {
+        Self(Arc::new(Mutex::new(now)))
+    }
+
+    /// Moves the clock forward by the provided duration.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.0.lock();
+        *now = now.saturating_add(duration);
+    }
+
+    /// Sets the clock to the provided date and time.
+    pub fn set(&self, now: OffsetDateTime) {
+        *self.0.lock() = now;
+    }
+}
+
+impl Default for MockClock {
+    /// Creates a mock clock starting at the current system time.
+    fn default() -> Self {
+        Self::new(OffsetDateTime::now_utc())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> OffsetDateTime {
+        *self.0.lock()
+    }
+}
+
+/// A cheaply cloneable, type-erased [`Clock`], defaulting to [`SystemClock`].
+///
+/// Session stores may hold one of these to support a configurable clock.
+#[derive(Debug, Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    /// Create a new shared clock from the provided clock.
+    pub fn new(clock: impl Clock) -> Self {
+        Self(Arc::new(clock))
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl Clock for SharedClock {
+    fn now(&self) -> OffsetDateTime {
+        self.0.now()
+    }
+}
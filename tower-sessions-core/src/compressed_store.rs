@@ -0,0 +1,357 @@
+//! Session records compressed before reaching another store.
+//!
+//! [`CompressedStore`] wraps a session store, compressing sessions with
+//! Zstandard or Brotli before they reach it and decompressing them as they're
+//! loaded, which saves space and bandwidth for sessions holding much data,
+//! whichever backend keeps them: Redis, SQL databases, and object storage
+//! alike.
+//!
+//! Sessions smaller than a threshold, where compression wouldn't pay off, are
+//! passed to the store as they are. Compressed sessions are kept in records
+//! starting with a header naming their compression, so the compression may be
+//! changed, or the threshold moved, without making saved sessions unreadable,
+//! and sessions saved before the store was wrapped load as they are.
+//!
+//! The session ID, principal, expiry date and version of records remain
+//! readable by the store, which needs them to index and expire sessions.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use tower_sessions::{
+//!     compressed_store::{CompressedStore, Compression},
+//!     MemoryStore, SessionManagerLayer,
+//! };
+//!
+//! let session_store = CompressedStore::new(MemoryStore::default())
+//!     .with_compression(Compression::Brotli(5))
+//!     .with_threshold(512);
+//! let session_layer = SessionManagerLayer::new(session_store);
+//! ```
+use std::{collections::HashMap, fmt, io};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    session::Id,
+    session_store::{ExpiredDeletion, ListSessions, UserSessions},
+    Expiry, Session, SessionStore,
+};
+
+/// The session data key under which records keep the compressed session.
+const COMPRESSED_KEY: &str = "__tower_sessions_compressed";
+
+/// The version of the record format, the first byte of the header.
+const FORMAT_VERSION: u8 = 1;
+
+/// The default size, in bytes, from which sessions are compressed.
+const DEFAULT_THRESHOLD: usize = 1024;
+
+/// The base two logarithm of the Brotli window size.
+const BROTLI_WINDOW: i32 = 22;
+
+/// The compression of sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard, at a level from 1, the fastest, to 22, the smallest. Zero
+    /// selects the default level.
+    Zstd(i32),
+
+    /// Brotli, at a quality from 0, the fastest, to 11, the smallest.
+    Brotli(u32),
+}
+
+impl Default for Compression {
+    /// Zstandard at level 3, trading little speed for much space.
+    fn default() -> Self {
+        Self::Zstd(3)
+    }
+}
+
+impl Compression {
+    /// The identifier of the compression, the second byte of the header.
+    fn id(self) -> u8 {
+        match self {
+            Self::Zstd(_) => 1,
+            Self::Brotli(_) => 2,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressed = vec![FORMAT_VERSION, self.id()];
+        match self {
+            Self::Zstd(level) => {
+                compressed.extend(zstd::bulk::compress(data, level)?);
+            }
+            Self::Brotli(quality) => {
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: quality.min(11) as i32,
+                    lgwin: BROTLI_WINDOW,
+                    ..Default::default()
+                };
+                brotli::BrotliCompress(&mut &data[..], &mut compressed, &params)?;
+            }
+        }
+
+        Ok(compressed)
+    }
+}
+
+/// Decompresses a record of any compression.
+fn decompress<Store: SessionStore>(record: &[u8]) -> Result<Vec<u8>, CompressedStoreError<Store>> {
+    let [version, id, data @ ..] = record else {
+        return Err(CompressedStoreError::Format("truncated header"));
+    };
+    if *version != FORMAT_VERSION {
+        return Err(CompressedStoreError::Format("unsupported format version"));
+    }
+
+    let mut decompressed = Vec::new();
+    match *id {
+        1 => zstd::stream::copy_decode(data, &mut decompressed)?,
+        2 => {
+            brotli::BrotliDecompress(&mut &data[..], &mut decompressed)?;
+        }
+        _ => return Err(CompressedStoreError::Format("unknown compression")),
+    }
+
+    Ok(decompressed)
+}
+
+/// An error of a [`CompressedStore`].
+#[derive(thiserror::Error)]
+pub enum CompressedStoreError<Store: SessionStore> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(Store::Error),
+
+    /// A session couldn't be serialized or deserialized.
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+
+    /// A session couldn't be compressed or decompressed.
+    #[error(transparent)]
+    Compression(#[from] io::Error),
+
+    /// A record isn't in a format the store can read.
+    #[error("Invalid compressed record: {0}")]
+    Format(&'static str),
+}
+
+impl<Store: SessionStore> fmt::Debug for CompressedStoreError<Store> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(err) => write!(f, "{:?}", err)?,
+            Self::Serde(err) => write!(f, "{:?}", err)?,
+            Self::Compression(err) => write!(f, "{:?}", err)?,
+            Self::Format(reason) => write!(f, "Format({:?})", reason)?,
+        };
+
+        Ok(())
+    }
+}
+
+/// A session store compressing the sessions it saves in another store.
+#[derive(Debug, Clone)]
+pub struct CompressedStore<Store: SessionStore> {
+    inner: Store,
+    compression: Compression,
+    threshold: usize,
+}
+
+impl<Store: SessionStore> CompressedStore<Store> {
+    /// Create a new `CompressedStore` wrapping the provided store, which
+    /// compresses sessions of at least 1 KiB with Zstandard at level 3.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{compressed_store::CompressedStore, MemoryStore};
+    ///
+    /// let session_store = CompressedStore::new(MemoryStore::default());
+    /// ```
+    pub fn new(inner: Store) -> Self {
+        Self {
+            inner,
+            compression: Compression::default(),
+            threshold: DEFAULT_THRESHOLD,
+        }
+    }
+
+    /// Configures the compression of sessions.
+    ///
+    /// Sessions saved with another compression remain readable.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{
+    ///     compressed_store::{CompressedStore, Compression},
+    ///     MemoryStore,
+    /// };
+    ///
+    /// let session_store =
+    ///     CompressedStore::new(MemoryStore::default()).with_compression(Compression::Zstd(9));
+    /// ```
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Configures the size of serialized sessions, in bytes, from which
+    /// they're compressed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{compressed_store::CompressedStore, MemoryStore};
+    ///
+    /// // Compress every session.
+    /// let session_store = CompressedStore::new(MemoryStore::default()).with_threshold(0);
+    /// ```
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns the record to save for the session: the session itself if it's
+    /// below the threshold, a compressed one otherwise.
+    fn compress_session(&self, session: &Session) -> Result<Session, CompressedStoreError<Store>> {
+        let serialized = serde_json::to_vec(session)?;
+        if serialized.len() < self.threshold {
+            return Ok(session.clone());
+        }
+
+        let compressed = self.compression.compress(&serialized)?;
+        let record = Session::from_parts(
+            *session.id(),
+            HashMap::from([(
+                COMPRESSED_KEY.to_owned(),
+                STANDARD.encode(compressed).into(),
+            )]),
+            session.principal(),
+            Some(Expiry::AtDateTime(session.expiry_date())),
+        );
+        record.set_version(session.version());
+        Ok(record)
+    }
+
+    fn decompress_session(&self, record: Session) -> Result<Session, CompressedStoreError<Store>> {
+        let Some(compressed) = record.get_value(COMPRESSED_KEY) else {
+            return Ok(record);
+        };
+        let compressed = compressed
+            .as_str()
+            .and_then(|compressed| STANDARD.decode(compressed).ok())
+            .ok_or(CompressedStoreError::Format("not base64"))?;
+
+        let session: Session = serde_json::from_slice(&decompress::<Store>(&compressed)?)?;
+        session.set_version(record.version());
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl<Store: SessionStore> SessionStore for CompressedStore<Store> {
+    type Error = CompressedStoreError<Store>;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let record = self.compress_session(session)?;
+        self.inner
+            .save(&record)
+            .await
+            .map_err(CompressedStoreError::Store)?;
+        session.set_version(record.version());
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let record = self.compress_session(session)?;
+        self.inner
+            .create(&record)
+            .await
+            .map_err(CompressedStoreError::Store)?;
+        session.set_version(record.version());
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        self.inner
+            .load(session_id)
+            .await
+            .map_err(CompressedStoreError::Store)?
+            .map(|record| self.decompress_session(record))
+            .transpose()
+    }
+
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        self.inner
+            .load_including_expired(session_id)
+            .await
+            .map_err(CompressedStoreError::Store)?
+            .map(|record| self.decompress_session(record))
+            .transpose()
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.inner
+            .delete(session_id)
+            .await
+            .map_err(CompressedStoreError::Store)
+    }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        self.inner
+            .flush()
+            .await
+            .map_err(CompressedStoreError::Store)
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.inner.db_system()
+    }
+}
+
+#[async_trait]
+impl<Store: ExpiredDeletion> ExpiredDeletion for CompressedStore<Store> {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        self.inner
+            .delete_expired()
+            .await
+            .map_err(CompressedStoreError::Store)
+    }
+}
+
+#[async_trait]
+impl<Store: ListSessions> ListSessions for CompressedStore<Store> {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        self.inner
+            .list_sessions()
+            .await
+            .map_err(CompressedStoreError::Store)
+    }
+}
+
+// Principals are stored uncompressed, so the store's own lookups apply.
+#[async_trait]
+impl<Store: UserSessions> UserSessions for CompressedStore<Store> {
+    async fn user_sessions(&self, principal: &str) -> Result<Vec<Id>, Self::Error> {
+        self.inner
+            .user_sessions(principal)
+            .await
+            .map_err(CompressedStoreError::Store)
+    }
+
+    async fn delete_user_sessions(&self, principal: &str) -> Result<(), Self::Error> {
+        self.inner
+            .delete_user_sessions(principal)
+            .await
+            .map_err(CompressedStoreError::Store)
+    }
+}
@@ -0,0 +1,399 @@
+//! Double-submit CSRF protection built on sessions.
+//!
+//! [`CsrfLayer`] guards against cross-site request forgery by requiring
+//! requests with unsafe methods, i.e. other than `GET`, `HEAD`, `OPTIONS`, and
+//! `TRACE`, to carry a token only the application's own pages can know. The
+//! token is kept in the session, so it shares the session's storage and
+//! lifecycle, and is mirrored in a cookie which scripts may read, the
+//! double-submit pattern: a page submits the token by copying the cookie into
+//! a request header, which a forged request from another site can't do.
+//!
+//! Tokens are created on demand, once a handler asks for one through
+//! [`CsrfToken`], e.g. to render a form or a page making requests. Creating a
+//! token modifies the session, which is then saved and given a cookie like any
+//! other modified session.
+//!
+//! The layer must be applied inside [`SessionManagerLayer`], so that requests
+//! reach it with their session.
+//!
+//! [`SessionManagerLayer`]: crate::SessionManagerLayer
+//!
+//! # Examples
+//!
+//! ```rust
+//! use axum::{
+//!     error_handling::HandleErrorLayer, http::StatusCode, routing::get, BoxError, Router,
+//! };
+//! use tower::ServiceBuilder;
+//! use tower_sessions::{
+//!     csrf::{CsrfLayer, CsrfToken},
+//!     MemoryStore, SessionManagerLayer,
+//! };
+//!
+//! async fn form(csrf_token: CsrfToken) -> String {
+//!     format!(
+//!         r#"<input type="hidden" name="csrf_token" value="{}">"#,
+//!         csrf_token.token()
+//!     )
+//! }
+//!
+//! async fn submit() -> &'static str {
+//!     "Submitted!"
+//! }
+//!
+//! let session_store = MemoryStore::default();
+//! let app = Router::<()>::new()
+//!     .route("/", get(form).post(submit))
+//!     .layer(
+//!         ServiceBuilder::new()
+//!             .layer(HandleErrorLayer::new(|_: BoxError| async {
+//!                 StatusCode::BAD_REQUEST
+//!             }))
+//!             .layer(SessionManagerLayer::new(session_store))
+//!             .layer(CsrfLayer::default()),
+//!     );
+//! ```
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::{header::HeaderName, Method, Request, Response, StatusCode};
+use tower_cookies::{cookie::SameSite, Cookie, Cookies};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    session::{Deletion, Id},
+    Session,
+};
+
+/// The session data key under which the token is kept.
+const TOKEN_KEY: &str = "__tower_sessions_csrf_token";
+
+/// The default name of the CSRF cookie.
+const DEFAULT_COOKIE_NAME: &str = "csrf-token";
+
+/// The default name of the header carrying the token.
+const DEFAULT_HEADER_NAME: HeaderName = HeaderName::from_static("x-csrf-token");
+
+/// The CSRF token of a session, provided as a request extension by
+/// [`CsrfMiddleware`].
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::csrf::CsrfToken;
+///
+/// async fn handler(csrf_token: CsrfToken) -> String {
+///     csrf_token.token()
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CsrfToken {
+    session: Session,
+}
+
+impl CsrfToken {
+    /// Returns the token of the session, creating it if the session has none
+    /// yet.
+    pub fn token(&self) -> String {
+        if let Some(token) = session_token(&self.session) {
+            return token;
+        }
+
+        let token = Id::default().to_string();
+        self.session.insert_value(TOKEN_KEY, token.clone().into());
+        token
+    }
+
+    /// Verifies a token submitted otherwise than in the header, e.g. as a form
+    /// field, against the token of the session.
+    pub fn verify(&self, token: &str) -> bool {
+        session_token(&self.session)
+            .is_some_and(|expected| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+    }
+}
+
+// Tokens are secrets, so they're kept out of logs.
+impl fmt::Debug for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsrfToken").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "axum-core")]
+#[async_trait::async_trait]
+impl<S> axum_core::extract::FromRequestParts<S> for CsrfToken
+where
+    S: Sync + Send,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<CsrfToken>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Can't extract CSRF token. Is `CsrfLayer` enabled?",
+        ))
+    }
+}
+
+fn session_token(session: &Session) -> Option<String> {
+    session
+        .get_value(TOKEN_KEY)
+        .and_then(|token| token.as_str().map(ToOwned::to_owned))
+}
+
+/// Compares the bytes in time independent of where they differ, so that
+/// response times don't reveal how much of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[derive(Debug, Clone)]
+struct CsrfConfig {
+    cookie_name: String,
+    header_name: HeaderName,
+    secure: bool,
+    path: String,
+    domain: Option<String>,
+}
+
+impl CsrfConfig {
+    /// Builds the CSRF cookie, which isn't `HttpOnly` as scripts are meant to
+    /// read it.
+    fn build_cookie<'c>(&self, token: String) -> Cookie<'c> {
+        let mut cookie_builder = Cookie::build((self.cookie_name.clone(), token))
+            .http_only(false)
+            .same_site(SameSite::Strict)
+            .secure(self.secure)
+            .path(self.path.clone());
+
+        if let Some(domain) = &self.domain {
+            cookie_builder = cookie_builder.domain(domain.clone());
+        }
+
+        cookie_builder.build()
+    }
+
+    fn reject<B: Default>(&self) -> Response<B> {
+        tracing::debug!("rejecting request with missing or invalid CSRF token");
+
+        let mut res = Response::new(B::default());
+        *res.status_mut() = StatusCode::FORBIDDEN;
+        res
+    }
+}
+
+impl Default for CsrfConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: DEFAULT_COOKIE_NAME.to_owned(),
+            header_name: DEFAULT_HEADER_NAME,
+            secure: false,
+            path: String::from("/"),
+            domain: None,
+        }
+    }
+}
+
+/// A middleware that verifies the CSRF token of requests with unsafe methods
+/// and provides [`CsrfToken`] as a request extension.
+///
+/// Requests failing verification are answered with `403 Forbidden` without
+/// calling the inner service.
+#[derive(Debug, Clone)]
+pub struct CsrfMiddleware<S> {
+    inner: S,
+    config: CsrfConfig,
+}
+
+impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for CsrfMiddleware<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let config = self.config.clone();
+
+        // See: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let is_safe = matches!(
+                *req.method(),
+                Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+            );
+
+            // Without a session, e.g. when it failed to load, there's no token to verify
+            // against.
+            let Some(session) = req.extensions().get::<Session>().cloned() else {
+                tracing::warn!("no session to verify the CSRF token against");
+                return if is_safe {
+                    inner.call(req).await
+                } else {
+                    Ok(config.reject())
+                };
+            };
+
+            let submitted_token = req
+                .headers()
+                .get(&config.header_name)
+                .and_then(|value| value.to_str().ok());
+            let csrf_token = CsrfToken {
+                session: session.clone(),
+            };
+            if !is_safe && !submitted_token.is_some_and(|token| csrf_token.verify(token)) {
+                return Ok(config.reject());
+            }
+
+            let cookies = req.extensions().get::<Cookies>().cloned();
+            req.extensions_mut().insert(csrf_token);
+
+            let res = inner.call(req).await?;
+
+            // The cookie follows the token of the session, which may have been created or
+            // deleted along with the session.
+            if let Some(cookies) = cookies {
+                let cookie_token = cookies
+                    .get(&config.cookie_name)
+                    .map(|cookie| cookie.value().to_owned());
+                match (session.deleted(), session_token(&session)) {
+                    (Some(Deletion::Deleted), _) | (_, None) => {
+                        if cookie_token.is_some() {
+                            cookies.remove(config.build_cookie(String::new()));
+                        }
+                    }
+                    (_, Some(token)) => {
+                        if cookie_token.as_ref() != Some(&token) {
+                            cookies.add(config.build_cookie(token));
+                        }
+                    }
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+/// A layer for [`CsrfMiddleware`].
+#[derive(Debug, Clone, Default)]
+pub struct CsrfLayer {
+    config: CsrfConfig,
+}
+
+impl CsrfLayer {
+    /// Create a new [`CsrfLayer`] with the default cookie and header names,
+    /// `csrf-token` and `x-csrf-token`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::csrf::CsrfLayer;
+    ///
+    /// let csrf_layer = CsrfLayer::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures the name of the cookie mirroring the token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::csrf::CsrfLayer;
+    ///
+    /// let csrf_layer = CsrfLayer::new().with_cookie_name("XSRF-TOKEN");
+    /// ```
+    pub fn with_cookie_name(mut self, cookie_name: &str) -> Self {
+        self.config.cookie_name = cookie_name.to_owned();
+        self
+    }
+
+    /// Configures the name of the header requests submit the token in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use http::HeaderName;
+    /// use tower_sessions::csrf::CsrfLayer;
+    ///
+    /// let csrf_layer = CsrfLayer::new().with_header_name(HeaderName::from_static("x-xsrf-token"));
+    /// ```
+    pub fn with_header_name(mut self, header_name: HeaderName) -> Self {
+        self.config.header_name = header_name;
+        self
+    }
+
+    /// Configures the `"Secure"` attribute of the cookie mirroring the token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::csrf::CsrfLayer;
+    ///
+    /// let csrf_layer = CsrfLayer::new().with_secure(true);
+    /// ```
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.config.secure = secure;
+        self
+    }
+
+    /// Configures the `"Path"` attribute of the cookie mirroring the token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::csrf::CsrfLayer;
+    ///
+    /// let csrf_layer = CsrfLayer::new().with_path("/app".to_string());
+    /// ```
+    pub fn with_path(mut self, path: String) -> Self {
+        self.config.path = path;
+        self
+    }
+
+    /// Configures the `"Domain"` attribute of the cookie mirroring the token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::csrf::CsrfLayer;
+    ///
+    /// let csrf_layer = CsrfLayer::new().with_domain("example.com".to_string());
+    /// ```
+    pub fn with_domain(mut self, domain: String) -> Self {
+        self.config.domain = Some(domain);
+        self
+    }
+}
+
+impl<S> Layer<S> for CsrfLayer {
+    type Service = CsrfMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CsrfMiddleware {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
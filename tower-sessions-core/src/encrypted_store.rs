@@ -0,0 +1,659 @@
+//! Session records encrypted with a data key per principal.
+//!
+//! [`EncryptedStore`] wraps a session store, encrypting sessions before they
+//! reach it and decrypting them as they're loaded. Sessions with a
+//! [principal](crate::Session::principal) are encrypted with a data key of
+//! their own principal, which is kept in a [`DataKeyStore`] wrapped, i.e.
+//! encrypted, with a master key fetched from a
+//! [`KeyProvider`](crate::key_provider::KeyProvider). Sessions without one are
+//! encrypted with a key derived from the master key.
+//!
+//! Deleting the data key of a principal with [`EncryptedStore::shred`] makes
+//! every one of their sessions unreadable at once, wherever copies of them
+//! may be, backups included, without finding and deleting each of them: such
+//! sessions load as absent. This is known as crypto-shredding, and meets a
+//! request for erasure as long as the data keys themselves aren't kept in
+//! backups beyond the erasure delay. Sessions saved for the principal after
+//! that are encrypted with a new data key.
+//!
+//! The session ID, principal, expiry date and version of records remain
+//! readable by the store, which needs them to index and expire sessions.
+//!
+//! The master key is refreshed periodically as the store is used, in a
+//! background task when running on a Tokio runtime. Data keys
+//! wrapped with the master key in use before a rotation are still unwrapped,
+//! and wrapped again with the new master key. When a data key unwraps with
+//! neither, e.g. because another instance wrapped it again with a master key
+//! this one hasn't fetched yet, the master key is fetched once more; failing
+//! that, loads and saves of the principal's sessions fail with
+//! [`EncryptedStoreError::Unwrap`]. Master keys therefore mustn't be rotated
+//! twice before every data key has been wrapped again, unless the data keys
+//! left behind are shredded.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use tower_sessions::{
+//!     encrypted_store::{EncryptedStore, MemoryDataKeyStore},
+//!     key_provider::EnvKeyProvider,
+//!     MemoryStore,
+//! };
+//!
+//! # tokio_test::block_on(async {
+//! let session_store = EncryptedStore::new(
+//!     MemoryStore::default(),
+//!     MemoryDataKeyStore::default(),
+//!     EnvKeyProvider::new("SESSION_MASTER_KEY"),
+//! )
+//! .await
+//! .unwrap();
+//!
+//! // Later, once the user has asked for their data to be erased.
+//! session_store.shred("alice").await.unwrap();
+//! # })
+//! ```
+use std::{collections::HashMap, convert::Infallible, fmt, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use time::Duration;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    key_provider::{KeyProvider, KeyProviderError, Rotation, DEFAULT_REFRESH_INTERVAL, KEY_LEN},
+    session::Id,
+    session_store::{ExpiredDeletion, ListSessions, UserSessions},
+    Expiry, Session, SessionStore,
+};
+
+/// The session data key under which records keep the encrypted session.
+const CIPHERTEXT_KEY: &str = "__tower_sessions_ciphertext";
+
+/// The length of AES-GCM nonces, which prefix ciphertexts.
+const NONCE_LEN: usize = 12;
+
+/// A store of the wrapped data keys of principals.
+///
+/// Data keys must be kept apart from the sessions they encrypt, and must not
+/// outlive an erasure in backups, for shredding to be effective.
+///
+/// # Implementing your own data key store
+///
+/// Implementations typically keep data keys in a table of the application's
+/// database, keyed by principal. Inserts must not overwrite a key saved
+/// concurrently, or sessions encrypted with the overwritten key would become
+/// unreadable.
+#[async_trait]
+pub trait DataKeyStore: Clone + Send + Sync + 'static {
+    /// An error that occurs when interacting with the store.
+    type Error: std::error::Error + Send + Sync;
+
+    /// Loads the wrapped data key of the principal.
+    async fn load(&self, principal: &str) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Saves the wrapped data key of the principal unless one is already
+    /// saved, returning the saved key.
+    async fn insert(&self, principal: &str, wrapped_key: Vec<u8>) -> Result<Vec<u8>, Self::Error>;
+
+    /// Saves the wrapped data key of the principal, replacing any saved key.
+    ///
+    /// This is used to wrap keys again after the master key has been rotated,
+    /// so the key replaced is the same data key.
+    async fn save(&self, principal: &str, wrapped_key: Vec<u8>) -> Result<(), Self::Error>;
+
+    /// Deletes the data key of the principal.
+    async fn delete(&self, principal: &str) -> Result<(), Self::Error>;
+}
+
+/// A data key store kept in memory.
+///
+/// Keys don't survive restarts, after which every session of a principal is
+/// unreadable, so this is meant for tests and single-process deployments
+/// with a memory session store.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDataKeyStore(Arc<Mutex<HashMap<String, Vec<u8>>>>);
+
+#[async_trait]
+impl DataKeyStore for MemoryDataKeyStore {
+    type Error = Infallible;
+
+    async fn load(&self, principal: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(self.0.lock().get(principal).cloned())
+    }
+
+    async fn insert(&self, principal: &str, wrapped_key: Vec<u8>) -> Result<Vec<u8>, Self::Error> {
+        Ok(self
+            .0
+            .lock()
+            .entry(principal.to_owned())
+            .or_insert(wrapped_key)
+            .clone())
+    }
+
+    async fn save(&self, principal: &str, wrapped_key: Vec<u8>) -> Result<(), Self::Error> {
+        self.0.lock().insert(principal.to_owned(), wrapped_key);
+        Ok(())
+    }
+
+    async fn delete(&self, principal: &str) -> Result<(), Self::Error> {
+        self.0.lock().remove(principal);
+        Ok(())
+    }
+}
+
+/// An error of an [`EncryptedStore`].
+#[derive(thiserror::Error)]
+pub enum EncryptedStoreError<Store: SessionStore, DataKeys: DataKeyStore> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(Store::Error),
+
+    /// A data key store-related error.
+    #[error(transparent)]
+    DataKeys(DataKeys::Error),
+
+    /// A session couldn't be serialized.
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+
+    /// A session or data key couldn't be encrypted.
+    #[error("Failed to encrypt")]
+    Encryption,
+
+    /// The data key of a principal is wrapped with neither the current nor the
+    /// previous master key.
+    #[error("Failed to unwrap data key")]
+    Unwrap,
+}
+
+impl<Store: SessionStore, DataKeys: DataKeyStore> fmt::Debug
+    for EncryptedStoreError<Store, DataKeys>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(err) => write!(f, "{:?}", err)?,
+            Self::DataKeys(err) => write!(f, "{:?}", err)?,
+            Self::Serde(err) => write!(f, "{:?}", err)?,
+            Self::Encryption => write!(f, "Encryption")?,
+            Self::Unwrap => write!(f, "Unwrap")?,
+        };
+
+        Ok(())
+    }
+}
+
+/// The keys derived from the master key.
+struct MasterKey {
+    /// The key wrapping data keys.
+    wrapping: [u8; 32],
+    /// The key encrypting sessions without a principal.
+    anonymous: [u8; 32],
+}
+
+// Keys are secrets, so they're kept out of logs.
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasterKey").finish_non_exhaustive()
+    }
+}
+
+impl MasterKey {
+    fn derive(material: &[u8]) -> Result<Self, KeyProviderError> {
+        if material.len() < KEY_LEN {
+            return Err(KeyProviderError::TooShort {
+                len: material.len(),
+            });
+        }
+
+        let derive_key = |purpose: &[u8]| -> [u8; 32] {
+            Sha256::new()
+                .chain_update(purpose)
+                .chain_update(material)
+                .finalize()
+                .into()
+        };
+
+        Ok(Self {
+            wrapping: derive_key(b"tower-sessions data key wrapping"),
+            anonymous: derive_key(b"tower-sessions anonymous sessions"),
+        })
+    }
+}
+
+fn encrypt(key: &[u8], plaintext: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .ok()?;
+
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    Some(data)
+}
+
+fn decrypt(key: &[u8], data: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        return None;
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .ok()
+}
+
+/// A session store encrypting sessions with a data key per principal.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct EncryptedStore<Store: SessionStore, DataKeys: DataKeyStore> {
+    store: Store,
+    data_keys: DataKeys,
+    master_key: Arc<Rotation<MasterKey>>,
+    refresh_interval: Duration,
+    clock: SharedClock,
+}
+
+// Derived, this would require the master key to be `Clone`, which it needn't be
+// as it's shared.
+impl<Store: SessionStore, DataKeys: DataKeyStore> Clone for EncryptedStore<Store, DataKeys> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            data_keys: self.data_keys.clone(),
+            master_key: self.master_key.clone(),
+            refresh_interval: self.refresh_interval,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<Store: SessionStore, DataKeys: DataKeyStore> EncryptedStore<Store, DataKeys> {
+    /// Create a new encrypted store, fetching the master key from the
+    /// provider.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::{
+    ///     encrypted_store::{EncryptedStore, MemoryDataKeyStore},
+    ///     key_provider::FileKeyProvider,
+    ///     MemoryStore,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = EncryptedStore::new(
+    ///     MemoryStore::default(),
+    ///     MemoryDataKeyStore::default(),
+    ///     FileKeyProvider::new("/run/secrets/session-master-key"),
+    /// )
+    /// .await
+    /// .unwrap();
+    /// # })
+    /// ```
+    pub async fn new(
+        store: Store,
+        data_keys: DataKeys,
+        master_key_provider: impl KeyProvider,
+    ) -> Result<Self, KeyProviderError> {
+        Ok(Self {
+            store,
+            data_keys,
+            master_key: Arc::new(
+                Rotation::new(master_key_provider, MasterKey::derive, "master").await?,
+            ),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            clock: SharedClock::default(),
+        })
+    }
+
+    /// Configures the interval at which the master key is fetched again, one
+    /// hour by default.
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    /// Configures the clock against which the master key is due to be
+    /// refreshed.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Deletes the data key of the principal, making all of their sessions
+    /// unreadable.
+    ///
+    /// The records of the sessions remain in the store until they expire, or
+    /// are deleted, e.g. with
+    /// [`delete_user_sessions`](UserSessions::delete_user_sessions).
+    pub async fn shred(&self, principal: &str) -> Result<(), EncryptedStoreError<Store, DataKeys>> {
+        tracing::info!("shredding data key of principal");
+        self.data_keys
+            .delete(principal)
+            .await
+            .map_err(EncryptedStoreError::DataKeys)
+    }
+
+    async fn refresh_master_key(&self) {
+        self.master_key
+            .refresh_if_due(self.clock.now(), self.refresh_interval)
+            .await;
+    }
+
+    /// Saves a new data key for the principal unless one is already saved,
+    /// returning the saved key, still wrapped.
+    async fn insert_data_key(
+        &self,
+        principal: &str,
+    ) -> Result<Vec<u8>, EncryptedStoreError<Store, DataKeys>> {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let wrapped_key = encrypt(
+            &self.master_key.keys().current.wrapping,
+            &data_key,
+            principal.as_bytes(),
+        )
+        .ok_or(EncryptedStoreError::Encryption)?;
+        self.data_keys
+            .insert(principal, wrapped_key)
+            .await
+            .map_err(EncryptedStoreError::DataKeys)
+    }
+
+    /// Returns the data key of the principal, creating it if `create` is set
+    /// and the principal has none.
+    async fn data_key(
+        &self,
+        principal: &str,
+        create: bool,
+    ) -> Result<Option<Vec<u8>>, EncryptedStoreError<Store, DataKeys>> {
+        let wrapped_key = match self.data_keys.load(principal).await {
+            Ok(Some(wrapped_key)) => wrapped_key,
+            Ok(None) if create => self.insert_data_key(principal).await?,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(EncryptedStoreError::DataKeys(err)),
+        };
+
+        if let Some(data_key) = self.unwrap_data_key(principal, &wrapped_key).await? {
+            return Ok(Some(data_key));
+        }
+
+        // Another instance may have wrapped the key again with a master key this one
+        // hasn't fetched yet. The key is never replaced, lest the principal's
+        // sessions are lost to a transient skew between instances.
+        if let Err(err) = self.master_key.refresh().await {
+            tracing::error!(err = %err, "failed to refresh master key");
+        }
+        match self.unwrap_data_key(principal, &wrapped_key).await? {
+            Some(data_key) => Ok(Some(data_key)),
+            None => {
+                tracing::error!("failed to unwrap data key of principal");
+                Err(EncryptedStoreError::Unwrap)
+            }
+        }
+    }
+
+    /// Unwraps a data key of the principal, or returns `None` if neither the
+    /// current nor the previous master key wrapped it.
+    async fn unwrap_data_key(
+        &self,
+        principal: &str,
+        wrapped_key: &[u8],
+    ) -> Result<Option<Vec<u8>>, EncryptedStoreError<Store, DataKeys>> {
+        let (data_key, rewrapped_key) = {
+            let master_key = self.master_key.keys();
+            match decrypt(
+                &master_key.current.wrapping,
+                wrapped_key,
+                principal.as_bytes(),
+            ) {
+                Some(data_key) => (data_key, None),
+                None => {
+                    let Some(data_key) = master_key.previous.as_ref().and_then(|previous| {
+                        decrypt(&previous.wrapping, wrapped_key, principal.as_bytes())
+                    }) else {
+                        return Ok(None);
+                    };
+                    let rewrapped_key = encrypt(
+                        &master_key.current.wrapping,
+                        &data_key,
+                        principal.as_bytes(),
+                    )
+                    .ok_or(EncryptedStoreError::Encryption)?;
+                    (data_key, Some(rewrapped_key))
+                }
+            }
+        };
+
+        // Keys wrapped with the previous master key are wrapped again before it's
+        // rotated out.
+        if let Some(rewrapped_key) = rewrapped_key {
+            self.data_keys
+                .save(principal, rewrapped_key)
+                .await
+                .map_err(EncryptedStoreError::DataKeys)?;
+        }
+
+        Ok(Some(data_key))
+    }
+
+    async fn encrypt_session(
+        &self,
+        session: &Session,
+    ) -> Result<Session, EncryptedStoreError<Store, DataKeys>> {
+        self.refresh_master_key().await;
+
+        let principal = session.principal();
+        let key = match &principal {
+            Some(principal) => self
+                .data_key(principal, true)
+                .await?
+                .ok_or(EncryptedStoreError::Encryption)?,
+            None => self.master_key.keys().current.anonymous.to_vec(),
+        };
+
+        let plaintext = serde_json::to_vec(session)?;
+        let ciphertext = encrypt(&key, &plaintext, session.id().to_string().as_bytes())
+            .ok_or(EncryptedStoreError::Encryption)?;
+
+        let record = Session::from_parts(
+            *session.id(),
+            HashMap::from([(
+                CIPHERTEXT_KEY.to_owned(),
+                STANDARD.encode(ciphertext).into(),
+            )]),
+            principal,
+            Some(Expiry::AtDateTime(session.expiry_date())),
+        );
+        record.set_version(session.version());
+        Ok(record)
+    }
+
+    /// Decrypts the session of a record, or returns `None` if it's
+    /// unreadable, e.g. because its data key has been shredded.
+    async fn decrypt_session(
+        &self,
+        record: Session,
+    ) -> Result<Option<Session>, EncryptedStoreError<Store, DataKeys>> {
+        self.refresh_master_key().await;
+
+        let Some(ciphertext) = record
+            .get_value(CIPHERTEXT_KEY)
+            .and_then(|value| value.as_str().and_then(|value| STANDARD.decode(value).ok()))
+        else {
+            tracing::warn!("session record isn't encrypted");
+            return Ok(None);
+        };
+
+        let aad = record.id().to_string();
+        let plaintext = match record.principal() {
+            Some(principal) => {
+                let Some(data_key) = self.data_key(&principal, false).await? else {
+                    tracing::debug!("session of principal without data key");
+                    return Ok(None);
+                };
+                decrypt(&data_key, &ciphertext, aad.as_bytes())
+            }
+            None => {
+                let master_key = self.master_key.keys();
+                decrypt(&master_key.current.anonymous, &ciphertext, aad.as_bytes()).or_else(|| {
+                    master_key.previous.as_ref().and_then(|previous| {
+                        decrypt(&previous.anonymous, &ciphertext, aad.as_bytes())
+                    })
+                })
+            }
+        };
+
+        let Some(plaintext) = plaintext else {
+            tracing::warn!("failed to decrypt session");
+            return Ok(None);
+        };
+
+        let session: Session = serde_json::from_slice(&plaintext)?;
+        session.set_version(record.version());
+        Ok(Some(session))
+    }
+}
+
+#[async_trait]
+impl<Store, DataKeys> SessionStore for EncryptedStore<Store, DataKeys>
+where
+    Store: SessionStore,
+    DataKeys: DataKeyStore,
+{
+    type Error = EncryptedStoreError<Store, DataKeys>;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let record = self.encrypt_session(session).await?;
+        self.store
+            .save(&record)
+            .await
+            .map_err(EncryptedStoreError::Store)?;
+        session.set_version(record.version());
+
+        Ok(())
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        let record = self.encrypt_session(session).await?;
+        self.store
+            .create(&record)
+            .await
+            .map_err(EncryptedStoreError::Store)?;
+        session.set_version(record.version());
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        match self
+            .store
+            .load(session_id)
+            .await
+            .map_err(EncryptedStoreError::Store)?
+        {
+            Some(record) => self.decrypt_session(record).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        match self
+            .store
+            .load_including_expired(session_id)
+            .await
+            .map_err(EncryptedStoreError::Store)?
+        {
+            Some(record) => self.decrypt_session(record).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.store
+            .delete(session_id)
+            .await
+            .map_err(EncryptedStoreError::Store)
+    }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        self.store.flush().await.map_err(EncryptedStoreError::Store)
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.store.db_system()
+    }
+}
+
+#[async_trait]
+impl<Store, DataKeys> ExpiredDeletion for EncryptedStore<Store, DataKeys>
+where
+    Store: ExpiredDeletion,
+    DataKeys: DataKeyStore,
+{
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        self.store
+            .delete_expired()
+            .await
+            .map_err(EncryptedStoreError::Store)
+    }
+}
+
+#[async_trait]
+impl<Store, DataKeys> ListSessions for EncryptedStore<Store, DataKeys>
+where
+    Store: ListSessions,
+    DataKeys: DataKeyStore,
+{
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        self.store
+            .list_sessions()
+            .await
+            .map_err(EncryptedStoreError::Store)
+    }
+}
+
+// Principals are stored in the clear, so the store's own lookups apply.
+#[async_trait]
+impl<Store, DataKeys> UserSessions for EncryptedStore<Store, DataKeys>
+where
+    Store: UserSessions,
+    DataKeys: DataKeyStore,
+{
+    async fn user_sessions(&self, principal: &str) -> Result<Vec<Id>, Self::Error> {
+        self.store
+            .user_sessions(principal)
+            .await
+            .map_err(EncryptedStoreError::Store)
+    }
+
+    async fn delete_user_sessions(&self, principal: &str) -> Result<(), Self::Error> {
+        self.store
+            .delete_user_sessions(principal)
+            .await
+            .map_err(EncryptedStoreError::Store)
+    }
+}
@@ -0,0 +1,135 @@
+//! Session lifecycle events emitted by the session middleware.
+//!
+//! [`SessionManager`](crate::SessionManager) emits a [`SessionEvent`] through
+//! [`SessionEvents`], when configured with
+//! [`with_events`](crate::SessionManagerLayer::with_events), as sessions are
+//! created, renewed, destroyed, and found expired. Events identify sessions by
+//! their [fingerprint](crate::session::Id::fingerprint) rather than their ID,
+//! so they may be logged or forwarded without exposing session IDs.
+use tokio::sync::broadcast;
+
+use crate::session::Id;
+
+/// The number of events buffered for each subscriber by default.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// The kind of lifecycle event a session went through.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SessionEventKind {
+    /// A new session was saved to the store for the first time.
+    Created,
+
+    /// The ID of a stored session was cycled, e.g. with
+    /// [`Session::cycle_id`](crate::Session::cycle_id); the event carries the
+    /// new ID.
+    Renewed,
+
+    /// A stored session was deleted, e.g. with
+    /// [`Session::delete`](crate::Session::delete) or by being emptied.
+    Destroyed,
+
+    /// A session loaded from the store was past its expiry date and so was
+    /// deleted.
+    ///
+    /// Stores which don't return expired sessions from
+    /// [`load_including_expired`](crate::SessionStore::load_including_expired),
+    /// as is the default, can't tell a request presenting the cookie of an
+    /// expired session apart from one presenting an unknown cookie, and so no
+    /// event is emitted for those.
+    Expired,
+}
+
+/// A session lifecycle event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEvent {
+    /// What happened to the session.
+    pub kind: SessionEventKind,
+
+    /// The fingerprint of the session ID.
+    pub session_id: String,
+
+    /// The principal of the session, if any.
+    pub principal: Option<String>,
+}
+
+/// A channel broadcasting [`SessionEvent`]s to any number of subscribers.
+///
+/// Each subscriber buffers a bounded number of events. Subscribers which fall
+/// behind miss the oldest events, as reported by
+/// [`RecvError::Lagged`](broadcast::error::RecvError::Lagged), and the
+/// middleware never waits on them. Events emitted while there are no
+/// subscribers are dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::{events::SessionEvents, MemoryStore, SessionManagerLayer};
+///
+/// let events = SessionEvents::default();
+/// let mut subscriber = events.subscribe();
+///
+/// let session_store = MemoryStore::default();
+/// let session_service = SessionManagerLayer::new(session_store).with_events(events);
+///
+/// # let _ = async move {
+/// while let Ok(event) = subscriber.recv().await {
+///     println!("{:?} session {}", event.kind, event.session_id);
+/// }
+/// # };
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionEvents {
+    sender: broadcast::Sender<SessionEvent>,
+}
+
+impl SessionEvents {
+    /// Create a new channel buffering up to `capacity` events for each
+    /// subscriber.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `capacity` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::events::SessionEvents;
+    ///
+    /// let events = SessionEvents::new(64);
+    /// ```
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Subscribe to the events emitted from now on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::events::SessionEvents;
+    ///
+    /// let events = SessionEvents::default();
+    /// let subscriber = events.subscribe();
+    /// ```
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn emit(&self, kind: SessionEventKind, session_id: &Id, principal: Option<String>) {
+        tracing::trace!(?kind, "emitting session event");
+
+        // Sending only fails when there are no subscribers.
+        let _ = self.sender.send(SessionEvent {
+            kind,
+            session_id: session_id.fingerprint(),
+            principal,
+        });
+    }
+}
+
+impl Default for SessionEvents {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
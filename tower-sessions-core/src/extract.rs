@@ -1,20 +1,92 @@
+//! An `axum` extractor for [`Session`].
 use async_trait::async_trait;
-use axum_core::extract::FromRequestParts;
+use axum_core::{
+    extract::FromRequestParts,
+    response::{IntoResponse, Response},
+};
 use http::{request::Parts, StatusCode};
 
-use crate::session::Session;
+use crate::{service::SessionLoadError, session::Session};
+
+/// The rejection of the [`Session`] extractor.
+///
+/// As a response, the rejection has the status given by
+/// [`status`](SessionRejection::status) and a plain text body describing it.
+/// Handlers may respond otherwise by extracting `Result<Session,
+/// SessionRejection>` instead.
+///
+/// # Examples
+///
+/// ```rust
+/// use axum::{http::StatusCode, routing::get, Router};
+/// use tower_sessions::{extract::SessionRejection, Session};
+///
+/// async fn handler(session: Result<Session, SessionRejection>) -> Result<String, StatusCode> {
+///     match session {
+///         Ok(session) => Ok(format!("{:?}", session.principal())),
+///         Err(SessionRejection::Load(_)) => Err(StatusCode::SERVICE_UNAVAILABLE),
+///         Err(rejection) => Err(rejection.status()),
+///     }
+/// }
+///
+/// let app = Router::<()>::new().route("/", get(handler));
+/// ```
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
+pub enum SessionRejection {
+    /// The request wasn't handled by the session middleware.
+    #[error("Can't extract session. Is `SessionManagerLayer` enabled?")]
+    MissingLayer,
+
+    /// The session couldn't be loaded from the store, as deferred with
+    /// [`with_deferred_load_errors`](crate::SessionManagerLayer::with_deferred_load_errors).
+    #[error(transparent)]
+    Load(SessionLoadError),
+}
+
+impl SessionRejection {
+    /// Returns the status of the rejection's response: `500 Internal Server
+    /// Error` for a missing layer, and `503 Service Unavailable` for a store
+    /// which failed to load the session.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::MissingLayer => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Load(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl IntoResponse for SessionRejection {
+    fn into_response(self) -> Response {
+        // Store errors may carry connection details, so they're logged rather than
+        // returned.
+        let body = match &self {
+            Self::MissingLayer => self.to_string(),
+            Self::Load(err) => {
+                tracing::error!(err = %err, "rejecting request without session");
+                "Session unavailable".to_owned()
+            }
+        };
+
+        (self.status(), body).into_response()
+    }
+}
 
 #[async_trait]
 impl<S> FromRequestParts<S> for Session
 where
     S: Sync + Send,
 {
-    type Rejection = (http::StatusCode, &'static str);
+    type Rejection = SessionRejection;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        parts.extensions.get::<Session>().cloned().ok_or((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Can't extract session. Is `SessionManagerLayer` enabled?",
-        ))
+        if let Some(session) = parts.extensions.get::<Session>() {
+            return Ok(session.clone());
+        }
+
+        match parts.extensions.get::<SessionLoadError>() {
+            Some(err) => Err(SessionRejection::Load(err.clone())),
+            None => Err(SessionRejection::MissingLayer),
+        }
     }
 }
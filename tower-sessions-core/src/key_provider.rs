@@ -0,0 +1,420 @@
+//! External sources of the keys signing and encrypting cookies.
+//!
+//! Rather than being generated at startup or read from configuration, keys
+//! may be fetched from where secrets are managed through a [`KeyProvider`].
+//! This crate provides [`EnvKeyProvider`] and [`FileKeyProvider`], the latter
+//! suiting secrets mounted as files, e.g. by Kubernetes; providers for AWS KMS
+//! and HashiCorp Vault are found in the `tower-sessions-key-providers` crate.
+//!
+//! Providers yield raw key material. Signed and private cookies take it
+//! through a `ManagedKey`, which fetches the key once and again every refresh
+//! interval, so that rotating the secret at its source reaches running
+//! services without a restart. Cookies signed or encrypted with the key in use
+//! before a rotation are still accepted until the next one.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use tower_sessions::key_provider::{EnvKeyProvider, FileKeyProvider};
+//!
+//! // A key from the environment, e.g. injected by the deployment platform.
+//! let key_provider = EnvKeyProvider::new("SESSION_KEY");
+//!
+//! // A key mounted as a file, which may be replaced on rotation.
+//! let key_provider = FileKeyProvider::new("/run/secrets/session-key");
+//! ```
+//!
+//! See [`SessionManagerLayer::with_signed_managed_key`] and
+//! [`SessionManagerLayer::with_private_managed_key`] for using the keys.
+//!
+//! [`SessionManagerLayer::with_signed_managed_key`]: crate::SessionManagerLayer::with_signed_managed_key
+//! [`SessionManagerLayer::with_private_managed_key`]: crate::SessionManagerLayer::with_private_managed_key
+use std::{fmt::Debug, path::PathBuf};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// The number of bytes of key material cookie keys are made of.
+pub const KEY_LEN: usize = 64;
+
+/// An error fetching key material.
+#[derive(thiserror::Error, Debug)]
+pub enum KeyProviderError {
+    /// The key wasn't found at its source.
+    #[error("Key not found: {0}")]
+    NotFound(String),
+
+    /// The key couldn't be read.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// The key isn't valid base64.
+    #[error(transparent)]
+    Decode(#[from] base64::DecodeError),
+
+    /// The key is shorter than [`KEY_LEN`] bytes.
+    #[error("Key is {len} bytes long, but at least {KEY_LEN} bytes are required")]
+    TooShort {
+        /// The length of the key.
+        len: usize,
+    },
+
+    /// The source of the key failed.
+    #[error("Key provider backend error: {0}")]
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// A source of key material.
+///
+/// Implementations are asked for the key when it's first needed and then
+/// every refresh interval, so they should return the key currently in effect
+/// at their source rather than caching it.
+#[async_trait]
+pub trait KeyProvider: Debug + Send + Sync + 'static {
+    /// Fetches the current key material.
+    async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError>;
+}
+
+/// Decodes base64 key material, ignoring surrounding whitespace, and checks it
+/// is at least [`KEY_LEN`] bytes long.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::key_provider::decode_key;
+///
+/// assert!(decode_key(&"A".repeat(88)).is_ok());
+/// assert!(decode_key("c2hvcnQ=").is_err());
+/// ```
+pub fn decode_key(encoded: &str) -> Result<Vec<u8>, KeyProviderError> {
+    let key = STANDARD.decode(encoded.trim())?;
+    if key.len() < KEY_LEN {
+        return Err(KeyProviderError::TooShort { len: key.len() });
+    }
+    Ok(key)
+}
+
+/// A provider reading a base64-encoded key from an environment variable.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::key_provider::EnvKeyProvider;
+///
+/// let key_provider = EnvKeyProvider::new("SESSION_KEY");
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvKeyProvider {
+    var: String,
+}
+
+impl EnvKeyProvider {
+    /// Create a new provider reading the provided environment variable.
+    pub fn new(var: &str) -> Self {
+        Self {
+            var: var.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for EnvKeyProvider {
+    async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+        let encoded =
+            std::env::var(&self.var).map_err(|_| KeyProviderError::NotFound(self.var.clone()))?;
+        decode_key(&encoded)
+    }
+}
+
+/// A provider reading a base64-encoded key from a file.
+///
+/// The file is read again on every refresh, so that replacing it, as mounted
+/// secrets are on rotation, replaces the key. It's read on Tokio's blocking
+/// thread pool, so fetching the key requires a Tokio runtime, except on
+/// wasm32.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::key_provider::FileKeyProvider;
+///
+/// let key_provider = FileKeyProvider::new("/run/secrets/session-key");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileKeyProvider {
+    path: PathBuf,
+}
+
+impl FileKeyProvider {
+    /// Create a new provider reading the file at the provided path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for FileKeyProvider {
+    async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+        // Without threads to read files on, wasm32 reads them in place.
+        #[cfg(not(target_arch = "wasm32"))]
+        let encoded = tokio::fs::read_to_string(&self.path).await;
+        #[cfg(target_arch = "wasm32")]
+        let encoded = std::fs::read_to_string(&self.path);
+
+        let encoded = encoded.map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => {
+                KeyProviderError::NotFound(self.path.display().to_string())
+            }
+            _ => err.into(),
+        })?;
+        decode_key(&encoded)
+    }
+}
+
+#[cfg(any(feature = "signed", feature = "private"))]
+pub(crate) use self::managed::KeySource;
+#[cfg(any(feature = "signed", feature = "private"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "signed", feature = "private"))))]
+pub use self::managed::ManagedKey;
+#[cfg(any(feature = "signed", feature = "private", feature = "encrypted-store"))]
+pub(crate) use self::rotation::{Rotation, DEFAULT_REFRESH_INTERVAL};
+
+#[cfg(any(feature = "signed", feature = "private", feature = "encrypted-store"))]
+mod rotation {
+    use std::{fmt, sync::Arc};
+
+    use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+    use time::{Duration, OffsetDateTime};
+
+    use super::{KeyProvider, KeyProviderError};
+
+    /// The default interval at which keys are fetched again.
+    pub(crate) const DEFAULT_REFRESH_INTERVAL: Duration = Duration::hours(1);
+
+    pub(crate) struct Keys<T> {
+        pub(crate) current: T,
+        /// The key in use before the last rotation, which data may still have
+        /// been signed or encrypted with.
+        pub(crate) previous: Option<T>,
+        /// The material of the current key, telling when it has changed.
+        material: Vec<u8>,
+    }
+
+    // Keys are secrets, so they're kept out of logs.
+    impl<T> fmt::Debug for Keys<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Keys").finish_non_exhaustive()
+        }
+    }
+
+    /// Keys fetched from a [`KeyProvider`], rotated as they're refreshed.
+    #[derive(Debug)]
+    pub(crate) struct Rotation<T> {
+        provider: Arc<dyn KeyProvider>,
+        keys: RwLock<Keys<T>>,
+        parse: fn(&[u8]) -> Result<T, KeyProviderError>,
+        /// What the keys are for, as logged.
+        purpose: &'static str,
+        /// When the key is next due to be refreshed, once first checked.
+        next_refresh: Mutex<Option<OffsetDateTime>>,
+    }
+
+    impl<T: Send + Sync + 'static> Rotation<T> {
+        /// Fetches the key from the provider, parsing it with `parse`.
+        pub(crate) async fn new(
+            provider: impl KeyProvider,
+            parse: fn(&[u8]) -> Result<T, KeyProviderError>,
+            purpose: &'static str,
+        ) -> Result<Self, KeyProviderError> {
+            let material = provider.fetch().await?;
+            let current = parse(&material)?;
+            Ok(Self {
+                provider: Arc::new(provider),
+                keys: RwLock::new(Keys {
+                    current,
+                    previous: None,
+                    material,
+                }),
+                parse,
+                purpose,
+                next_refresh: Mutex::new(None),
+            })
+        }
+
+        pub(crate) fn keys(&self) -> RwLockReadGuard<'_, Keys<T>> {
+            self.keys.read()
+        }
+
+        /// Fetches the key from the provider, rotating it if it changed.
+        pub(crate) async fn refresh(&self) -> Result<(), KeyProviderError> {
+            let material = self.provider.fetch().await?;
+            let key = (self.parse)(&material)?;
+
+            let mut keys = self.keys.write();
+            if keys.material != material {
+                tracing::info!(purpose = self.purpose, "rotated key");
+                keys.previous = Some(std::mem::replace(&mut keys.current, key));
+                keys.material = material;
+            }
+
+            Ok(())
+        }
+
+        /// Refreshes the key if the refresh interval has elapsed since the
+        /// last refresh.
+        ///
+        /// Only the first caller past the due date refreshes the key, and does
+        /// so in a background task when running on a Tokio runtime, so that
+        /// neither it nor concurrent callers wait for the provider: they carry
+        /// on with the current key until the refreshed one replaces it.
+        pub(crate) async fn refresh_if_due(
+            self: &Arc<Self>,
+            now: OffsetDateTime,
+            refresh_interval: Duration,
+        ) {
+            let is_due = {
+                let mut next_refresh = self.next_refresh.lock();
+                let is_due = match *next_refresh {
+                    Some(due) if now < due => return,
+                    Some(_) => true,
+                    None => false,
+                };
+                *next_refresh = Some(now + refresh_interval);
+                is_due
+            };
+            if !is_due {
+                return;
+            }
+
+            let rotation = Arc::clone(self);
+            let refresh = async move {
+                if let Err(err) = rotation.refresh().await {
+                    tracing::error!(err = %err, purpose = rotation.purpose, "failed to refresh key");
+                }
+            };
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn(refresh);
+                }
+                // Without a runtime to spawn on, e.g. on wasm32, the caller waits.
+                Err(_) => refresh.await,
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "signed", feature = "private"))]
+mod managed {
+    use std::sync::Arc;
+
+    use time::{Duration, OffsetDateTime};
+    use tower_cookies::cookie::Key;
+
+    use super::{KeyProvider, KeyProviderError, Rotation, DEFAULT_REFRESH_INTERVAL};
+
+    /// A cookie key fetched from a [`KeyProvider`] and refreshed periodically.
+    ///
+    /// The key is refreshed as requests are served: the first request after
+    /// the refresh interval has elapsed fetches it again in a background
+    /// task, while it and concurrent requests carry on with the current key.
+    /// When the fetched key differs, it replaces the current key, which is
+    /// kept to verify cookies issued before the rotation. A failed refresh is
+    /// logged and the current key kept until the next attempt.
+    ///
+    /// Clones share their keys.
+    #[derive(Debug, Clone)]
+    pub struct ManagedKey {
+        rotation: Arc<Rotation<Key>>,
+        refresh_interval: Duration,
+    }
+
+    impl ManagedKey {
+        /// Create a new managed key, fetching it from the provider.
+        ///
+        /// # Examples
+        ///
+        /// ```rust,no_run
+        /// use tower_sessions::key_provider::{FileKeyProvider, ManagedKey};
+        ///
+        /// # tokio_test::block_on(async {
+        /// let key = ManagedKey::new(FileKeyProvider::new("/run/secrets/session-key"))
+        ///     .await
+        ///     .unwrap();
+        /// # })
+        /// ```
+        pub async fn new(provider: impl KeyProvider) -> Result<Self, KeyProviderError> {
+            Ok(Self {
+                rotation: Arc::new(Rotation::new(provider, to_key, "cookie").await?),
+                refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            })
+        }
+
+        /// Configures the interval at which the key is fetched again, one hour
+        /// by default.
+        ///
+        /// # Examples
+        ///
+        /// ```rust,no_run
+        /// use time::Duration;
+        /// use tower_sessions::key_provider::{EnvKeyProvider, ManagedKey};
+        ///
+        /// # tokio_test::block_on(async {
+        /// let key = ManagedKey::new(EnvKeyProvider::new("SESSION_KEY"))
+        ///     .await
+        ///     .unwrap()
+        ///     .with_refresh_interval(Duration::minutes(5));
+        /// # })
+        /// ```
+        pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+            self.refresh_interval = refresh_interval;
+            self
+        }
+
+        /// Fetches the key from the provider, rotating it if it changed.
+        pub async fn refresh(&self) -> Result<(), KeyProviderError> {
+            self.rotation.refresh().await
+        }
+    }
+
+    fn to_key(key: &[u8]) -> Result<Key, KeyProviderError> {
+        Key::try_from(key).map_err(|_| KeyProviderError::TooShort { len: key.len() })
+    }
+
+    /// The key of signed or private cookies, either provided as is or managed.
+    #[derive(Debug, Clone)]
+    pub(crate) enum KeySource {
+        Static(Key),
+        Managed(ManagedKey),
+    }
+
+    impl KeySource {
+        pub(crate) async fn refresh_if_due(&self, now: OffsetDateTime) {
+            if let Self::Managed(managed_key) = self {
+                managed_key
+                    .rotation
+                    .refresh_if_due(now, managed_key.refresh_interval)
+                    .await;
+            }
+        }
+
+        /// Calls `f` with the current key.
+        pub(crate) fn with_current<T>(&self, f: impl FnOnce(&Key) -> T) -> T {
+            match self {
+                Self::Static(key) => f(key),
+                Self::Managed(managed_key) => f(&managed_key.rotation.keys().current),
+            }
+        }
+
+        /// Calls `f` with the current key, and failing that, with the key in
+        /// use before the last rotation.
+        pub(crate) fn verify<T>(&self, f: impl Fn(&Key) -> Option<T>) -> Option<T> {
+            match self {
+                Self::Static(key) => f(key),
+                Self::Managed(managed_key) => {
+                    let keys = managed_key.rotation.keys();
+                    f(&keys.current).or_else(|| keys.previous.as_ref().and_then(&f))
+                }
+            }
+        }
+    }
+}
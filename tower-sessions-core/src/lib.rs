@@ -4,12 +4,45 @@ pub use tower_cookies::cookie;
 pub use self::{
     service::{SessionManager, SessionManagerLayer},
     session::{Expiry, Session},
-    session_store::{CachingSessionStore, ExpiredDeletion, SessionStore},
+    session_store::{
+        CachingSessionStore, ExpiredDeletion, ListSessions, SessionStore, TakeSession, UserSessions,
+    },
 };
 
+pub mod audit;
+#[cfg(feature = "blocking")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+pub mod blocking;
+pub mod clock;
+#[cfg(feature = "compressed-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compressed-store")))]
+pub mod compressed_store;
+pub mod csrf;
+#[cfg(feature = "encrypted-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encrypted-store")))]
+pub mod encrypted_store;
+pub mod events;
 #[cfg(feature = "axum-core")]
 #[cfg_attr(docsrs, doc(cfg(feature = "axum-core")))]
 pub mod extract;
+pub mod key_provider;
+pub mod limit;
+#[cfg(feature = "maintenance")]
+#[cfg_attr(docsrs, doc(cfg(feature = "maintenance")))]
+pub mod maintenance;
+pub mod metrics;
+pub mod one_time;
+pub mod persistence;
+pub mod remember;
+pub mod scope;
 pub mod service;
 pub mod session;
 pub mod session_store;
+#[cfg(feature = "private")]
+#[cfg_attr(docsrs, doc(cfg(feature = "private")))]
+pub mod stateless;
+pub mod telemetry;
+#[cfg(feature = "timeout-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "timeout-store")))]
+pub mod timeout_store;
+pub mod transfer;
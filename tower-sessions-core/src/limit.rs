@@ -0,0 +1,144 @@
+//! Limiting the rate at which clients create sessions.
+//!
+//! Every request without an active session gets a new session, and unless
+//! limited, each one a handler modifies is saved to the store. Clients which
+//! discard their cookies, such as bots, may thus fill the store with sessions
+//! no one will come back for. A [`CreationLimit`], configured with
+//! [`with_creation_limit`](crate::SessionManagerLayer::with_creation_limit),
+//! caps the number of sessions created per client within a window of time.
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use http::request::Parts;
+use parking_lot::Mutex;
+use time::{Duration, OffsetDateTime};
+
+/// The number of tracked clients past which clients whose window has ended
+/// are pruned.
+const MIN_PRUNE_THRESHOLD: usize = 1024;
+
+type KeyFn = dyn Fn(&Parts) -> Option<String> + Send + Sync;
+
+#[derive(Debug)]
+struct Windows {
+    /// The start of the current window of each client and the sessions they
+    /// created within it.
+    clients: HashMap<String, (OffsetDateTime, u32)>,
+    prune_threshold: usize,
+}
+
+/// A limit on the number of sessions each client may create within a window
+/// of time.
+///
+/// Clients are told apart by a key derived from their requests, typically
+/// their IP address. Once a client has created `max_sessions` sessions within
+/// a window, further requests of the client are still served, but their new
+/// sessions are neither saved nor given a cookie, as if they hadn't been
+/// modified, until the window ends. Sessions which are already stored aren't
+/// affected.
+///
+/// Windows are tracked in memory, so each instance of a service limits its
+/// clients independently. Cloned limits share their windows.
+///
+/// # Examples
+///
+/// Limiting clients by the address of their connection, as provided by
+/// `axum`'s `into_make_service_with_connect_info`:
+///
+/// ```rust
+/// use std::net::SocketAddr;
+///
+/// use axum::extract::ConnectInfo;
+/// use time::Duration;
+/// use tower_sessions::{limit::CreationLimit, MemoryStore, SessionManagerLayer};
+///
+/// let creation_limit = CreationLimit::new(10, Duration::minutes(1), |parts| {
+///     let ConnectInfo(addr) = parts.extensions.get::<ConnectInfo<SocketAddr>>()?;
+///     Some(addr.ip().to_string())
+/// });
+///
+/// let session_store = MemoryStore::default();
+/// let session_service =
+///     SessionManagerLayer::new(session_store).with_creation_limit(creation_limit);
+/// ```
+#[derive(Clone)]
+pub struct CreationLimit {
+    max_sessions: u32,
+    window: Duration,
+    key: Arc<KeyFn>,
+    windows: Arc<Mutex<Windows>>,
+}
+
+impl fmt::Debug for CreationLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreationLimit")
+            .field("max_sessions", &self.max_sessions)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CreationLimit {
+    /// Create a new limit of `max_sessions` sessions per client within each
+    /// `window`, with clients told apart by the provided key function.
+    ///
+    /// Requests for which the key function returns `None` aren't limited.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::limit::CreationLimit;
+    ///
+    /// // Behind a proxy which sets `X-Real-IP`.
+    /// let creation_limit = CreationLimit::new(10, Duration::minutes(1), |parts| {
+    ///     let real_ip = parts.headers.get("x-real-ip")?;
+    ///     real_ip.to_str().ok().map(str::to_owned)
+    /// });
+    /// ```
+    pub fn new(
+        max_sessions: u32,
+        window: Duration,
+        key: impl Fn(&Parts) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            max_sessions,
+            window,
+            key: Arc::new(key),
+            windows: Arc::new(Mutex::new(Windows {
+                clients: HashMap::new(),
+                prune_threshold: MIN_PRUNE_THRESHOLD,
+            })),
+        }
+    }
+
+    pub(crate) fn key(&self, parts: &Parts) -> Option<String> {
+        (self.key)(parts)
+    }
+
+    /// Counts a session created by the client, returning whether the client
+    /// is within its limit.
+    pub(crate) fn try_create(&self, key: &str, now: OffsetDateTime) -> bool {
+        let mut windows = self.windows.lock();
+        let window = self.window;
+
+        if windows.clients.len() >= windows.prune_threshold {
+            windows
+                .clients
+                .retain(|_, (started_at, _)| *started_at + window > now);
+            windows.prune_threshold = MIN_PRUNE_THRESHOLD.max(windows.clients.len() * 2);
+        }
+
+        let (started_at, created) = windows.clients.entry(key.to_owned()).or_insert((now, 0));
+        if *started_at + window <= now {
+            *started_at = now;
+            *created = 0;
+        }
+
+        if *created < self.max_sessions {
+            *created += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Scheduling the background maintenance of session stores.
+//!
+//! Stores may need work done outside of requests: deleting expired sessions,
+//! pruning indexes, or flushing buffered writes. [`Maintenance`] runs such
+//! tasks in the background, each on its own interval, instead of each
+//! application or store spawning a loop of its own:
+//!
+//! - Intervals are jittered, so that instances of a service started together
+//!   don't all hit the store at once.
+//! - A task failing or panicking is logged and run again at its next interval,
+//!   without affecting the other tasks.
+//! - On shutdown, tasks finish the run in progress before stopping, so that
+//!   e.g. buffered writes aren't left half-flushed. Flush tasks then run once
+//!   more, so that writes buffered since their last run aren't lost.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use tower_sessions::{maintenance::Maintenance, MemoryStore};
+//!
+//! # tokio_test::block_on(async {
+//! let session_store = MemoryStore::default();
+//!
+//! let maintenance = Maintenance::new()
+//!     .with_flush(session_store.clone(), Duration::from_secs(5))
+//!     .with_task("report", Duration::from_secs(60), || async {
+//!         tracing::info!("still here");
+//!         Ok::<_, std::io::Error>(())
+//!     })
+//!     .spawn();
+//!
+//! // On shutdown, e.g. after the server's graceful shutdown completes.
+//! maintenance.shutdown().await;
+//! # })
+//! ```
+use std::{error::Error, fmt, future::Future, sync::Arc, time::Duration};
+
+use futures::{
+    future::{self, BoxFuture, Either},
+    FutureExt,
+};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{session_store::ExpiredDeletion, SessionStore};
+
+/// The default jitter of task intervals, as a fraction of the interval.
+const DEFAULT_JITTER: f64 = 0.1;
+
+type TaskError = Box<dyn Error + Send + Sync>;
+
+type TaskFn = dyn Fn() -> BoxFuture<'static, Result<(), TaskError>> + Send + Sync;
+
+#[derive(Clone)]
+struct Task {
+    name: String,
+    period: Duration,
+    run: Arc<TaskFn>,
+    /// Whether the task runs once more on shutdown.
+    run_on_shutdown: bool,
+}
+
+impl Task {
+    /// Runs the task on its interval until shutdown is signalled.
+    async fn run_until(self, jitter: f64, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            let sleep = std::pin::pin!(tokio::time::sleep(jittered(self.period, jitter)));
+            let shutdown_signalled = std::pin::pin!(async {
+                // Without a handle left to signal it, shutdown never comes.
+                if shutdown.changed().await.is_err() {
+                    future::pending::<()>().await;
+                }
+            });
+            if let Either::Right(_) = future::select(sleep, shutdown_signalled).await {
+                if self.run_on_shutdown {
+                    self.run_once().await;
+                }
+                break;
+            }
+
+            if !self.run_once().await {
+                break;
+            }
+        }
+
+        tracing::debug!(task = %self.name, "maintenance task stopped");
+    }
+
+    /// Runs the task once, returning whether the runtime is still running.
+    async fn run_once(&self) -> bool {
+        // Each run is a task of its own, so that a panic ends the run rather than the
+        // schedule.
+        match tokio::spawn((self.run)()).await {
+            Ok(Ok(())) => tracing::trace!(task = %self.name, "maintenance task ran"),
+            Ok(Err(err)) => {
+                tracing::error!(task = %self.name, err = %err, "maintenance task failed")
+            }
+            Err(err) if err.is_panic() => {
+                tracing::error!(task = %self.name, "maintenance task panicked")
+            }
+            Err(_) => return false,
+        }
+        true
+    }
+}
+
+/// Spreads the period by up to `jitter` of it either way.
+fn jittered(period: Duration, jitter: f64) -> Duration {
+    // The last bytes of a v4 UUID are random, sparing a dependency on `rand`.
+    let random = uuid::Uuid::new_v4().as_u64_pair().1 as u32;
+    let unit = random as f64 / (u32::MAX as f64 + 1.0);
+    period.mul_f64(1.0 + jitter * (2.0 * unit - 1.0))
+}
+
+/// A schedule of store maintenance tasks.
+///
+/// Tasks first run one interval after the schedule is spawned.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use tower_sessions::{maintenance::Maintenance, MemoryStore};
+///
+/// let session_store = MemoryStore::default();
+/// let maintenance = Maintenance::new()
+///     .with_jitter(0.25)
+///     .with_flush(session_store, Duration::from_secs(5));
+/// ```
+#[derive(Clone)]
+pub struct Maintenance {
+    tasks: Vec<Task>,
+    jitter: f64,
+}
+
+impl fmt::Debug for Maintenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tasks: Vec<_> = self
+            .tasks
+            .iter()
+            .map(|task| (&task.name, task.period))
+            .collect();
+        f.debug_struct("Maintenance")
+            .field("tasks", &tasks)
+            .field("jitter", &self.jitter)
+            .finish()
+    }
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            tasks: Vec::new(),
+            jitter: DEFAULT_JITTER,
+        }
+    }
+}
+
+impl Maintenance {
+    /// Create a new schedule without tasks, jittering intervals by up to 10%.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures how much task intervals are jittered, as a fraction of the
+    /// interval between `0.0`, for none, and `1.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::maintenance::Maintenance;
+    ///
+    /// // Intervals are spread between 75% and 125% of their period.
+    /// let maintenance = Maintenance::new().with_jitter(0.25);
+    /// ```
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Adds a task, named for logging, run every period.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{maintenance::Maintenance, MemoryStore, UserSessions};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let maintenance =
+    ///     Maintenance::new().with_task("revoke-banned", Duration::from_secs(60), move || {
+    ///         let session_store = session_store.clone();
+    ///         async move { session_store.delete_user_sessions("mallory").await }
+    ///     });
+    /// ```
+    pub fn with_task<F, Fut, E>(mut self, name: &str, period: Duration, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<TaskError>,
+    {
+        self.tasks.push(Task {
+            name: name.to_owned(),
+            period,
+            run: Arc::new(move || task().map(|res| res.map_err(Into::into)).boxed()),
+            run_on_shutdown: false,
+        });
+        self
+    }
+
+    /// Adds a task deleting the expired sessions of the store every period.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{maintenance::Maintenance, sqlx::SqlitePool, SqliteStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+    /// let session_store = SqliteStore::new(pool);
+    /// let maintenance =
+    ///     Maintenance::new().with_expired_deletion(session_store, Duration::from_secs(60));
+    /// # })
+    /// ```
+    pub fn with_expired_deletion<S>(self, store: S, period: Duration) -> Self
+    where
+        S: ExpiredDeletion,
+        S::Error: Send + Sync + 'static,
+    {
+        self.with_task("delete-expired", period, move || {
+            let store = store.clone();
+            async move { store.delete_expired().await }
+        })
+    }
+
+    /// Adds a task flushing the buffered writes of the store every period, and
+    /// once more on shutdown.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{maintenance::Maintenance, MemoryStore};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let maintenance = Maintenance::new().with_flush(session_store, Duration::from_secs(5));
+    /// ```
+    pub fn with_flush<S>(self, store: S, period: Duration) -> Self
+    where
+        S: SessionStore,
+        S::Error: Send + Sync + 'static,
+    {
+        let mut maintenance = self.with_task("flush", period, move || {
+            let store = store.clone();
+            async move { store.flush().await }
+        });
+        if let Some(task) = maintenance.tasks.last_mut() {
+            task.run_on_shutdown = true;
+        }
+        maintenance
+    }
+
+    /// Spawns the tasks onto the Tokio runtime, returning a handle to shut
+    /// them down with.
+    ///
+    /// Dropping the handle leaves the tasks running in the background.
+    pub fn spawn(self) -> MaintenanceHandle {
+        let (shutdown, signal) = watch::channel(false);
+        let runners = self
+            .tasks
+            .into_iter()
+            .map(|task| tokio::spawn(task.run_until(self.jitter, signal.clone())))
+            .collect();
+
+        MaintenanceHandle { shutdown, runners }
+    }
+
+    /// Runs the tasks until the shutdown signal completes, then waits for the
+    /// runs in progress, and the final runs of flush tasks, to finish.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{maintenance::Maintenance, MemoryStore};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = MemoryStore::default();
+    /// let maintenance = Maintenance::new().with_flush(session_store, Duration::from_secs(5));
+    ///
+    /// // E.g. `tokio::signal::ctrl_c()`.
+    /// let shutdown_signal = async {};
+    /// maintenance.run_until(shutdown_signal).await;
+    /// # })
+    /// ```
+    pub async fn run_until(self, shutdown_signal: impl Future<Output = ()>) {
+        let handle = self.spawn();
+        shutdown_signal.await;
+        handle.shutdown().await;
+    }
+}
+
+/// A handle to the tasks of a spawned [`Maintenance`] schedule.
+#[derive(Debug)]
+pub struct MaintenanceHandle {
+    shutdown: watch::Sender<bool>,
+    runners: Vec<JoinHandle<()>>,
+}
+
+impl MaintenanceHandle {
+    /// Stops the tasks, waiting for the runs in progress, and the final runs
+    /// of flush tasks, to finish.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for runner in self.runners {
+            let _ = runner.await;
+        }
+    }
+}
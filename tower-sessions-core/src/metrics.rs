@@ -0,0 +1,70 @@
+//! Metrics emitted by the session middleware.
+//!
+//! When the `metrics` feature is enabled,
+//! [`SessionManager`](crate::SessionManager) reports the following through the [`metrics`](https://docs.rs/metrics)
+//! facade, giving a view of session health that's independent of the store in
+//! use. Any `metrics` exporter, such as Prometheus, may be installed to
+//! collect them.
+use std::future::Future;
+
+/// Counter of sessions created, i.e. new sessions saved for the first time.
+pub const SESSIONS_CREATED: &str = "tower_sessions_sessions_created_total";
+
+/// Counter of requests presenting a session cookie for which the store had no
+/// active session.
+pub const SESSIONS_MISSING: &str = "tower_sessions_sessions_missing_total";
+
+/// Histogram of the time taken to load sessions from the store, in seconds.
+pub const LOAD_DURATION: &str = "tower_sessions_load_duration_seconds";
+
+/// Histogram of the time taken to save sessions to the store, in seconds.
+pub const SAVE_DURATION: &str = "tower_sessions_save_duration_seconds";
+
+/// Counter of failed attempts to save sessions to the store.
+pub const SAVE_FAILURES: &str = "tower_sessions_save_failures_total";
+
+/// Counter of sessions evicted from a bounded `MemoryStore`, labelled with the
+/// `reason` of the eviction: `capacity` for sessions evicted to make room, and
+/// `expired` for expired sessions.
+///
+/// This is reported by the memory store rather than the middleware, when the
+/// `metrics` feature is enabled.
+pub const MEMORY_STORE_EVICTIONS: &str = "tower_sessions_memory_store_evictions_total";
+
+pub(crate) fn session_created() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(SESSIONS_CREATED).increment(1);
+}
+
+pub(crate) fn session_missing() {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(SESSIONS_MISSING).increment(1);
+}
+
+pub(crate) async fn time_load<T, E>(load: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    timed(LOAD_DURATION, load).await
+}
+
+pub(crate) async fn time_save<T, E>(save: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    let res = timed(SAVE_DURATION, save).await;
+
+    #[cfg(feature = "metrics")]
+    if res.is_err() {
+        ::metrics::counter!(SAVE_FAILURES).increment(1);
+    }
+
+    res
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+async fn timed<F: Future>(name: &'static str, fut: F) -> F::Output {
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+
+    let output = fut.await;
+
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!(name).record(started.elapsed().as_secs_f64());
+
+    output
+}
@@ -0,0 +1,291 @@
+//! Single-use tokens, e.g. for email verification, magic links, and password
+//! resets.
+//!
+//! [`OneTimeTokens`] issues tokens for a purpose, carrying a payload such as
+//! the address to verify, and stores them through any [`SessionStore`], with
+//! the same backends and expiry as sessions. Redeeming a token with
+//! [`consume`](OneTimeTokens::consume), which requires a store implementing
+//! [`TakeSession`], returns its payload once: the token is then spent, and a
+//! replay is told apart from an invalid token, e.g. to alert when a reset link
+//! is used twice.
+//!
+//! Tokens are stored as records holding a hash of the token, never the token
+//! itself, so a leak of the store doesn't leak usable links. Spent tokens
+//! leave their record behind, without the payload, until it expires. Records
+//! of tokens issued for a principal carry it, so revoking a user's sessions
+//! with [`UserSessions`](crate::session_store::UserSessions) revokes their
+//! tokens as well, but the session middleware never loads them as sessions.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use tower_sessions::{
+//!     one_time::{OneTimeError, OneTimeTokens},
+//!     MemoryStore,
+//! };
+//!
+//! # tokio_test::block_on(async {
+//! let tokens = OneTimeTokens::new(MemoryStore::default());
+//!
+//! // Sent by email, e.g. as `https://example.com/verify?token=...`.
+//! let token = tokens
+//!     .issue("verify-email", &"alice@example.com")
+//!     .await
+//!     .unwrap();
+//!
+//! let email: String = tokens.consume("verify-email", &token).await.unwrap();
+//! assert_eq!(email, "alice@example.com");
+//!
+//! let replay = tokens.consume::<String>("verify-email", &token).await;
+//! assert!(matches!(replay, Err(OneTimeError::Replayed)));
+//! # })
+//! ```
+use serde::{de::DeserializeOwned, Serialize};
+use time::Duration;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    remember::{hash, parse, token},
+    session::Id,
+    session_store::TakeSession,
+    Expiry, Session, SessionStore,
+};
+
+/// The session data key under which records hold the purpose of their token.
+const PURPOSE_KEY: &str = "__tower_sessions_one_time_purpose";
+
+/// The session data key under which records hold the hash of their token.
+const TOKEN_HASH_KEY: &str = "__tower_sessions_one_time_token_hash";
+
+/// The session data key under which records are marked as spent.
+const SPENT_KEY: &str = "__tower_sessions_one_time_spent";
+
+/// The session data key under which records hold the payload of their token,
+/// until it's spent.
+const PAYLOAD_KEY: &str = "__tower_sessions_one_time_payload";
+
+/// The default lifetime of tokens.
+const DEFAULT_DURATION: Duration = Duration::hours(1);
+
+/// An error of [`OneTimeTokens`].
+#[derive(thiserror::Error, Debug)]
+pub enum OneTimeError<StoreError: std::error::Error> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(StoreError),
+
+    /// A payload couldn't be serialized or deserialized.
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+
+    /// The token is malformed, was issued for another purpose, or expired or
+    /// was revoked.
+    #[error("Invalid one-time token")]
+    Invalid,
+
+    /// The token was spent before.
+    #[error("One-time token already used")]
+    Replayed,
+}
+
+/// Single-use tokens kept in a session store.
+///
+/// Tokens are spent by the first consumption which takes their record from
+/// the store, so that of concurrent consumptions of a token, only one returns
+/// its payload.
+#[derive(Debug, Clone)]
+pub struct OneTimeTokens<Store: SessionStore> {
+    store: Store,
+    duration: Duration,
+    clock: SharedClock,
+}
+
+impl<Store: SessionStore> OneTimeTokens<Store> {
+    /// Create new one-time tokens kept in the provided store, expiring an hour
+    /// after they're issued.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{one_time::OneTimeTokens, MemoryStore};
+    ///
+    /// let tokens = OneTimeTokens::new(MemoryStore::default());
+    /// ```
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            duration: DEFAULT_DURATION,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures the lifetime of tokens.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{one_time::OneTimeTokens, MemoryStore};
+    ///
+    /// let tokens = OneTimeTokens::new(MemoryStore::default()).with_duration(Duration::minutes(15));
+    /// ```
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Configures the clock against which tokens expire.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, one_time::OneTimeTokens, MemoryStore};
+    ///
+    /// let tokens = OneTimeTokens::new(MemoryStore::default()).with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Issues a token for the purpose, carrying the payload, and returns its
+    /// value.
+    pub async fn issue<T: Serialize>(
+        &self,
+        purpose: &str,
+        payload: &T,
+    ) -> Result<String, OneTimeError<Store::Error>> {
+        self.issue_with_principal(purpose, None, payload).await
+    }
+
+    /// Issues a token of the principal for the purpose, carrying the payload,
+    /// and returns its value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{one_time::OneTimeTokens, MemoryStore, UserSessions};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = MemoryStore::default();
+    /// let tokens = OneTimeTokens::new(session_store.clone());
+    /// let token = tokens
+    ///     .issue_for("alice", "reset-password", &())
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// // Revoking the principal's sessions revokes their tokens too.
+    /// session_store.delete_user_sessions("alice").await.unwrap();
+    /// assert!(tokens
+    ///     .consume::<()>("reset-password", &token)
+    ///     .await
+    ///     .is_err());
+    /// # })
+    /// ```
+    pub async fn issue_for<T: Serialize>(
+        &self,
+        principal: &str,
+        purpose: &str,
+        payload: &T,
+    ) -> Result<String, OneTimeError<Store::Error>> {
+        self.issue_with_principal(purpose, Some(principal), payload)
+            .await
+    }
+
+    async fn issue_with_principal<T: Serialize>(
+        &self,
+        purpose: &str,
+        principal: Option<&str>,
+        payload: &T,
+    ) -> Result<String, OneTimeError<Store::Error>> {
+        let expiry_date = self.clock.now().saturating_add(self.duration);
+        let record = Session::new(Some(Expiry::AtDateTime(expiry_date)));
+        record.set_principal(principal);
+        let secret = Id::default();
+        record.insert_value(PURPOSE_KEY, purpose.into());
+        record.insert_value(TOKEN_HASH_KEY, hash(&secret).into());
+        record.insert_value(PAYLOAD_KEY, serde_json::to_value(payload)?);
+        self.store
+            .create(&record)
+            .await
+            .map_err(OneTimeError::Store)?;
+
+        Ok(token(record.id(), &secret))
+    }
+
+    /// Revokes the token, whether it was spent or not.
+    pub async fn revoke(&self, value: &str) -> Result<(), OneTimeError<Store::Error>> {
+        match parse(value) {
+            Some((record_id, _)) => self
+                .store
+                .delete(&record_id)
+                .await
+                .map_err(OneTimeError::Store),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<Store: TakeSession> OneTimeTokens<Store> {
+    /// Consumes the token issued for the purpose, returning its payload.
+    ///
+    /// The token is spent even if its payload can't be deserialized as `T`.
+    pub async fn consume<T: DeserializeOwned>(
+        &self,
+        purpose: &str,
+        value: &str,
+    ) -> Result<T, OneTimeError<Store::Error>> {
+        let Some((record_id, secret)) = parse(value) else {
+            return Err(OneTimeError::Invalid);
+        };
+        let Some(record) = self
+            .store
+            .load(&record_id)
+            .await
+            .map_err(OneTimeError::Store)?
+            .filter(|record| {
+                record.get_value(PURPOSE_KEY) == Some(purpose.into())
+                    && record.expiry_date() > self.clock.now()
+            })
+        else {
+            return Err(OneTimeError::Invalid);
+        };
+
+        if record.get_value(TOKEN_HASH_KEY) != Some(hash(&secret).into()) {
+            return Err(OneTimeError::Invalid);
+        }
+        if record.get_value(SPENT_KEY).is_some() {
+            return Err(OneTimeError::Replayed);
+        }
+
+        // The record is only checked once loaded, whereas taking it is what spends the
+        // token: a record gone by then was spent or revoked in the meantime.
+        let Some(record) = self
+            .store
+            .take(&record_id)
+            .await
+            .map_err(OneTimeError::Store)?
+        else {
+            return Err(OneTimeError::Replayed);
+        };
+
+        let replayed = record.get_value(SPENT_KEY).is_some();
+        let payload = record.remove_value(PAYLOAD_KEY).unwrap_or_default();
+        record.insert_value(SPENT_KEY, true.into());
+        self.store
+            .save(&record)
+            .await
+            .map_err(OneTimeError::Store)?;
+        if replayed {
+            return Err(OneTimeError::Replayed);
+        }
+
+        Ok(serde_json::from_value(payload)?)
+    }
+}
+
+/// Whether the session is the record of a one-time token rather than a
+/// session.
+pub(crate) fn is_record(session: &Session) -> bool {
+    session.get_value(PURPOSE_KEY).is_some()
+}
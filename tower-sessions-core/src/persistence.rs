@@ -0,0 +1,201 @@
+//! Loading and saving the session of a request, for middleware of other
+//! frameworks.
+//!
+//! [`SessionPersistence`] implements the store-facing half of
+//! `SessionManagerLayer`: loading the session of a request, skipping expired
+//! sessions and the records of remember-me and one-time tokens, and saving it
+//! once the request has been handled, deleting, cycling, or extending it as
+//! needed. Middleware transporting the session ID their own way, e.g. in a
+//! cookie of another framework or in gRPC metadata, use it to share sessions
+//! with `SessionManagerLayer` through the same store.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use time::Duration;
+//! use tower_sessions::{persistence::SessionPersistence, Expiry, MemoryStore};
+//!
+//! # tokio_test::block_on(async {
+//! let session_store = MemoryStore::default();
+//! let persistence =
+//!     SessionPersistence::default().with_expiry(Expiry::OnInactivity(Duration::hours(1)));
+//!
+//! // The ID is that sent by the client, if any.
+//! let loaded = persistence.load(&session_store, None).await.unwrap();
+//! let is_loaded = loaded.is_some();
+//! let mut session = loaded.unwrap_or_else(|| persistence.new_session());
+//!
+//! // The request is handled.
+//! session.insert("foo", 42).unwrap();
+//!
+//! let saved = persistence
+//!     .save(&session_store, &mut session, is_loaded)
+//!     .await
+//!     .unwrap();
+//! assert!(matches!(saved, tower_sessions::persistence::Saved::Persisted));
+//! # })
+//! ```
+use time::Duration;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    session::{Deletion, Id},
+    session_store::is_internal_record,
+    Expiry, Session, SessionStore,
+};
+
+/// What became of a session once saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saved {
+    /// The session was left as is, and its ID needn't be sent again.
+    Unchanged,
+
+    /// The session was deleted, and its ID should be removed from the client.
+    Deleted,
+
+    /// The session was persisted, possibly under a new ID, which should be
+    /// sent to the client.
+    Persisted,
+}
+
+/// Extends the expiry of a session which expires on inactivity, unless a
+/// refresh threshold is given and the session's remaining lifetime is above
+/// it. Returns whether the expiry was extended.
+///
+/// This only marks the session as modified, so that it's persisted with its
+/// new expiry date when saved.
+pub fn extend_inactivity_expiry(session: &Session, refresh_threshold: Option<Duration>) -> bool {
+    let Some(Expiry::OnInactivity(_)) = session.expiry() else {
+        return false;
+    };
+    let needs_refresh =
+        refresh_threshold.is_none_or(|refresh_threshold| session.expiry_age() < refresh_threshold);
+    if needs_refresh {
+        session.refresh();
+    }
+    needs_refresh
+}
+
+/// The configuration of loading and saving sessions.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug, Clone, Default)]
+pub struct SessionPersistence {
+    expiry: Option<Expiry>,
+    refresh_threshold: Option<Duration>,
+    clock: SharedClock,
+}
+
+impl SessionPersistence {
+    /// Configures the expiry of new sessions.
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /// Configures the remaining lifetime below which sessions expiring on
+    /// inactivity are extended. Without a threshold, such sessions are
+    /// extended by every request.
+    pub fn with_refresh_threshold(mut self, refresh_threshold: Duration) -> Self {
+        self.refresh_threshold = Some(refresh_threshold);
+        self
+    }
+
+    /// Configures the clock against which sessions expire.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Creates a new session, for requests without a loaded session.
+    pub fn new_session(&self) -> Session {
+        let session = Session::new(self.expiry.clone());
+        session.set_clock(self.clock.clone());
+        session
+    }
+
+    /// Loads the session of the ID, unless it's expired or isn't a session.
+    ///
+    /// Expired sessions which the store returns are deleted.
+    pub async fn load<Store: SessionStore>(
+        &self,
+        session_store: &Store,
+        session_id: Option<&Id>,
+    ) -> Result<Option<Session>, Store::Error> {
+        let Some(session_id) = session_id else {
+            return Ok(None);
+        };
+
+        let Some(session) = session_store
+            .load_including_expired(session_id)
+            .await?
+            // Records of remember-me and one-time tokens are never sessions.
+            .filter(|session| !is_internal_record(session))
+        else {
+            return Ok(None);
+        };
+
+        if session.expiry_date() <= self.clock.now() {
+            tracing::debug!("session expired");
+            session_store.delete(session.id()).await?;
+            return Ok(None);
+        }
+
+        session.set_clock(self.clock.clone());
+        Ok(Some(session))
+    }
+
+    /// Persists the changes a request made to the session, cycling its ID if
+    /// asked to, in which case the session takes on its new ID.
+    ///
+    /// `is_loaded` tells whether the session was loaded from the store, rather
+    /// than created for the request.
+    pub async fn save<Store: SessionStore>(
+        &self,
+        session_store: &Store,
+        session: &mut Session,
+        is_loaded: bool,
+    ) -> Result<Saved, Store::Error> {
+        // N.B. When a session is empty, it will be deleted.
+        let mut is_cycled = false;
+        match session.deleted() {
+            Some(Deletion::Deleted) => {
+                if is_loaded {
+                    session_store.delete(session.id()).await?;
+                }
+                return Ok(Saved::Deleted);
+            }
+
+            Some(Deletion::Cycled(deleted_id)) => {
+                if is_loaded {
+                    session_store.delete(&deleted_id).await?;
+                }
+                session.reset_deleted();
+                session.id = Id::default();
+                is_cycled = true;
+            }
+
+            None => {}
+        }
+
+        if is_loaded {
+            extend_inactivity_expiry(session, self.refresh_threshold);
+        }
+
+        // Empty sessions aren't modified, and so are never persisted.
+        if !session.is_modified() {
+            return Ok(Saved::Unchanged);
+        }
+
+        session.reset_modified();
+        if is_loaded && !is_cycled {
+            session_store.save(session).await?;
+        } else {
+            // The session is persisted under a new ID, so stores may guard against
+            // collisions.
+            session_store.create(session).await?;
+        }
+
+        Ok(Saved::Persisted)
+    }
+}
@@ -0,0 +1,194 @@
+//! Long-lived "remember me" tokens re-establishing sessions once they expire.
+//!
+//! With [`RememberMe`] configured via
+//! [`with_remember_me`](crate::SessionManagerLayer::with_remember_me), a
+//! handler may call [`Session::remember`] upon login to issue a token in a
+//! separate cookie. When a later request arrives without an active session but
+//! with a valid token, the middleware establishes a new session for the
+//! token's principal before calling the inner service, and rotates the token.
+//!
+//! Tokens belong to families, one per login. Each use of a token replaces it
+//! with the next token of its family, so a token presented after it was
+//! replaced means that it has been copied: the family is then revoked, so
+//! neither the thief nor the victim may use its tokens again. Only the family
+//! is revoked: sessions already established from its tokens stay active until
+//! they expire. The middleware logs a warning with the principal when it
+//! detects a replayed token; ending those sessions as well takes revoking the
+//! principal's sessions with
+//! [`UserSessions::delete_user_sessions`](crate::session_store::UserSessions::delete_user_sessions).
+//!
+//! Families are stored in the session store as records holding the principal
+//! and a hash of the current token, never the token itself. Records carry the
+//! principal of the family, so revoking a user's sessions with
+//! [`UserSessions`](crate::session_store::UserSessions) revokes their tokens
+//! as well, but the middleware never loads them as sessions.
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+
+use crate::{session::Id, Expiry, Session, SessionStore};
+
+/// The session data key under which records hold the hash of the current
+/// token of their family.
+const TOKEN_HASH_KEY: &str = "__tower_sessions_remember_me_token_hash";
+
+/// The default name of the remember-me cookie.
+const DEFAULT_NAME: &str = "remember";
+
+/// The default lifetime of remember-me tokens.
+const DEFAULT_DURATION: Duration = Duration::days(30);
+
+/// Configuration of remember-me tokens.
+///
+/// # Examples
+///
+/// ```rust
+/// use time::Duration;
+/// use tower_sessions::{remember::RememberMe, MemoryStore, SessionManagerLayer};
+///
+/// let remember_me = RememberMe::new(Duration::days(90)).with_name("stay-logged-in");
+///
+/// let session_store = MemoryStore::default();
+/// let session_service = SessionManagerLayer::new(session_store).with_remember_me(remember_me);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RememberMe {
+    pub(crate) name: String,
+    pub(crate) duration: Duration,
+}
+
+impl RememberMe {
+    /// Create a new configuration of tokens lasting the provided duration
+    /// since their last use.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::remember::RememberMe;
+    ///
+    /// let remember_me = RememberMe::new(Duration::days(90));
+    /// ```
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            name: DEFAULT_NAME.to_owned(),
+            duration,
+        }
+    }
+
+    /// Configures the name of the remember-me cookie.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::remember::RememberMe;
+    ///
+    /// let remember_me = RememberMe::default().with_name("stay-logged-in");
+    /// ```
+    pub fn with_name<N: Into<String>>(mut self, name: N) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Creates a new family for the principal, returning the value of its
+    /// first token.
+    pub(crate) async fn issue<Store: SessionStore>(
+        &self,
+        store: &Store,
+        now: OffsetDateTime,
+        principal: &str,
+    ) -> Result<String, Store::Error> {
+        let record = Session::new(Some(Expiry::AtDateTime(now.saturating_add(self.duration))));
+        record.set_principal(Some(principal));
+        let secret = Id::default();
+        record.insert_value(TOKEN_HASH_KEY, hash(&secret).into());
+        store.create(&record).await?;
+
+        Ok(token(record.id(), &secret))
+    }
+
+    /// Verifies the token, rotating it if it's current.
+    pub(crate) async fn verify<Store: SessionStore>(
+        &self,
+        store: &Store,
+        now: OffsetDateTime,
+        value: &str,
+    ) -> Result<Verification, Store::Error> {
+        let Some((family, secret)) = parse(value) else {
+            return Ok(Verification::Invalid);
+        };
+        let Some(record) = store.load(&family).await?.filter(is_record) else {
+            return Ok(Verification::Invalid);
+        };
+        let Some(principal) = record.principal() else {
+            return Ok(Verification::Invalid);
+        };
+
+        if record.get_value(TOKEN_HASH_KEY) != Some(hash(&secret).into()) {
+            store.delete(&family).await?;
+            return Ok(Verification::Reused { principal });
+        }
+
+        let secret = Id::default();
+        record.insert_value(TOKEN_HASH_KEY, hash(&secret).into());
+        record.set_expiry(Some(Expiry::AtDateTime(now.saturating_add(self.duration))));
+        store.save(&record).await?;
+
+        Ok(Verification::Valid {
+            principal,
+            value: token(&family, &secret),
+        })
+    }
+
+    /// Revokes the family of the token.
+    pub(crate) async fn revoke<Store: SessionStore>(
+        &self,
+        store: &Store,
+        value: &str,
+    ) -> Result<(), Store::Error> {
+        match parse(value) {
+            Some((family, _)) => store.delete(&family).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for RememberMe {
+    fn default() -> Self {
+        Self::new(DEFAULT_DURATION)
+    }
+}
+
+/// The outcome of verifying a remember-me token.
+pub(crate) enum Verification {
+    /// The token is the current one of its family, and has been replaced by
+    /// the token with the given value.
+    Valid { principal: String, value: String },
+
+    /// The token was replaced before, so its family has been revoked.
+    Reused { principal: String },
+
+    /// The token is malformed, or its family expired or was revoked.
+    Invalid,
+}
+
+/// Whether the session is the record of a family of remember-me tokens rather
+/// than a session.
+pub(crate) fn is_record(session: &Session) -> bool {
+    session.get_value(TOKEN_HASH_KEY).is_some()
+}
+
+pub(crate) fn token(family: &Id, secret: &Id) -> String {
+    format!("{family}.{secret}")
+}
+
+pub(crate) fn parse(value: &str) -> Option<(Id, Id)> {
+    let (family, secret) = value.split_once('.')?;
+    Some((family.try_into().ok()?, secret.try_into().ok()?))
+}
+
+pub(crate) fn hash(secret: &Id) -> String {
+    Sha256::digest(secret.to_string().as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
@@ -0,0 +1,186 @@
+//! Sharing one session across subdomains, with data scoped to each of them.
+//!
+//! Services on subdomains of a common parent domain, e.g. `app.example.com`
+//! and `admin.example.com`, share a session when:
+//!
+//! - their session cookie is set on the parent domain, with
+//!   [`with_domain`](crate::SessionManagerLayer::with_domain), under the same
+//!   name,
+//! - they load sessions from the same store, under the same keys, e.g. the same
+//!   Redis database and key prefix or the same SQL table,
+//! - and, if cookies are signed or private, they share the key.
+//!
+//! The session's principal and data are then visible to every service, so
+//! that a login on one subdomain is a login on all of them. Data which only
+//! concerns one service is better kept in its [`Scope`], a namespaced
+//! partition of the session record, so that services don't trip over each
+//! other's keys. With
+//! [`with_scope`](crate::SessionManagerLayer::with_scope), the scope of each
+//! service is provided as a request extension.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use tower_sessions::{scope::Scope, MemoryStore, Session, SessionManagerLayer};
+//!
+//! // Served on `admin.example.com`, next to a similar service on
+//! // `app.example.com` with the "app" scope.
+//! let session_store = MemoryStore::default();
+//! let session_service = SessionManagerLayer::new(session_store)
+//!     .with_domain("example.com".to_string())
+//!     .with_scope("admin");
+//!
+//! async fn handler(session: Session, scope: Scope) {
+//!     // Logged in on any subdomain.
+//!     if session.principal().is_some() {
+//!         // Only seen by the admin service.
+//!         scope.insert("last_visited", "/dashboard").unwrap();
+//!     }
+//! }
+//! ```
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{session, Session};
+
+/// The prefix of the session data keys under which scopes are kept.
+const SCOPE_KEY_PREFIX: &str = "__tower_sessions_scope.";
+
+/// A namespaced partition of a session's data.
+///
+/// A scope is kept in the session under a single key, as a map of its own
+/// keys and values. Its values are accessed like the session's, and modifying
+/// them modifies the session.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::Session;
+///
+/// let session = Session::default();
+/// let app = session.scope("app");
+/// let admin = session.scope("admin");
+///
+/// app.insert("theme", "dark").unwrap();
+/// assert_eq!(app.get::<String>("theme").unwrap().as_deref(), Some("dark"));
+/// assert_eq!(admin.get::<String>("theme").unwrap(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scope {
+    session: Session,
+    name: String,
+}
+
+impl Scope {
+    pub(crate) fn new(session: Session, name: &str) -> Self {
+        Self {
+            session,
+            name: name.to_owned(),
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("{SCOPE_KEY_PREFIX}{}", self.name)
+    }
+
+    fn data(&self) -> Map<String, Value> {
+        match self.session.get_value(&self.key()) {
+            Some(Value::Object(data)) => data,
+            _ => Map::new(),
+        }
+    }
+
+    fn set_data(&self, data: Map<String, Value>) {
+        if data.is_empty() {
+            self.session.remove_value(&self.key());
+        } else {
+            self.session.insert_value(&self.key(), Value::Object(data));
+        }
+    }
+
+    /// Returns the name of the scope.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Inserts a `impl Serialize` value into the scope.
+    ///
+    /// # Errors
+    ///
+    /// This method can fail when [`serde_json::to_value`] fails.
+    pub fn insert(&self, key: &str, value: impl Serialize) -> Result<(), session::Error> {
+        self.insert_value(key, serde_json::to_value(&value)?);
+        Ok(())
+    }
+
+    /// Inserts a `serde_json::Value` into the scope, returning the value the
+    /// key had, if any.
+    pub fn insert_value(&self, key: &str, value: Value) -> Option<Value> {
+        let mut data = self.data();
+        let previous = data.insert(key.to_owned(), value);
+        self.set_data(data);
+        previous
+    }
+
+    /// Gets a value from the scope.
+    ///
+    /// # Errors
+    ///
+    /// This method can fail when [`serde_json::from_value`] fails.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, session::Error> {
+        Ok(self
+            .get_value(key)
+            .map(serde_json::from_value)
+            .transpose()?)
+    }
+
+    /// Gets a `serde_json::Value` from the scope.
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        self.data().remove(key)
+    }
+
+    /// Removes a value from the scope, returning the value of the key if it
+    /// was present.
+    ///
+    /// # Errors
+    ///
+    /// This method can fail when [`serde_json::from_value`] fails.
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, session::Error> {
+        Ok(self
+            .remove_value(key)
+            .map(serde_json::from_value)
+            .transpose()?)
+    }
+
+    /// Removes a `serde_json::Value` from the scope.
+    pub fn remove_value(&self, key: &str) -> Option<Value> {
+        let mut data = self.data();
+        let removed = data.remove(key)?;
+        self.set_data(data);
+        Some(removed)
+    }
+
+    /// Clears the scope, leaving the rest of the session as is.
+    pub fn clear(&self) {
+        self.session.remove_value(&self.key());
+    }
+}
+
+#[cfg(feature = "axum-core")]
+#[async_trait::async_trait]
+impl<S> axum_core::extract::FromRequestParts<S> for Scope
+where
+    S: Sync + Send,
+{
+    type Rejection = (http::StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Scope>().cloned().ok_or((
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            "Can't extract scope. Is `SessionManagerLayer::with_scope` configured?",
+        ))
+    }
+}
@@ -1,19 +1,35 @@
 //! A middleware that provides [`Session`] as a request extension.
 use std::{
+    error::Error,
+    fmt,
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
-use http::{Request, Response};
+use http::{Request, Response, StatusCode};
+use time::Duration;
+use tokio::sync::RwLock;
+#[cfg(any(feature = "signed", feature = "private"))]
+use tower_cookies::cookie::Key;
 use tower_cookies::{cookie::SameSite, Cookie, CookieManager, Cookies};
 use tower_layer::Layer;
 use tower_service::Service;
 use tracing::Instrument;
 
+#[cfg(any(feature = "signed", feature = "private"))]
+use crate::key_provider::{KeySource, ManagedKey};
 use crate::{
-    session::{Deletion, Expiry, Id},
-    Session, SessionStore,
+    clock::{Clock, SharedClock},
+    events::{SessionEventKind, SessionEvents},
+    limit::CreationLimit,
+    metrics,
+    persistence::extend_inactivity_expiry,
+    remember::{RememberMe, Verification},
+    session::{Deletion, Expiry, ExpiryLimit, Id},
+    session_store::is_internal_record,
+    telemetry, Session, SessionStore,
 };
 
 #[derive(Debug, Clone)]
@@ -22,9 +38,22 @@ struct SessionConfig {
     http_only: bool,
     same_site: SameSite,
     expiry: Option<Expiry>,
+    refresh_threshold: Option<Duration>,
+    absolute_timeout: Option<Duration>,
     secure: bool,
     path: String,
     domain: Option<String>,
+    partitioned: bool,
+    consent_required: bool,
+    strict_mode: Option<StatusCode>,
+    cookie_controller: CookieController,
+    external_cookie_manager: bool,
+    clock: SharedClock,
+    events: Option<SessionEvents>,
+    creation_limit: Option<CreationLimit>,
+    remember_me: Option<RememberMe>,
+    defer_load_errors: bool,
+    scope: Option<String>,
 }
 
 impl SessionConfig {
@@ -35,6 +64,10 @@ impl SessionConfig {
             .secure(self.secure)
             .path(self.path.clone());
 
+        if self.partitioned {
+            cookie_builder = cookie_builder.partitioned(true);
+        }
+
         cookie_builder = cookie_builder.max_age(session.expiry_age());
 
         if let Some(domain) = &self.domain {
@@ -44,8 +77,75 @@ impl SessionConfig {
         cookie_builder.build()
     }
 
+    /// Builds a cookie removing the session cookie, whose value can't be relied
+    /// upon, with the attributes browsers tell cookies apart by.
+    fn build_removal_cookie<'c>(&self) -> Cookie<'c> {
+        let mut cookie_builder = Cookie::build(self.name.clone())
+            .http_only(self.http_only)
+            .same_site(self.same_site)
+            .secure(self.secure)
+            .path(self.path.clone());
+
+        if self.partitioned {
+            cookie_builder = cookie_builder.partitioned(true);
+        }
+
+        if let Some(domain) = &self.domain {
+            cookie_builder = cookie_builder.domain(domain.clone());
+        }
+
+        cookie_builder.build()
+    }
+
+    /// Builds the remember-me cookie, which is always `HttpOnly` as scripts
+    /// have no business with it.
+    fn build_remember_cookie<'c>(&self, remember_me: &RememberMe, value: String) -> Cookie<'c> {
+        let mut cookie_builder = Cookie::build((remember_me.name.clone(), value))
+            .http_only(true)
+            .same_site(self.same_site)
+            .secure(self.secure)
+            .path(self.path.clone())
+            .max_age(remember_me.duration);
+
+        if self.partitioned {
+            cookie_builder = cookie_builder.partitioned(true);
+        }
+
+        if let Some(domain) = &self.domain {
+            cookie_builder = cookie_builder.domain(domain.clone());
+        }
+
+        cookie_builder.build()
+    }
+
     fn new_session(&self) -> Session {
-        Session::new(self.expiry.clone())
+        let session = Session::new(self.expiry.clone());
+        session.set_clock(self.clock.clone());
+        self.apply_absolute_timeout(&session);
+        session
+    }
+
+    /// Builds the response returned in strict mode in place of calling the
+    /// inner service.
+    fn reject<B: Default>(&self) -> Response<B> {
+        tracing::debug!("rejecting request in strict mode");
+        tracing::Span::current().record("session.outcome", "rejected");
+
+        let mut res = Response::new(B::default());
+        *res.status_mut() = self.strict_mode.unwrap_or(StatusCode::UNAUTHORIZED);
+        res
+    }
+
+    fn emit(&self, kind: SessionEventKind, session_id: &Id, principal: Option<String>) {
+        if let Some(events) = &self.events {
+            events.emit(kind, session_id, principal);
+        }
+    }
+
+    fn apply_absolute_timeout(&self, session: &Session) {
+        if let (Some(absolute_timeout), None) = (self.absolute_timeout, session.absolute_expiry()) {
+            session.set_absolute_expiry(self.clock.now().saturating_add(absolute_timeout));
+        }
     }
 }
 
@@ -56,19 +156,183 @@ impl Default for SessionConfig {
             http_only: true,
             same_site: SameSite::Strict,
             expiry: None, // TODO: Is `Max-Age: "Session"` the right default?
+            refresh_threshold: None,
+            absolute_timeout: None,
             secure: false,
             path: String::from("/"),
             domain: None,
+            partitioned: false,
+            consent_required: false,
+            strict_mode: None,
+            cookie_controller: CookieController::Plain,
+            external_cookie_manager: false,
+            clock: SharedClock::default(),
+            events: None,
+            creation_limit: None,
+            remember_me: None,
+            defer_load_errors: false,
+            scope: None,
         }
     }
 }
 
+/// Determines which `tower-cookies` jar the session cookie is read from and
+/// written to.
+#[derive(Debug, Clone)]
+enum CookieController {
+    Plain,
+    #[cfg(feature = "signed")]
+    Signed(KeySource),
+    #[cfg(feature = "private")]
+    Private(KeySource),
+}
+
+impl CookieController {
+    /// Refreshes a managed key if it's due.
+    #[cfg_attr(
+        not(any(feature = "signed", feature = "private")),
+        allow(unused_variables)
+    )]
+    async fn refresh_key(&self, now: time::OffsetDateTime) {
+        match self {
+            Self::Plain => {}
+            #[cfg(feature = "signed")]
+            Self::Signed(keys) => keys.refresh_if_due(now).await,
+            #[cfg(feature = "private")]
+            Self::Private(keys) => keys.refresh_if_due(now).await,
+        }
+    }
+
+    fn get(&self, cookies: &Cookies, name: &str) -> Option<Cookie<'static>> {
+        match self {
+            Self::Plain => cookies.get(name).map(Cookie::into_owned),
+            #[cfg(feature = "signed")]
+            Self::Signed(keys) => keys.verify(|key| cookies.signed(key).get(name)),
+            #[cfg(feature = "private")]
+            Self::Private(keys) => keys.verify(|key| cookies.private(key).get(name)),
+        }
+    }
+
+    fn add(&self, cookies: &Cookies, cookie: Cookie<'static>) {
+        match self {
+            Self::Plain => cookies.add(cookie),
+            #[cfg(feature = "signed")]
+            Self::Signed(keys) => keys.with_current(|key| cookies.signed(key).add(cookie)),
+            #[cfg(feature = "private")]
+            Self::Private(keys) => keys.with_current(|key| cookies.private(key).add(cookie)),
+        }
+    }
+
+    /// Whether a cookie with the given name was sent but failed verification.
+    fn is_tampered(&self, cookies: &Cookies, name: &str) -> bool {
+        !matches!(self, Self::Plain)
+            && cookies.get(name).is_some()
+            && self.get(cookies, name).is_none()
+    }
+
+    fn remove(&self, cookies: &Cookies, cookie: Cookie<'static>) {
+        match self {
+            Self::Plain => cookies.remove(cookie),
+            #[cfg(feature = "signed")]
+            Self::Signed(keys) => keys.with_current(|key| cookies.signed(key).remove(cookie)),
+            #[cfg(feature = "private")]
+            Self::Private(keys) => keys.with_current(|key| cookies.private(key).remove(cookie)),
+        }
+    }
+}
+
+/// A marker signalling that the visitor has consented to the session cookie.
+///
+/// When [`SessionManagerLayer::with_consent_required`] is enabled, inserting
+/// this type as a request extension, for example from a middleware that
+/// inspects a consent cookie, allows the session to be saved and its cookie to
+/// be issued.
+///
+/// # Examples
+///
+/// ```rust
+/// use http::Request;
+/// use tower_sessions::service::Consent;
+///
+/// let mut req = Request::new(());
+/// req.extensions_mut().insert(Consent);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Consent;
+
+/// An error loading the session of a request.
+///
+/// When [`SessionManagerLayer::with_deferred_load_errors`] is enabled, this is
+/// provided as a request extension in place of the [`Session`], and the
+/// `Session` extractor rejects with it.
+#[derive(Debug, Clone)]
+pub struct SessionLoadError(Arc<dyn Error + Send + Sync>);
+
+impl fmt::Display for SessionLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to load session: {}", self.0)
+    }
+}
+
+impl Error for SessionLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.0)
+    }
+}
+
+/// The limit which ended the expired session of a request.
+///
+/// When the session of a request's cookie has expired, it's deleted and the
+/// request is given a new session, along with this request extension, so that
+/// applications may tell e.g. an idle visitor from one whose session reached
+/// its absolute timeout.
+///
+/// # Examples
+///
+/// ```rust
+/// use http::Request;
+/// use tower_sessions::{service::SessionExpired, session::ExpiryLimit};
+///
+/// fn signed_out_for_good<B>(req: &Request<B>) -> bool {
+///     req.extensions()
+///         .get::<SessionExpired>()
+///         .is_some_and(|expired| expired.limit() == ExpiryLimit::AbsoluteTimeout)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionExpired {
+    limit: ExpiryLimit,
+}
+
+impl SessionExpired {
+    /// Returns the limit which ended the session.
+    pub fn limit(&self) -> ExpiryLimit {
+        self.limit
+    }
+}
+
 /// A middleware that provides [`Session`] as a request extension.
+///
+/// # Tracing
+///
+/// Each request is instrumented with a `session_middleware` span, allowing
+/// request logs to be correlated with the session life cycle. The span has the
+/// following fields:
+///
+/// - `session.id`: the session ID's
+///   [fingerprint](crate::session::Id::fingerprint), never the ID itself,
+/// - `session.state`: `"new"` when no session cookie was sent, `"loaded"` when
+///   the session was loaded from the store, or `"expired"` when a session
+///   cookie was sent but no active session was found,
+/// - `session.outcome`: `"saved"`, `"deleted"`, `"unchanged"`, `"rejected"`
+///   when strict mode refused the request, or `"failed"` when a store operation
+///   returned an error.
 #[derive(Debug, Clone)]
 pub struct SessionManager<S, Store: SessionStore> {
     inner: S,
     session_store: Store,
     session_config: SessionConfig,
+    pending_writes: Arc<RwLock<()>>,
 }
 
 impl<S, Store: SessionStore> SessionManager<S, Store> {
@@ -78,8 +342,32 @@ impl<S, Store: SessionStore> SessionManager<S, Store> {
             inner,
             session_store,
             session_config: Default::default(),
+            pending_writes: Default::default(),
         }
     }
+
+    /// Waits for requests in flight to complete their session writes and then
+    /// flushes the session store.
+    ///
+    /// This is intended to be called once the server has stopped accepting
+    /// requests, for example after a graceful shutdown, so that the last
+    /// session mutations aren't lost when the process exits. See
+    /// [`SessionStore::flush`].
+    pub async fn shutdown(&self) -> Result<(), Store::Error> {
+        shutdown(&self.pending_writes, &self.session_store).await
+    }
+}
+
+async fn shutdown<Store: SessionStore>(
+    pending_writes: &RwLock<()>,
+    session_store: &Store,
+) -> Result<(), Store::Error> {
+    // Acquiring the write lock waits for every in-flight write to release its read
+    // lock.
+    drop(pending_writes.write().await);
+    tracing::debug!("pending session writes completed");
+
+    session_store.flush().await
 }
 
 impl<ReqBody, ResBody, S, Store: SessionStore> Service<Request<ReqBody>>
@@ -89,7 +377,7 @@ where
     S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
     S::Future: Send,
     ReqBody: Send + 'static,
-    ResBody: Send,
+    ResBody: Default + Send + 'static,
 {
     type Response = S::Response;
     type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -101,10 +389,36 @@ where
     }
 
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
-        let span = tracing::debug_span!("session_middleware", session.id = tracing::field::Empty);
+        // Unless an enclosing `CookieManager` is expected to provide the cookie jar, we
+        // provide our own by wrapping ourselves in one.
+        if !self.session_config.external_cookie_manager {
+            let clone = self.inner.clone();
+            let inner = std::mem::replace(&mut self.inner, clone);
+
+            let mut session_config = self.session_config.clone();
+            session_config.external_cookie_manager = true;
+
+            let mut cookie_manager = CookieManager::new(SessionManager {
+                inner,
+                session_store: self.session_store.clone(),
+                session_config,
+                pending_writes: self.pending_writes.clone(),
+            });
+
+            return Box::pin(cookie_manager.call(req));
+        }
+
+        let span = tracing::debug_span!(
+            "session_middleware",
+            session.id = tracing::field::Empty,
+            session.state = tracing::field::Empty,
+            session.outcome = tracing::field::Empty,
+            session.new = tracing::field::Empty,
+        );
 
         let session_store = self.session_store.clone();
         let session_config = self.session_config.clone();
+        let pending_writes = self.pending_writes.clone();
 
         // This is necessary to prevent potential panics.
         //
@@ -114,15 +428,31 @@ where
 
         Box::pin(
             async move {
-                let cookies = req
-                    .extensions()
-                    .get::<Cookies>()
-                    .cloned()
-                    .expect("Something has gone wrong with tower-cookies.");
+                let cookies = req.extensions().get::<Cookies>().cloned().expect(
+                    "Something has gone wrong with tower-cookies. Is `CookieManagerLayer` enabled?",
+                );
 
                 let mut has_session_cookie = false;
+                let mut is_loaded = false;
+                let mut is_cycled = false;
+                let mut loaded_principal = None;
+                let mut has_consent =
+                    !session_config.consent_required || req.extensions().get::<Consent>().is_some();
+                let cookie_controller = &session_config.cookie_controller;
+                cookie_controller
+                    .refresh_key(session_config.clock.now())
+                    .await;
+
+                if session_config.strict_mode.is_some()
+                    && cookie_controller.is_tampered(&cookies, &session_config.name)
+                {
+                    tracing::debug!("session cookie failed verification");
+                    cookies.remove(session_config.build_removal_cookie());
+                    return Ok(session_config.reject());
+                }
+
                 let mut session = if let Some(session_cookie) =
-                    cookies.get(&session_config.name).map(Cookie::into_owned)
+                    cookie_controller.get(&cookies, &session_config.name)
                 {
                     // We do have a session cookie, so we retrieve it either from memory or the
                     // backing session store.
@@ -130,28 +460,170 @@ where
                     has_session_cookie = true;
                     let session_id = session_cookie.value().try_into()?;
 
-                    let session = session_store.load(&session_id).await?;
+                    let loaded = metrics::time_load(telemetry::instrument(
+                        "load",
+                        session_store.db_system(),
+                        session_store.load_including_expired(&session_id),
+                    ))
+                    .await
+                    .map_err(record_failure);
+                    let mut session = match loaded {
+                        Ok(session) => session,
+                        Err(err) if session_config.defer_load_errors => {
+                            // The inner service decides what becomes of the request, while the
+                            // cookie is left as is for a later request to load.
+                            tracing::debug!("deferring session load error");
+                            let err: Box<dyn Error + Send + Sync> = err.into();
+                            req.extensions_mut().insert(SessionLoadError(err.into()));
+                            return inner.call(req).await.map_err(Into::into);
+                        }
+                        Err(err) => return Err(err.into()),
+                    };
                     tracing::trace!("loaded from store");
 
+                    // Remember-me and one-time token records share the store, but are never
+                    // sessions.
+                    if session.as_ref().is_some_and(is_internal_record) {
+                        session = None;
+                    }
+
+                    // Stores may return sessions they found expired, and whichever limit comes
+                    // first is enforced here as well.
+                    if let Some(expired) = session
+                        .as_ref()
+                        .filter(|session| session.expiry_date() <= session_config.clock.now())
+                    {
+                        let limit = expired.expiry_limit();
+                        tracing::debug!(?limit, "session expired");
+                        telemetry::instrument(
+                            "delete",
+                            session_store.db_system(),
+                            session_store.delete(expired.id()),
+                        )
+                        .await
+                        .map_err(record_failure)?;
+                        session_config.emit(
+                            SessionEventKind::Expired,
+                            expired.id(),
+                            expired.principal(),
+                        );
+                        req.extensions_mut().insert(SessionExpired { limit });
+                        session = None;
+                    }
+
                     // N.B.: Our store will *not* have the session if the session is empty.
                     if session.is_none() {
-                        cookies.remove(session_cookie);
+                        tracing::Span::current().record("session.state", "expired");
+                        metrics::session_missing();
+                        cookie_controller.remove(&cookies, session_config.build_removal_cookie());
+
+                        if session_config.strict_mode.is_some() {
+                            return Ok(session_config.reject());
+                        }
+                    } else {
+                        tracing::Span::current().record("session.state", "loaded");
+                        // A stored session implies its cookie was issued with consent.
+                        has_consent = true;
+                        is_loaded = true;
                     }
 
-                    session.unwrap_or_else(|| session_config.new_session())
+                    match session {
+                        Some(session) => {
+                            session.set_clock(session_config.clock.clone());
+                            loaded_principal = session.principal();
+
+                            // Sessions saved before an absolute timeout was configured start
+                            // their absolute lifetime now.
+                            session_config.apply_absolute_timeout(&session);
+                            session
+                        }
+                        None => session_config.new_session(),
+                    }
                 } else {
                     // We don't have a session cookie, so let's create a new session.
                     let session = session_config.new_session();
                     tracing::debug!("created new session");
+                    tracing::Span::current().record("session.state", "new");
                     session
                 };
 
-                tracing::Span::current().record("session.id", session.id().to_string());
+                let mut remember_value = session_config
+                    .remember_me
+                    .as_ref()
+                    .and_then(|remember_me| cookie_controller.get(&cookies, &remember_me.name))
+                    .map(|remember_cookie| remember_cookie.value().to_owned());
+
+                // Without an active session, a remember-me token re-establishes one.
+                if let (Some(remember_me), Some(value), false) = (
+                    &session_config.remember_me,
+                    remember_value.clone(),
+                    is_loaded,
+                ) {
+                    let verification = remember_me
+                        .verify(&session_store, session_config.clock.now(), &value)
+                        .await
+                        .map_err(record_failure)?;
+                    match verification {
+                        Verification::Valid { principal, value } => {
+                            tracing::debug!("restored session from remember-me token");
+                            // A remember-me cookie implies it was issued with consent.
+                            has_consent = true;
+                            session.set_principal(Some(&principal));
+                            cookie_controller.add(
+                                &cookies,
+                                session_config.build_remember_cookie(remember_me, value.clone()),
+                            );
+                            remember_value = Some(value);
+                        }
+                        Verification::Reused { principal } => {
+                            tracing::warn!(
+                                principal,
+                                "remember-me token reused, revoked its family"
+                            );
+                            cookie_controller.remove(
+                                &cookies,
+                                session_config.build_remember_cookie(remember_me, String::new()),
+                            );
+                            remember_value = None;
+                        }
+                        Verification::Invalid => {
+                            tracing::debug!("invalid remember-me token");
+                            cookie_controller.remove(
+                                &cookies,
+                                session_config.build_remember_cookie(remember_me, String::new()),
+                            );
+                            remember_value = None;
+                        }
+                    }
+                }
+
+                tracing::Span::current().record("session.id", session.id().fingerprint());
+                tracing::Span::current().record("session.new", !is_loaded);
+
+                // New sessions are counted against the limit of the client making the request.
+                let client_key = match &session_config.creation_limit {
+                    Some(creation_limit) if !is_loaded => {
+                        let (parts, body) = req.into_parts();
+                        let client_key = creation_limit.key(&parts);
+                        req = Request::from_parts(parts, body);
+                        client_key
+                    }
+                    _ => None,
+                };
+
+                // Held from before the inner service runs until the session has been written,
+                // so that shutdown waits on requests in flight to write their sessions.
+                let _pending_write = pending_writes.read().await;
 
                 req.extensions_mut().insert(session.clone());
+                if let Some(scope) = &session_config.scope {
+                    req.extensions_mut().insert(session.scope(scope));
+                }
 
                 let res = Ok(inner.call(req).await.map_err(Into::into)?);
 
+                has_consent = has_consent || session.has_consent();
+
                 // N.B. When a session is empty, it will be deleted. Here the deleted method
                 // accounts for this check.
                 if let Some(session_deletion) = session.deleted() {
@@ -159,11 +631,45 @@ where
                         Deletion::Deleted => {
                             tracing::debug!("deleted state");
 
+                            // Logging out also forgets the visitor.
+                            if let (Some(remember_me), Some(value)) =
+                                (&session_config.remember_me, &remember_value)
+                            {
+                                remember_me
+                                    .revoke(&session_store, value)
+                                    .await
+                                    .map_err(record_failure)?;
+                                cookie_controller.remove(
+                                    &cookies,
+                                    session_config
+                                        .build_remember_cookie(remember_me, String::new()),
+                                );
+                            }
+
                             if has_session_cookie {
-                                session_store.delete(session.id()).await?;
-                                cookies.remove(session_config.build_cookie(&session));
+                                telemetry::instrument(
+                                    "delete",
+                                    session_store.db_system(),
+                                    session_store.delete(session.id()),
+                                )
+                                .await
+                                .map_err(record_failure)?;
+                                cookie_controller
+                                    .remove(&cookies, session_config.build_cookie(&session));
+                                if is_loaded {
+                                    // Sessions may have been emptied, so the principal they were
+                                    // loaded with is reported.
+                                    session_config.emit(
+                                        SessionEventKind::Destroyed,
+                                        session.id(),
+                                        session.principal().or(loaded_principal),
+                                    );
+                                }
 
                                 tracing::trace!("deleted from store");
+                                tracing::Span::current().record("session.outcome", "deleted");
+                            } else {
+                                tracing::Span::current().record("session.outcome", "unchanged");
                             }
 
                             // Since the session has been deleted, there's no need for further
@@ -174,15 +680,34 @@ where
                         Deletion::Cycled(deleted_id) => {
                             tracing::debug!("cycled state");
 
-                            session_store.delete(&deleted_id).await?;
-                            cookies.remove(session_config.build_cookie(&session));
+                            telemetry::instrument(
+                                "delete",
+                                session_store.db_system(),
+                                session_store.delete(&deleted_id),
+                            )
+                            .await
+                            .map_err(record_failure)?;
+                            cookie_controller
+                                .remove(&cookies, session_config.build_cookie(&session));
                             session.reset_deleted();
 
                             session.id = Id::default();
+                            is_cycled = true;
+                            tracing::Span::current()
+                                .record("session.id", session.id().fingerprint());
                         }
                     }
                 };
 
+                // Sessions which expire on inactivity are extended by every request, or only
+                // once their remaining lifetime drops below the refresh threshold, if one is
+                // configured.
+                if has_session_cookie
+                    && extend_inactivity_expiry(&session, session_config.refresh_threshold)
+                {
+                    tracing::debug!("refreshing expiry");
+                }
+
                 // For further consideration:
                 //
                 // We only persist the session in the store when the `modified` flag is set.
@@ -192,11 +717,87 @@ where
                 // the `Set-Cookie` header whenever modified or if some "always save" marker is
                 // set.
                 if session.is_modified() {
+                    if !has_consent {
+                        // Without consent, nothing is persisted and no cookie is issued.
+                        tracing::debug!("modified state without consent");
+                        tracing::Span::current().record("session.outcome", "unchanged");
+                        return res;
+                    }
+
+                    if let (Some(creation_limit), Some(client_key)) =
+                        (&session_config.creation_limit, &client_key)
+                    {
+                        if !creation_limit.try_create(client_key, session_config.clock.now()) {
+                            // Over the limit, the new session is neither persisted nor issued.
+                            tracing::debug!("session creation limited");
+                            tracing::Span::current().record("session.outcome", "limited");
+                            return res;
+                        }
+                    }
+
                     tracing::debug!("modified state");
                     session.reset_modified();
 
-                    session_store.save(&session).await?;
-                    cookies.add(session_config.build_cookie(&session));
+                    if is_loaded && !is_cycled {
+                        metrics::time_save(telemetry::instrument(
+                            "save",
+                            session_store.db_system(),
+                            session_store.save(&session),
+                        ))
+                        .await
+                        .map_err(record_failure)?;
+                    } else {
+                        // The session is persisted under a new ID, so stores may guard against
+                        // collisions.
+                        metrics::time_save(telemetry::instrument(
+                            "create",
+                            session_store.db_system(),
+                            session_store.create(&session),
+                        ))
+                        .await
+                        .map_err(record_failure)?;
+                    }
+                    if !is_loaded {
+                        metrics::session_created();
+                        session_config.emit(
+                            SessionEventKind::Created,
+                            session.id(),
+                            session.principal(),
+                        );
+                    } else if is_cycled {
+                        session_config.emit(
+                            SessionEventKind::Renewed,
+                            session.id(),
+                            session.principal(),
+                        );
+                    }
+                    cookie_controller.add(&cookies, session_config.build_cookie(&session));
+                    tracing::Span::current().record("session.outcome", "saved");
+                } else {
+                    tracing::Span::current().record("session.outcome", "unchanged");
+                }
+
+                if session.take_remember() {
+                    match (&session_config.remember_me, session.principal()) {
+                        (Some(remember_me), Some(principal)) if has_consent => {
+                            // A new login starts a new family.
+                            if let Some(value) = &remember_value {
+                                remember_me
+                                    .revoke(&session_store, value)
+                                    .await
+                                    .map_err(record_failure)?;
+                            }
+                            let value = remember_me
+                                .issue(&session_store, session_config.clock.now(), &principal)
+                                .await
+                                .map_err(record_failure)?;
+                            cookie_controller.add(
+                                &cookies,
+                                session_config.build_remember_cookie(remember_me, value),
+                            );
+                        }
+                        _ => tracing::debug!("remember-me token requested but not issued"),
+                    }
                 }
 
                 res
@@ -206,11 +807,19 @@ where
     }
 }
 
+/// Records a failed store operation on the current span, passing the error
+/// through.
+fn record_failure<E>(err: E) -> E {
+    tracing::Span::current().record("session.outcome", "failed");
+    err
+}
+
 /// A layer for providing [`Session`] as a request extension.
 #[derive(Debug, Clone)]
 pub struct SessionManagerLayer<Store: SessionStore> {
     session_store: Store,
     session_config: SessionConfig,
+    pending_writes: Arc<RwLock<()>>,
 }
 
 impl<Store: SessionStore> SessionManagerLayer<Store> {
@@ -284,6 +893,60 @@ impl<Store: SessionStore> SessionManagerLayer<Store> {
         self
     }
 
+    /// Configures a threshold below which the remaining lifetime of a session
+    /// is extended.
+    ///
+    /// This only applies to sessions using [`Expiry::OnInactivity`]. When a
+    /// session is loaded but not otherwise modified, its expiry is persisted
+    /// again, and its cookie reissued, only if less than `refresh_threshold`
+    /// of its lifetime remains. For example, a two hour session with a one
+    /// hour threshold is saved at most once per hour while it's being read,
+    /// which can greatly reduce writes to the store for active visitors.
+    ///
+    /// Without a threshold, such sessions are extended by every request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{Expiry, MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store)
+    ///     .with_expiry(Expiry::OnInactivity(Duration::hours(2)))
+    ///     .with_refresh_threshold(Duration::hours(1));
+    /// ```
+    pub fn with_refresh_threshold(mut self, refresh_threshold: Duration) -> Self {
+        self.session_config.refresh_threshold = Some(refresh_threshold);
+        self
+    }
+
+    /// Configures an absolute timeout for sessions.
+    ///
+    /// Sessions end once `absolute_timeout` has elapsed since they were
+    /// created, no matter how active they are. This may be combined with an
+    /// inactivity timeout via [`with_expiry`](Self::with_expiry), in which
+    /// case whichever limit is reached first ends the session. The limit
+    /// which applies to a given session is available via
+    /// [`Session::expiry_limit`], and the limit which ended a request's
+    /// session via the [`SessionExpired`] request extension.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{Expiry, MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store)
+    ///     .with_expiry(Expiry::OnInactivity(Duration::minutes(30)))
+    ///     .with_absolute_timeout(Duration::hours(8));
+    /// ```
+    pub fn with_absolute_timeout(mut self, absolute_timeout: Duration) -> Self {
+        self.session_config.absolute_timeout = Some(absolute_timeout);
+        self
+    }
+
     /// Configures the `"Secure"` attribute of the cookie used for the session.
     ///
     /// # Examples
@@ -330,6 +993,371 @@ impl<Store: SessionStore> SessionManagerLayer<Store> {
         self.session_config.domain = Some(domain);
         self
     }
+
+    /// Configures the scope of the service, provided as a request extension
+    /// along with the session, for data only concerning this service among
+    /// several sharing the session, e.g. across subdomains. See the
+    /// [`scope`](crate::scope) module.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store)
+    ///     .with_domain("example.com".to_string())
+    ///     .with_scope("admin");
+    /// ```
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        self.session_config.scope = Some(scope.to_owned());
+        self
+    }
+
+    /// Configures the `"Partitioned"` attribute of the cookie used for the
+    /// session.
+    ///
+    /// Partitioned cookies, also known as [CHIPS][chips], keep working in
+    /// embedded, third-party contexts as browsers phase out unpartitioned
+    /// third-party cookies. Browsers only accept the attribute on cookies that
+    /// are also `Secure`, and embedded contexts generally require
+    /// `SameSite=None`.
+    ///
+    /// [chips]: https://developer.mozilla.org/en-US/docs/Web/Privacy/Partitioned_cookies
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::SameSite, MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store)
+    ///     .with_same_site(SameSite::None)
+    ///     .with_secure(true)
+    ///     .with_partitioned(true);
+    /// ```
+    pub fn with_partitioned(mut self, partitioned: bool) -> Self {
+        self.session_config.partitioned = partitioned;
+        self
+    }
+
+    /// Configures the session cookie to be signed with the provided key.
+    ///
+    /// Signed cookies are read from and written to `tower-cookies`'
+    /// [signed jar](tower_cookies::Cookies::signed), which guarantees the
+    /// integrity of the session ID. Cookies which fail verification are treated
+    /// as absent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, MemoryStore, SessionManagerLayer};
+    ///
+    /// let key = Key::generate();
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_signed(key);
+    /// ```
+    #[cfg(feature = "signed")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed")))]
+    pub fn with_signed(mut self, key: Key) -> Self {
+        self.session_config.cookie_controller = CookieController::Signed(KeySource::Static(key));
+        self
+    }
+
+    /// Configures the session cookie to be encrypted with the provided key.
+    ///
+    /// Private cookies are read from and written to `tower-cookies`'
+    /// [private jar](tower_cookies::Cookies::private), which guarantees the
+    /// confidentiality and integrity of the session ID. Cookies which fail
+    /// decryption are treated as absent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, MemoryStore, SessionManagerLayer};
+    ///
+    /// let key = Key::generate();
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_private(key);
+    /// ```
+    #[cfg(feature = "private")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "private")))]
+    pub fn with_private(mut self, key: Key) -> Self {
+        self.session_config.cookie_controller = CookieController::Private(KeySource::Static(key));
+        self
+    }
+
+    /// Configures the session cookie to be signed with a key fetched from a
+    /// [`KeyProvider`](crate::key_provider::KeyProvider).
+    ///
+    /// This is like [`with_signed`](Self::with_signed), except that the key is
+    /// refreshed periodically, and cookies signed with the key in use before a
+    /// rotation are still accepted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::{
+    ///     key_provider::{EnvKeyProvider, ManagedKey},
+    ///     MemoryStore, SessionManagerLayer,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let key = ManagedKey::new(EnvKeyProvider::new("SESSION_KEY"))
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_signed_managed_key(key);
+    /// # })
+    /// ```
+    #[cfg(feature = "signed")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed")))]
+    pub fn with_signed_managed_key(mut self, key: ManagedKey) -> Self {
+        self.session_config.cookie_controller = CookieController::Signed(KeySource::Managed(key));
+        self
+    }
+
+    /// Configures the session cookie to be encrypted with a key fetched from a
+    /// [`KeyProvider`](crate::key_provider::KeyProvider).
+    ///
+    /// This is like [`with_private`](Self::with_private), except that the key
+    /// is refreshed periodically, and cookies encrypted with the key in use
+    /// before a rotation are still accepted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions::{
+    ///     key_provider::{FileKeyProvider, ManagedKey},
+    ///     MemoryStore, SessionManagerLayer,
+    /// };
+    ///
+    /// # tokio_test::block_on(async {
+    /// let key = ManagedKey::new(FileKeyProvider::new("/run/secrets/session-key"))
+    ///     .await
+    ///     .unwrap();
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_private_managed_key(key);
+    /// # })
+    /// ```
+    #[cfg(feature = "private")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "private")))]
+    pub fn with_private_managed_key(mut self, key: ManagedKey) -> Self {
+        self.session_config.cookie_controller = CookieController::Private(KeySource::Managed(key));
+        self
+    }
+
+    /// Configures whether the session cookie is managed through the cookie jar
+    /// of an enclosing
+    /// [`CookieManagerLayer`](tower_cookies::CookieManagerLayer).
+    ///
+    /// By default, the session layer provides its own cookie jar. Applications
+    /// which already use `tower-cookies` may instead share their jar with the
+    /// session layer, so that all cookies, including the session cookie, are
+    /// emitted through a single `Set-Cookie` handling path. When enabled, a
+    /// `CookieManagerLayer` **must** be applied outside of the session layer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower::ServiceBuilder;
+    /// use tower_cookies::CookieManagerLayer;
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = ServiceBuilder::new()
+    ///     .layer(CookieManagerLayer::new())
+    ///     .layer(SessionManagerLayer::new(session_store).with_external_cookie_manager(true));
+    /// ```
+    pub fn with_external_cookie_manager(mut self, external_cookie_manager: bool) -> Self {
+        self.session_config.external_cookie_manager = external_cookie_manager;
+        self
+    }
+
+    /// Configures whether consent is required before the session cookie is
+    /// issued.
+    ///
+    /// When enabled, a modified session is only saved to the store, and its
+    /// `Set-Cookie` header only sent, once consent is present. Consent is
+    /// present when the request carries a [`Consent`] extension, when the
+    /// session has been marked with [`Session::grant_consent`], or when the
+    /// session was already loaded from the store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_consent_required(true);
+    /// ```
+    pub fn with_consent_required(mut self, consent_required: bool) -> Self {
+        self.session_config.consent_required = consent_required;
+        self
+    }
+
+    /// Configures the clock used to compute session expiry.
+    ///
+    /// This defaults to the system time and is mostly useful in tests, where a
+    /// [`MockClock`](crate::clock::MockClock) may be advanced to exercise
+    /// expiry without sleeping. Session stores which check expiry themselves
+    /// should generally be given the same clock.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, MemoryStore, SessionManagerLayer};
+    ///
+    /// let clock = MockClock::default();
+    /// let session_store = MemoryStore::default().with_clock(clock.clone());
+    /// let session_service = SessionManagerLayer::new(session_store).with_clock(clock);
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.session_config.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Configures the channel through which session lifecycle events are
+    /// emitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{events::SessionEvents, MemoryStore, SessionManagerLayer};
+    ///
+    /// let events = SessionEvents::default();
+    /// let subscriber = events.subscribe();
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_events(events);
+    /// ```
+    pub fn with_events(mut self, events: SessionEvents) -> Self {
+        self.session_config.events = Some(events);
+        self
+    }
+
+    /// Configures a limit on the number of sessions each client may create
+    /// within a window of time.
+    ///
+    /// Once a client reaches the limit, its requests are still served, but
+    /// their new sessions are neither saved nor given a cookie until the
+    /// window ends. This keeps clients which discard their cookies from
+    /// flooding the store. See [`CreationLimit`] for how clients are told
+    /// apart.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{limit::CreationLimit, MemoryStore, SessionManagerLayer};
+    ///
+    /// let creation_limit = CreationLimit::new(10, Duration::minutes(1), |parts| {
+    ///     let real_ip = parts.headers.get("x-real-ip")?;
+    ///     real_ip.to_str().ok().map(str::to_owned)
+    /// });
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service =
+    ///     SessionManagerLayer::new(session_store).with_creation_limit(creation_limit);
+    /// ```
+    pub fn with_creation_limit(mut self, creation_limit: CreationLimit) -> Self {
+        self.session_config.creation_limit = Some(creation_limit);
+        self
+    }
+
+    /// Configures remember-me tokens, issued with [`Session::remember`], which
+    /// re-establish sessions once they expire.
+    ///
+    /// Tokens are sent in a separate cookie, with the same attributes as the
+    /// session cookie except that it's always `HttpOnly` and lasts as long as
+    /// the tokens. See [`remember`](crate::remember) for how tokens are stored
+    /// and rotated.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{remember::RememberMe, MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service =
+    ///     SessionManagerLayer::new(session_store).with_remember_me(RememberMe::default());
+    /// ```
+    pub fn with_remember_me(mut self, remember_me: RememberMe) -> Self {
+        self.session_config.remember_me = Some(remember_me);
+        self
+    }
+
+    /// Configures whether errors loading the session are deferred to the inner
+    /// service rather than failing the request.
+    ///
+    /// When enabled and the store fails to load a session, the inner service
+    /// is called with a [`SessionLoadError`] in place of the [`Session`], so
+    /// that requests which don't use the session are still served, while the
+    /// `Session` extractor rejects those which do. Nothing is saved for such
+    /// requests.
+    ///
+    /// By default, load errors fail the request with the service's error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_deferred_load_errors(true);
+    /// ```
+    pub fn with_deferred_load_errors(mut self, defer_load_errors: bool) -> Self {
+        self.session_config.defer_load_errors = defer_load_errors;
+        self
+    }
+
+    /// Configures whether requests presenting a session cookie which doesn't
+    /// correspond to an active session are rejected.
+    ///
+    /// By default, a session cookie that is unknown to the store, for instance
+    /// because the session expired or was deleted, or that fails verification
+    /// as a signed or private cookie, is silently replaced with a new session.
+    /// In strict mode the inner service is not called and a `401
+    /// Unauthorized` response with an empty body is returned instead, along
+    /// with the removal of the cookie, so that clients such as APIs can tell
+    /// that their session has vanished. Requests without a session cookie are
+    /// unaffected.
+    ///
+    /// The status code may be changed with
+    /// [`with_strict_mode_status`](Self::with_strict_mode_status).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service = SessionManagerLayer::new(session_store).with_strict_mode(true);
+    /// ```
+    pub fn with_strict_mode(mut self, strict_mode: bool) -> Self {
+        self.session_config.strict_mode = strict_mode.then_some(StatusCode::UNAUTHORIZED);
+        self
+    }
+
+    /// Enables strict mode, responding with the provided status code when a
+    /// request is rejected.
+    ///
+    /// See [`with_strict_mode`](Self::with_strict_mode).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use http::StatusCode;
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_service =
+    ///     SessionManagerLayer::new(session_store).with_strict_mode_status(StatusCode::FORBIDDEN);
+    /// ```
+    pub fn with_strict_mode_status(mut self, status: StatusCode) -> Self {
+        self.session_config.strict_mode = Some(status);
+        self
+    }
 }
 
 impl<Store: SessionStore> SessionManagerLayer<Store> {
@@ -350,20 +1378,45 @@ impl<Store: SessionStore> SessionManagerLayer<Store> {
         Self {
             session_store,
             session_config,
+            pending_writes: Default::default(),
         }
     }
+
+    /// Waits for requests in flight through services of this layer to
+    /// complete their session writes and then flushes the session store.
+    ///
+    /// Because clones of the layer share this state, a clone may be retained
+    /// for the purpose of shutting down once the server has stopped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{MemoryStore, SessionManagerLayer};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let session_store = MemoryStore::default();
+    /// let session_layer = SessionManagerLayer::new(session_store);
+    /// let shutdown_handle = session_layer.clone();
+    ///
+    /// // ...serve the application with `session_layer` until shutdown.
+    ///
+    /// shutdown_handle.shutdown().await.unwrap();
+    /// # })
+    /// ```
+    pub async fn shutdown(&self) -> Result<(), Store::Error> {
+        shutdown(&self.pending_writes, &self.session_store).await
+    }
 }
 
 impl<S, Store: SessionStore> Layer<S> for SessionManagerLayer<Store> {
-    type Service = CookieManager<SessionManager<S, Store>>;
+    type Service = SessionManager<S, Store>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        let session_manager = SessionManager {
+        SessionManager {
             inner,
             session_store: self.session_store.clone(),
             session_config: self.session_config.clone(),
-        };
-
-        CookieManager::new(session_manager)
+            pending_writes: self.pending_writes.clone(),
+        }
     }
 }
@@ -10,10 +10,16 @@ use std::{
 use parking_lot::Mutex;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use time::Duration;
 use tower_cookies::cookie::time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::{
+    clock::{Clock, SharedClock},
+    scope::Scope,
+};
+
 const DEFAULT_DURATION: Duration = Duration::weeks(2);
 
 /// Session errors.
@@ -74,6 +80,43 @@ impl Session {
         }
     }
 
+    /// Create a session from its parts, e.g. when decoding a session record
+    /// stored in a format other than a serialized `Session`.
+    ///
+    /// The session isn't marked as modified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    ///
+    /// use tower_sessions::{session::Id, Session};
+    ///
+    /// let data = HashMap::from([("foo".to_owned(), serde_json::json!(42))]);
+    /// let session = Session::from_parts(Id::default(), data, Some("alice".to_owned()), None);
+    /// assert_eq!(session.get::<usize>("foo").unwrap(), Some(42));
+    /// assert_eq!(session.principal().as_deref(), Some("alice"));
+    /// assert!(!session.is_modified());
+    /// ```
+    pub fn from_parts(
+        id: Id,
+        data: HashMap<String, Value>,
+        principal: Option<String>,
+        expiry: Option<Expiry>,
+    ) -> Self {
+        let inner = Inner {
+            data,
+            expiry,
+            principal,
+            ..Default::default()
+        };
+
+        Self {
+            id,
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
     /// Inserts a `impl Serialize` value into the session.
     ///
     /// # Examples
@@ -119,7 +162,7 @@ impl Session {
     pub fn insert_value(&self, key: &str, value: Value) -> Option<Value> {
         let mut inner = self.inner.lock();
         if inner.data.get(key) != Some(&value) {
-            inner.modified_at = Some(OffsetDateTime::now_utc());
+            inner.mark_modified();
             inner.data.insert(key.to_string(), value)
         } else {
             None
@@ -213,14 +256,36 @@ impl Session {
     pub fn remove_value(&self, key: &str) -> Option<Value> {
         let mut inner = self.inner.lock();
         if let Some(removed) = inner.data.remove(key) {
-            inner.modified_at = Some(OffsetDateTime::now_utc());
+            inner.mark_modified();
             Some(removed)
         } else {
             None
         }
     }
 
-    /// Clears the session data.
+    /// Returns the scope with the given name, a namespaced partition of the
+    /// session data, e.g. for data only concerning one of several services
+    /// sharing the session. See the [`scope`](crate::scope) module.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// session.scope("admin").insert("foo", 42).unwrap();
+    ///
+    /// assert_eq!(
+    ///     session.scope("admin").get::<usize>("foo").unwrap(),
+    ///     Some(42)
+    /// );
+    /// assert_eq!(session.get::<usize>("foo").unwrap(), None);
+    /// ```
+    pub fn scope(&self, name: &str) -> Scope {
+        Scope::new(self.clone(), name)
+    }
+
+    /// Clears the session data, including its principal.
     ///
     /// # Examples
     ///
@@ -235,6 +300,7 @@ impl Session {
     pub fn clear(&self) {
         let mut inner = self.inner.lock();
         inner.data.clear();
+        inner.principal = None;
     }
 
     /// Sets `deleted` on the session to `Deletion::Deleted`.
@@ -266,7 +332,14 @@ impl Session {
 
     pub(crate) fn reset_modified(&self) {
         let mut inner = self.inner.lock();
-        inner.modified_at = None;
+        inner.modified = false;
+    }
+
+    /// Marks the session as modified so that its inactivity expiry is
+    /// extended when it's next saved.
+    pub(crate) fn refresh(&self) {
+        let mut inner = self.inner.lock();
+        inner.mark_modified();
     }
 
     /// Sets `deleted` on the session to `Deletion::Cycled(self.id))`.
@@ -293,7 +366,37 @@ impl Session {
     pub fn cycle_id(&self) {
         let mut inner = self.inner.lock();
         inner.deleted = Some(Deletion::Cycled(self.id));
-        inner.modified_at = Some(OffsetDateTime::now_utc());
+        inner.mark_modified();
+    }
+
+    /// Returns a copy of the session under a new ID, marked as modified, for
+    /// session managers carrying out [`cycle_id`](Session::cycle_id).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// session.insert("foo", 42).unwrap();
+    /// session.cycle_id();
+    ///
+    /// let cycled = session.cycled();
+    /// assert_ne!(cycled.id(), session.id());
+    /// assert_eq!(cycled.get::<usize>("foo").unwrap(), Some(42));
+    /// assert!(cycled.deleted().is_none());
+    /// assert!(cycled.is_modified());
+    /// ```
+    pub fn cycled(&self) -> Session {
+        let mut inner = self.inner.lock().clone();
+        inner.deleted = None;
+        inner.version = None;
+        inner.mark_modified();
+
+        Session {
+            id: Id::default(),
+            inner: Arc::new(Mutex::new(inner)),
+        }
     }
 
     /// Sets `deleted` on the session to `Deletion::Deleted` and clears
@@ -318,6 +421,165 @@ impl Session {
         self.delete();
     }
 
+    /// Records that the visitor has consented to the session cookie.
+    ///
+    /// When the session manager is configured to require consent, a session
+    /// is neither saved nor sent to the client as a cookie until consent has
+    /// been given. Calling this method from a handler, for instance one that
+    /// accepts a cookie banner, is one way to signal that consent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// assert!(!session.has_consent());
+    ///
+    /// session.grant_consent();
+    /// assert!(session.has_consent());
+    /// ```
+    pub fn grant_consent(&self) {
+        let mut inner = self.inner.lock();
+        inner.consented = true;
+    }
+
+    /// Returns `true` if consent has been recorded on the session and `false`
+    /// otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// assert!(!session.has_consent());
+    /// ```
+    pub fn has_consent(&self) -> bool {
+        self.inner.lock().consented
+    }
+
+    /// Get the principal of the session, i.e. the user or other entity it was
+    /// established for, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// assert!(session.principal().is_none());
+    ///
+    /// session.set_principal(Some("alice"));
+    /// assert_eq!(session.principal().as_deref(), Some("alice"));
+    /// ```
+    pub fn principal(&self) -> Option<String> {
+        self.inner.lock().principal.clone()
+    }
+
+    /// Set the principal of the session, marking it as modified.
+    ///
+    /// Stores implementing
+    /// [`UserSessions`](crate::session_store::UserSessions) find sessions by
+    /// their principal, e.g. to revoke every session of a user.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// session.set_principal(Some("alice"));
+    /// assert!(session.is_modified());
+    ///
+    /// session.set_principal(None);
+    /// assert!(session.principal().is_none());
+    /// ```
+    pub fn set_principal(&self, principal: Option<&str>) {
+        let mut inner = self.inner.lock();
+        inner.principal = principal.map(str::to_owned);
+        inner.mark_modified();
+    }
+
+    /// Promotes an anonymous session to one established for the principal,
+    /// e.g. upon login.
+    ///
+    /// The session ID is cycled, so that an ID planted or observed before the
+    /// login can't be used to ride the authenticated session, and only the
+    /// values under `retained_keys`, such as a shopping cart, are carried
+    /// over. The principal is then set, so that stores implementing
+    /// [`UserSessions`](crate::session_store::UserSessions) find the session
+    /// by it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{session::Deletion, Session};
+    ///
+    /// let session = Session::default();
+    /// session.insert("cart", vec!["apple"]).unwrap();
+    /// session.insert("csrf_token", "abc").unwrap();
+    ///
+    /// session.promote("alice", &["cart"]);
+    /// assert_eq!(session.principal().as_deref(), Some("alice"));
+    /// assert!(session.get_value("cart").is_some());
+    /// assert!(session.get_value("csrf_token").is_none());
+    /// assert!(matches!(session.deleted(), Some(Deletion::Cycled(_))));
+    /// ```
+    pub fn promote(&self, principal: &str, retained_keys: &[&str]) {
+        let mut inner = self.inner.lock();
+        inner
+            .data
+            .retain(|key, _| retained_keys.contains(&key.as_str()));
+        inner.principal = Some(principal.to_owned());
+        inner.deleted = Some(Deletion::Cycled(self.id));
+        inner.mark_modified();
+    }
+
+    /// Requests that a remember-me token be issued for the principal of the
+    /// session, e.g. upon a login with "remember me" checked.
+    ///
+    /// Once the request completes, the session manager issues the token in a
+    /// separate cookie, provided it's configured with
+    /// [`with_remember_me`](crate::SessionManagerLayer::with_remember_me) and
+    /// the session has a principal. Any token presented with the request is
+    /// revoked. See [`remember`](crate::remember) for how tokens re-establish
+    /// sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// session.promote("alice", &[]);
+    /// session.remember();
+    /// ```
+    pub fn remember(&self) {
+        self.inner.lock().remember = true;
+    }
+
+    /// Returns whether a remember-me token was requested, resetting the
+    /// request.
+    pub(crate) fn take_remember(&self) -> bool {
+        std::mem::take(&mut self.inner.lock().remember)
+    }
+
+    /// Get a copy of the session data.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// session.insert("foo", 42).unwrap();
+    /// assert_eq!(session.data().get("foo"), Some(&serde_json::json!(42)));
+    /// ```
+    pub fn data(&self) -> HashMap<String, Value> {
+        self.inner.lock().data.clone()
+    }
+
     /// Get the session ID.
     ///
     /// # Examples
@@ -377,7 +639,7 @@ impl Session {
     pub fn set_expiry(&self, expiry: Option<Expiry>) {
         let mut inner = self.inner.lock();
         inner.expiry = expiry;
-        inner.modified_at = Some(OffsetDateTime::now_utc());
+        inner.mark_modified();
     }
 
     /// Get session expiry as `OffsetDateTime`.
@@ -393,16 +655,101 @@ impl Session {
     /// ```
     pub fn expiry_date(&self) -> OffsetDateTime {
         let inner = self.inner.lock();
-        match inner.expiry {
-            Some(Expiry::OnInactivity(duration)) => {
-                let modified_at = inner.modified_at.unwrap_or_else(OffsetDateTime::now_utc);
-                modified_at.saturating_add(duration)
-            }
-            Some(Expiry::AtDateTime(datetime)) => datetime,
-            Some(Expiry::OnSessionEnd) | None => {
-                // TODO: The default should probably be configurable.
-                OffsetDateTime::now_utc().saturating_add(DEFAULT_DURATION)
+        let expiry_date = inner.expiry_date();
+        match inner.absolute_expiry {
+            Some(absolute_expiry) => std::cmp::min(expiry_date, absolute_expiry),
+            None => expiry_date,
+        }
+    }
+
+    /// Get the absolute expiry of the session, if one has been set.
+    ///
+    /// Unlike [`Expiry`], the absolute expiry is never extended by activity:
+    /// once this date and time is reached, the session ends regardless of how
+    /// recently it has been used.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// assert!(session.absolute_expiry().is_none());
+    /// ```
+    pub fn absolute_expiry(&self) -> Option<OffsetDateTime> {
+        self.inner.lock().absolute_expiry
+    }
+
+    /// Get the store-assigned version of the session, if any.
+    ///
+    /// Stores which support optimistic concurrency, e.g. via compare-and-swap,
+    /// set this when loading or saving a session and compare it when saving
+    /// again. It is not serialized and is `None` for new sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// assert!(session.version().is_none());
+    /// ```
+    pub fn version(&self) -> Option<u64> {
+        self.inner.lock().version
+    }
+
+    /// Set the store-assigned version of the session.
+    ///
+    /// This is intended for [`SessionStore`](crate::SessionStore)
+    /// implementations and does not mark the session as modified.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    ///
+    /// let session = Session::default();
+    /// session.set_version(Some(42));
+    /// assert_eq!(session.version(), Some(42));
+    /// assert!(!session.is_modified());
+    /// ```
+    pub fn set_version(&self, version: Option<u64>) {
+        self.inner.lock().version = version;
+    }
+
+    /// Sets the clock used for this session's expiry computations.
+    pub(crate) fn set_clock(&self, clock: SharedClock) {
+        self.inner.lock().clock = clock;
+    }
+
+    pub(crate) fn now(&self) -> OffsetDateTime {
+        self.inner.lock().clock.now()
+    }
+
+    pub(crate) fn set_absolute_expiry(&self, absolute_expiry: OffsetDateTime) {
+        let mut inner = self.inner.lock();
+        inner.absolute_expiry = Some(absolute_expiry);
+    }
+
+    /// Returns the limit which determines the session's
+    /// [`expiry_date`](Session::expiry_date), i.e. whichever of its
+    /// [`Expiry`] and its absolute expiry comes first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{session::ExpiryLimit, Session};
+    ///
+    /// let session = Session::default();
+    /// assert_eq!(session.expiry_limit(), ExpiryLimit::Expiry);
+    /// ```
+    pub fn expiry_limit(&self) -> ExpiryLimit {
+        let inner = self.inner.lock();
+        match inner.absolute_expiry {
+            Some(absolute_expiry) if absolute_expiry < inner.expiry_date() => {
+                ExpiryLimit::AbsoluteTimeout
             }
+            _ => ExpiryLimit::Expiry,
         }
     }
 
@@ -422,10 +769,7 @@ impl Session {
     /// session.set_expiry(Some(Expiry::AtDateTime(yesterday)));
     /// assert_eq!(session.expiry_age(), Duration::ZERO);
     pub fn expiry_age(&self) -> Duration {
-        std::cmp::max(
-            self.expiry_date() - OffsetDateTime::now_utc(),
-            Duration::ZERO,
-        )
+        std::cmp::max(self.expiry_date() - self.now(), Duration::ZERO)
     }
 
     /// Returns `true` if the session has been modified and `false` otherwise.
@@ -443,7 +787,7 @@ impl Session {
     /// ```
     pub fn is_modified(&self) -> bool {
         let inner = self.inner.lock();
-        inner.modified_at.is_some() && !inner.data.is_empty()
+        inner.modified && !inner.is_empty()
     }
 
     /// Returns `Some(Deletion)` if one has been set and `None`
@@ -473,7 +817,7 @@ impl Session {
         self.inner.lock().deleted
     }
 
-    /// Returns `true` if the session is empty.
+    /// Returns `true` if the session has neither data nor a principal.
     ///
     /// # Examples
     ///
@@ -487,7 +831,7 @@ impl Session {
     /// assert!(!session.is_empty());
     /// ```
     pub fn is_empty(&self) -> bool {
-        self.inner.lock().data.is_empty()
+        self.inner.lock().is_empty()
     }
 }
 
@@ -511,12 +855,51 @@ impl Borrow<Id> for Session {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct Inner {
     data: Data,
     expiry: Option<Expiry>,
     modified_at: Option<OffsetDateTime>,
     deleted: Option<Deletion>,
+    #[serde(default)]
+    consented: bool,
+    #[serde(default)]
+    absolute_expiry: Option<OffsetDateTime>,
+    #[serde(default)]
+    principal: Option<String>,
+    #[serde(skip)]
+    modified: bool,
+    #[serde(skip)]
+    clock: SharedClock,
+    #[serde(skip)]
+    version: Option<u64>,
+    #[serde(skip)]
+    remember: bool,
+}
+
+impl Inner {
+    fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.principal.is_none()
+    }
+
+    fn mark_modified(&mut self) {
+        self.modified_at = Some(self.clock.now());
+        self.modified = true;
+    }
+
+    fn expiry_date(&self) -> OffsetDateTime {
+        match self.expiry {
+            Some(Expiry::OnInactivity(duration)) => {
+                let modified_at = self.modified_at.unwrap_or_else(|| self.clock.now());
+                modified_at.saturating_add(duration)
+            }
+            Some(Expiry::AtDateTime(datetime)) => datetime,
+            Some(Expiry::OnSessionEnd) | None => {
+                // TODO: The default should probably be configurable.
+                self.clock.now().saturating_add(DEFAULT_DURATION)
+            }
+        }
+    }
 }
 
 /// An ID type for sessions.
@@ -531,6 +914,32 @@ struct Inner {
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, Eq, Hash, PartialEq)]
 pub struct Id(pub Uuid);
 
+impl Id {
+    /// Returns a fingerprint of the ID, suitable for logs and other places the
+    /// ID itself must not appear.
+    ///
+    /// The fingerprint is the first 16 hexadecimal digits of the SHA-256 hash
+    /// of the ID. It's stable, so records mentioning the same session can be
+    /// correlated, but the ID can't be recovered from it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::session::Id;
+    ///
+    /// let session_id = Id::default();
+    /// assert_eq!(session_id.fingerprint().len(), 16);
+    /// assert_eq!(session_id.fingerprint(), session_id.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.to_string().as_bytes());
+        digest[..8]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
 impl Default for Id {
     fn default() -> Self {
         Self(Uuid::new_v4())
@@ -604,3 +1013,17 @@ pub enum Expiry {
     /// [`set_expiry`](Session::set_expiry).
     AtDateTime(OffsetDateTime),
 }
+
+/// The limit which ends a session.
+///
+/// Sessions may be bounded both by their [`Expiry`], such as an inactivity
+/// timeout, and by an absolute timeout measured from when they were created.
+/// Whichever of these is reached first ends the session.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExpiryLimit {
+    /// The session's [`Expiry`] is reached first.
+    Expiry,
+
+    /// The session's absolute timeout is reached first.
+    AbsoluteTimeout,
+}
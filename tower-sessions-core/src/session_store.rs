@@ -57,11 +57,67 @@ pub trait SessionStore: Clone + Send + Sync + 'static {
     /// A method for saving a session in a store.
     async fn save(&self, session: &Session) -> Result<(), Self::Error>;
 
+    /// A method for saving a session in a store for the first time.
+    ///
+    /// This is called instead of [`save`](SessionStore::save) when a session
+    /// is persisted under a new ID, i.e. when it is new or its ID has been
+    /// cycled. Stores which can do so cheaply should refuse to overwrite an
+    /// existing session here, returning an error on ID collisions. By default,
+    /// this defers to `save`.
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.save(session).await
+    }
+
     /// A method for loading a session from a store.
     async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error>;
 
+    /// A method for loading a session from a store even when it has expired.
+    ///
+    /// This is called by the session manager instead of
+    /// [`load`](SessionStore::load), which should never return expired
+    /// sessions. Stores which find sessions expired as they load them may
+    /// return those here, so that the session manager can delete them and
+    /// report their expiry rather than treat their cookies as unknown. By
+    /// default, this defers to `load`.
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        self.load(session_id).await
+    }
+
     /// A method for deleting a session from a store.
     async fn delete(&self, session_id: &Id) -> Result<(), Self::Error>;
+
+    /// A method for flushing buffered writes to a store.
+    ///
+    /// Stores which defer or coalesce writes should persist anything pending
+    /// here. This is called by the session manager on shutdown. By default,
+    /// stores are assumed to write immediately and so this does nothing.
+    async fn flush(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The database system backing the store, e.g. `redis` or `postgresql`.
+    ///
+    /// With the `opentelemetry` feature, this is reported as the `db.system`
+    /// attribute of the spans of store operations. By default, stores report
+    /// none, as befits stores which aren't backed by a database system.
+    fn db_system(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Returns whether a record loaded from a store belongs to one of this crate's
+/// own subsystems, i.e. is a family of
+/// [remember-me tokens](crate::remember) or a [one-time
+/// token](crate::one_time), rather than a session.
+///
+/// Such records share the store with sessions, so middleware loading sessions
+/// by ID must treat them as absent, lest a client presents the ID of a record
+/// as its session cookie.
+pub fn is_internal_record(session: &Session) -> bool {
+    crate::remember::is_record(session) || crate::one_time::is_record(session)
 }
 
 /// An enumeration of both `SessionStore` error types.
@@ -140,6 +196,18 @@ where
         Ok(())
     }
 
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        // The store is the authority on collisions, so the cache is only written
+        // once the store has accepted the session.
+        self.store
+            .create(session)
+            .await
+            .map_err(Self::Error::Store)?;
+        self.cache.save(session).await.map_err(Self::Error::Cache)?;
+
+        Ok(())
+    }
+
     async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
         match self.cache.load(session_id).await {
             // We found a session in the cache, so let's use it.
@@ -167,6 +235,33 @@ where
         }
     }
 
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        match self.cache.load_including_expired(session_id).await {
+            Ok(Some(session)) => Ok(Some(session)),
+
+            // As with `load`, though an expired session hydrating the cache is deleted from
+            // both again by the session manager.
+            Ok(None) => {
+                let session = self
+                    .store
+                    .load_including_expired(session_id)
+                    .await
+                    .map_err(Self::Error::Store)?;
+
+                if let Some(ref session) = session {
+                    self.cache.save(session).await.map_err(Self::Error::Cache)?;
+                }
+
+                Ok(session)
+            }
+
+            Err(err) => Err(Self::Error::Cache(err)),
+        }
+    }
+
     async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
         let store_delete_fut = self.store.delete(session_id).map_err(Self::Error::Store);
         let cache_delete_fut = self.cache.delete(session_id).map_err(Self::Error::Cache);
@@ -175,6 +270,19 @@ where
 
         Ok(())
     }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        let store_flush_fut = self.store.flush().map_err(Self::Error::Store);
+        let cache_flush_fut = self.cache.flush().map_err(Self::Error::Cache);
+
+        futures::try_join!(store_flush_fut, cache_flush_fut)?;
+
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.store.db_system()
+    }
 }
 
 /// A trait providing a deletion method for expired methods and optionally a
@@ -227,3 +335,102 @@ pub trait ExpiredDeletion: SessionStore {
         }
     }
 }
+
+/// A trait providing a method for enumerating the sessions of a store.
+#[async_trait]
+pub trait ListSessions: SessionStore {
+    /// A method for listing the IDs of the active sessions in the store.
+    ///
+    /// Sessions past their expiry date may be listed by stores which only
+    /// remove them periodically.
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error>;
+}
+
+/// A trait providing methods for finding and revoking the sessions of a
+/// principal, as set with [`Session::set_principal`].
+///
+/// By default, these load every session listed by the store, which stores
+/// indexing sessions by principal should avoid by overriding them.
+#[async_trait]
+pub trait UserSessions: ListSessions {
+    /// A method for listing the IDs of the active sessions of a principal.
+    async fn user_sessions(&self, principal: &str) -> Result<Vec<Id>, Self::Error> {
+        let mut session_ids = Vec::new();
+        for session_id in self.list_sessions().await? {
+            let Some(session) = self.load(&session_id).await? else {
+                continue;
+            };
+            if session.principal().as_deref() == Some(principal) {
+                session_ids.push(session_id);
+            }
+        }
+
+        Ok(session_ids)
+    }
+
+    /// A method for deleting every session of a principal.
+    async fn delete_user_sessions(&self, principal: &str) -> Result<(), Self::Error> {
+        for session_id in self.user_sessions(principal).await? {
+            self.delete(&session_id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A trait providing a method for atomically removing a session from a store.
+#[async_trait]
+pub trait TakeSession: SessionStore {
+    /// A method for deleting a session from the store, returning it if it was
+    /// there and hadn't expired.
+    ///
+    /// Of concurrent calls for the same session, at most one returns it.
+    async fn take(&self, session_id: &Id) -> Result<Option<Session>, Self::Error>;
+}
+
+#[async_trait]
+impl<Cache, Store> ListSessions for CachingSessionStore<Cache, Store>
+where
+    Cache: SessionStore,
+    Store: ListSessions,
+{
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        self.store.list_sessions().await.map_err(Self::Error::Store)
+    }
+}
+
+#[async_trait]
+impl<Cache, Store> UserSessions for CachingSessionStore<Cache, Store>
+where
+    Cache: SessionStore,
+    Store: UserSessions,
+{
+    async fn user_sessions(&self, principal: &str) -> Result<Vec<Id>, Self::Error> {
+        self.store
+            .user_sessions(principal)
+            .await
+            .map_err(Self::Error::Store)
+    }
+}
+
+#[async_trait]
+impl<Cache, Store> TakeSession for CachingSessionStore<Cache, Store>
+where
+    Cache: SessionStore,
+    Store: TakeSession,
+{
+    async fn take(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        // The store is the authority on whether the session was still there.
+        let session = self
+            .store
+            .take(session_id)
+            .await
+            .map_err(Self::Error::Store)?;
+        self.cache
+            .delete(session_id)
+            .await
+            .map_err(Self::Error::Cache)?;
+
+        Ok(session)
+    }
+}
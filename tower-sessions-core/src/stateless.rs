@@ -0,0 +1,563 @@
+//! A middleware keeping the entire session in an encrypted cookie.
+//!
+//! [`StatelessSessionManagerLayer`] provides [`Session`] as a request
+//! extension like [`SessionManagerLayer`](crate::SessionManagerLayer), but
+//! without a session store: the session record, along with its expiry date, is
+//! serialized and encrypted into the session cookie itself. This suits
+//! deployments which aren't ready to run a store yet, provided that sessions
+//! remain small.
+//!
+//! Cookies are encrypted and authenticated with the [private
+//! jar's](tower_cookies::cookie::PrivateJar) AES-256-GCM, so their contents are
+//! neither readable nor forgeable without the key. Because nothing is kept on
+//! the server though:
+//!
+//! - a session can't be revoked before its embedded expiry date: deleting it
+//!   removes the cookie from the visitor's browser, but a copy of the cookie
+//!   remains valid until it expires, as does any earlier version of the
+//!   session,
+//! - the session must fit in a cookie, see
+//!   [`with_max_size`](StatelessSessionManagerLayer::with_max_size), or be
+//!   split across a few, see
+//!   [`with_max_chunks`](StatelessSessionManagerLayer::with_max_chunks).
+//!
+//! Features built on the store, such as remember-me tokens and listing
+//! sessions, aren't available.
+use std::{
+    future::Future,
+    ops::Range,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::{Request, Response};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tower_cookies::{
+    cookie::{CookieJar, Key, SameSite},
+    Cookie, CookieManager, Cookies,
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    clock::{Clock, SharedClock},
+    session::{Deletion, Expiry, Id},
+    Session,
+};
+
+/// The default maximum size of the session cookie's name and value, the
+/// minimum that browsers are required to support.
+const DEFAULT_MAX_SIZE: usize = 4096;
+
+/// The default maximum number of cookies a session is split across.
+const DEFAULT_MAX_CHUNKS: usize = 1;
+
+/// An error type for [`StatelessSessionManager`].
+#[derive(thiserror::Error, Debug)]
+pub enum StatelessSessionError {
+    /// A variant for sessions which don't fit in the maximum number of
+    /// cookies of the maximum size.
+    #[error(
+        "Encrypted session of {size} bytes exceeds {max_chunks} cookie(s) of at most {max_size} \
+         bytes"
+    )]
+    TooLarge {
+        /// The size of the encrypted session.
+        size: usize,
+
+        /// The configured maximum size of a cookie.
+        max_size: usize,
+
+        /// The configured maximum number of cookies.
+        max_chunks: usize,
+    },
+
+    /// A variant to map `serde_json` errors.
+    #[error("JSON serialization/deserialization error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+/// The plaintext of a session cookie.
+#[derive(Serialize, Deserialize)]
+struct Token<S> {
+    #[serde(with = "time::serde::timestamp")]
+    expiry_date: OffsetDateTime,
+    session: S,
+}
+
+#[derive(Debug, Clone)]
+struct StatelessConfig {
+    key: Key,
+    name: String,
+    http_only: bool,
+    same_site: SameSite,
+    expiry: Option<Expiry>,
+    secure: bool,
+    path: String,
+    domain: Option<String>,
+    max_size: usize,
+    max_chunks: usize,
+    clock: SharedClock,
+}
+
+impl StatelessConfig {
+    fn new(key: Key) -> Self {
+        Self {
+            key,
+            name: String::from("id"),
+            http_only: true,
+            same_site: SameSite::Strict,
+            expiry: None,
+            secure: false,
+            path: String::from("/"),
+            domain: None,
+            max_size: DEFAULT_MAX_SIZE,
+            max_chunks: DEFAULT_MAX_CHUNKS,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// The name of the cookie holding the chunk at the index, the first being
+    /// held by the session cookie itself.
+    fn chunk_name(&self, index: usize) -> String {
+        match index {
+            0 => self.name.clone(),
+            _ => format!("{}.{index}", self.name),
+        }
+    }
+
+    fn build_cookie<'c>(&self, name: String, session: &Session, value: String) -> Cookie<'c> {
+        let mut cookie_builder = Cookie::build((name, value))
+            .http_only(self.http_only)
+            .same_site(self.same_site)
+            .secure(self.secure)
+            .path(self.path.clone())
+            .max_age(session.expiry_age());
+
+        if let Some(domain) = &self.domain {
+            cookie_builder = cookie_builder.domain(domain.clone());
+        }
+
+        cookie_builder.build()
+    }
+
+    /// Reads the chunks sent with the request, in order.
+    fn read_chunks(&self, cookies: &Cookies) -> Vec<String> {
+        (0..self.max_chunks)
+            .map_while(|index| {
+                cookies
+                    .get(&self.chunk_name(index))
+                    .map(|cookie| cookie.value().to_owned())
+            })
+            .collect()
+    }
+
+    /// Removes the cookies holding the chunks in the range.
+    fn remove_chunks(&self, cookies: &Cookies, chunks: Range<usize>) {
+        for index in chunks {
+            let mut cookie_builder = Cookie::build(self.chunk_name(index)).path(self.path.clone());
+
+            if let Some(domain) = &self.domain {
+                cookie_builder = cookie_builder.domain(domain.clone());
+            }
+
+            cookies.remove(cookie_builder.build());
+        }
+    }
+
+    fn new_session(&self) -> Session {
+        let session = Session::new(self.expiry.clone());
+        session.set_clock(self.clock.clone());
+        session
+    }
+
+    /// Encrypts the value, authenticating the cookie name along with it.
+    fn seal(&self, value: String) -> String {
+        let mut jar = CookieJar::new();
+        jar.private_mut(&self.key)
+            .add(Cookie::new(self.name.clone(), value));
+        jar.get(&self.name)
+            .map(|cookie| cookie.value().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Decrypts the value, if it was sealed with our key and cookie name.
+    fn open(&self, value: &str) -> Option<String> {
+        let mut jar = CookieJar::new();
+        jar.add_original(Cookie::new(self.name.clone(), value.to_owned()));
+        jar.private(&self.key)
+            .get(&self.name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+
+    /// Encodes the session into the values of as many cookies as its size
+    /// requires.
+    ///
+    /// The session is sealed as a whole before being split, so a missing,
+    /// reordered, or substituted chunk fails verification of the session.
+    fn encode(&self, session: &Session) -> Result<Vec<String>, StatelessSessionError> {
+        let token = Token {
+            expiry_date: session.expiry_date(),
+            session,
+        };
+        let value = self.seal(serde_json::to_string(&token)?);
+
+        // Sealed values are base64, so they may be split at any byte.
+        let mut chunks = Vec::new();
+        let mut rest = value.as_str();
+        while !rest.is_empty() {
+            let capacity = self
+                .max_size
+                .saturating_sub(self.chunk_name(chunks.len()).len());
+            if chunks.len() == self.max_chunks || capacity == 0 {
+                return Err(StatelessSessionError::TooLarge {
+                    size: value.len(),
+                    max_size: self.max_size,
+                    max_chunks: self.max_chunks,
+                });
+            }
+
+            let (chunk, tail) = rest.split_at(capacity.min(rest.len()));
+            chunks.push(chunk.to_owned());
+            rest = tail;
+        }
+
+        Ok(chunks)
+    }
+
+    /// Decodes the session of the chunks, unless it fails verification or has
+    /// expired.
+    fn decode(&self, chunks: &[String]) -> Option<Session> {
+        let token: Token<Session> = serde_json::from_str(&self.open(&chunks.concat())?).ok()?;
+        if token.expiry_date <= self.clock.now() {
+            return None;
+        }
+
+        token.session.set_clock(self.clock.clone());
+        Some(token.session)
+    }
+}
+
+/// A middleware that provides [`Session`] as a request extension, keeping it
+/// in an encrypted cookie.
+#[derive(Debug, Clone)]
+pub struct StatelessSessionManager<S> {
+    inner: S,
+    config: StatelessConfig,
+}
+
+impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for StatelessSessionManager<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let config = self.config.clone();
+
+        // See: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let cookies = req.extensions().get::<Cookies>().cloned().expect(
+                "Something has gone wrong with tower-cookies. Is `CookieManagerLayer` enabled?",
+            );
+
+            let chunks = config.read_chunks(&cookies);
+            let sent_chunks = chunks.len();
+            let session = if chunks.is_empty() {
+                config.new_session()
+            } else {
+                match config.decode(&chunks) {
+                    Some(session) => {
+                        tracing::debug!(chunks = sent_chunks, "loaded session from cookie");
+                        session
+                    }
+                    None => {
+                        // The cookie expired, or was sealed with another key.
+                        tracing::debug!("session cookie failed verification or expired");
+                        config.remove_chunks(&cookies, 0..sent_chunks);
+                        config.new_session()
+                    }
+                }
+            };
+
+            req.extensions_mut().insert(session.clone());
+
+            let res = inner.call(req).await.map_err(Into::into)?;
+
+            match session.deleted() {
+                Some(Deletion::Deleted) => {
+                    tracing::debug!("deleted state");
+                    config.remove_chunks(&cookies, 0..sent_chunks);
+                    return Ok(res);
+                }
+                Some(Deletion::Cycled(_)) => {
+                    tracing::debug!("cycled state");
+                    session.reset_deleted();
+                    let mut session = session.clone();
+                    session.id = Id::default();
+                    finish(&config, &cookies, &session, sent_chunks)?;
+                    return Ok(res);
+                }
+                None => {}
+            }
+
+            finish(&config, &cookies, &session, sent_chunks)?;
+
+            Ok(res)
+        })
+    }
+}
+
+/// Issues the cookies of a modified session, removing those of chunks it no
+/// longer needs.
+fn finish(
+    config: &StatelessConfig,
+    cookies: &Cookies,
+    session: &Session,
+    sent_chunks: usize,
+) -> Result<(), StatelessSessionError> {
+    if session.is_modified() {
+        tracing::debug!("modified state");
+        session.reset_modified();
+        let chunks = config.encode(session)?;
+        let issued_chunks = chunks.len();
+        for (index, value) in chunks.into_iter().enumerate() {
+            cookies.add(config.build_cookie(config.chunk_name(index), session, value));
+        }
+        config.remove_chunks(cookies, issued_chunks..sent_chunks);
+    }
+
+    Ok(())
+}
+
+/// A layer for providing [`Session`] as a request extension, keeping it in an
+/// encrypted cookie.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+///
+/// let key = Key::generate();
+/// let session_service = StatelessSessionManagerLayer::new(key);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatelessSessionManagerLayer {
+    config: StatelessConfig,
+}
+
+impl StatelessSessionManagerLayer {
+    /// Create a new [`StatelessSessionManagerLayer`] encrypting session
+    /// cookies with the provided key, and with default cookie configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let key = Key::generate();
+    /// let session_service = StatelessSessionManagerLayer::new(key);
+    /// ```
+    pub fn new(key: Key) -> Self {
+        Self {
+            config: StatelessConfig::new(key),
+        }
+    }
+
+    /// Configures the name of the cookie used for the session.
+    /// The default value is `"id"`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service = StatelessSessionManagerLayer::new(Key::generate()).with_name("my.sid");
+    /// ```
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.config.name = name.to_owned();
+        self
+    }
+
+    /// Configures the `"HttpOnly"` attribute of the cookie used for the
+    /// session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service = StatelessSessionManagerLayer::new(Key::generate()).with_http_only(true);
+    /// ```
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.config.http_only = http_only;
+        self
+    }
+
+    /// Configures the `"SameSite"` attribute of the cookie used for the
+    /// session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{
+    ///     cookie::{Key, SameSite},
+    ///     stateless::StatelessSessionManagerLayer,
+    /// };
+    ///
+    /// let session_service =
+    ///     StatelessSessionManagerLayer::new(Key::generate()).with_same_site(SameSite::Lax);
+    /// ```
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.config.same_site = same_site;
+        self
+    }
+
+    /// Configures the expiry of sessions, which is embedded in their cookie
+    /// and sets its `"Max-Age"` attribute.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer, Expiry};
+    ///
+    /// let session_expiry = Expiry::OnInactivity(Duration::hours(1));
+    /// let session_service =
+    ///     StatelessSessionManagerLayer::new(Key::generate()).with_expiry(session_expiry);
+    /// ```
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        self.config.expiry = Some(expiry);
+        self
+    }
+
+    /// Configures the `"Secure"` attribute of the cookie used for the session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service = StatelessSessionManagerLayer::new(Key::generate()).with_secure(true);
+    /// ```
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.config.secure = secure;
+        self
+    }
+
+    /// Configures the `"Path"` attribute of the cookie used for the session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service =
+    ///     StatelessSessionManagerLayer::new(Key::generate()).with_path("/some/path".to_string());
+    /// ```
+    pub fn with_path(mut self, path: String) -> Self {
+        self.config.path = path;
+        self
+    }
+
+    /// Configures the `"Domain"` attribute of the cookie used for the session.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service =
+    ///     StatelessSessionManagerLayer::new(Key::generate()).with_domain("localhost".to_string());
+    /// ```
+    pub fn with_domain(mut self, domain: String) -> Self {
+        self.config.domain = Some(domain);
+        self
+    }
+
+    /// Configures the maximum size of the session cookie's name and value, in
+    /// bytes. The default value is 4096 bytes, the minimum that browsers are
+    /// required to support.
+    ///
+    /// A session which would exceed it is split across several cookies, up to
+    /// [`with_max_chunks`](Self::with_max_chunks). A session which can't be
+    /// kept in those either fails the request with
+    /// [`StatelessSessionError::TooLarge`], rather than letting the browser
+    /// drop the cookie.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service = StatelessSessionManagerLayer::new(Key::generate()).with_max_size(2048);
+    /// ```
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.config.max_size = max_size;
+        self
+    }
+
+    /// Configures the maximum number of cookies a session may be split across
+    /// once it exceeds the [maximum size](Self::with_max_size) of a cookie.
+    /// The default value is 1, keeping sessions in the session cookie alone.
+    ///
+    /// The first chunk is kept in the session cookie, and the next ones in
+    /// cookies named after it with a numbered suffix, e.g. `id.1` and `id.2`.
+    /// Chunks are reassembled before the session is decrypted, so the session
+    /// is verified as a whole. Note that every chunk is sent along with each
+    /// request, and that servers commonly limit the size of request headers
+    /// to 8 KiB.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service = StatelessSessionManagerLayer::new(Key::generate()).with_max_chunks(2);
+    /// ```
+    pub fn with_max_chunks(mut self, max_chunks: usize) -> Self {
+        self.config.max_chunks = max_chunks;
+        self
+    }
+
+    /// Configures the clock used to compute and check the expiry of sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, cookie::Key, stateless::StatelessSessionManagerLayer};
+    ///
+    /// let session_service =
+    ///     StatelessSessionManagerLayer::new(Key::generate()).with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.config.clock = SharedClock::new(clock);
+        self
+    }
+}
+
+impl<S> Layer<S> for StatelessSessionManagerLayer {
+    type Service = CookieManager<StatelessSessionManager<S>>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CookieManager::new(StatelessSessionManager {
+            inner,
+            config: self.config.clone(),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+//! OpenTelemetry instrumentation of the session middleware.
+//!
+//! When the `opentelemetry` feature is enabled,
+//! [`SessionManager`](crate::SessionManager) wraps each store operation it
+//! performs in a span nested in its `session_middleware` span, carrying
+//! OpenTelemetry semantic attributes. These are [`tracing`](https://docs.rs/tracing)
+//! spans, so with [`tracing-opentelemetry`](https://docs.rs/tracing-opentelemetry)
+//! installed, session activity shows up in the request's distributed trace,
+//! whichever store is in use.
+//!
+//! Store operation spans are named [`STORE_SPAN`] and carry:
+//!
+//! - [`DB_SYSTEM`], as reported by
+//!   [`SessionStore::db_system`](crate::SessionStore::db_system), if any.
+//! - [`DB_OPERATION_NAME`], one of `load`, `create`, `save`, and `delete`.
+//! - `otel.name`, the operation, and `otel.kind`, `client`.
+//! - `otel.status_code`, `ERROR`, when the operation fails.
+//!
+//! Regardless of the feature, the `session_middleware` span records
+//! [`SESSION_NEW`], whether the request started a new session.
+use std::future::Future;
+
+/// The name of the spans of store operations.
+pub const STORE_SPAN: &str = "session_store";
+
+/// The attribute holding the database system backing the store.
+pub const DB_SYSTEM: &str = "db.system";
+
+/// The attribute holding the store operation.
+pub const DB_OPERATION_NAME: &str = "db.operation.name";
+
+/// The attribute of the `session_middleware` span holding whether the request
+/// started a new session.
+pub const SESSION_NEW: &str = "session.new";
+
+#[cfg_attr(not(feature = "opentelemetry"), allow(unused_variables))]
+pub(crate) async fn instrument<T, E>(
+    operation: &'static str,
+    db_system: Option<&'static str>,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    #[cfg(feature = "opentelemetry")]
+    {
+        use tracing::Instrument;
+
+        let span = tracing::debug_span!(
+            STORE_SPAN,
+            db.system = db_system,
+            db.operation.name = operation,
+            otel.name = operation,
+            otel.kind = "client",
+            otel.status_code = tracing::field::Empty,
+        );
+
+        let res = fut.instrument(span.clone()).await;
+        if res.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+
+        res
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    fut.await
+}
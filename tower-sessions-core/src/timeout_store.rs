@@ -0,0 +1,293 @@
+//! A session store enforcing deadlines on the operations of another.
+//!
+//! A database that slows down, e.g. under load or while failing over, holds
+//! every request waiting on its sessions. [`TimeoutStore`] bounds how long
+//! loading, saving, and deleting sessions may take, failing them with a
+//! [`Timeout`](TimeoutStoreError::Timeout) error past their deadline, whichever
+//! store it wraps.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use tower_sessions::{timeout_store::TimeoutStore, MemoryStore, SessionManagerLayer};
+//!
+//! let session_store = TimeoutStore::new(MemoryStore::default(), Duration::from_millis(500))
+//!     .with_save_timeout(Duration::from_secs(1));
+//! let session_layer = SessionManagerLayer::new(session_store);
+//! ```
+use std::{fmt, future::Future, time::Duration};
+
+use async_trait::async_trait;
+
+use crate::{
+    session::{Id, Session},
+    session_store::{ExpiredDeletion, ListSessions, UserSessions},
+    SessionStore,
+};
+
+/// An operation of a [`TimeoutStore`] with a deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Saving a session for the first time.
+    Create,
+    /// Saving a session.
+    Save,
+    /// Loading a session.
+    Load,
+    /// Deleting a session.
+    Delete,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Create => "create",
+            Self::Save => "save",
+            Self::Load => "load",
+            Self::Delete => "delete",
+        })
+    }
+}
+
+/// An error of a [`TimeoutStore`].
+#[derive(thiserror::Error)]
+pub enum TimeoutStoreError<Store: SessionStore> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(Store::Error),
+
+    /// The operation didn't complete before its deadline.
+    #[error("Session store {operation} timed out after {timeout:?}")]
+    Timeout {
+        /// The operation which timed out.
+        operation: Operation,
+        /// The deadline of the operation.
+        timeout: Duration,
+    },
+}
+
+impl<Store: SessionStore> TimeoutStoreError<Store> {
+    /// Returns whether the operation timed out, rather than failed in the
+    /// store.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout { .. })
+    }
+}
+
+impl<Store: SessionStore> fmt::Debug for TimeoutStoreError<Store> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(err) => write!(f, "{:?}", err)?,
+            Self::Timeout { operation, timeout } => f
+                .debug_struct("Timeout")
+                .field("operation", operation)
+                .field("timeout", timeout)
+                .finish()?,
+        };
+
+        Ok(())
+    }
+}
+
+/// A session store failing the operations of the store it wraps past their
+/// deadline.
+///
+/// An operation past its deadline is abandoned, so whether a timed out save or
+/// delete was applied by the store is unknown.
+///
+/// Deleting expired sessions, listing sessions, and the operations on the
+/// sessions of a principal aren't given deadlines, as they run in the
+/// background or from administration tools, and may legitimately take long.
+#[derive(Debug, Clone)]
+pub struct TimeoutStore<Store: SessionStore> {
+    inner: Store,
+    load_timeout: Duration,
+    save_timeout: Duration,
+    delete_timeout: Duration,
+}
+
+impl<Store: SessionStore> TimeoutStore<Store> {
+    /// Create a new `TimeoutStore` wrapping the provided store, with the same
+    /// deadline for every operation.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{timeout_store::TimeoutStore, MemoryStore};
+    ///
+    /// let session_store = TimeoutStore::new(MemoryStore::default(), Duration::from_millis(500));
+    /// ```
+    pub fn new(inner: Store, timeout: Duration) -> Self {
+        Self {
+            inner,
+            load_timeout: timeout,
+            save_timeout: timeout,
+            delete_timeout: timeout,
+        }
+    }
+
+    /// Configures the deadline for loading sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{timeout_store::TimeoutStore, MemoryStore};
+    ///
+    /// let session_store = TimeoutStore::new(MemoryStore::default(), Duration::from_millis(500))
+    ///     .with_load_timeout(Duration::from_millis(100));
+    /// ```
+    pub fn with_load_timeout(mut self, timeout: Duration) -> Self {
+        self.load_timeout = timeout;
+        self
+    }
+
+    /// Configures the deadline for saving sessions, whether for the first time
+    /// or not.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{timeout_store::TimeoutStore, MemoryStore};
+    ///
+    /// let session_store = TimeoutStore::new(MemoryStore::default(), Duration::from_millis(500))
+    ///     .with_save_timeout(Duration::from_secs(1));
+    /// ```
+    pub fn with_save_timeout(mut self, timeout: Duration) -> Self {
+        self.save_timeout = timeout;
+        self
+    }
+
+    /// Configures the deadline for deleting sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use tower_sessions::{timeout_store::TimeoutStore, MemoryStore};
+    ///
+    /// let session_store = TimeoutStore::new(MemoryStore::default(), Duration::from_millis(500))
+    ///     .with_delete_timeout(Duration::from_secs(1));
+    /// ```
+    pub fn with_delete_timeout(mut self, timeout: Duration) -> Self {
+        self.delete_timeout = timeout;
+        self
+    }
+
+    async fn with_deadline<T>(
+        &self,
+        operation: Operation,
+        timeout: Duration,
+        fut: impl Future<Output = Result<T, Store::Error>>,
+    ) -> Result<T, TimeoutStoreError<Store>> {
+        match tokio::time::timeout(timeout, fut).await {
+            Ok(res) => res.map_err(TimeoutStoreError::Store),
+            Err(_) => {
+                tracing::warn!(%operation, ?timeout, "session store operation timed out");
+                Err(TimeoutStoreError::Timeout { operation, timeout })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<Store: SessionStore> SessionStore for TimeoutStore<Store> {
+    type Error = TimeoutStoreError<Store>;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        self.with_deadline(Operation::Save, self.save_timeout, self.inner.save(session))
+            .await
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        self.with_deadline(
+            Operation::Create,
+            self.save_timeout,
+            self.inner.create(session),
+        )
+        .await
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        self.with_deadline(
+            Operation::Load,
+            self.load_timeout,
+            self.inner.load(session_id),
+        )
+        .await
+    }
+
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        self.with_deadline(
+            Operation::Load,
+            self.load_timeout,
+            self.inner.load_including_expired(session_id),
+        )
+        .await
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.with_deadline(
+            Operation::Delete,
+            self.delete_timeout,
+            self.inner.delete(session_id),
+        )
+        .await
+    }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        self.inner.flush().await.map_err(TimeoutStoreError::Store)
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.inner.db_system()
+    }
+}
+
+#[async_trait]
+impl<Store: ExpiredDeletion> ExpiredDeletion for TimeoutStore<Store> {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        self.inner
+            .delete_expired()
+            .await
+            .map_err(TimeoutStoreError::Store)
+    }
+}
+
+#[async_trait]
+impl<Store: ListSessions> ListSessions for TimeoutStore<Store> {
+    async fn list_sessions(&self) -> Result<Vec<Id>, Self::Error> {
+        self.inner
+            .list_sessions()
+            .await
+            .map_err(TimeoutStoreError::Store)
+    }
+}
+
+#[async_trait]
+impl<Store: UserSessions> UserSessions for TimeoutStore<Store> {
+    async fn user_sessions(&self, principal: &str) -> Result<Vec<Id>, Self::Error> {
+        self.inner
+            .user_sessions(principal)
+            .await
+            .map_err(TimeoutStoreError::Store)
+    }
+
+    async fn delete_user_sessions(&self, principal: &str) -> Result<(), Self::Error> {
+        self.inner
+            .delete_user_sessions(principal)
+            .await
+            .map_err(TimeoutStoreError::Store)
+    }
+}
@@ -0,0 +1,281 @@
+//! Exporting sessions as newline-delimited JSON and importing them back.
+//!
+//! [`export`] lists the sessions of a store and yields one JSON line per
+//! session, which [`import`] saves into a store, e.g. to back sessions up
+//! before a risky migration, move them to another backend, or seed a staging
+//! environment. [`Transfer`] configures both: whether expired sessions are
+//! kept, and a transform applied to each session, e.g. to anonymize fixtures.
+//!
+//! Sessions are exported as they're loaded from the store, and imported as
+//! saved into it. So sessions exported from an
+//! [`EncryptedStore`](crate::encrypted_store::EncryptedStore) are decrypted,
+//! and sessions imported into one are encrypted with its keys: transferring
+//! sessions between encrypted stores re-encrypts them. To keep a backup
+//! encrypted instead, export the store it wraps.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use futures::TryStreamExt;
+//! use tower_sessions::{transfer, MemoryStore, Session, SessionStore};
+//!
+//! # tokio_test::block_on(async {
+//! let source = MemoryStore::default();
+//! let session = Session::default();
+//! session.insert("foo", 42).unwrap();
+//! source.save(&session).await.unwrap();
+//!
+//! let lines: Vec<String> = transfer::export(&source).try_collect().await.unwrap();
+//!
+//! let destination = MemoryStore::default();
+//! let lines = futures::stream::iter(lines.into_iter().map(Ok::<_, std::io::Error>));
+//! let imported = transfer::import(&destination, lines).await.unwrap();
+//! assert_eq!(imported, 1);
+//! # })
+//! ```
+use std::{error::Error, fmt, sync::Arc};
+
+use futures::{stream, Stream, StreamExt};
+
+use crate::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    session_store::ListSessions,
+    Session, SessionStore,
+};
+
+type TransformFn = dyn Fn(Session) -> Option<Session> + Send + Sync;
+
+/// An error exporting or importing sessions.
+#[derive(thiserror::Error, Debug)]
+pub enum TransferError<StoreError: Error> {
+    /// A store-related error.
+    #[error(transparent)]
+    Store(StoreError),
+
+    /// A session couldn't be serialized.
+    #[error(transparent)]
+    Serialize(serde_json::Error),
+
+    /// A line couldn't be deserialized as a session.
+    #[error("Line {line}: {source}")]
+    Deserialize {
+        /// The number of the line, starting at one.
+        line: usize,
+        /// The deserialization error.
+        source: serde_json::Error,
+    },
+
+    /// A line couldn't be read.
+    #[error(transparent)]
+    Read(Box<dyn Error + Send + Sync>),
+}
+
+/// Options of session exports and imports.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::transfer::Transfer;
+///
+/// // Staging fixtures without personal data.
+/// let transfer = Transfer::new().with_transform(|session| {
+///     session.set_principal(None);
+///     session.remove_value("email");
+///     Some(session)
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct Transfer {
+    include_expired: bool,
+    transform: Option<Arc<TransformFn>>,
+    clock: SharedClock,
+}
+
+impl fmt::Debug for Transfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transfer")
+            .field("include_expired", &self.include_expired)
+            .field("clock", &self.clock)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Transfer {
+    /// Create new transfer options, skipping expired sessions and keeping
+    /// sessions as they are.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures whether sessions past their expiry date are exported and
+    /// imported, which they aren't by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::transfer::Transfer;
+    ///
+    /// let transfer = Transfer::new().with_expired(true);
+    /// ```
+    pub fn with_expired(mut self, include_expired: bool) -> Self {
+        self.include_expired = include_expired;
+        self
+    }
+
+    /// Configures a transform applied to each session exported or imported,
+    /// which skips the session by returning `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::transfer::Transfer;
+    ///
+    /// // Only sessions of signed in users.
+    /// let transfer =
+    ///     Transfer::new().with_transform(|session| session.principal().is_some().then_some(session));
+    /// ```
+    pub fn with_transform(
+        mut self,
+        transform: impl Fn(Session) -> Option<Session> + Send + Sync + 'static,
+    ) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Configures the clock against which sessions are found expired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, transfer::Transfer};
+    ///
+    /// let transfer = Transfer::new().with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    fn apply(&self, session: Session) -> Option<Session> {
+        if !self.include_expired && session.expiry_date() <= self.clock.now() {
+            return None;
+        }
+
+        match &self.transform {
+            Some(transform) => transform(session),
+            None => Some(session),
+        }
+    }
+
+    /// Exports the sessions of the store, yielding a JSON line, ending with a
+    /// newline, per session.
+    ///
+    /// Sessions are loaded one at a time as the stream is polled, so sessions
+    /// deleted meanwhile are skipped.
+    pub fn export<'a, S: ListSessions>(
+        &self,
+        store: &'a S,
+    ) -> impl Stream<Item = Result<String, TransferError<S::Error>>> + Send + 'a {
+        enum State {
+            Listing,
+            Loading(std::vec::IntoIter<Id>),
+            Done,
+        }
+
+        let transfer = self.clone();
+        stream::unfold(State::Listing, move |state| {
+            let transfer = transfer.clone();
+            async move {
+                let mut session_ids = match state {
+                    State::Listing => match store.list_sessions().await {
+                        Ok(session_ids) => session_ids.into_iter(),
+                        Err(err) => return Some((Err(TransferError::Store(err)), State::Done)),
+                    },
+                    State::Loading(session_ids) => session_ids,
+                    State::Done => return None,
+                };
+
+                for session_id in session_ids.by_ref() {
+                    let session = match store.load(&session_id).await {
+                        Ok(Some(session)) => session,
+                        Ok(None) => continue,
+                        Err(err) => return Some((Err(TransferError::Store(err)), State::Done)),
+                    };
+                    let Some(session) = transfer.apply(session) else {
+                        continue;
+                    };
+
+                    let line = serde_json::to_string(&session)
+                        .map(|line| line + "\n")
+                        .map_err(TransferError::Serialize);
+                    return Some((line, State::Loading(session_ids)));
+                }
+
+                None
+            }
+        })
+    }
+
+    /// Imports sessions from JSON lines, as exported, saving them into the
+    /// store and returning how many were saved.
+    ///
+    /// Sessions are saved as they are read, so when an error stops the
+    /// import, the sessions read before it remain saved. Blank lines are
+    /// skipped.
+    pub async fn import<S, L, E>(
+        &self,
+        store: &S,
+        lines: L,
+    ) -> Result<usize, TransferError<S::Error>>
+    where
+        S: SessionStore,
+        L: Stream<Item = Result<String, E>>,
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let mut lines = std::pin::pin!(lines);
+        let mut line_number = 0;
+        let mut imported = 0;
+
+        while let Some(line) = lines.next().await {
+            line_number += 1;
+            let line = line.map_err(|err| TransferError::Read(err.into()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let session: Session =
+                serde_json::from_str(&line).map_err(|source| TransferError::Deserialize {
+                    line: line_number,
+                    source,
+                })?;
+            let Some(session) = self.apply(session) else {
+                continue;
+            };
+
+            store.save(&session).await.map_err(TransferError::Store)?;
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+}
+
+/// Exports the sessions of the store with the default [`Transfer`] options,
+/// i.e. skipping expired sessions.
+pub fn export<S: ListSessions>(
+    store: &S,
+) -> impl Stream<Item = Result<String, TransferError<S::Error>>> + Send + '_ {
+    Transfer::new().export(store)
+}
+
+/// Imports sessions into the store with the default [`Transfer`] options, i.e.
+/// skipping expired sessions.
+pub async fn import<S, L, E>(store: &S, lines: L) -> Result<usize, TransferError<S::Error>>
+where
+    S: SessionStore,
+    L: Stream<Item = Result<String, E>>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    Transfer::new().import(store, lines).await
+}
@@ -0,0 +1,97 @@
+//! A key provider decrypting a data key with AWS KMS.
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_sdk_kms::{primitives::Blob, Client};
+use tower_sessions_core::key_provider::{KeyProvider, KeyProviderError, KEY_LEN};
+
+/// A provider decrypting a data key with AWS KMS.
+///
+/// The key is kept, encrypted under a KMS key, alongside the application's
+/// configuration, and decrypted whenever it's fetched. A data key of the right
+/// length may be generated with:
+///
+/// ```sh
+/// aws kms generate-data-key --key-id alias/sessions --number-of-bytes 64 \
+///     --query CiphertextBlob --output text
+/// ```
+///
+/// As the ciphertext is decrypted on every refresh, disabling the KMS key or
+/// revoking the application's access to it takes effect on running services
+/// once their key is next refreshed: the refresh fails and is retried, while
+/// the last decrypted key remains in use.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tower_sessions_key_providers::{aws_sdk_kms, KmsKeyProvider};
+///
+/// # tokio_test::block_on(async {
+/// let config = aws_config::load_from_env().await;
+/// let client = aws_sdk_kms::Client::new(&config);
+/// let ciphertext = std::fs::read("session-key.enc").unwrap();
+/// let key_provider = KmsKeyProvider::new(client, ciphertext).with_key_id("alias/sessions");
+/// # })
+/// ```
+#[derive(Debug, Clone)]
+pub struct KmsKeyProvider {
+    client: Client,
+    ciphertext: Vec<u8>,
+    key_id: Option<String>,
+    encryption_context: HashMap<String, String>,
+}
+
+impl KmsKeyProvider {
+    /// Create a new provider decrypting the provided ciphertext blob.
+    pub fn new(client: Client, ciphertext: Vec<u8>) -> Self {
+        Self {
+            client,
+            ciphertext,
+            key_id: None,
+            encryption_context: HashMap::new(),
+        }
+    }
+
+    /// Configures the KMS key the ciphertext must have been encrypted under.
+    ///
+    /// KMS finds the key in the ciphertext of symmetric keys, but checking it
+    /// guards against decrypting a ciphertext substituted for another one.
+    pub fn with_key_id(mut self, key_id: &str) -> Self {
+        self.key_id = Some(key_id.to_owned());
+        self
+    }
+
+    /// Adds a pair to the encryption context the data key was generated with.
+    pub fn with_encryption_context(mut self, key: &str, value: &str) -> Self {
+        self.encryption_context
+            .insert(key.to_owned(), value.to_owned());
+        self
+    }
+}
+
+#[async_trait]
+impl KeyProvider for KmsKeyProvider {
+    async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+        let output = self
+            .client
+            .decrypt()
+            .ciphertext_blob(Blob::new(self.ciphertext.clone()))
+            .set_key_id(self.key_id.clone())
+            .set_encryption_context(
+                (!self.encryption_context.is_empty()).then(|| self.encryption_context.clone()),
+            )
+            .send()
+            .await
+            .map_err(|err| KeyProviderError::Backend(Box::new(err)))?;
+
+        let key = output
+            .plaintext
+            .map(Blob::into_inner)
+            .ok_or_else(|| KeyProviderError::NotFound("KMS plaintext".to_owned()))?;
+        if key.len() < KEY_LEN {
+            return Err(KeyProviderError::TooShort { len: key.len() });
+        }
+
+        Ok(key)
+    }
+}
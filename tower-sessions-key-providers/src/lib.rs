@@ -0,0 +1,44 @@
+//! AWS KMS and HashiCorp Vault key providers for `tower-sessions`.
+//!
+//! These implement the [`KeyProvider`] trait of `tower-sessions`, so that the
+//! keys signing or encrypting session cookies may be kept where the rest of an
+//! application's secrets are, and fetched again periodically through a
+//! [`ManagedKey`]:
+//!
+//! - With the `aws-kms` feature, [`KmsKeyProvider`] decrypts a data key with
+//!   AWS KMS.
+//! - With the `vault` feature, [`VaultKeyProvider`] reads a key from a
+//!   HashiCorp Vault KV secret.
+//!
+//! [`KeyProvider`]: tower_sessions_core::key_provider::KeyProvider
+//! [`ManagedKey`]: https://docs.rs/tower-sessions/latest/tower_sessions/key_provider/struct.ManagedKey.html
+#![warn(
+    clippy::all,
+    nonstandard_style,
+    future_incompatible,
+    missing_debug_implementations
+)]
+#![deny(missing_docs)]
+#![forbid(unsafe_code)]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+#[cfg(feature = "aws-kms")]
+pub use aws_sdk_kms;
+#[cfg(feature = "vault")]
+pub use reqwest;
+
+#[cfg(feature = "aws-kms")]
+#[cfg_attr(docsrs, doc(cfg(feature = "aws-kms")))]
+#[doc(inline)]
+pub use self::kms::KmsKeyProvider;
+#[cfg(feature = "vault")]
+#[cfg_attr(docsrs, doc(cfg(feature = "vault")))]
+#[doc(inline)]
+pub use self::vault::VaultKeyProvider;
+
+#[cfg(feature = "aws-kms")]
+#[cfg_attr(docsrs, doc(cfg(feature = "aws-kms")))]
+pub mod kms;
+#[cfg(feature = "vault")]
+#[cfg_attr(docsrs, doc(cfg(feature = "vault")))]
+pub mod vault;
@@ -0,0 +1,115 @@
+//! A key provider reading a key from a HashiCorp Vault KV secret.
+use std::fmt;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tower_sessions_core::key_provider::{decode_key, KeyProvider, KeyProviderError};
+
+/// A provider reading a base64-encoded key from a field of a secret of
+/// Vault's KV version 2 secrets engine.
+///
+/// The latest version of the secret is read on every refresh, so writing a
+/// new version rotates the key. A key may be written with:
+///
+/// ```sh
+/// vault kv put secret/sessions key="$(openssl rand -base64 64 | tr -d '\n')"
+/// ```
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_key_providers::VaultKeyProvider;
+///
+/// let key_provider =
+///     VaultKeyProvider::new("https://vault.example.com:8200", "hvs.token", "sessions")
+///         .with_mount("kv")
+///         .with_field("cookie-key");
+/// ```
+#[derive(Clone)]
+pub struct VaultKeyProvider {
+    client: Client,
+    address: String,
+    token: String,
+    mount: String,
+    path: String,
+    field: String,
+}
+
+// Tokens are secrets, so they're kept out of logs.
+impl fmt::Debug for VaultKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultKeyProvider")
+            .field("address", &self.address)
+            .field("mount", &self.mount)
+            .field("path", &self.path)
+            .field("field", &self.field)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VaultKeyProvider {
+    /// Create a new provider reading the secret at the provided path, from the
+    /// `key` field of the secret and the `secret` mount by default.
+    pub fn new(address: &str, token: &str, path: &str) -> Self {
+        Self {
+            client: Client::new(),
+            address: address.trim_end_matches('/').to_owned(),
+            token: token.to_owned(),
+            mount: "secret".to_owned(),
+            path: path.trim_matches('/').to_owned(),
+            field: "key".to_owned(),
+        }
+    }
+
+    /// Configures the mount of the KV secrets engine, `secret` by default.
+    pub fn with_mount(mut self, mount: &str) -> Self {
+        self.mount = mount.trim_matches('/').to_owned();
+        self
+    }
+
+    /// Configures the field of the secret holding the key, `key` by default.
+    pub fn with_field(mut self, field: &str) -> Self {
+        self.field = field.to_owned();
+        self
+    }
+
+    /// Configures the HTTP client used to reach Vault, e.g. to trust a private
+    /// certificate authority.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn url(&self) -> String {
+        format!("{}/v1/{}/data/{}", self.address, self.mount, self.path)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for VaultKeyProvider {
+    async fn fetch(&self) -> Result<Vec<u8>, KeyProviderError> {
+        let res = self
+            .client
+            .get(self.url())
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(|err| KeyProviderError::Backend(Box::new(err)))?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Err(KeyProviderError::NotFound(self.url()));
+        }
+
+        let secret: Value = res
+            .error_for_status()
+            .map_err(|err| KeyProviderError::Backend(Box::new(err)))?
+            .json()
+            .await
+            .map_err(|err| KeyProviderError::Backend(Box::new(err)))?;
+
+        let encoded = secret["data"]["data"][&self.field]
+            .as_str()
+            .ok_or_else(|| KeyProviderError::NotFound(format!("{}#{}", self.url(), self.field)))?;
+        decode_key(encoded)
+    }
+}
@@ -0,0 +1,98 @@
+//! A middleware recording the session outcome of requests.
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::{Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+use tower_sessions_core::{session::Deletion, Session};
+
+use crate::REQUESTS;
+
+/// A middleware recording [`REQUESTS`] by the outcome of the request's
+/// session.
+///
+/// Sessions are `deleted` when deleted or emptied by the request, `cycled`
+/// when their ID was cycled, `modified` when otherwise modified, and
+/// `unchanged` otherwise, including sessions which were empty throughout.
+///
+/// This reads the session from the request's extensions, so it must be
+/// applied inside
+/// [`SessionManagerLayer`](tower_sessions_core::SessionManagerLayer),
+/// e.g. after it in a `ServiceBuilder`. Requests without a session aren't
+/// recorded.
+#[derive(Debug, Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+}
+
+impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let session = req.extensions().get::<Session>().cloned();
+        let was_empty = session.as_ref().is_some_and(Session::is_empty);
+        let fut = self.inner.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+
+            if let Some(session) = session {
+                let outcome = match session.deleted() {
+                    // Sessions which were empty throughout also report being deleted.
+                    Some(Deletion::Deleted) if !was_empty || !session.is_empty() => "deleted",
+                    Some(Deletion::Deleted) => "unchanged",
+                    Some(Deletion::Cycled(_)) => "cycled",
+                    None if session.is_modified() => "modified",
+                    None => "unchanged",
+                };
+                metrics::counter!(REQUESTS, "outcome" => outcome).increment(1);
+            }
+
+            res
+        })
+    }
+}
+
+/// A layer for providing [`MetricsService`].
+#[derive(Debug, Clone, Default)]
+pub struct MetricsLayer {
+    _priv: (),
+}
+
+impl MetricsLayer {
+    /// Create a new `MetricsLayer`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions_metrics::MetricsLayer;
+    ///
+    /// let metrics_layer = MetricsLayer::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService { inner }
+    }
+}
@@ -0,0 +1,132 @@
+//! Standardized session metrics for `tower-sessions`.
+//!
+//! This crate reports the following through the [`metrics`](https://docs.rs/metrics)
+//! facade, under names shared by every application using it, so that
+//! dashboards and alerts may be reused across them:
+//!
+//! - [`MetricsStore`] wraps a session store, recording loads, load misses,
+//!   saves, save errors, the duration of store operations, the size of saved
+//!   sessions and an estimate of active sessions.
+//! - [`MetricsLayer`] records the outcome of each request with respect to its
+//!   session: whether it was modified, left unchanged, deleted or cycled.
+//!
+//! [`describe`] registers descriptions and units for these metrics with the
+//! installed recorder. With the `prometheus` feature,
+//! [`install_prometheus_recorder`] installs a Prometheus recorder suited to
+//! them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use axum::{error_handling::HandleErrorLayer, routing::get, BoxError, Router};
+//! use http::StatusCode;
+//! use tower::ServiceBuilder;
+//! use tower_sessions::{MemoryStore, SessionManagerLayer};
+//! use tower_sessions_metrics::{MetricsLayer, MetricsStore};
+//!
+//! let session_store = MetricsStore::new(MemoryStore::default());
+//! let session_service = ServiceBuilder::new()
+//!     .layer(HandleErrorLayer::new(|_: BoxError| async {
+//!         StatusCode::BAD_REQUEST
+//!     }))
+//!     .layer(SessionManagerLayer::new(session_store))
+//!     .layer(MetricsLayer::new());
+//!
+//! let app: Router = Router::new()
+//!     .route("/", get(|| async { "Hello, World!" }))
+//!     .layer(session_service);
+//! ```
+#![warn(
+    clippy::all,
+    nonstandard_style,
+    future_incompatible,
+    missing_debug_implementations
+)]
+#![deny(missing_docs)]
+#![forbid(unsafe_code)]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+pub use metrics;
+use metrics::Unit;
+
+#[cfg(feature = "prometheus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "prometheus")))]
+#[doc(inline)]
+pub use self::prometheus::{install_prometheus_recorder, prometheus_builder};
+#[doc(inline)]
+pub use self::{
+    layer::{MetricsLayer, MetricsService},
+    store::MetricsStore,
+};
+
+pub mod layer;
+#[cfg(feature = "prometheus")]
+#[cfg_attr(docsrs, doc(cfg(feature = "prometheus")))]
+pub mod prometheus;
+pub mod store;
+
+/// Gauge of sessions created less sessions deleted through the store.
+///
+/// This is only an estimate of active sessions: sessions which expire without
+/// being deleted through the store aren't subtracted, while deletions of
+/// sessions the store doesn't have, e.g. for requests with stale cookies, are.
+pub const ACTIVE_SESSIONS: &str = "tower_sessions_active_sessions";
+
+/// Counter of attempts to load sessions from the store.
+pub const LOADS: &str = "tower_sessions_store_loads_total";
+
+/// Counter of loads for which the store had no active session.
+pub const LOAD_MISSES: &str = "tower_sessions_store_load_misses_total";
+
+/// Counter of attempts to save sessions to the store, including sessions
+/// created.
+pub const SAVES: &str = "tower_sessions_store_saves_total";
+
+/// Counter of failed attempts to save sessions to the store, including
+/// sessions created.
+pub const SAVE_ERRORS: &str = "tower_sessions_store_save_errors_total";
+
+/// Histogram of the duration of store operations, in seconds, labeled by
+/// `operation`: one of `load`, `create`, `save`, `delete` and
+/// `delete_expired`.
+pub const OPERATION_DURATION: &str = "tower_sessions_store_operation_duration_seconds";
+
+/// Histogram of the size of sessions saved to the store, in bytes, as encoded
+/// in MessagePack.
+pub const PAYLOAD_SIZE: &str = "tower_sessions_session_payload_bytes";
+
+/// Counter of requests handled with a session, labeled by `outcome`: one of
+/// `modified`, `unchanged`, `deleted` and `cycled`.
+pub const REQUESTS: &str = "tower_sessions_requests_total";
+
+/// Register descriptions and units for the metrics of this crate with the
+/// installed recorder.
+///
+/// This should be called once the recorder is installed.
+pub fn describe() {
+    metrics::describe_gauge!(
+        ACTIVE_SESSIONS,
+        "Sessions created less sessions deleted through the store."
+    );
+    metrics::describe_counter!(LOADS, "Attempts to load sessions from the store.");
+    metrics::describe_counter!(
+        LOAD_MISSES,
+        "Loads for which the store had no active session."
+    );
+    metrics::describe_counter!(SAVES, "Attempts to save sessions to the store.");
+    metrics::describe_counter!(
+        SAVE_ERRORS,
+        "Failed attempts to save sessions to the store."
+    );
+    metrics::describe_histogram!(
+        OPERATION_DURATION,
+        Unit::Seconds,
+        "Duration of store operations."
+    );
+    metrics::describe_histogram!(
+        PAYLOAD_SIZE,
+        Unit::Bytes,
+        "Size of sessions saved to the store."
+    );
+    metrics::describe_counter!(REQUESTS, "Requests handled with a session.");
+}
@@ -0,0 +1,63 @@
+//! Registration of the metrics of this crate with Prometheus.
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::{describe, OPERATION_DURATION, PAYLOAD_SIZE};
+
+/// Buckets for [`OPERATION_DURATION`], from a millisecond to ten seconds.
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Buckets for [`PAYLOAD_SIZE`], from 64 bytes to a megabyte.
+const PAYLOAD_SIZE_BUCKETS: &[f64] = &[
+    64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0,
+];
+
+/// Create a Prometheus exporter builder rendering the histograms of this crate
+/// with suitable buckets.
+///
+/// The builder may be configured further, e.g. with an HTTP listener, before
+/// installing it. Descriptions are registered with [`describe`] once it's
+/// installed.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions_metrics::prometheus_builder;
+///
+/// let recorder = prometheus_builder().build_recorder();
+/// ```
+pub fn prometheus_builder() -> PrometheusBuilder {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(OPERATION_DURATION.to_string()),
+            DURATION_BUCKETS,
+        )
+        .and_then(|builder| {
+            builder.set_buckets_for_metric(
+                Matcher::Full(PAYLOAD_SIZE.to_string()),
+                PAYLOAD_SIZE_BUCKETS,
+            )
+        })
+        .expect("buckets are not empty")
+}
+
+/// Install a Prometheus recorder as the global recorder and register the
+/// metrics of this crate with it, returning a handle to render them.
+///
+/// The rendered metrics are typically served from a `/metrics` route.
+///
+/// # Examples
+///
+/// ```rust
+/// use axum::{routing::get, Router};
+/// use tower_sessions_metrics::install_prometheus_recorder;
+///
+/// let handle = install_prometheus_recorder().unwrap();
+/// let app: Router = Router::new().route("/metrics", get(move || async move { handle.render() }));
+/// ```
+pub fn install_prometheus_recorder() -> Result<PrometheusHandle, BuildError> {
+    let handle = prometheus_builder().install_recorder()?;
+    describe();
+    Ok(handle)
+}
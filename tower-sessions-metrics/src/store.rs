@@ -0,0 +1,139 @@
+//! A session store wrapper recording store metrics.
+use std::{future::Future, time::Instant};
+
+use async_trait::async_trait;
+use tower_sessions_core::{session::Id, ExpiredDeletion, Session, SessionStore};
+
+use crate::{
+    ACTIVE_SESSIONS, LOADS, LOAD_MISSES, OPERATION_DURATION, PAYLOAD_SIZE, SAVES, SAVE_ERRORS,
+};
+
+/// A session store recording metrics about the operations of the store it
+/// wraps.
+///
+/// See the [crate documentation](crate) for the metrics recorded. Recording
+/// the size of saved sessions requires encoding them once more, which is
+/// usually negligible next to the cost of the store operation itself.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::{MemoryStore, Session, SessionStore};
+/// use tower_sessions_metrics::MetricsStore;
+///
+/// # tokio_test::block_on(async {
+/// let session_store = MetricsStore::new(MemoryStore::default());
+///
+/// let session = Session::default();
+/// session_store.create(&session).await.unwrap();
+/// assert!(session_store.load(session.id()).await.unwrap().is_some());
+/// # })
+/// ```
+#[derive(Debug, Clone)]
+pub struct MetricsStore<Store: SessionStore> {
+    inner: Store,
+}
+
+impl<Store: SessionStore> MetricsStore<Store> {
+    /// Create a new `MetricsStore` wrapping the provided store.
+    pub fn new(inner: Store) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a reference to the wrapped store.
+    pub fn get_ref(&self) -> &Store {
+        &self.inner
+    }
+
+    /// Consumes the `MetricsStore`, returning the wrapped store.
+    pub fn into_inner(self) -> Store {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<Store: SessionStore> SessionStore for MetricsStore<Store> {
+    type Error = Store::Error;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        record_payload_size(session);
+        let res = timed("save", self.inner.save(session)).await;
+        record_save(&res);
+        res
+    }
+
+    async fn create(&self, session: &Session) -> Result<(), Self::Error> {
+        record_payload_size(session);
+        let res = timed("create", self.inner.create(session)).await;
+        record_save(&res);
+        if res.is_ok() {
+            metrics::gauge!(ACTIVE_SESSIONS).increment(1.0);
+        }
+        res
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let res = timed("load", self.inner.load(session_id)).await;
+        metrics::counter!(LOADS).increment(1);
+        if let Ok(None) = res {
+            metrics::counter!(LOAD_MISSES).increment(1);
+        }
+        res
+    }
+
+    async fn load_including_expired(
+        &self,
+        session_id: &Id,
+    ) -> Result<Option<Session>, Self::Error> {
+        let res = timed("load", self.inner.load_including_expired(session_id)).await;
+        metrics::counter!(LOADS).increment(1);
+        if let Ok(None) = res {
+            metrics::counter!(LOAD_MISSES).increment(1);
+        }
+        res
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        let res = timed("delete", self.inner.delete(session_id)).await;
+        if res.is_ok() {
+            metrics::gauge!(ACTIVE_SESSIONS).decrement(1.0);
+        }
+        res
+    }
+
+    async fn flush(&self) -> Result<(), Self::Error> {
+        self.inner.flush().await
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        self.inner.db_system()
+    }
+}
+
+#[async_trait]
+impl<Store: ExpiredDeletion> ExpiredDeletion for MetricsStore<Store> {
+    async fn delete_expired(&self) -> Result<(), Self::Error> {
+        timed("delete_expired", self.inner.delete_expired()).await
+    }
+}
+
+async fn timed<F: Future>(operation: &'static str, fut: F) -> F::Output {
+    let started = Instant::now();
+    let output = fut.await;
+    metrics::histogram!(OPERATION_DURATION, "operation" => operation)
+        .record(started.elapsed().as_secs_f64());
+    output
+}
+
+fn record_payload_size(session: &Session) {
+    if let Ok(payload) = rmp_serde::to_vec(session) {
+        metrics::histogram!(PAYLOAD_SIZE).record(payload.len() as f64);
+    }
+}
+
+fn record_save<E>(res: &Result<(), E>) {
+    metrics::counter!(SAVES).increment(1);
+    if res.is_err() {
+        metrics::counter!(SAVE_ERRORS).increment(1);
+    }
+}
@@ -0,0 +1,399 @@
+//! A [`tonic`](https://docs.rs/tonic) layer and interceptor for gRPC sessions
+//! backed by `tower-sessions` stores.
+//!
+//! [`GrpcSessionLayer`] loads and persists the same [`Session`] records as
+//! `tower-sessions`' own middleware, through any [`SessionStore`], so that gRPC
+//! and HTTP services may share a store, and with it their users' sessions.
+//! Instead of a cookie, the session ID travels in the `x-session-id` metadata
+//! of requests, and is sent back in the response metadata whenever it's
+//! created or changes. Handlers access the session through
+//! [`RequestSessionExt::session`].
+//!
+//! [`SessionInterceptor`] propagates a session to the services a client
+//! calls, setting its ID in the metadata of outgoing requests, e.g. for an
+//! `axum` handler calling a gRPC backend on behalf of its visitor.
+//!
+//! Sessions are loaded and saved as `SessionManagerLayer` does, through
+//! [`SessionPersistence`], with the same expiry and refresh threshold. The ID
+//! is that of the session cookie of `SessionManagerLayer`, so signed and
+//! private cookies aren't supported, nor are consent and strict mode.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use tonic::{Request, Response, Status};
+//! use tower_sessions::MemoryStore;
+//! use tower_sessions_tonic::{GrpcSessionLayer, RequestSessionExt};
+//!
+//! async fn counter(request: Request<()>) -> Result<Response<String>, Status> {
+//!     let session = request.session()?;
+//!     let counter: usize = session.get("counter").unwrap().unwrap_or_default();
+//!     session.insert("counter", counter + 1).unwrap();
+//!     Ok(Response::new(format!("Current count: {counter}")))
+//! }
+//!
+//! // E.g. with `tonic::transport::Server::builder().layer(session_layer)`.
+//! let session_layer = GrpcSessionLayer::new(MemoryStore::default());
+//! ```
+#![warn(
+    clippy::all,
+    nonstandard_style,
+    future_incompatible,
+    missing_debug_implementations
+)]
+#![deny(missing_docs)]
+#![forbid(unsafe_code)]
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{HeaderName, HeaderValue, Request, Response};
+use time::Duration;
+use tonic::{
+    metadata::{Ascii, MetadataKey},
+    service::Interceptor,
+    Status,
+};
+use tower_layer::Layer;
+use tower_service::Service;
+use tower_sessions_core::{
+    clock::Clock,
+    persistence::{Saved, SessionPersistence},
+    session::Id,
+    Expiry, Session, SessionStore,
+};
+
+/// The default metadata key holding the session ID.
+const DEFAULT_METADATA_KEY: &str = "x-session-id";
+
+#[derive(Debug, Clone)]
+struct SessionConfig {
+    metadata_key: HeaderName,
+    persistence: SessionPersistence,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            metadata_key: HeaderName::from_static(DEFAULT_METADATA_KEY),
+            persistence: SessionPersistence::default(),
+        }
+    }
+}
+
+/// The service produced by [`GrpcSessionLayer`].
+#[derive(Debug, Clone)]
+pub struct GrpcSessionService<S, Store: SessionStore> {
+    inner: S,
+    session_store: Store,
+    session_config: Arc<SessionConfig>,
+}
+
+impl<ReqBody, ResBody, S, Store: SessionStore> Service<Request<ReqBody>>
+    for GrpcSessionService<S, Store>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let session_store = self.session_store.clone();
+        let session_config = self.session_config.clone();
+
+        // Because the inner service can panic until ready, we need to ensure we only
+        // use the ready service.
+        //
+        // See: https://docs.rs/tower/latest/tower/trait.Service.html#be-careful-when-cloning-inner-services
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let metadata_key = &session_config.metadata_key;
+            let persistence = &session_config.persistence;
+            let has_token = req.headers().contains_key(metadata_key);
+
+            // Unknown and malformed IDs alike are replaced by a new session.
+            let session_id = req
+                .headers()
+                .get(metadata_key)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| Id::try_from(value).ok());
+
+            let loaded = match persistence.load(&session_store, session_id.as_ref()).await {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    tracing::error!(err = %err, "failed to load session");
+                    return Ok(store_error());
+                }
+            };
+
+            let is_loaded = loaded.is_some();
+            let mut session = loaded.unwrap_or_else(|| persistence.new_session());
+
+            req.extensions_mut().insert(session.clone());
+
+            let mut res = inner.call(req).await?;
+
+            // The token sent back is empty when the session was deleted.
+            let token = match persistence
+                .save(&session_store, &mut session, is_loaded)
+                .await
+            {
+                Ok(Saved::Deleted) => has_token.then(String::new),
+                Ok(Saved::Persisted) => Some(session.id().to_string()),
+                Ok(Saved::Unchanged) => None,
+                Err(err) => {
+                    tracing::error!(err = %err, "failed to save session");
+                    return Ok(store_error());
+                }
+            };
+            if let Some(token) = token.and_then(|token| HeaderValue::try_from(token).ok()) {
+                res.headers_mut().insert(metadata_key.clone(), token);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+/// The response of requests whose session couldn't be loaded or saved.
+fn store_error<ResBody: Default>() -> Response<ResBody> {
+    Status::internal("Session store error").into_http()
+}
+
+/// A layer providing [`Session`] to `tonic` services.
+///
+/// Its services respond with an `INTERNAL` status when the store fails to
+/// load or save the session.
+#[derive(Debug, Clone)]
+pub struct GrpcSessionLayer<Store: SessionStore> {
+    session_store: Store,
+    session_config: Arc<SessionConfig>,
+}
+
+impl<Store: SessionStore> GrpcSessionLayer<Store> {
+    /// Create a new layer persisting sessions with the provided store.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_tonic::GrpcSessionLayer;
+    ///
+    /// let session_store = MemoryStore::default();
+    /// let session_layer = GrpcSessionLayer::new(session_store);
+    /// ```
+    pub fn new(session_store: Store) -> Self {
+        Self {
+            session_store,
+            session_config: Arc::default(),
+        }
+    }
+
+    fn config_mut(&mut self) -> &mut SessionConfig {
+        Arc::make_mut(&mut self.session_config)
+    }
+
+    /// Configures the metadata key holding the session ID, in requests and
+    /// responses alike. The default value is `"x-session-id"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key isn't a valid metadata key, e.g. if it contains
+    /// uppercase characters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::MemoryStore;
+    /// use tower_sessions_tonic::GrpcSessionLayer;
+    ///
+    /// let session_layer = GrpcSessionLayer::new(MemoryStore::default()).with_metadata_key("my-sid");
+    /// ```
+    pub fn with_metadata_key(mut self, metadata_key: &'static str) -> Self {
+        self.config_mut().metadata_key = HeaderName::from_static(metadata_key);
+        self
+    }
+
+    /// Configures the expiry of new sessions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{Expiry, MemoryStore};
+    /// use tower_sessions_tonic::GrpcSessionLayer;
+    ///
+    /// let session_layer = GrpcSessionLayer::new(MemoryStore::default())
+    ///     .with_expiry(Expiry::OnInactivity(Duration::hours(1)));
+    /// ```
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        let session_config = self.config_mut();
+        session_config.persistence = session_config.persistence.clone().with_expiry(expiry);
+        self
+    }
+
+    /// Configures the remaining lifetime below which sessions expiring on
+    /// inactivity are extended. Without a threshold, such sessions are
+    /// extended by every request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use time::Duration;
+    /// use tower_sessions::{Expiry, MemoryStore};
+    /// use tower_sessions_tonic::GrpcSessionLayer;
+    ///
+    /// let session_layer = GrpcSessionLayer::new(MemoryStore::default())
+    ///     .with_expiry(Expiry::OnInactivity(Duration::hours(1)))
+    ///     .with_refresh_threshold(Duration::minutes(15));
+    /// ```
+    pub fn with_refresh_threshold(mut self, refresh_threshold: Duration) -> Self {
+        let session_config = self.config_mut();
+        session_config.persistence = session_config
+            .persistence
+            .clone()
+            .with_refresh_threshold(refresh_threshold);
+        self
+    }
+
+    /// Configures the clock against which sessions expire.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::{clock::MockClock, MemoryStore};
+    /// use tower_sessions_tonic::GrpcSessionLayer;
+    ///
+    /// let session_layer = GrpcSessionLayer::new(MemoryStore::default()).with_clock(MockClock::default());
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        let session_config = self.config_mut();
+        session_config.persistence = session_config.persistence.clone().with_clock(clock);
+        self
+    }
+}
+
+impl<S, Store: SessionStore> Layer<S> for GrpcSessionLayer<Store> {
+    type Service = GrpcSessionService<S, Store>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcSessionService {
+            inner,
+            session_store: self.session_store.clone(),
+            session_config: self.session_config.clone(),
+        }
+    }
+}
+
+/// An extension of `tonic` requests providing their [`Session`].
+pub trait RequestSessionExt {
+    /// Returns the session of the request, or an `INTERNAL` status if
+    /// [`GrpcSessionLayer`] isn't enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tonic::{Request, Response, Status};
+    /// use tower_sessions_tonic::RequestSessionExt;
+    ///
+    /// async fn handler(request: Request<()>) -> Result<Response<String>, Status> {
+    ///     let session = request.session()?;
+    ///     Ok(Response::new(format!("{:?}", session.principal())))
+    /// }
+    /// ```
+    fn session(&self) -> Result<Session, Status>;
+}
+
+impl<T> RequestSessionExt for tonic::Request<T> {
+    fn session(&self) -> Result<Session, Status> {
+        self.extensions().get::<Session>().cloned().ok_or_else(|| {
+            Status::internal("Can't extract session. Is `GrpcSessionLayer` enabled?")
+        })
+    }
+}
+
+/// A client interceptor propagating a session to the services it calls.
+///
+/// The session's current ID is set in the metadata of each request, so it
+/// must have been saved for the services to load it: sessions created by the
+/// request being served are only saved once it completes. The services send
+/// back the new ID of sessions they cycle in the response metadata, under the
+/// same key.
+///
+/// # Examples
+///
+/// ```rust
+/// use tower_sessions::Session;
+/// use tower_sessions_tonic::SessionInterceptor;
+///
+/// async fn handler(session: Session) {
+///     let interceptor = SessionInterceptor::new(session);
+///     // E.g. `GreeterClient::with_interceptor(channel, interceptor)`.
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionInterceptor {
+    session: Session,
+    metadata_key: MetadataKey<Ascii>,
+}
+
+impl SessionInterceptor {
+    /// Create a new interceptor propagating the session.
+    pub fn new(session: Session) -> Self {
+        Self {
+            session,
+            metadata_key: MetadataKey::from_static(DEFAULT_METADATA_KEY),
+        }
+    }
+
+    /// Configures the metadata key holding the session ID. The default value
+    /// is `"x-session-id"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key isn't a valid metadata key, e.g. if it contains
+    /// uppercase characters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tower_sessions::Session;
+    /// use tower_sessions_tonic::SessionInterceptor;
+    ///
+    /// let interceptor = SessionInterceptor::new(Session::new(None)).with_metadata_key("my-sid");
+    /// ```
+    pub fn with_metadata_key(mut self, metadata_key: &'static str) -> Self {
+        self.metadata_key = MetadataKey::from_static(metadata_key);
+        self
+    }
+}
+
+impl Interceptor for SessionInterceptor {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let token = self
+            .session
+            .id()
+            .to_string()
+            .parse()
+            .map_err(|_| Status::internal("Invalid session ID"))?;
+        request
+            .metadata_mut()
+            .insert(self.metadata_key.clone(), token);
+        Ok(request)
+    }
+}
@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use time::Duration;
+use tower_sessions_core::{
+    clock::{Clock, SharedClock},
+    session::Id,
+    Session, SessionStore,
+};
+pub use worker;
+use worker::{send::IntoSendFuture, KvError, KvStore};
+
+/// The minimum time to live of Workers KV keys.
+const MIN_TTL: Duration = Duration::seconds(60);
+
+/// An error type for `WorkersKvStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum WorkersKvStoreError {
+    /// A variant to map to `worker::KvError` errors.
+    #[error("Workers KV error: {0}")]
+    Kv(#[from] KvError),
+
+    /// A variant to map `rmp_serde` encode errors.
+    #[error("Rust MsgPack encode error: {0}")]
+    RmpSerdeEncode(#[from] rmp_serde::encode::Error),
+
+    /// A variant to map `rmp_serde` decode errors.
+    #[error("Rust MsgPack decode error: {0}")]
+    RmpSerdeDecode(#[from] rmp_serde::decode::Error),
+}
+
+/// A Cloudflare Workers KV session store, for use in Workers compiled to
+/// `wasm32-unknown-unknown`.
+///
+/// Sessions are stored under their ID alongside their expiry date, with the
+/// key's expiration set accordingly so that Workers KV removes expired
+/// sessions. Keys must live for at least 60 seconds, so sessions expiring
+/// sooner are kept until then but ignored when loading.
+///
+/// Workers KV is eventually consistent: sessions saved in one location may
+/// take up to a minute to be visible in others, and there's no way to create
+/// keys atomically, so [`create`](SessionStore::create) overwrites existing
+/// sessions.
+#[derive(Clone, Debug)]
+pub struct WorkersKvStore {
+    kv: KvStore,
+    clock: SharedClock,
+}
+
+impl WorkersKvStore {
+    /// Create a new Workers KV store with the provided KV namespace.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tower_sessions_workers_kv_store::{worker::Env, WorkersKvStore};
+    ///
+    /// # fn example(env: Env) -> worker::Result<()> {
+    /// let session_store = WorkersKvStore::new(env.kv("SESSIONS")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(kv: KvStore) -> Self {
+        Self {
+            kv,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Configures the clock used to determine whether a session has expired.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+}
+
+#[async_trait]
+impl SessionStore for WorkersKvStore {
+    type Error = WorkersKvStoreError;
+
+    async fn save(&self, session: &Session) -> Result<(), Self::Error> {
+        let now = self.clock.now();
+        let expiry_date = session.expiry_date();
+        if expiry_date <= now {
+            return self.delete(session.id()).await;
+        }
+
+        let record = rmp_serde::to_vec(&(expiry_date.unix_timestamp(), session))?;
+        let expiration = expiry_date.max(now + MIN_TTL).unix_timestamp() as u64;
+        self.kv
+            .put_bytes(&session.id().to_string(), &record)?
+            .expiration(expiration)
+            .execute()
+            .into_send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load(&self, session_id: &Id) -> Result<Option<Session>, Self::Error> {
+        let record = self
+            .kv
+            .get(&session_id.to_string())
+            .bytes()
+            .into_send()
+            .await?;
+        let Some(record) = record else {
+            return Ok(None);
+        };
+
+        let (expiry_date, session): (i64, Session) = rmp_serde::from_slice(&record)?;
+        if expiry_date <= self.clock.now().unix_timestamp() {
+            return Ok(None);
+        }
+
+        Ok(Some(session))
+    }
+
+    async fn delete(&self, session_id: &Id) -> Result<(), Self::Error> {
+        self.kv.delete(&session_id.to_string()).into_send().await?;
+        Ok(())
+    }
+
+    fn db_system(&self) -> Option<&'static str> {
+        Some("workers_kv")
+    }
+}